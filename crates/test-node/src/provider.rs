@@ -9,16 +9,39 @@ pub mod block_store;
 mod event_stream;
 pub mod key_store;
 
+use std::collections::HashMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
 use anyhow::Result;
 use blockstore::InMemoryBlockstore;
+use chrono::{DateTime, TimeDelta, Utc};
+use dwn_node::endpoint::{MAX_MESSAGE_SIZE, RateLimiter};
 use dwn_node::provider::{
-    DataStore, DidResolver, Document, EventLog, MessageStore, Provider, TaskStore,
+    Clock, DataStore, DidResolver, Document, EventLog, MessageStore, Provider, TaskStore,
 };
+use dwn_node::store::MAX_ENCODED_SIZE;
 
 #[derive(Clone)]
 pub struct ProviderImpl {
     blockstore: InMemoryBlockstore<64>,
     pub nats_client: async_nats::Client,
+    pub strict_timestamps: bool,
+    pub retained_versions: usize,
+    pub max_encoded_size: usize,
+    pub max_message_size: usize,
+    /// When set, allow one message per owner and author within the window.
+    pub rate_limit: Option<Duration>,
+    last_seen: Arc<Mutex<HashMap<String, Instant>>>,
+    /// The number of blocks fetched from the block store.
+    pub block_reads: Arc<AtomicUsize>,
+    /// The number of batches stored in the block store.
+    pub block_batches: Arc<AtomicUsize>,
+    /// The clock used by handlers, advanced to move time forward.
+    pub clock: Arc<TestClock>,
+    /// When set, the number of blocks to store before a single store fails.
+    pub fail_after_puts: Arc<Mutex<Option<usize>>>,
 }
 
 impl ProviderImpl {
@@ -26,16 +49,100 @@ impl ProviderImpl {
         Ok(Self {
             blockstore: InMemoryBlockstore::<64>::new(),
             nats_client: async_nats::connect("demo.nats.io").await?,
+            strict_timestamps: false,
+            retained_versions: 0,
+            max_encoded_size: MAX_ENCODED_SIZE,
+            max_message_size: MAX_MESSAGE_SIZE,
+            rate_limit: None,
+            last_seen: Arc::new(Mutex::new(HashMap::new())),
+            block_reads: Arc::new(AtomicUsize::new(0)),
+            block_batches: Arc::new(AtomicUsize::new(0)),
+            clock: Arc::new(TestClock::default()),
+            fail_after_puts: Arc::new(Mutex::new(None)),
         })
     }
+
+    #[must_use]
+    pub const fn with_max_encoded_size(mut self, max_encoded_size: usize) -> Self {
+        self.max_encoded_size = max_encoded_size;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
 }
 
-impl Provider for ProviderImpl {}
+impl Provider for ProviderImpl {
+    fn strict_timestamps(&self) -> bool {
+        self.strict_timestamps
+    }
+
+    fn retained_versions(&self) -> usize {
+        self.retained_versions
+    }
+
+    fn max_encoded_size(&self) -> usize {
+        self.max_encoded_size
+    }
+
+    fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    fn rate_limiter(&self) -> Option<&dyn RateLimiter> {
+        Some(self)
+    }
+
+    fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+}
+
+/// A clock running at system time, offset by however far it has been
+/// advanced.
+#[derive(Debug, Default)]
+pub struct TestClock {
+    offset: Mutex<TimeDelta>,
+}
+
+impl TestClock {
+    /// Move the clock forward by `delta`.
+    pub fn advance(&self, delta: TimeDelta) {
+        *self.offset.lock().expect("should lock") += delta;
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now() + *self.offset.lock().expect("should lock")
+    }
+}
 impl MessageStore for ProviderImpl {}
 impl DataStore for ProviderImpl {}
 impl EventLog for ProviderImpl {}
 impl TaskStore for ProviderImpl {}
 
+impl RateLimiter for ProviderImpl {
+    fn check(&self, owner: &str, author: Option<&str>) -> Option<Duration> {
+        let window = self.rate_limit?;
+        let key = format!("{owner}:{}", author.unwrap_or_default());
+        let now = Instant::now();
+
+        let mut last_seen = self.last_seen.lock().expect("should lock");
+        if let Some(last) = last_seen.get(&key) {
+            let elapsed = now.duration_since(*last);
+            if elapsed < window {
+                return Some(window - elapsed);
+            }
+        }
+        last_seen.insert(key, now);
+        None
+    }
+}
+
 impl DidResolver for ProviderImpl {
     async fn resolve(&self, url: &str) -> Result<Document> {
         // let resolved =
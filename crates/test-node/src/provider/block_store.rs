@@ -1,19 +1,43 @@
 use std::str::FromStr;
+use std::sync::PoisonError;
+use std::sync::atomic::Ordering;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use blockstore::Blockstore as _;
 use dwn_node::provider::BlockStore;
 
 use super::ProviderImpl;
 
+impl ProviderImpl {
+    // Fail a single store once the configured number of blocks are stored.
+    fn check_put(&self) -> Result<()> {
+        let mut fail_after = self.fail_after_puts.lock().unwrap_or_else(PoisonError::into_inner);
+        match *fail_after {
+            Some(0) => {
+                *fail_after = None;
+                Err(anyhow!("block store unavailable"))
+            }
+            Some(ref mut remaining) => {
+                *remaining -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
 impl BlockStore for ProviderImpl {
     async fn put(&self, owner: &str, partition: &str, cid: &str, block: &[u8]) -> Result<()> {
+        self.check_put()?;
+
         // HACK: convert libipld CID to blockstore CID
         let block_cid = cid::Cid::from_str(cid)?;
         self.blockstore.put_keyed(&block_cid, block).await.map_err(Into::into)
     }
 
     async fn get(&self, owner: &str, partition: &str, cid: &str) -> Result<Option<Vec<u8>>> {
+        self.block_reads.fetch_add(1, Ordering::SeqCst);
+
         // HACK: convert libipld CID to blockstore CID
         let block_cid = cid::Cid::try_from(cid)?;
         let Some(bytes) = self.blockstore.get(&block_cid).await? else {
@@ -22,6 +46,19 @@ impl BlockStore for ProviderImpl {
         Ok(Some(bytes))
     }
 
+    async fn put_many(
+        &self, owner: &str, partition: &str, blocks: &[(String, Vec<u8>)],
+    ) -> Result<()> {
+        self.block_batches.fetch_add(1, Ordering::SeqCst);
+
+        for (cid, block) in blocks {
+            self.check_put()?;
+            let block_cid = cid::Cid::from_str(cid)?;
+            self.blockstore.put_keyed(&block_cid, block).await?;
+        }
+        Ok(())
+    }
+
     async fn delete(&self, owner: &str, partition: &str, cid: &str) -> Result<()> {
         let cid = cid::Cid::from_str(cid)?;
         self.blockstore.remove(&cid).await?;
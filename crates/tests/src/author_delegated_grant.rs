@@ -32,6 +32,7 @@ async fn configure_any() {
         .scope(Scope::Messages {
             method: Method::Query,
             protocol: None,
+            interface: None,
         });
 
     let bob_grant = builder.sign(&*ALICE).build().await.expect("should create grant");
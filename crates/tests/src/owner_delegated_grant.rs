@@ -28,7 +28,7 @@ async fn configure() {
         .delegated(true)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "chat".to_string(),
+            protocol: Some("chat".to_string()),
             limited_to: None,
         });
     let grant_to_appx = builder.sign(&*ALICE).build().await.expect("should create grant");
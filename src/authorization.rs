@@ -3,6 +3,9 @@
 //! The `Authorization` module groups types and functionality loosely related
 //! to message authorization and authentication.
 
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use anyhow::anyhow;
 use base64ct::{Base64UrlUnpadded, Encoding};
 use serde::{Deserialize, Serialize};
@@ -12,7 +15,7 @@ use vercre_infosec::{Jws, Signer};
 
 use crate::records::DelegatedGrant;
 use crate::utils::cid;
-use crate::{Result, unexpected};
+use crate::{Result, forbidden, unexpected};
 
 /// Creates a closure to resolve pub key material required by `Jws::decode`.
 ///
@@ -27,16 +30,20 @@ use crate::{Result, unexpected};
 /// ```
 #[doc(hidden)]
 macro_rules! verify_key {
-    ($resolver:expr) => {{
-        // create local reference before moving into closure
+    ($resolver:expr) => {{ verify_key!($resolver, Arc::new(AtomicBool::new(false))) }};
+    ($resolver:expr, $missing_key:expr) => {{
+        // create local references before moving into closure
         let resolver = $resolver;
+        let missing_key = $missing_key;
         move |kid: String| {
             let local_resolver = resolver.clone();
+            let missing_key = missing_key.clone();
             async move {
                 let resp = dereference(&kid, None, local_resolver)
                     .await
                     .map_err(|e| anyhow!("issue dereferencing DID: {e}"))?;
                 let Some(Resource::VerificationMethod(vm)) = resp.content_stream else {
+                    missing_key.store(true, Ordering::Relaxed);
                     return Err(anyhow!("Verification method not found"));
                 };
                 vm.method_type.jwk().map_err(|e| anyhow!("JWK not found: {e}"))
@@ -100,18 +107,49 @@ pub struct Authorization {
 
 impl Authorization {
     /// Verify message signature.
+    ///
+    /// The key used to verify each signature is dereferenced from the
+    /// signature's `kid` using the provider's [`DidResolver`]. Key rotation is
+    /// left to the DID method: a signature made with a key no longer in the
+    /// signer's resolved DID document is rejected with `Error::Forbidden`.
+    ///
+    /// A delegated grant not committed to by the delegate's signature is
+    /// also rejected with `Error::Forbidden`.
     pub(crate) async fn verify(&self, resolver: impl DidResolver) -> Result<()> {
-        let verifier = verify_key!(resolver);
+        let missing_key = Arc::new(AtomicBool::new(false));
+        let verifier = verify_key!(resolver, missing_key.clone());
+        let key_error = |e: anyhow::Error| {
+            if missing_key.load(Ordering::Relaxed) {
+                forbidden!("signing key not found in the signer's DID document")
+            } else {
+                e.into()
+            }
+        };
 
-        self.signature.verify(verifier.clone()).await?;
+        self.signature.verify(verifier.clone()).await.map_err(key_error)?;
         if let Some(signature) = &self.owner_signature {
-            signature.verify(verifier.clone()).await?;
+            signature.verify(verifier.clone()).await.map_err(key_error)?;
         }
         if let Some(grant) = &self.author_delegated_grant {
-            grant.authorization.signature.verify(verifier.clone()).await?;
+            grant.authorization.signature.verify(verifier.clone()).await.map_err(key_error)?;
+
+            // the author-delegate's signature must commit to the delegated grant
+            let grant_id = cid::from_value(grant)?;
+            if self.payload()?.delegated_grant_id.as_ref() != Some(&grant_id) {
+                return Err(forbidden!("delegated grant ID does not match the signature"));
+            }
         }
         if let Some(grant) = &self.owner_delegated_grant {
-            grant.authorization.signature.verify(verifier).await?;
+            grant.authorization.signature.verify(verifier).await.map_err(key_error)?;
+
+            // as must the owner-delegate's
+            let Some(signature) = &self.owner_signature else {
+                return Err(forbidden!("owner delegated grant requires an owner signature"));
+            };
+            let grant_id = cid::from_value(grant)?;
+            if decode_payload(signature)?.delegated_grant_id.as_ref() != Some(&grant_id) {
+                return Err(forbidden!("owner delegated grant ID does not match the signature"));
+            }
         }
 
         Ok(())
@@ -167,13 +205,49 @@ impl Authorization {
     ///
     /// Will return an error if the payload cannot be decoded or deserialized.
     pub fn payload(&self) -> Result<JwsPayload> {
-        let decoded = Base64UrlUnpadded::decode_vec(&self.signature.payload)
-            .map_err(|e| unexpected!("issue decoding signature payload: {e}"))?;
-        serde_json::from_slice(&decoded)
-            .map_err(|e| unexpected!("issue deserializing signature payload: {e}"))
+        decode_payload(&self.signature)
+    }
+
+    /// Whether the message has been counter-signed by the web node owner,
+    /// authorizing its author to act as the owner. Owner-delegated signatures
+    /// are not considered.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Forbidden` when the owner signature was not made by
+    /// `owner` or does not commit to the message's descriptor, so it cannot be
+    /// lifted from another message.
+    pub(crate) fn owner_signed(&self, owner: &str, descriptor_cid: &str) -> Result<bool> {
+        let Some(signature) = &self.owner_signature else {
+            return Ok(false);
+        };
+        if self.owner_delegated_grant.is_some() {
+            return Ok(false);
+        }
+        if signature.did()? != owner {
+            return Err(forbidden!("owner signature is not web node owner"));
+        }
+        if decode_payload(signature)?.descriptor_cid != descriptor_cid {
+            return Err(forbidden!("owner signature does not match message descriptor"));
+        }
+        Ok(true)
     }
 }
 
+// Decode the `JwsPayload` signed by the JWS.
+fn decode_payload(jws: &Jws) -> Result<JwsPayload> {
+    let decoded = Base64UrlUnpadded::decode_vec(&jws.payload)
+        .map_err(|e| unexpected!("issue decoding signature payload: {e}"))?;
+    serde_json::from_slice(&decoded)
+        .map_err(|e| unexpected!("issue deserializing signature payload: {e}"))
+}
+
+/// Verify the signatures of a standalone JWS, such as a record's attestation,
+/// using the provided DID resolver to dereference signing keys.
+pub(crate) async fn verify_jws(jws: &Jws, resolver: impl DidResolver) -> anyhow::Result<()> {
+    jws.verify(verify_key!(resolver)).await
+}
+
 /// Options to use when creating a permission grant.
 #[derive(Clone, Debug, Default)]
 pub struct AuthorizationBuilder {
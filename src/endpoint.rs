@@ -5,12 +5,42 @@
 //! serialized to a JSON object.
 
 use std::fmt::Debug;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::field::Empty;
+use tracing::{Instrument, debug, debug_span};
 
 use crate::authorization::Authorization;
-use crate::provider::Provider;
-use crate::{Descriptor, Result, schema, unauthorized};
+use crate::provider::{
+    BlockStore, CachedResolver, DEFAULT_CACHE_CAPACITY, DidResolver, EventLog, MessageStore,
+    Provider, ResolverCache,
+};
+use crate::store::StoreStats;
+use crate::utils::cid;
+use crate::{
+    Descriptor, Error, Interface, Method, Result, forbidden, messages, protocols, records, schema,
+    unauthorized, unexpected,
+};
+
+/// JSON content type.
+pub const CONTENT_TYPE_JSON: &str = "application/json";
+
+/// DAG-CBOR content type.
+pub const CONTENT_TYPE_CBOR: &str = "application/cbor";
+
+/// The default maximum size, in bytes, of a JSON-encoded message. Streamed
+/// data is not included, being limited by the record's `data_size` and any
+/// protocol `$size` rules.
+pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+// The owner and partition probed by [`health`]. Probed blocks are not
+// expected to exist: stores only need to respond.
+const PROBE_OWNER: &str = "did:health:probe";
+const PROBE_PARTITION: &str = "HEALTH";
 
 /// Handle incoming messages.
 ///
@@ -25,10 +55,258 @@ use crate::{Descriptor, Result, schema, unauthorized};
 pub async fn handle<T>(
     owner: &str, message: impl Message<Reply = T>, provider: &impl Provider,
 ) -> Result<Reply<T>> {
+    // correlate all processing of the message using its CID
+    let descriptor = message.descriptor();
+    let span = debug_span!(
+        "handle",
+        owner,
+        interface = %descriptor.interface,
+        method = %descriptor.method,
+        message_cid = %message.cid().unwrap_or_default(),
+        status = Empty,
+    );
+
+    let result = process(owner, message, provider).instrument(span.clone()).await;
+    match &result {
+        Ok(reply) => {
+            span.record("status", reply.status.code);
+            span.in_scope(|| debug!("message handled"));
+        }
+        Err(e) => {
+            span.record("status", e.code().as_u16());
+            span.in_scope(|| debug!(error = ?e, "message failed"));
+        }
+    }
+    result
+}
+
+// Run the message through the handling pipeline within the `handle` span.
+async fn process<T>(
+    owner: &str, message: impl Message<Reply = T>, provider: &impl Provider,
+) -> Result<Reply<T>> {
+    // shed load before doing any work on the message
+    rate_limit(owner, &message, provider)?;
+    check_size(&message, provider)?;
     message.validate(owner, provider).await?;
     message.handle(owner, provider).await
 }
 
+// Reject messages larger than the provider's maximum message size before
+// any work is done on them.
+fn check_size(message: &impl Message, provider: &impl Provider) -> Result<()> {
+    if message.encoded_size()? > provider.max_message_size() {
+        return Err(unexpected!("message too large"));
+    }
+    Ok(())
+}
+
+/// Count the bytes in the JSON encoding of `value` without buffering it.
+pub(crate) fn json_size(value: &impl Serialize) -> Result<usize> {
+    struct Counter(usize);
+
+    impl io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    serde_json::to_writer(&mut counter, value)?;
+    Ok(counter.0)
+}
+
+/// Dry run a message, returning the status it would be handled with without
+/// persisting any of its changes.
+///
+/// The message is put through the same validation pipeline as [`handle`] —
+/// authorization, schema, protocol rules, data size, etc. — within a
+/// [`Transaction`] that is never committed. Changes are staged in the
+/// transaction rather than written to the provider, so the `BlockStore`,
+/// `MessageStore`, and `EventLog` are not modified, concurrent readers never
+/// see the message, and no events are emitted.
+///
+/// # Errors
+///
+/// Returns the error [`handle`] would return for the message.
+///
+/// [`Transaction`]: crate::provider::Transaction
+pub async fn validate<T>(
+    owner: &str, message: impl Message<Reply = T>, provider: &impl Provider,
+) -> Result<Status> {
+    check_size(&message, provider)?;
+
+    let tx = provider.transaction();
+    let result = match message.validate(owner, &tx).await {
+        Ok(()) => message.handle(owner, &tx).await,
+        Err(e) => Err(e),
+    };
+    tx.rollback().await?;
+    result.map(|reply| reply.status)
+}
+
+/// Handle a batch of messages atomically: either every message is processed
+/// successfully or none of the messages' changes are committed.
+///
+/// Messages are authorized and processed in order within a single
+/// [`Transaction`], so later messages may depend on earlier ones (e.g. a
+/// thread root followed by its first reply). Replies are returned in the same
+/// order as the messages.
+///
+/// # Errors
+///
+/// Returns the error of the first message to fail, with the message's index
+/// in the batch prefixed to the error detail. The batch is rolled back before
+/// the error is returned.
+///
+/// [`Transaction`]: crate::provider::Transaction
+pub async fn handle_batch<M: Message>(
+    owner: &str, messages: Vec<M>, provider: &impl Provider,
+) -> Result<Vec<Reply<M::Reply>>> {
+    let tx = provider.transaction();
+    let mut replies = Vec::with_capacity(messages.len());
+
+    for (index, message) in messages.into_iter().enumerate() {
+        let reply = match rate_limit(owner, &message, provider)
+            .and_then(|()| check_size(&message, provider))
+        {
+            Ok(()) => match message.validate(owner, &tx).await {
+                Ok(()) => message.handle(owner, &tx).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+        match reply {
+            Ok(reply) => replies.push(reply),
+            Err(e) => {
+                tx.rollback().await?;
+                return Err(e.map_detail(|detail| format!("message {index}: {detail}")));
+            }
+        }
+    }
+
+    tx.commit().await?;
+    Ok(replies)
+}
+
+/// Return statistics for the `owner`'s web node — the number of stored
+/// messages, total record data bytes, and number of distinct protocols —
+/// for use by operators in capacity planning.
+///
+/// The statistics reflect the current state of the store and are computed
+/// from message indexes without fetching messages or data.
+///
+/// # Errors
+///
+/// Returns an error when the `MessageStore` cannot be read.
+pub async fn stats(owner: &str, provider: &impl Provider) -> Result<StoreStats> {
+    Ok(MessageStore::stats(provider, owner).await?)
+}
+
+/// Check the provider's stores are reachable, reporting each store's status
+/// and latency so, for example, a load balancer can gate traffic to the node.
+///
+/// Each store is probed with a read of a block that is not expected to exist,
+/// so no store is modified.
+///
+/// # Errors
+///
+/// Returns an error when the probe's CID cannot be computed. Stores failing
+/// their probe are reported as unhealthy rather than returned as an error.
+pub async fn health(provider: &impl Provider) -> Result<HealthReport> {
+    let probe_cid = cid::from_value(&PROBE_OWNER)?;
+
+    let message_store = probe(MessageStore::get(provider, PROBE_OWNER, &probe_cid)).await;
+    let block_store =
+        probe(BlockStore::get(provider, PROBE_OWNER, PROBE_PARTITION, &probe_cid)).await;
+    let event_log = probe(EventLog::watermark(provider, PROBE_OWNER)).await;
+
+    Ok(HealthReport {
+        message_store,
+        block_store,
+        event_log,
+    })
+}
+
+// Time a store read, recording any error it returns.
+async fn probe<T>(read: impl Future<Output = anyhow::Result<T>>) -> ComponentHealth {
+    let start = Instant::now();
+    let result = read.await;
+    ComponentHealth {
+        ok: result.is_ok(),
+        latency: start.elapsed(),
+        error: result.err().map(|e| e.to_string()),
+    }
+}
+
+/// The health of a node's stores, as reported by [`health`].
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    /// The health of the `MessageStore`.
+    pub message_store: ComponentHealth,
+
+    /// The health of the `BlockStore`.
+    pub block_store: ComponentHealth,
+
+    /// The health of the `EventLog`.
+    pub event_log: ComponentHealth,
+}
+
+impl HealthReport {
+    /// Whether every store is healthy.
+    #[must_use]
+    pub const fn healthy(&self) -> bool {
+        self.message_store.ok && self.block_store.ok && self.event_log.ok
+    }
+}
+
+/// The health of a single store.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentHealth {
+    /// Whether the store responded to its probe without error.
+    pub ok: bool,
+
+    /// How long the store took to respond.
+    pub latency: Duration,
+
+    /// The error returned by the store, when unhealthy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// `RateLimiter` is consulted by [`handle`] before a message is processed,
+/// allowing operators to protect a node from overload.
+///
+/// Providers opt in by returning a limiter from [`Provider::rate_limiter`].
+pub trait RateLimiter: Send + Sync {
+    /// Check whether a message to the `owner`'s web node may be processed.
+    /// The `author` is the (as yet unauthenticated) message author, if any.
+    ///
+    /// Returns how long the requestor should wait before retrying when the
+    /// message is denied.
+    fn check(&self, owner: &str, author: Option<&str>) -> Option<Duration>;
+}
+
+// Consult the provider's rate limiter, if any.
+fn rate_limit(owner: &str, message: &impl Message, provider: &impl Provider) -> Result<()> {
+    let Some(limiter) = provider.rate_limiter() else {
+        return Ok(());
+    };
+    let author = message.authorization().and_then(|authzn| authzn.author().ok());
+    if let Some(retry_after) = limiter.check(owner, author.as_deref()) {
+        let secs = retry_after.as_millis().div_ceil(1000);
+        return Err(Error::TooManyRequests(format!("rate limit exceeded, retry after {secs}s")));
+    }
+    Ok(())
+}
+
 /// Methods common to all messages.
 ///
 /// The primary role of this trait is to provide a common interface for
@@ -51,6 +329,17 @@ pub trait Message: Serialize + Clone + Debug + Send + Sync {
     /// Returns the messages's authorization, when set.
     fn authorization(&self) -> Option<&Authorization>;
 
+    /// The size, in bytes, of the JSON-encoded message, excluding any record
+    /// data carried with it. Checked against the provider's maximum message
+    /// size.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail if the message cannot be serialized to JSON.
+    fn encoded_size(&self) -> Result<usize> {
+        json_size(self)
+    }
+
     /// Routes the message to the concrete handler used to process the message.
     fn handle(
         self, owner: &str, provider: &impl Provider,
@@ -74,9 +363,7 @@ pub trait Message: Serialize + Clone + Debug + Send + Sync {
 
             // authenticate the requestor
             if let Some(authzn) = self.authorization() {
-                if let Err(e) = authzn.verify(provider.clone()).await {
-                    return Err(unauthorized!("failed to authenticate: {e}"));
-                }
+                authenticate(authzn, provider).await?;
             }
 
             Ok(())
@@ -84,6 +371,61 @@ pub trait Message: Serialize + Clone + Debug + Send + Sync {
     }
 }
 
+// Resolve the message signer's DID document, once, and verify the message
+// signatures against it.
+//
+// Documents are cached using the provider's `ResolverCache` or, when the
+// provider has none, for the duration of the message. A cached document that
+// fails verification is replaced only when re-resolving the signer's DID
+// returns a different set of keys, so invalid signatures cannot be used to
+// flush the cache.
+async fn authenticate(authzn: &Authorization, provider: &impl Provider) -> Result<()> {
+    let signer = authzn.signer().map_err(|e| unauthorized!("failed to authenticate: {e}"))?;
+    let cache = provider
+        .resolver_cache()
+        .unwrap_or_else(|| Arc::new(ResolverCache::new(DEFAULT_CACHE_CAPACITY, Duration::MAX)));
+
+    let (document, cached) = match cache.get(&signer) {
+        Some(document) => (document, true),
+        None => {
+            let Ok(document) = provider.resolve(&signer).await else {
+                return Err(forbidden!("unable to resolve signer DID"));
+            };
+            cache.insert(&signer, document.clone());
+            (document, false)
+        }
+    };
+
+    let resolver = CachedResolver::with_cache(provider.clone(), cache.clone());
+    let Err(e) = authzn.verify(resolver.clone()).await else {
+        return Ok(());
+    };
+
+    // the cached document may predate a key rotation
+    if cached {
+        if let Ok(fresh) = provider.resolve(&signer).await {
+            if serde_json::to_value(&fresh.verification_method).ok()
+                != serde_json::to_value(&document.verification_method).ok()
+            {
+                cache.insert(&signer, fresh);
+                return authzn.verify(resolver).await.map_err(authentication_error);
+            }
+        }
+    }
+
+    Err(authentication_error(e))
+}
+
+// Signatures that are invalid fail authentication, while those made with a
+// key no longer in the signer's DID document, or misusing a delegated grant,
+// are forbidden.
+fn authentication_error(e: Error) -> Error {
+    match e {
+        Error::Forbidden { .. } => e,
+        _ => unauthorized!("failed to authenticate: {e}"),
+    }
+}
+
 /// Top-level reply data structure common to all handler.
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Reply<ReplyBody> {
@@ -107,3 +449,125 @@ pub struct Status {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub detail: Option<String>,
 }
+
+/// A message decoded from its transport encoding.
+///
+/// Each variant wraps the concrete message type identified by the message's
+/// `descriptor.interface` and `descriptor.method`.
+#[derive(Clone, Debug)]
+pub enum Request {
+    /// `RecordsWrite` message.
+    RecordsWrite(records::Write),
+
+    /// `RecordsRead` message.
+    RecordsRead(records::Read),
+
+    /// `RecordsQuery` message.
+    RecordsQuery(records::Query),
+
+    /// `RecordsSubscribe` message.
+    RecordsSubscribe(records::Subscribe),
+
+    /// `RecordsDelete` message.
+    RecordsDelete(records::Delete),
+
+    /// `ProtocolsConfigure` message.
+    ProtocolsConfigure(protocols::Configure),
+
+    /// `ProtocolsQuery` message.
+    ProtocolsQuery(protocols::Query),
+
+    /// `MessagesQuery` message.
+    MessagesQuery(messages::Query),
+
+    /// `MessagesRead` message.
+    MessagesRead(messages::Read),
+
+    /// `MessagesSubscribe` message.
+    MessagesSubscribe(messages::Subscribe),
+}
+
+/// Decode a message serialized as either JSON (`application/json`) or
+/// DAG-CBOR (`application/cbor`).
+///
+/// # Errors
+///
+/// Returns `Error::BadRequest` when the content type is not supported, the
+/// message cannot be decoded, the `descriptor` or `authorization` are
+/// malformed, or the interface and method do not identify a supported
+/// message.
+pub fn decode_message(bytes: &[u8], content_type: &str) -> Result<Request> {
+    let value: Value = match media_type(content_type)? {
+        CONTENT_TYPE_JSON => {
+            serde_json::from_slice(bytes).map_err(|e| unexpected!("invalid JSON: {e}"))?
+        }
+        _ => serde_ipld_dagcbor::from_slice(bytes).map_err(|e| unexpected!("invalid CBOR: {e}"))?,
+    };
+
+    // verify the shape of common message properties
+    let Some(descriptor) = value.get("descriptor") else {
+        return Err(unexpected!("message is missing `descriptor`"));
+    };
+    for field in ["interface", "method", "messageTimestamp"] {
+        if descriptor.get(field).is_none() {
+            return Err(unexpected!("descriptor is missing `{field}`"));
+        }
+    }
+    let descriptor =
+        Descriptor::deserialize(descriptor).map_err(|e| unexpected!("invalid descriptor: {e}"))?;
+    if let Some(authorization) = value.get("authorization") {
+        Authorization::deserialize(authorization)
+            .map_err(|e| unexpected!("invalid authorization: {e}"))?;
+    }
+
+    let request = match (&descriptor.interface, &descriptor.method) {
+        (Interface::Records, Method::Write) => Request::RecordsWrite(from_value(value)?),
+        (Interface::Records, Method::Read) => Request::RecordsRead(from_value(value)?),
+        (Interface::Records, Method::Query) => Request::RecordsQuery(from_value(value)?),
+        (Interface::Records, Method::Subscribe) => Request::RecordsSubscribe(from_value(value)?),
+        (Interface::Records, Method::Delete) => Request::RecordsDelete(from_value(value)?),
+        (Interface::Protocols, Method::Configure) => {
+            Request::ProtocolsConfigure(from_value(value)?)
+        }
+        (Interface::Protocols, Method::Query) => Request::ProtocolsQuery(from_value(value)?),
+        (Interface::Messages, Method::Query) => Request::MessagesQuery(from_value(value)?),
+        (Interface::Messages, Method::Read) => Request::MessagesRead(from_value(value)?),
+        (Interface::Messages, Method::Subscribe) => Request::MessagesSubscribe(from_value(value)?),
+        (interface, method) => {
+            return Err(unexpected!("unsupported message: {interface}{method}"));
+        }
+    };
+
+    Ok(request)
+}
+
+/// Encode a reply as either JSON (`application/json`) or DAG-CBOR
+/// (`application/cbor`).
+///
+/// # Errors
+///
+/// Returns `Error::BadRequest` when the content type is not supported and
+/// `Error::InternalServerError` when the reply cannot be serialized.
+pub fn encode_reply<T: Serialize>(reply: &Reply<T>, content_type: &str) -> Result<Vec<u8>> {
+    match media_type(content_type)? {
+        CONTENT_TYPE_JSON => serde_json::to_vec(reply).map_err(Into::into),
+        _ => serde_ipld_dagcbor::to_vec(reply)
+            .map_err(|e| Error::InternalServerError(format!("serde_ipld_dagcbor: {e}"))),
+    }
+}
+
+// Returns the supported media type, ignoring any parameters (e.g. `charset`).
+fn media_type(content_type: &str) -> Result<&'static str> {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    if media_type.eq_ignore_ascii_case(CONTENT_TYPE_JSON) {
+        Ok(CONTENT_TYPE_JSON)
+    } else if media_type.eq_ignore_ascii_case(CONTENT_TYPE_CBOR) {
+        Ok(CONTENT_TYPE_CBOR)
+    } else {
+        Err(unexpected!("unsupported content type: {content_type}"))
+    }
+}
+
+fn from_value<T: for<'de> Deserialize<'de>>(value: Value) -> Result<T> {
+    serde_json::from_value(value).map_err(|e| unexpected!("invalid message: {e}"))
+}
@@ -16,13 +16,23 @@ pub enum Error {
     #[error(r#"{{"code": 400, "detail": "{0}"}}"#)]
     BadRequest(String),
 
-    /// Semantically, this response means 'unauthenticated'.
+    /// Semantically, this response means 'unauthenticated'. Returned when a
+    /// message requiring authorization is received without any (i.e. from an
+    /// anonymous requestor).
     #[error(r#"{{"code": 401, "detail": "{0}"}}"#)]
     Unauthorized(String),
 
     /// The client does not have access rights to the content, i.e. is unauthorized.
-    #[error(r#"{{"code": 403, "detail": "{0}"}}"#)]
-    Forbidden(String),
+    /// Unlike [`Error::Unauthorized`], the client's identity is known.
+    #[error(r#"{{"code": 403, "detail": "{message}"}}"#)]
+    Forbidden {
+        /// The machine-readable reason for common denials.
+        #[serde(default)]
+        code: Option<ForbiddenCode>,
+
+        /// The error detail.
+        message: String,
+    },
 
     /// A required resource was not found.
     #[error(r#"{{"code": 404, "detail": "{0}"}}"#)]
@@ -32,6 +42,11 @@ pub enum Error {
     #[error(r#"{{"code": 409, "detail": "{0}"}}"#)]
     Conflict(String),
 
+    /// The node is overloaded or the requestor has sent too many requests in
+    /// a given amount of time.
+    #[error(r#"{{"code": 429, "detail": "{0}"}}"#)]
+    TooManyRequests(String),
+
     /// The server has encountered a situation it does not know how to handle.
     /// Used when the web node encounters an unexpected condition in a dependant
     /// library.
@@ -43,6 +58,65 @@ pub enum Error {
     Unimplemented(String),
 }
 
+/// Machine-readable codes for common [`Error::Forbidden`] denials.
+///
+/// Each code has a fixed error detail, so existing string matching continues
+/// to work while clients can use [`Error::forbidden_code`] to recover the code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[non_exhaustive]
+pub enum ForbiddenCode {
+    /// The protocol's rules do not permit the action.
+    ActionNotPermitted,
+
+    /// Neither ownership, publication, a protocol rule, nor a grant
+    /// authorizes reading the record.
+    ReadNotAuthorized,
+
+    /// The permission grant could not be found.
+    GrantNotFound,
+
+    /// The permission grant is not yet active.
+    GrantNotActive,
+
+    /// The permission grant has expired.
+    GrantExpired,
+
+    /// The permission grant has been revoked.
+    GrantRevoked,
+
+    /// The message is outside the scope of the permission grant.
+    ScopeMismatch,
+
+    /// The protocol has been deactivated.
+    ProtocolDeactivated,
+}
+
+impl ForbiddenCode {
+    /// The error detail returned for the code.
+    #[must_use]
+    pub const fn detail(self) -> &'static str {
+        match self {
+            Self::ActionNotPermitted => "action not permitted",
+            Self::ReadNotAuthorized => "read cannot be authorized",
+            Self::GrantNotFound => "no grant found",
+            Self::GrantNotActive => "grant is not yet active",
+            Self::GrantExpired => "grant has expired",
+            Self::GrantRevoked => "grant has been revoked",
+            Self::ScopeMismatch => "message failed scope authorization",
+            Self::ProtocolDeactivated => "protocol is deactivated",
+        }
+    }
+}
+
+impl From<ForbiddenCode> for Error {
+    fn from(code: ForbiddenCode) -> Self {
+        Self::Forbidden {
+            code: Some(code),
+            message: code.detail().to_string(),
+        }
+    }
+}
+
 impl Serialize for Error {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         use serde::ser::Error as SerdeError;
@@ -61,13 +135,76 @@ impl Error {
         match self {
             Self::BadRequest(_) => StatusCode::BAD_REQUEST,
             Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
-            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::Forbidden { .. } => StatusCode::FORBIDDEN,
             Self::NotFound(_) => StatusCode::NOT_FOUND,
             Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
             Self::InternalServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::Unimplemented(_) => StatusCode::NOT_IMPLEMENTED,
         }
     }
+
+    /// Returns the error detail.
+    #[must_use]
+    pub fn detail(&self) -> &str {
+        match self {
+            Self::BadRequest(detail)
+            | Self::Unauthorized(detail)
+            | Self::Forbidden { message: detail, .. }
+            | Self::NotFound(detail)
+            | Self::Conflict(detail)
+            | Self::TooManyRequests(detail)
+            | Self::InternalServerError(detail)
+            | Self::Unimplemented(detail) => detail,
+        }
+    }
+
+    /// Returns the machine-readable code of an [`Error::Forbidden`] error, or
+    /// `None` when the error is not a denial with a code.
+    #[must_use]
+    pub const fn forbidden_code(&self) -> Option<ForbiddenCode> {
+        match self {
+            Self::Forbidden { code, .. } => *code,
+            _ => None,
+        }
+    }
+
+    /// Replace the error detail, retaining the error type.
+    #[must_use]
+    pub(crate) fn map_detail(self, f: impl FnOnce(String) -> String) -> Self {
+        match self {
+            Self::BadRequest(detail) => Self::BadRequest(f(detail)),
+            Self::Unauthorized(detail) => Self::Unauthorized(f(detail)),
+            Self::Forbidden { code, message } => Self::Forbidden {
+                code,
+                message: f(message),
+            },
+            Self::NotFound(detail) => Self::NotFound(f(detail)),
+            Self::Conflict(detail) => Self::Conflict(f(detail)),
+            Self::TooManyRequests(detail) => Self::TooManyRequests(f(detail)),
+            Self::InternalServerError(detail) => Self::InternalServerError(f(detail)),
+            Self::Unimplemented(detail) => Self::Unimplemented(f(detail)),
+        }
+    }
+
+    /// Construct an error from a reply status code and detail, such as one
+    /// recorded for replay. Unrecognised codes are treated as server errors.
+    #[must_use]
+    pub(crate) fn from_status(code: u16, detail: String) -> Self {
+        match StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR) {
+            StatusCode::BAD_REQUEST => Self::BadRequest(detail),
+            StatusCode::UNAUTHORIZED => Self::Unauthorized(detail),
+            StatusCode::FORBIDDEN => Self::Forbidden {
+                code: None,
+                message: detail,
+            },
+            StatusCode::NOT_FOUND => Self::NotFound(detail),
+            StatusCode::CONFLICT => Self::Conflict(detail),
+            StatusCode::TOO_MANY_REQUESTS => Self::TooManyRequests(detail),
+            StatusCode::NOT_IMPLEMENTED => Self::Unimplemented(detail),
+            _ => Self::InternalServerError(detail),
+        }
+    }
 }
 
 impl PartialEq for Error {
@@ -138,13 +275,13 @@ macro_rules! unexpected {
 #[macro_export]
 macro_rules! forbidden {
     ($fmt:expr, $($arg:tt)*) => {
-        $crate::Error::Forbidden(format!($fmt, $($arg)*))
+        $crate::Error::Forbidden { code: None, message: format!($fmt, $($arg)*) }
     };
     // ($msg:literal $(,)?) => {
-    //     $crate::Error::Forbidden($msg.into())
+    //     $crate::Error::Forbidden { code: None, message: $msg.into() }
     // };
      ($err:expr $(,)?) => {
-        $crate::Error::Forbidden(format!($err))
+        $crate::Error::Forbidden { code: None, message: format!($err) }
     };
 }
 
@@ -218,4 +355,22 @@ mod test {
         let ser = serde_json::to_value(&err).unwrap();
         assert_eq!(ser, json!({"code": 400, "detail": "bad request: a token"}));
     }
+
+    // Test that forbidden codes are carried by the error, not its detail.
+    #[test]
+    fn forbidden_code() {
+        let err = Error::from(ForbiddenCode::GrantRevoked);
+        assert_eq!(err.forbidden_code(), Some(ForbiddenCode::GrantRevoked));
+        let ser = serde_json::to_value(&err).unwrap();
+        assert_eq!(ser, json!({"code": 403, "detail": "grant has been revoked"}));
+
+        // the code survives a change of detail
+        let err = err.map_detail(|detail| format!("message 1: {detail}"));
+        assert_eq!(err.forbidden_code(), Some(ForbiddenCode::GrantRevoked));
+        assert_eq!(err.detail(), "message 1: grant has been revoked");
+
+        // a matching detail does not imply a code
+        assert_eq!(forbidden!("grant has been revoked").forbidden_code(), None);
+        assert_eq!(unexpected!("grant has been revoked").forbidden_code(), None);
+    }
 }
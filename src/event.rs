@@ -3,16 +3,21 @@
 //! The event module provides the necessary structures and functionality for
 //! working with `RecordsSubscribe` events.
 
+use std::collections::VecDeque;
 use std::fmt;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 
-use futures::{Stream, stream};
+use chrono::{DateTime, Utc};
+use futures::channel::oneshot;
+use futures::{Stream, StreamExt, stream};
 use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
 
 use crate::messages::MessagesFilter;
 use crate::records::{RecordsFilter, Tag, TagFilter};
-use crate::store::{Entry, EntryType};
+use crate::store::{Entry, EntryType, MatchSet, Query};
 
 /// `Event` aliases `store::Entry` to provide a common type to use when
 /// interacting with events for any message type.
@@ -37,10 +42,15 @@ impl Default for SubscribeFilter {
     }
 }
 
+/// The default number of events buffered for a subscriber before the oldest
+/// buffered events are dropped.
+pub const SUBSCRIBER_CAPACITY: usize = 1024;
+
 /// `Subscriber` is intended to be used by local clients to process event
 ///  subscriptions.
 pub struct Subscriber {
     pub(crate) inner: Pin<Box<dyn Stream<Item = Event> + Send>>,
+    buffer: Option<Arc<Buffer>>,
 }
 
 impl Subscriber {
@@ -50,8 +60,120 @@ impl Subscriber {
     pub fn new(stream: impl Stream<Item = Event> + Send + 'static) -> Self {
         Self {
             inner: Box::pin(stream),
+            buffer: None,
+        }
+    }
+
+    /// Decouple the subscriber from the event producer using a buffer holding
+    /// at most `capacity` events.
+    ///
+    /// Events are read from the producer as soon as they are available. When
+    /// the consumer cannot keep up and the buffer is full, the oldest buffered
+    /// event is dropped to make room for the newest. Dropped events are
+    /// counted and can be retrieved using [`Subscriber::missed`], marking a
+    /// gap in the event sequence.
+    ///
+    /// Must be called from within a Tokio runtime.
+    #[must_use]
+    pub fn bounded(self, capacity: usize) -> Self {
+        let buffer = Arc::new(Buffer::new(capacity.max(1)));
+
+        // read events from the producer until it closes or the subscriber is
+        // dropped, dropping the cancellation sender held by the consumer
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        let producer = Arc::clone(&buffer);
+        let mut source = self.inner.take_until(cancelled);
+        tokio::spawn(async move {
+            while let Some(event) = source.next().await {
+                producer.push(event);
+            }
+            producer.close();
+        });
+
+        let consumer = Arc::clone(&buffer);
+        let inner = stream::unfold((consumer, cancel), |(buffer, cancel)| async move {
+            let event = buffer.pop().await?;
+            Some((event, (buffer, cancel)))
+        });
+
+        Self {
+            inner: Box::pin(inner),
+            buffer: Some(buffer),
+        }
+    }
+
+    /// The number of events dropped since the last call to `missed`. A
+    /// non-zero value indicates a gap between the previously received event
+    /// and the next.
+    ///
+    /// Always returns 0 for an unbounded subscriber.
+    #[must_use]
+    pub fn missed(&self) -> usize {
+        self.buffer.as_ref().map_or(0, |buffer| buffer.take_missed())
+    }
+}
+
+// Bounded, drop-oldest event buffer shared between the producer task and the
+// subscriber.
+struct Buffer {
+    capacity: usize,
+    state: Mutex<BufferState>,
+    notify: Notify,
+}
+
+#[derive(Default)]
+struct BufferState {
+    events: VecDeque<Event>,
+    missed: usize,
+    closed: bool,
+}
+
+impl Buffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(BufferState::default()),
+            notify: Notify::new(),
+        }
+    }
+
+    fn push(&self, event: Event) {
+        {
+            let mut state = self.state.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+            if state.events.len() >= self.capacity {
+                state.events.pop_front();
+                state.missed += 1;
+            }
+            state.events.push_back(event);
+        }
+        self.notify.notify_one();
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap_or_else(std::sync::PoisonError::into_inner).closed = true;
+        self.notify.notify_one();
+    }
+
+    async fn pop(&self) -> Option<Event> {
+        loop {
+            {
+                let mut state =
+                    self.state.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+                if let Some(event) = state.events.pop_front() {
+                    return Some(event);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            self.notify.notified().await;
         }
     }
+
+    fn take_missed(&self) -> usize {
+        let mut state = self.state.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        std::mem::take(&mut state.missed)
+    }
 }
 
 impl Stream for Subscriber {
@@ -67,6 +189,7 @@ impl Default for Subscriber {
     fn default() -> Self {
         Self {
             inner: Box::pin(stream::empty()),
+            buffer: None,
         }
     }
 }
@@ -105,8 +228,17 @@ impl RecordsFilter {
     #[allow(clippy::cognitive_complexity)]
     #[must_use]
     pub fn is_match(&self, event: &Entry) -> bool {
-        let EventType::Write(write) = &event.message else {
-            return false;
+        let write = match &event.message {
+            EventType::Write(write) => write,
+            // deletes are indexed with their initial write's fields
+            EventType::Delete(_) => {
+                let query = Query {
+                    match_sets: MatchSet::any_of(self),
+                    ..Query::default()
+                };
+                return query.is_match(event.indexes()).unwrap_or_default();
+            }
+            EventType::Configure(_) => return false,
         };
         let descriptor = &write.descriptor;
 
@@ -115,8 +247,24 @@ impl RecordsFilter {
                 return false;
             }
         }
-        if let Some(attester) = self.attester.clone() {
-            if Some(&attester) != event.indexes().get("attester") {
+        if let Some(attester) = &self.attester {
+            let attesters = event.indexes().get("attester");
+            if !attesters.is_some_and(|attesters| attesters.split(' ').any(|a| a == attester)) {
+                return false;
+            }
+        }
+        if let Some(protocol_config) = &self.protocol_config {
+            if Some(protocol_config) != event.indexes().get("protocolConfig") {
+                return false;
+            }
+        }
+        if let Some(text) = &self.text {
+            if text.hits(event.indexes()).is_none() {
+                return false;
+            }
+        }
+        if let Some(any_of) = &self.any_of {
+            if !any_of.iter().any(|filter| filter.is_match(event)) {
                 return false;
             }
         }
@@ -141,6 +289,16 @@ impl RecordsFilter {
                 return false;
             }
         }
+        if let Some(pinned) = &self.pinned {
+            if *pinned != event.indexes().get("pinned").is_some_and(|p| p == "true") {
+                return false;
+            }
+        }
+        if let Some(revised) = &self.revised {
+            if *revised == write.is_initial().unwrap_or_default() {
+                return false;
+            }
+        }
         if let Some(context_id) = &self.context_id {
             if !write.context_id.as_ref().unwrap_or(&String::new()).starts_with(context_id) {
                 return false;
@@ -151,11 +309,26 @@ impl RecordsFilter {
                 return false;
             }
         }
+        if let Some(not_protocol) = &self.not_protocol {
+            if Some(not_protocol) == descriptor.protocol.as_ref() {
+                return false;
+            }
+        }
+        if let Some(not_schema) = &self.not_schema {
+            if Some(not_schema) == descriptor.schema.as_ref() {
+                return false;
+            }
+        }
         if let Some(record_id) = &self.record_id {
             if record_id != &write.record_id {
                 return false;
             }
         }
+        if let Some(record_ids) = &self.record_ids {
+            if !record_ids.contains(&write.record_id) {
+                return false;
+            }
+        }
         if let Some(parent_id) = &self.parent_id {
             if Some(parent_id) != descriptor.parent_id.as_ref() {
                 return false;
@@ -166,13 +339,16 @@ impl RecordsFilter {
                 let Some(tags) = &descriptor.tags else {
                     return false;
                 };
-                if !filter.is_match(tags.get(property).unwrap_or(&Tag::Empty)) {
+                let Some(tag) = tags.get(property) else {
+                    return false;
+                };
+                if !filter.is_match(tag) {
                     return false;
                 }
             }
         }
         if let Some(data_format) = &self.data_format {
-            if data_format != &descriptor.data_format {
+            if Some(data_format) != descriptor.data_format.as_ref() {
                 return false;
             }
         }
@@ -192,7 +368,14 @@ impl RecordsFilter {
             }
         }
         if let Some(date_published) = &self.date_published {
-            if !date_published.contains(&descriptor.date_published.unwrap_or_default()) {
+            // prefer the indexed date, carried forward from earlier writes
+            let indexed = event
+                .indexes()
+                .get("datePublished")
+                .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+                .map(|date| date.with_timezone(&Utc));
+            let published = indexed.or_else(|| write.date_published()).unwrap_or_default();
+            if !date_published.contains(&published) {
                 return false;
             }
         }
@@ -209,14 +392,14 @@ impl TagFilter {
     fn is_match(&self, tag: &Tag) -> bool {
         match self {
             Self::StartsWith(value) => {
-                let tag = tag.as_str().unwrap_or_default();
-                tag.starts_with(value)
-            }
-            Self::Range(range) => {
-                let tag = tag.as_u64().unwrap_or_default();
-                range.contains(&usize::try_from(tag).unwrap_or_default())
+                tag.as_str().is_some_and(|tag| tag.starts_with(&value.starts_with))
             }
-            Self::Equal(other) => tag == other,
+            Self::Range(range) => tag
+                .as_u64()
+                .and_then(|tag| usize::try_from(tag).ok())
+                .is_some_and(|tag| range.contains(&tag)),
+            Self::FloatRange(range) => tag.as_f64().is_some_and(|tag| range.contains(&tag)),
+            Self::Equal(other) => tag.index_value() == other.index_value(),
         }
     }
 }
@@ -257,6 +440,11 @@ impl MessagesFilter {
                 return false;
             }
         }
+        if let Some(author) = &self.author {
+            if event.indexes().get("author") != Some(author) {
+                return false;
+            }
+        }
 
         true
     }
@@ -17,8 +17,8 @@ use crate::protocols::{GRANT_PATH, PROTOCOL_URI, REQUEST_PATH, REVOCATION_PATH};
 use crate::provider::MessageStore;
 use crate::records::{DelegatedGrant, RecordsFilter, Write};
 use crate::serde::rfc3339_micros;
-use crate::store::RecordsQueryBuilder;
-use crate::{Interface, Method, Result, forbidden, unexpected};
+use crate::store::{Entry, RecordsQueryBuilder};
+use crate::{ForbiddenCode, Interface, Method, Result, forbidden, unexpected};
 
 /// Fetches the grant specified by `grant_id`.
 pub async fn fetch_grant(owner: &str, grant_id: &str, store: &impl MessageStore) -> Result<Grant> {
@@ -27,12 +27,30 @@ pub async fn fetch_grant(owner: &str, grant_id: &str, store: &impl MessageStore)
     let (entries, _) = store.query(owner, &query).await?;
 
     let Some(entry) = entries.first() else {
-        return Err(forbidden!("no grant found"));
+        return Err(ForbiddenCode::GrantNotFound.into());
     };
     let Some(write) = entry.as_write() else {
         return Err(forbidden!("not a valid grant"));
     };
+    decode_grant(write)
+}
+
+/// Fetches the grants issued by `owner` to `grantee`.
+pub(crate) async fn fetch_grants(
+    owner: &str, grantee: &str, store: &impl MessageStore,
+) -> Result<Vec<Grant>> {
+    let filter = RecordsFilter::new()
+        .protocol(PROTOCOL_URI)
+        .protocol_path(GRANT_PATH)
+        .add_recipient(grantee);
+    let query = RecordsQueryBuilder::new().add_filter(filter).build();
+    let (entries, _) = store.query(owner, &query).await?;
+
+    entries.iter().filter_map(Entry::as_write).map(decode_grant).collect()
+}
 
+// Unpack the grant from the payload of a grant record.
+fn decode_grant(write: &Write) -> Result<Grant> {
     let desc = &write.descriptor;
 
     // unpack message payload
@@ -189,6 +207,28 @@ pub struct Conditions {
     /// make the message public.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub publication: Option<Publication>,
+
+    /// Additional conditions evaluated against a message at the time the
+    /// grant is invoked.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional: Vec<GrantCondition>,
+}
+
+/// A condition evaluated when a grant is used to authorize a message.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GrantCondition {
+    /// The message must be received within the (UTC) hours `from_hour`
+    /// (inclusive) to `to_hour` (exclusive), as measured by the node's clock.
+    /// A window where `from_hour` is greater than `to_hour` spans midnight.
+    #[serde(rename_all = "camelCase")]
+    TimeWindow {
+        /// The hour (0-23) the window opens.
+        from_hour: u32,
+
+        /// The hour (0-24) the window closes.
+        to_hour: u32,
+    },
 }
 
 /// Condition for publication of a message.
@@ -247,8 +287,9 @@ pub enum Scope {
         /// The method the permission is applied to.
         method: Method,
 
-        /// Scope protocol.
-        protocol: String,
+        /// Scope protocol. Required unless the scope is limited to a schema.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        protocol: Option<String>,
 
         /// Records scope options.
         #[serde(flatten)]
@@ -264,6 +305,12 @@ pub enum Scope {
         /// Scope protocol.
         #[serde(skip_serializing_if = "Option::is_none")]
         protocol: Option<String>,
+
+        /// Limits the scope to messages of the specified interface (e.g.
+        /// `Records`).
+        #[serde(rename = "messageInterface")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        interface: Option<Interface>,
     },
 
     /// Scope applies to the `Protocols` interface.
@@ -281,7 +328,7 @@ impl Default for Scope {
     fn default() -> Self {
         Self::Records {
             method: Method::default(),
-            protocol: String::new(),
+            protocol: None,
             limited_to: None,
         }
     }
@@ -308,14 +355,31 @@ impl Scope {
         }
     }
 
+    /// A shortcut to unpack the interface a `Messages` scope is limited to.
+    #[must_use]
+    pub const fn message_interface(&self) -> Option<&Interface> {
+        match &self {
+            Self::Messages { interface, .. } => interface.as_ref(),
+            Self::Records { .. } | Self::Protocols { .. } => None,
+        }
+    }
+
+    /// A shortcut to unpack the schema a `Records` scope is limited to.
+    #[must_use]
+    pub fn schema(&self) -> Option<&str> {
+        match &self {
+            Self::Records { limited_to, .. } => limited_to.as_ref().and_then(RecordsScope::schema),
+            Self::Messages { .. } | Self::Protocols { .. } => None,
+        }
+    }
+
     /// A shortcut to unpack the scope protocol.
     #[must_use]
     pub fn protocol(&self) -> Option<&str> {
         match &self {
-            Self::Records { protocol, .. } => Some(protocol),
-            Self::Messages { protocol, .. } | Self::Protocols { protocol, .. } => {
-                protocol.as_deref()
-            }
+            Self::Records { protocol, .. }
+            | Self::Messages { protocol, .. }
+            | Self::Protocols { protocol, .. } => protocol.as_deref(),
         }
     }
 }
@@ -330,6 +394,11 @@ pub enum RecordsScope {
 
     /// The protocol path of the record.
     ProtocolPath(String),
+
+    /// The schema of the record. Records of the schema are within scope
+    /// whatever their protocol, so the scope's `protocol` is not required
+    /// and, when set, is not compared.
+    Schema(String),
 }
 
 impl Default for RecordsScope {
@@ -344,7 +413,7 @@ impl RecordsScope {
     pub fn context_id(&self) -> Option<&str> {
         match self {
             Self::ContextId(id) => Some(id.as_str()),
-            Self::ProtocolPath(_) => None,
+            Self::ProtocolPath(_) | Self::Schema(_) => None,
         }
     }
 
@@ -353,7 +422,16 @@ impl RecordsScope {
     pub fn protocol_path(&self) -> Option<&str> {
         match self {
             Self::ProtocolPath(path) => Some(path.as_str()),
-            Self::ContextId(_) => None,
+            Self::ContextId(_) | Self::Schema(_) => None,
+        }
+    }
+
+    /// A shortcut to access the schema, if it is set.
+    #[must_use]
+    pub fn schema(&self) -> Option<&str> {
+        match self {
+            Self::Schema(schema) => Some(schema.as_str()),
+            Self::ContextId(_) | Self::ProtocolPath(_) => None,
         }
     }
 }
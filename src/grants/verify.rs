@@ -3,24 +3,24 @@
 //! The [`grant`] module handles verification of previously issued permission
 //! grants.
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Timelike, Utc};
 
 use super::{RecordsScope, Scope};
-use crate::grants::{Grant, Publication};
+use crate::grants::{Grant, GrantCondition, Publication};
 use crate::protocols::REVOCATION_PATH;
-use crate::provider::MessageStore;
+use crate::provider::{Clock, MessageStore, Provider};
 use crate::records::{Delete, Query, Read, RecordsFilter, Subscribe, Write};
 use crate::store::RecordsQueryBuilder;
-use crate::{Descriptor, Result, forbidden};
+use crate::{Descriptor, ForbiddenCode, Interface, Method, Result, forbidden, utils};
 
 impl Grant {
     /// Verify the `grantee` is sufficiently authorized to undertake the
     /// action reference by the [`Descriptor`].
     ///
-    /// Does not validate grant `conditions` or `scope` beyond `interface` and
-    /// `method`.
+    /// Does not validate grant `conditions` (other than `additional`) or
+    /// `scope` beyond `interface` and `method`.
     pub(crate) async fn verify(
-        &self, grantor: &str, grantee: &str, descriptor: &Descriptor, store: &impl MessageStore,
+        &self, grantor: &str, grantee: &str, descriptor: &Descriptor, provider: &impl Provider,
     ) -> Result<()> {
         // verify the `grantee` against intended recipient
         if grantee != self.grantee {
@@ -43,16 +43,20 @@ impl Grant {
         }
 
         // verify the message is within the grant's time frame
-        self.is_current(grantor, &descriptor.message_timestamp, store).await?;
+        self.is_current(grantor, &descriptor.message_timestamp, provider).await?;
+
+        // verify any additional conditions against the node's clock, rather
+        // than the (client-provided) message timestamp
+        self.verify_additional(&provider.clock().now())?;
 
         Ok(())
     }
 
     /// Verify the grant allows the `records::Write` message to be written.
     pub(crate) async fn permit_write(
-        &self, grantor: &str, grantee: &str, write: &Write, store: &impl MessageStore,
+        &self, grantor: &str, grantee: &str, write: &Write, provider: &impl Provider,
     ) -> Result<()> {
-        self.verify(grantor, grantee, &write.descriptor.base, store).await?;
+        self.verify(grantor, grantee, &write.descriptor.base, provider).await?;
         self.verify_scope(write)?;
         self.verify_conditions(write)?;
         Ok(())
@@ -61,9 +65,9 @@ impl Grant {
     /// Verify the grant allows the requestor to access `records::Query` and
     /// `records::Subscribe` records.
     pub(crate) async fn permit_read(
-        &self, grantor: &str, grantee: &str, read: &Read, write: &Write, store: &impl MessageStore,
+        &self, grantor: &str, grantee: &str, read: &Read, write: &Write, provider: &impl Provider,
     ) -> Result<()> {
-        self.verify(grantor, grantee, &read.descriptor.base, store).await?;
+        self.verify(grantor, grantee, &read.descriptor.base, provider).await?;
         self.verify_scope(write)?;
         Ok(())
     }
@@ -71,11 +75,16 @@ impl Grant {
     /// Verify the grant allows the requestor to access `records::Query` and
     /// `records::Subscribe` records.
     pub(crate) async fn permit_query(
-        &self, grantor: &str, grantee: &str, query: &Query, store: &impl MessageStore,
+        &self, grantor: &str, grantee: &str, query: &Query, provider: &impl Provider,
     ) -> Result<()> {
         let descriptor = &query.descriptor;
 
-        self.verify(grantor, grantee, &descriptor.base, store).await?;
+        self.verify(grantor, grantee, &descriptor.base, provider).await?;
+
+        // verify schemas match for schema-scoped grants
+        if let Some(schema) = self.data.scope.schema() {
+            return verify_schema(schema, descriptor.filter.schema.as_deref());
+        }
 
         // verify protocols match
         if self.data.scope.protocol().is_none() {
@@ -91,11 +100,16 @@ impl Grant {
     /// Verify the grant allows the requestor to access `records::Query` and
     /// `records::Subscribe` records.
     pub(crate) async fn permit_subscribe(
-        &self, grantor: &str, grantee: &str, subscribe: &Subscribe, store: &impl MessageStore,
+        &self, grantor: &str, grantee: &str, subscribe: &Subscribe, provider: &impl Provider,
     ) -> Result<()> {
         let descriptor = &subscribe.descriptor;
 
-        self.verify(grantor, grantee, &descriptor.base, store).await?;
+        self.verify(grantor, grantee, &descriptor.base, provider).await?;
+
+        // verify schemas match for schema-scoped grants
+        if let Some(schema) = self.data.scope.schema() {
+            return verify_schema(schema, descriptor.filter.schema.as_deref());
+        }
 
         // verify protocols match
         if self.data.scope.protocol().is_none() {
@@ -111,19 +125,34 @@ impl Grant {
     /// Verify the grant allows the `records::Write` message to be deleted.
     pub(crate) async fn permit_delete(
         &self, grantor: &str, grantee: &str, delete: &Delete, write: &Write,
-        store: &impl MessageStore,
+        provider: &impl Provider,
     ) -> Result<()> {
-        self.verify(grantor, grantee, &delete.descriptor.base, store).await?;
+        self.verify(grantor, grantee, &delete.descriptor.base, provider).await?;
+        self.verify_delete_scope(write)
+    }
 
-        // must be deleting a record with the same protocol
-        if self.data.scope.protocol().is_none() {
-            return Ok(());
-        }
-        if write.descriptor.protocol.as_deref() != self.data.scope.protocol() {
-            return Err(forbidden!("grant protocol does not match delete protocol",));
-        }
+    /// Verify the grant would allow the `grantee` to update (`Method::Write`)
+    /// or delete (`Method::Delete`) the `records::Write` message's record as
+    /// of the current time, without a message to authorize.
+    pub(crate) async fn permit_modify(
+        &self, grantor: &str, grantee: &str, method: Method, write: &Write,
+        provider: &impl Provider,
+    ) -> Result<()> {
+        let descriptor = Descriptor {
+            interface: Interface::Records,
+            method: method.clone(),
+            message_timestamp: provider.clock().now(),
+        };
+        self.verify(grantor, grantee, &descriptor, provider).await?;
 
-        Ok(())
+        match method {
+            Method::Write => {
+                self.verify_scope(write)?;
+                self.verify_conditions(write)
+            }
+            Method::Delete => self.verify_delete_scope(write),
+            _ => Err(forbidden!("method is not within grant scope")),
+        }
     }
 
     /// Verify that the message is within the allowed time frame of the grant, and
@@ -133,10 +162,10 @@ impl Grant {
     ) -> Result<()> {
         // Check that message is within the grant's time frame
         if timestamp.lt(&self.date_granted) {
-            return Err(forbidden!("grant is not yet active"));
+            return Err(ForbiddenCode::GrantNotActive.into());
         }
         if timestamp.ge(&self.data.date_expires) {
-            return Err(forbidden!("grant has expired"));
+            return Err(ForbiddenCode::GrantExpired.into());
         }
 
         // check if grant has been revoked — using latest revocation message
@@ -147,21 +176,42 @@ impl Grant {
         let (entries, _) = store.query(grantor, &query).await?;
         if let Some(oldest) = entries.first().cloned() {
             if oldest.descriptor().message_timestamp.lt(timestamp) {
-                return Err(forbidden!("grant has been revoked"));
+                return Err(ForbiddenCode::GrantRevoked.into());
             }
         }
 
         Ok(())
     }
 
+    /// Verify the grant has not expired as of the `clock`'s current time.
+    ///
+    /// Messages that are not stored (reads, queries, and subscriptions) are
+    /// checked against the current time as well as their `message_timestamp`,
+    /// which is set by the requestor.
+    pub(crate) fn verify_unexpired(&self, clock: &dyn Clock) -> Result<()> {
+        if clock.now().ge(&self.data.date_expires) {
+            return Err(ForbiddenCode::GrantExpired.into());
+        }
+        Ok(())
+    }
+
     pub(crate) fn verify_scope(&self, write: &Write) -> Result<()> {
         let Scope::Records {
             protocol, limited_to, ..
         } = &self.data.scope
         else {
-            return Err(forbidden!("invalid scope: `Records` scope must have protocol set"));
+            return Err(forbidden!("invalid scope: expected a `Records` scope"));
         };
 
+        // schema-scoped grants apply to records of the schema, whatever their
+        // protocol
+        if let Some(RecordsScope::Schema(schema)) = limited_to {
+            return verify_schema(schema, write.descriptor.schema.as_deref());
+        }
+
+        let Some(protocol) = protocol else {
+            return Err(forbidden!("invalid scope: `Records` scope must have protocol set"));
+        };
         if Some(protocol) != write.descriptor.protocol.as_ref() {
             return Err(forbidden!("scope protocol does not match write protocol"));
         }
@@ -180,7 +230,50 @@ impl Grant {
                     return Err(forbidden!("grant and record protocol paths do not match"));
                 }
             }
-            None => {}
+            Some(RecordsScope::Schema(_)) | None => {}
+        }
+
+        Ok(())
+    }
+
+    fn verify_additional(&self, timestamp: &DateTime<Utc>) -> Result<()> {
+        let Some(conditions) = &self.data.conditions else {
+            return Ok(());
+        };
+
+        for condition in &conditions.additional {
+            let met = match condition {
+                GrantCondition::TimeWindow { from_hour, to_hour } => {
+                    let hour = timestamp.hour();
+                    if from_hour <= to_hour {
+                        *from_hour <= hour && hour < *to_hour
+                    } else {
+                        hour >= *from_hour || hour < *to_hour
+                    }
+                }
+            };
+            if !met {
+                return Err(forbidden!("grant condition not met"));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Verify the record being deleted is within the grant's scope.
+    fn verify_delete_scope(&self, write: &Write) -> Result<()> {
+        // must be deleting a record with the same schema for schema-scoped
+        // grants
+        if let Some(schema) = self.data.scope.schema() {
+            return verify_schema(schema, write.descriptor.schema.as_deref());
+        }
+
+        // must be deleting a record with the same protocol
+        if self.data.scope.protocol().is_none() {
+            return Ok(());
+        }
+        if write.descriptor.protocol.as_deref() != self.data.scope.protocol() {
+            return Err(forbidden!("grant protocol does not match delete protocol",));
         }
 
         Ok(())
@@ -208,3 +301,15 @@ impl Grant {
         Ok(())
     }
 }
+
+// Verify the schema of a record, or the schema a query is filtered by, matches
+// the schema a grant is scoped to.
+fn verify_schema(grant_schema: &str, schema: Option<&str>) -> Result<()> {
+    let Some(schema) = schema else {
+        return Err(forbidden!("missing `schema`"));
+    };
+    if utils::uri::clean(grant_schema)? != utils::uri::clean(schema)? {
+        return Err(forbidden!("grant and record schemas do not match"));
+    }
+    Ok(())
+}
@@ -12,8 +12,11 @@
 //! Derived private keys are typically encrypted (using the recipient's public
 //! key) and distributed  ahead of their actual use.
 
+use std::collections::HashMap;
 use std::fmt::{self, Display};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
 use anyhow::anyhow;
 use base64ct::{Base64UrlUnpadded, Encoding};
@@ -105,7 +108,9 @@ pub enum DerivationPath<'a> {
     /// A full path from the root key to the descendant key.
     Full(&'a [String]),
 
-    /// A relative path from the ancestor key to the descendant key.
+    /// A relative path from the ancestor key to the descendant key. Relative
+    /// derivations compose: deriving `a` then `b` yields the same key as
+    /// deriving `a/b` in a single step.
     Relative(&'a [String]),
 }
 
@@ -125,12 +130,10 @@ pub fn derive_jwk(ancestor: DerivedPrivateJwk, path: &DerivationPath) -> Result<
     let sub_path = match path {
         DerivationPath::Full(descendant_path) => {
             // validate initial part of descendant path matches ancestor
-            for (i, segment) in ancestor_path.iter().enumerate() {
-                if segment != &descendant_path[i] {
-                    return Err(unexpected!(
-                        "ancestor and descendant key derivation segments do not match"
-                    ));
-                }
+            if !descendant_path.starts_with(ancestor_path) {
+                return Err(unexpected!(
+                    "ancestor and descendant key derivation segments do not match"
+                ));
             }
 
             // derive keypair for the descendant sub-path, i.e. the difference between
@@ -175,6 +178,91 @@ pub fn derive_jwk(ancestor: DerivedPrivateJwk, path: &DerivationPath) -> Result<
     })
 }
 
+/// A cache of derived keys, allowing repeated derivations of the same key to
+/// be served without repeating the HKDF and X25519 work.
+///
+/// Entries are keyed on the ancestor key's root key ID, derivation scheme,
+/// derivation path, and secret, together with the full derivation path of the
+/// derived key.
+#[derive(Debug, Default)]
+pub struct DerivationCache {
+    entries: Mutex<HashMap<CacheKey, DerivedPrivateJwk>>,
+    hits: AtomicUsize,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct CacheKey {
+    root_key_id: String,
+    derivation_scheme: String,
+    ancestor_path: Vec<String>,
+    ancestor_secret: String,
+    derivation_path: Vec<String>,
+}
+
+impl DerivationCache {
+    /// Returns a new, empty [`DerivationCache`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Derives a descendant private key as for [`derive_jwk`], returning the
+    /// cached key when the same key has previously been derived.
+    ///
+    /// # Errors
+    ///
+    /// This function will fail when [`derive_jwk`] fails.
+    pub fn derive_jwk(
+        &self, ancestor: DerivedPrivateJwk, path: &DerivationPath,
+    ) -> Result<DerivedPrivateJwk> {
+        let ancestor_path = ancestor.derivation_path.clone().unwrap_or_default();
+        let derivation_path = match path {
+            DerivationPath::Full(descendant_path) => descendant_path.to_vec(),
+            DerivationPath::Relative(sub_path) => [ancestor_path.as_slice(), sub_path].concat(),
+        };
+        let key = CacheKey {
+            root_key_id: ancestor.root_key_id.clone(),
+            derivation_scheme: ancestor.derivation_scheme.to_string(),
+            ancestor_path,
+            ancestor_secret: ancestor.derived_private_key.d.clone(),
+            derivation_path,
+        };
+
+        if let Some(derived) = self.entries().get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(derived.clone());
+        }
+
+        let derived = derive_jwk(ancestor, path)?;
+        self.entries().insert(key, derived.clone());
+        Ok(derived)
+    }
+
+    /// The number of derived keys held in the cache.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    /// Whether the cache holds no derived keys.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries().is_empty()
+    }
+
+    /// The number of derivations served from the cache.
+    #[must_use]
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    // a poisoned lock only means another thread panicked mid-insert, which
+    // cannot leave an entry partially written
+    fn entries(&self) -> MutexGuard<'_, HashMap<CacheKey, DerivedPrivateJwk>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
 /// Derives a hardened hierarchical deterministic private key using HKDF
 /// (HMAC-based Extract-and-Expand Key Derivation Function).
 ///
@@ -202,3 +290,73 @@ pub fn derive_key(
 
     Ok(derived_key)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_derivation() {
+        let root_key = DerivedPrivateJwk {
+            root_key_id: "did:example:alice#key-1".to_string(),
+            derivation_scheme: DerivationScheme::ProtocolContext,
+            derivation_path: None,
+            derived_private_key: PrivateKeyJwk {
+                public_key: PublicKeyJwk::default(),
+                d: "8rmFFiUcTjjrL5mgBzWykaH39D64VD0mbDHwILvsu30".to_string(),
+            },
+        };
+        let path = vec!["protocolContext".to_string(), "context_id".to_string()];
+
+        let cache = DerivationCache::new();
+        let first = cache
+            .derive_jwk(root_key.clone(), &DerivationPath::Relative(&path))
+            .expect("should derive");
+        assert_eq!(cache.hits(), 0);
+
+        // the second derivation of the same path is served from the cache
+        let second = cache
+            .derive_jwk(root_key.clone(), &DerivationPath::Relative(&path))
+            .expect("should derive");
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.len(), 1);
+
+        // cached keys are identical to freshly derived keys
+        let uncached =
+            derive_jwk(root_key, &DerivationPath::Relative(&path)).expect("should derive");
+        assert_eq!(first, second);
+        assert_eq!(second, uncached);
+    }
+
+    #[test]
+    fn relative_derivation() {
+        let root_key = DerivedPrivateJwk {
+            root_key_id: "did:example:alice#key-1".to_string(),
+            derivation_scheme: DerivationScheme::ProtocolPath,
+            derivation_path: None,
+            derived_private_key: PrivateKeyJwk {
+                public_key: PublicKeyJwk::default(),
+                d: "8rmFFiUcTjjrL5mgBzWykaH39D64VD0mbDHwILvsu30".to_string(),
+            },
+        };
+        let a = vec!["a".to_string()];
+        let b = vec!["b".to_string()];
+        let full = vec!["a".to_string(), "b".to_string()];
+
+        // derive root -> a -> b one relative step at a time
+        let derived_a =
+            derive_jwk(root_key.clone(), &DerivationPath::Relative(&a)).expect("should derive");
+        assert_eq!(derived_a.derivation_path, Some(a.clone()));
+        let derived_b =
+            derive_jwk(derived_a, &DerivationPath::Relative(&b)).expect("should derive");
+
+        // stepwise derivation is identical to deriving root -> a/b directly
+        let derived_full =
+            derive_jwk(root_key, &DerivationPath::Full(&full)).expect("should derive");
+        assert_eq!(derived_b.derivation_path, Some(full));
+        assert_eq!(derived_b, derived_full);
+
+        // a full path shorter than the ancestor's path cannot be derived
+        derive_jwk(derived_b, &DerivationPath::Full(&a)).expect_err("should not derive");
+    }
+}
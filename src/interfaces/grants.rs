@@ -4,8 +4,8 @@ use anyhow::{Result, anyhow};
 use base64ct::{Base64UrlUnpadded, Encoding};
 use chrono::{DateTime, Duration, Utc};
 
-pub use crate::grants::{Conditions, Publication, RecordsScope, Scope};
-use crate::grants::{GrantData, RequestData, RevocationData};
+pub use crate::grants::{Conditions, GrantCondition, Publication, RecordsScope, Scope};
+use crate::grants::{GrantCondition, GrantData, RequestData, RevocationData};
 use crate::interfaces::records::{Data, ProtocolBuilder, WriteBuilder};
 use crate::protocols::{self};
 use crate::provider::Signer;
@@ -117,6 +117,13 @@ impl<G, C> GrantBuilder<G, C, Unsigned> {
         self
     }
 
+    /// The date-time at which the issued grant will expire.
+    #[must_use]
+    pub const fn expires(mut self, date_expires: DateTime<Utc>) -> Self {
+        self.date_expires = date_expires;
+        self
+    }
+
     /// Specify an ID to use for the permission request.
     #[must_use]
     pub fn request_id(mut self, request_id: impl Into<String>) -> Self {
@@ -140,10 +147,17 @@ impl<G, C> GrantBuilder<G, C, Unsigned> {
 
     /// Specify conditions that must be met when the grant is used.
     #[must_use]
-    pub const fn conditions(mut self, conditions: Conditions) -> Self {
+    pub fn conditions(mut self, conditions: Conditions) -> Self {
         self.conditions = Some(conditions);
         self
     }
+
+    /// Add a condition that must be met when the grant is used.
+    #[must_use]
+    pub fn condition(mut self, condition: GrantCondition) -> Self {
+        self.conditions.get_or_insert_default().additional.push(condition);
+        self
+    }
 }
 
 impl GrantBuilder<Grantee, Scoped, Unsigned> {
@@ -176,8 +190,17 @@ impl<S: Signer> GrantBuilder<Grantee, Scoped, Signed<'_, S>> {
     /// is an issue authorizing the revocation message.
     pub async fn build(self) -> Result<records::Write> {
         let scope = self.scope.0;
-        if scope.interface() == Interface::Records && scope.protocol().is_none() {
-            return Err(anyhow!("`Records` scope must have protocol set"));
+        if scope.interface() == Interface::Records
+            && scope.protocol().is_none()
+            && scope.schema().is_none()
+        {
+            return Err(anyhow!("`Records` scope must have protocol or schema set"));
+        }
+        for condition in self.conditions.iter().flat_map(|c| &c.additional) {
+            let GrantCondition::TimeWindow { from_hour, to_hour } = condition;
+            if *from_hour > 23 || *to_hour > 24 {
+                return Err(anyhow!("time window hours must be between 0 and 24"));
+            }
         }
 
         let grant_bytes = serde_json::to_vec(&GrantData {
@@ -272,7 +295,7 @@ impl<C> RequestBuilder<C, Unsigned> {
 
     /// Specify conditions that must be met when the grant is used.
     #[must_use]
-    pub const fn conditions(mut self, conditions: Conditions) -> Self {
+    pub fn conditions(mut self, conditions: Conditions) -> Self {
         self.conditions = Some(conditions);
         self
     }
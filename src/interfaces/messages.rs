@@ -10,11 +10,10 @@ use anyhow::{Result, anyhow};
 use chrono::{DateTime, Utc};
 
 use crate::authorization::AuthorizationBuilder;
-pub use crate::messages::MessagesFilter;
-use crate::messages::{
-    Query, QueryDescriptor, Read, ReadDescriptor, Subscribe, SubscribeDescriptor,
-};
+pub use crate::messages::{MessagesFilter, Query, QueryReply, Read};
+use crate::messages::{QueryDescriptor, ReadDescriptor, Subscribe, SubscribeDescriptor};
 use crate::provider::Signer;
+use crate::store::Cursor;
 use crate::utils::cid;
 use crate::{Descriptor, Interface, Method};
 
@@ -22,6 +21,10 @@ use crate::{Descriptor, Interface, Method};
 pub struct QueryBuilder<S> {
     message_timestamp: DateTime<Utc>,
     filters: Option<Vec<MessagesFilter>>,
+    cursor: Option<Cursor>,
+    limit: Option<usize>,
+    latest: Option<bool>,
+    since: Option<u64>,
     permission_grant_id: Option<String>,
     signer: S,
 }
@@ -47,6 +50,10 @@ impl QueryBuilder<Unsigned> {
         Self {
             message_timestamp: Utc::now(),
             filters: None,
+            cursor: None,
+            limit: None,
+            latest: None,
+            since: None,
             permission_grant_id: None,
             signer: Unsigned,
         }
@@ -59,6 +66,37 @@ impl QueryBuilder<Unsigned> {
         self
     }
 
+    /// Limit the number of events returned. When more events match, the
+    /// reply includes a cursor for fetching the next page.
+    #[must_use]
+    pub const fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Continue from the cursor returned with a previous page of events.
+    #[must_use]
+    pub fn cursor(mut self, cursor: Cursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    /// Return the `count` most recently logged events, newest first.
+    #[must_use]
+    pub const fn latest(mut self, count: usize) -> Self {
+        self.limit = Some(count);
+        self.latest = Some(true);
+        self
+    }
+
+    /// Only return events logged after the specified watermark, such as the
+    /// `watermark` returned with a previous reply.
+    #[must_use]
+    pub const fn since(mut self, watermark: u64) -> Self {
+        self.since = Some(watermark);
+        self
+    }
+
     /// Specify a permission grant ID to use with the configuration.
     #[must_use]
     pub fn permission_grant_id(mut self, permission_grant_id: impl Into<String>) -> Self {
@@ -77,6 +115,10 @@ impl QueryBuilder<Unsigned> {
 
             message_timestamp: self.message_timestamp,
             filters: self.filters,
+            cursor: self.cursor,
+            limit: self.limit,
+            latest: self.latest,
+            since: self.since,
             permission_grant_id: self.permission_grant_id,
         }
     }
@@ -100,7 +142,10 @@ impl<S: Signer> QueryBuilder<Signed<'_, S>> {
                 message_timestamp: self.message_timestamp,
             },
             filters: self.filters.unwrap_or_default(),
-            cursor: None,
+            cursor: self.cursor,
+            limit: self.limit,
+            latest: self.latest,
+            since: self.since,
         };
 
         // authorization
@@ -117,7 +162,8 @@ impl<S: Signer> QueryBuilder<Signed<'_, S>> {
     }
 }
 
-/// Options to use when creating a permission grant.
+/// Options to use when creating a `MessagesRead` message, used to fetch a
+/// single stored message by CID (e.g. one returned by a `MessagesQuery`).
 pub struct ReadBuilder<M, S> {
     message_timestamp: DateTime<Utc>,
     permission_grant_id: Option<String>,
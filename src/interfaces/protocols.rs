@@ -21,13 +21,15 @@ use chrono::{DateTime, Utc};
 
 use crate::authorization::AuthorizationBuilder;
 pub use crate::protocols::{
-    Action, ActionRule, Actor, Configure, ConfigureDescriptor, Definition, ProtocolType,
-    ProtocolsFilter, Query, QueryDescriptor, RuleSet, Size,
+    Action, ActionRule, Actor, Configure, ConfigureDescriptor, Definition, DeriveRule,
+    PathEncryption, ProtocolType, ProtocolsFilter, Query, QueryDescriptor, RuleSet, Size,
+    Transform,
 };
 use crate::provider::Signer;
 use crate::records::DelegatedGrant;
+use crate::store::Pagination;
 use crate::utils::cid;
-use crate::{Descriptor, Interface, Method, Result, protocols, utils};
+use crate::{Descriptor, Interface, Method, Result, utils};
 
 /// Options to use when creating a permission grant.
 pub struct ConfigureBuilder<D, S> {
@@ -86,6 +88,23 @@ impl ConfigureBuilder<Undefined, Unsigned> {
     }
 }
 
+impl ConfigureBuilder<Defined, Unsigned> {
+    /// Set whether the protocol accepts new writes. Reconfiguring with
+    /// `active(true)` re-enables writes to a deactivated protocol.
+    #[must_use]
+    pub const fn active(mut self, active: bool) -> Self {
+        self.definition.0.active = Some(active);
+        self
+    }
+
+    /// Deactivate the protocol so new writes are rejected while existing
+    /// records remain readable.
+    #[must_use]
+    pub const fn deactivate(self) -> Self {
+        self.active(false)
+    }
+}
+
 impl<D> ConfigureBuilder<D, Unsigned> {
     /// The delegated grant invoked to sign on behalf of the logical author,
     /// who is the grantor of the delegated grant.
@@ -135,7 +154,7 @@ impl<S: Signer> ConfigureBuilder<Defined, Signed<'_, S>> {
             }
         }
 
-        protocols::validate_structure(&definition)?;
+        definition.validate()?;
 
         let descriptor = ConfigureDescriptor {
             base: Descriptor {
@@ -167,6 +186,7 @@ impl<S: Signer> ConfigureBuilder<Defined, Signed<'_, S>> {
 pub struct QueryBuilder<S> {
     message_timestamp: DateTime<Utc>,
     filter: Option<ProtocolsFilter>,
+    pagination: Option<Pagination>,
     permission_grant_id: Option<String>,
     signer: S,
 }
@@ -186,6 +206,7 @@ impl QueryBuilder<Unsigned> {
         Self {
             message_timestamp: Utc::now(),
             filter: None,
+            pagination: None,
             permission_grant_id: None,
             signer: Unsigned,
         }
@@ -194,9 +215,22 @@ impl QueryBuilder<Unsigned> {
     /// Specify a permission grant ID to use with the configuration.
     #[must_use]
     pub fn filter(mut self, protocol: impl Into<String>) -> Self {
-        self.filter = Some(ProtocolsFilter {
-            protocol: protocol.into(),
-        });
+        self.filter.get_or_insert_default().protocol = Some(protocol.into());
+        self
+    }
+
+    /// Filter protocols by their published state. Queries made by anyone
+    /// other than the owner only ever return published protocols.
+    #[must_use]
+    pub fn published(mut self, published: Option<bool>) -> Self {
+        self.filter.get_or_insert_default().published = published;
+        self
+    }
+
+    /// Sets the limit (size) and offset of the resultset pagination cursor.
+    #[must_use]
+    pub fn pagination(mut self, pagination: Pagination) -> Self {
+        self.pagination = Some(pagination);
         self
     }
 
@@ -217,6 +251,7 @@ impl QueryBuilder<Unsigned> {
             signer: Signed(signer),
             message_timestamp: self.message_timestamp,
             filter: self.filter,
+            pagination: self.pagination,
             permission_grant_id: self.permission_grant_id,
         }
     }
@@ -232,6 +267,7 @@ impl QueryBuilder<Unsigned> {
                     message_timestamp: self.message_timestamp,
                 },
                 filter: self.filter,
+                pagination: self.pagination,
             },
             authorization: None,
         }
@@ -252,6 +288,7 @@ impl<S: Signer> QueryBuilder<Signed<'_, S>> {
                 message_timestamp: self.message_timestamp,
             },
             filter: self.filter,
+            pagination: self.pagination,
         };
 
         let mut authorization =
@@ -7,23 +7,30 @@
 //! DWN-baswed apps and services to share datasets with one another. This leads
 //! to improved cross-app experiences for users.
 
-use std::collections::HashMap;
-use std::io::Cursor;
+use std::collections::BTreeMap;
+use std::io::{self, Cursor};
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
 
 use anyhow::{Result, anyhow};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::io::{AllowStdIo, Take};
+use futures::{AsyncRead, AsyncReadExt};
 use vercre_infosec::jose::{Jws, JwsBuilder};
 
 use crate::authorization::{Authorization, AuthorizationBuilder};
-use crate::hd_key::DerivationScheme;
+use crate::hd_key::{self, DerivationPath, DerivationScheme, DerivedPrivateJwk};
+use crate::protocols::{self, Definition};
 use crate::provider::Signer;
 pub use crate::records::{
-    Attestation, DelegatedGrant, DeleteDescriptor, EncryptOptions, Recipient, RecordsFilter,
-    SignaturePayload, Sort, decrypt,
+    Attestation, Bundle, ByteRange, DataReader, DelegatedGrant, DeleteDescriptor, DeletePreview,
+    EncryptOptions, GroupBy, Keyring, ReadBatch, Recipient, RecordsFilter, SignaturePayload, Sort,
+    SortKey, StartsWith, Tag, TagFilter, TextFilter, decrypt, decrypt_stream, decrypt_with_keyring,
+    export, export_record, import, pin, preview_delete, verify_write,
 };
 use crate::records::{
-    Delete, EncryptionProperty, Query, QueryDescriptor, Read, ReadDescriptor, Subscribe,
-    SubscribeDescriptor, Tag, Write, WriteDescriptor,
+    Delete, Encrypted, EncryptionProperty, Query, QueryDescriptor, Read, ReadDescriptor, Subscribe,
+    SubscribeDescriptor, Write, WriteDescriptor,
 };
 use crate::store::Pagination;
 use crate::utils::cid;
@@ -34,6 +41,7 @@ pub struct DeleteBuilder<R, S> {
     message_timestamp: DateTime<Utc>,
     record_id: R,
     prune: Option<bool>,
+    retain_for: Option<TimeDelta>,
     permission_grant_id: Option<String>,
     protocol_role: Option<String>,
     signer: S,
@@ -60,6 +68,7 @@ impl DeleteBuilder<NoRecordId, Unsigned> {
             message_timestamp: Utc::now(),
             record_id: NoRecordId,
             prune: None,
+            retain_for: None,
             permission_grant_id: None,
             protocol_role: None,
             signer: Unsigned,
@@ -74,6 +83,7 @@ impl DeleteBuilder<NoRecordId, Unsigned> {
 
             message_timestamp: self.message_timestamp,
             prune: self.prune,
+            retain_for: self.retain_for,
             permission_grant_id: self.permission_grant_id,
             protocol_role: self.protocol_role,
             signer: self.signer,
@@ -82,13 +92,22 @@ impl DeleteBuilder<NoRecordId, Unsigned> {
 }
 
 impl<R> DeleteBuilder<R, Unsigned> {
-    /// Specifies the permission grant ID.
+    /// Specifies whether the record's descendants should also be deleted.
     #[must_use]
     pub const fn prune(mut self, prune: bool) -> Self {
         self.prune = Some(prune);
         self
     }
 
+    /// Retain the delete's tombstone for the specified period, after which
+    /// it can be removed by garbage collection. Without a retention period,
+    /// the tombstone is retained indefinitely.
+    #[must_use]
+    pub const fn retain_for(mut self, retain_for: TimeDelta) -> Self {
+        self.retain_for = Some(retain_for);
+        self
+    }
+
     /// Specifies the permission grant ID.
     #[must_use]
     pub fn permission_grant_id(mut self, permission_grant_id: impl Into<String>) -> Self {
@@ -117,6 +136,7 @@ impl DeleteBuilder<RecordId, Unsigned> {
             message_timestamp: self.message_timestamp,
             record_id: self.record_id,
             prune: self.prune,
+            retain_for: self.retain_for,
             permission_grant_id: self.permission_grant_id,
             protocol_role: self.protocol_role,
         }
@@ -138,6 +158,7 @@ impl<S: Signer> DeleteBuilder<RecordId, Signed<'_, S>> {
             },
             record_id: self.record_id.0,
             prune: self.prune.unwrap_or(false),
+            retain_until: self.retain_for.map(|period| self.message_timestamp + period),
         };
 
         let mut auth_builder =
@@ -162,7 +183,13 @@ pub struct QueryBuilder<F, S> {
     message_timestamp: DateTime<Utc>,
     filter: F,
     date_sort: Option<Sort>,
+    sort: Option<Vec<SortKey>>,
     pagination: Option<Pagination>,
+    max_response_bytes: Option<usize>,
+    count_only: Option<bool>,
+    group_by: Option<GroupBy>,
+    include_initial_write: Option<bool>,
+    no_data: Option<bool>,
     protocol_role: Option<String>,
     permission_grant_id: Option<String>,
     delegated_grant: Option<DelegatedGrant>,
@@ -198,7 +225,13 @@ impl QueryBuilder<Unfiltered, Unsigned> {
             filter: Unfiltered,
             signer: Unsigned,
             date_sort: None,
+            sort: None,
             pagination: None,
+            max_response_bytes: None,
+            count_only: None,
+            group_by: None,
+            include_initial_write: None,
+            no_data: None,
             protocol_role: None,
             permission_grant_id: None,
             delegated_grant: None,
@@ -212,7 +245,13 @@ impl QueryBuilder<Unfiltered, Unsigned> {
             filter: Filtered(filter),
             message_timestamp: self.message_timestamp,
             date_sort: self.date_sort,
+            sort: self.sort,
             pagination: self.pagination,
+            max_response_bytes: self.max_response_bytes,
+            count_only: self.count_only,
+            group_by: self.group_by,
+            include_initial_write: self.include_initial_write,
+            no_data: self.no_data,
             signer: self.signer,
             protocol_role: self.protocol_role,
             permission_grant_id: self.permission_grant_id,
@@ -244,13 +283,22 @@ impl<'a, F> QueryBuilder<F, Unsigned> {
         self
     }
 
-    /// Determines which date to use when sorting query results.
+    /// Determines which date to use when sorting query results. When not set,
+    /// results are sorted by message timestamp, oldest first.
     #[must_use]
     pub const fn date_sort(mut self, date_sort: Sort) -> Self {
         self.date_sort = Some(date_sort);
         self
     }
 
+    /// Add a key to the composite sort order of query results. Keys are
+    /// applied in the order added.
+    #[must_use]
+    pub fn add_sort(mut self, sort_key: SortKey) -> Self {
+        self.sort.get_or_insert_with(Vec::new).push(sort_key);
+        self
+    }
+
     /// Sets the limit (size) and offset of the resultset pagination cursor.
     #[must_use]
     pub fn pagination(mut self, pagination: Pagination) -> Self {
@@ -258,6 +306,47 @@ impl<'a, F> QueryBuilder<F, Unsigned> {
         self
     }
 
+    /// Limits the total (serialized) size of entries returned in a page of
+    /// results. A continuation cursor is returned when the page is truncated.
+    #[must_use]
+    pub const fn max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = Some(max_response_bytes);
+        self
+    }
+
+    /// Return only the number of matching records rather than the records
+    /// themselves.
+    #[must_use]
+    pub const fn count_only(mut self, count_only: bool) -> Self {
+        self.count_only = Some(count_only);
+        self
+    }
+
+    /// Return the number of matching records for each distinct value of the
+    /// `group_by` field rather than the records themselves. Records without a
+    /// value for the field are not counted.
+    #[must_use]
+    pub const fn group_by(mut self, group_by: GroupBy) -> Self {
+        self.group_by = Some(group_by);
+        self
+    }
+
+    /// Include the initial write of updated records in the reply. When
+    /// `false`, only each record's latest write is returned.
+    #[must_use]
+    pub const fn include_initial_write(mut self, include: bool) -> Self {
+        self.include_initial_write = Some(include);
+        self
+    }
+
+    /// Return only record metadata, omitting any `encoded_data` from
+    /// returned entries.
+    #[must_use]
+    pub const fn no_data(mut self, no_data: bool) -> Self {
+        self.no_data = Some(no_data);
+        self
+    }
+
     /// Logically (from user POV), sign the record.
     ///
     /// At this point, the builder simply captures the signer for use in the
@@ -270,7 +359,13 @@ impl<'a, F> QueryBuilder<F, Unsigned> {
             message_timestamp: self.message_timestamp,
             filter: self.filter,
             date_sort: self.date_sort,
+            sort: self.sort,
             pagination: self.pagination,
+            max_response_bytes: self.max_response_bytes,
+            count_only: self.count_only,
+            group_by: self.group_by,
+            include_initial_write: self.include_initial_write,
+            no_data: self.no_data,
             protocol_role: self.protocol_role,
             permission_grant_id: self.permission_grant_id,
             delegated_grant: self.delegated_grant,
@@ -295,7 +390,13 @@ impl QueryBuilder<Filtered, Unsigned> {
                 },
                 filter: self.filter.0.normalize()?,
                 date_sort: self.date_sort,
+                sort: self.sort,
                 pagination: self.pagination,
+                max_response_bytes: self.max_response_bytes,
+                count_only: self.count_only,
+                group_by: self.group_by,
+                include_initial_write: self.include_initial_write,
+                no_data: self.no_data,
             },
             authorization: None,
         })
@@ -319,7 +420,13 @@ impl<S: Signer> QueryBuilder<Filtered, Signed<'_, S>> {
             },
             filter: self.filter.0.normalize()?,
             date_sort: self.date_sort,
+            sort: self.sort,
             pagination: self.pagination,
+            max_response_bytes: self.max_response_bytes,
+            count_only: self.count_only,
+            group_by: self.group_by,
+            include_initial_write: self.include_initial_write,
+            no_data: self.no_data,
         };
 
         let mut auth_builder =
@@ -347,6 +454,12 @@ impl<S: Signer> QueryBuilder<Filtered, Signed<'_, S>> {
 pub struct ReadBuilder<F, S> {
     message_timestamp: DateTime<Utc>,
     filter: F,
+    date_sort: Option<Sort>,
+    include_permissions: Option<bool>,
+    range: Option<ByteRange>,
+    verify_integrity: Option<bool>,
+    as_of: Option<DateTime<Utc>>,
+    defer_data: bool,
     permission_grant_id: Option<String>,
     protocol_role: Option<String>,
     delegated_grant: Option<DelegatedGrant>,
@@ -366,6 +479,12 @@ impl ReadBuilder<Unfiltered, Unsigned> {
         Self {
             message_timestamp: Utc::now(),
             filter: Unfiltered,
+            date_sort: None,
+            include_permissions: None,
+            range: None,
+            verify_integrity: None,
+            as_of: None,
+            defer_data: false,
             permission_grant_id: None,
             protocol_role: None,
             delegated_grant: None,
@@ -379,12 +498,29 @@ impl ReadBuilder<Unfiltered, Unsigned> {
         ReadBuilder {
             message_timestamp: self.message_timestamp,
             filter: Filtered(filter),
+            date_sort: self.date_sort,
+            include_permissions: self.include_permissions,
+            range: self.range,
+            verify_integrity: self.verify_integrity,
+            as_of: self.as_of,
+            defer_data: self.defer_data,
             permission_grant_id: self.permission_grant_id,
             protocol_role: self.protocol_role,
             delegated_grant: self.delegated_grant,
             signer: Unsigned,
         }
     }
+
+    /// Read the most recent record matching the filter. Unless otherwise
+    /// specified using `date_sort`, records are sorted by `message_timestamp`
+    /// with the message CID used as a tie-breaker (the larger CID wins).
+    #[must_use]
+    pub fn latest(self, filter: RecordsFilter) -> ReadBuilder<Filtered, Unsigned> {
+        let date_sort = self.date_sort.clone().unwrap_or(Sort::TimestampDesc);
+        let mut builder = self.filter(filter);
+        builder.date_sort = Some(date_sort);
+        builder
+    }
 }
 
 impl<'a, F> ReadBuilder<F, Unsigned> {
@@ -409,6 +545,58 @@ impl<'a, F> ReadBuilder<F, Unsigned> {
         self
     }
 
+    /// Determines which date to use when selecting the latest record.
+    #[must_use]
+    pub fn date_sort(mut self, date_sort: Sort) -> Self {
+        self.date_sort = Some(date_sort);
+        self
+    }
+
+    /// Include whether the reader is authorized to update and delete the
+    /// record in the read reply.
+    #[must_use]
+    pub const fn include_permissions(mut self) -> Self {
+        self.include_permissions = Some(true);
+        self
+    }
+
+    /// Read only the specified byte range of the record's data. Both `start`
+    /// and `end` are inclusive. When `end` is `None`, the range extends to the
+    /// end of the data.
+    #[must_use]
+    pub const fn range(mut self, start: u64, end: Option<u64>) -> Self {
+        self.range = Some(ByteRange { start, end });
+        self
+    }
+
+    /// Verify the record's data against its `data_cid` as it is read, failing
+    /// the read if the data has been corrupted in the store.
+    #[must_use]
+    pub const fn verify_integrity(mut self, verify: bool) -> Self {
+        self.verify_integrity = Some(verify);
+        self
+    }
+
+    /// Read the version of the record that was current as of the specified
+    /// time. Besides the initial write, superseded versions are only retained
+    /// up to the provider's [`retained_versions`] limit, and without their
+    /// data unless it was encoded with the message.
+    ///
+    /// [`retained_versions`]: crate::provider::Provider::retained_versions
+    #[must_use]
+    pub const fn as_of(mut self, as_of: DateTime<Utc>) -> Self {
+        self.as_of = Some(as_of);
+        self
+    }
+
+    /// Defer reading the record's data until it is opened using the reply
+    /// entry's `open_data` method, rather than reading it into the reply.
+    #[must_use]
+    pub const fn defer_data(mut self) -> Self {
+        self.defer_data = true;
+        self
+    }
+
     /// The delegated grant used with this record.
     #[must_use]
     pub fn delegated_grant(mut self, delegated_grant: DelegatedGrant) -> Self {
@@ -425,6 +613,12 @@ impl<'a, F> ReadBuilder<F, Unsigned> {
         ReadBuilder {
             message_timestamp: self.message_timestamp,
             filter: self.filter,
+            date_sort: self.date_sort,
+            include_permissions: self.include_permissions,
+            range: self.range,
+            verify_integrity: self.verify_integrity,
+            as_of: self.as_of,
+            defer_data: self.defer_data,
             permission_grant_id: self.permission_grant_id,
             protocol_role: self.protocol_role,
             delegated_grant: self.delegated_grant,
@@ -444,11 +638,17 @@ impl ReadBuilder<Filtered, Unsigned> {
                 message_timestamp: self.message_timestamp,
             },
             filter: self.filter.0,
+            date_sort: self.date_sort,
+            include_permissions: self.include_permissions,
+            range: self.range,
+            verify_integrity: self.verify_integrity,
+            as_of: self.as_of,
         };
 
         Read {
             descriptor,
             authorization: None,
+            defer_data: self.defer_data,
         }
     }
 }
@@ -468,6 +668,11 @@ impl<S: Signer> ReadBuilder<Filtered, Signed<'_, S>> {
                 message_timestamp: self.message_timestamp,
             },
             filter: self.filter.0.normalize()?,
+            date_sort: self.date_sort,
+            include_permissions: self.include_permissions,
+            range: self.range,
+            verify_integrity: self.verify_integrity,
+            as_of: self.as_of,
         };
 
         let mut auth_builder =
@@ -485,10 +690,129 @@ impl<S: Signer> ReadBuilder<Filtered, Signed<'_, S>> {
         Ok(Read {
             descriptor,
             authorization: Some(auth_builder.build(self.signer.0).await?),
+            defer_data: self.defer_data,
         })
     }
 }
 
+/// Options to use when creating a batch of `RecordsRead` messages.
+pub struct ReadBatchBuilder<S> {
+    record_ids: Vec<String>,
+    permission_grant_id: Option<String>,
+    protocol_role: Option<String>,
+    delegated_grant: Option<DelegatedGrant>,
+    signer: S,
+}
+
+impl Default for ReadBatchBuilder<Unsigned> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReadBatchBuilder<Unsigned> {
+    /// Returns a new [`ReadBatchBuilder`]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            record_ids: Vec::new(),
+            permission_grant_id: None,
+            protocol_role: None,
+            delegated_grant: None,
+            signer: Unsigned,
+        }
+    }
+
+    /// Add a record to read.
+    #[must_use]
+    pub fn add_record_id(mut self, record_id: impl Into<String>) -> Self {
+        self.record_ids.push(record_id.into());
+        self
+    }
+
+    /// Add a set of records to read.
+    #[must_use]
+    pub fn record_ids(mut self, record_ids: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.record_ids.extend(record_ids.into_iter().map(Into::into));
+        self
+    }
+
+    /// Specifies the permission grant ID used for each read.
+    #[must_use]
+    pub fn permission_grant_id(mut self, permission_grant_id: impl Into<String>) -> Self {
+        self.permission_grant_id = Some(permission_grant_id.into());
+        self
+    }
+
+    /// Specify a protocol role used for each read.
+    #[must_use]
+    pub fn protocol_role(mut self, protocol_role: impl Into<String>) -> Self {
+        self.protocol_role = Some(protocol_role.into());
+        self
+    }
+
+    /// The delegated grant used for each read.
+    #[must_use]
+    pub fn delegated_grant(mut self, delegated_grant: DelegatedGrant) -> Self {
+        self.delegated_grant = Some(delegated_grant);
+        self
+    }
+
+    /// Logically (from user POV), sign the reads.
+    ///
+    /// At this point, the builder simply captures the signer for use in the
+    /// final build step.
+    #[must_use]
+    pub fn sign<S: Signer>(self, signer: &S) -> ReadBatchBuilder<Signed<'_, S>> {
+        ReadBatchBuilder {
+            record_ids: self.record_ids,
+            permission_grant_id: self.permission_grant_id,
+            protocol_role: self.protocol_role,
+            delegated_grant: self.delegated_grant,
+            signer: Signed(signer),
+        }
+    }
+
+    /// Build an anonymous (unsigned) batch of reads.
+    #[must_use]
+    pub fn build(self) -> ReadBatch {
+        let reads = self
+            .record_ids
+            .into_iter()
+            .map(|id| ReadBuilder::new().filter(RecordsFilter::new().record_id(id)).build())
+            .collect();
+        ReadBatch { reads }
+    }
+}
+
+impl<S: Signer> ReadBatchBuilder<Signed<'_, S>> {
+    /// Build the batch of reads, signing each read.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail when there is an issue authorizing any of the
+    /// reads.
+    pub async fn build(self) -> Result<ReadBatch> {
+        let mut reads = Vec::with_capacity(self.record_ids.len());
+
+        for record_id in self.record_ids {
+            let mut builder = ReadBuilder::new().filter(RecordsFilter::new().record_id(record_id));
+            if let Some(id) = &self.permission_grant_id {
+                builder = builder.permission_grant_id(id);
+            }
+            if let Some(role) = &self.protocol_role {
+                builder = builder.protocol_role(role);
+            }
+            if let Some(delegated_grant) = &self.delegated_grant {
+                builder = builder.delegated_grant(delegated_grant.clone());
+            }
+            reads.push(builder.sign(self.signer.0).build().await?);
+        }
+
+        Ok(ReadBatch { reads })
+    }
+}
+
 /// Options to use when creating a permission grant.
 pub struct SubscribeBuilder<F, S> {
     message_timestamp: DateTime<Utc>,
@@ -632,11 +956,13 @@ pub struct WriteBuilder<'a, O, A, S> {
     message_timestamp: DateTime<Utc>,
     recipient: Option<String>,
     protocol: Option<ProtocolBuilder<'a>>,
+    protocol_definition: Option<&'a Definition>,
     schema: Option<String>,
-    tags: Option<HashMap<String, Tag>>,
+    data_reference: bool,
+    tags: Option<BTreeMap<String, Tag>>,
     record_id: Option<String>,
     data: Option<Data>,
-    data_format: String,
+    data_format: Option<String>,
     date_created: DateTime<Utc>,
     published: Option<bool>,
     date_published: Option<DateTime<Utc>>,
@@ -644,10 +970,15 @@ pub struct WriteBuilder<'a, O, A, S> {
     permission_grant_id: Option<String>,
     delegated_grant: Option<DelegatedGrant>,
     existing: Option<Write>,
+    expected_parent_cid: Option<String>,
+    create_only: Option<bool>,
+    idempotency_key: Option<String>,
     encryption: Option<EncryptionProperty>,
+    protocol_encryption: Option<(&'a Definition, &'a Keyring)>,
     origin: O,
     attesters: A,
     signer: S,
+    owner_signer: Option<S>,
 }
 
 impl Default for WriteBuilder<'_, New, Unattested, Unsigned> {
@@ -696,6 +1027,23 @@ pub enum Data {
         /// otherwise should be left unset.
         data_size: usize,
     },
+
+    /// Data is streamed from an asynchronous reader when the message is
+    /// handled, rather than being buffered in memory.
+    ///
+    /// As the message is signed before the data is read, the data's CID and
+    /// size must be provided. These can be computed without buffering using
+    /// [`cid::from_async_reader`].
+    Reader {
+        /// Reader providing the data.
+        reader: DataReader,
+
+        /// CID of the data to be read.
+        data_cid: String,
+
+        /// Size of the data to be read, in bytes.
+        data_size: usize,
+    },
 }
 
 impl Default for Data {
@@ -710,6 +1058,73 @@ impl From<Vec<u8>> for Data {
     }
 }
 
+impl Data {
+    /// Create a `Data::Reader` streaming data from an asynchronous reader,
+    /// such as a multipart upload, when the message is handled.
+    ///
+    /// As the message is signed before the data is read, the data's CID must
+    /// be provided. The CID computed by [`cid::from_async_reader`] is
+    /// identical to that computed for the same bytes provided using
+    /// [`Data::from`].
+    ///
+    /// No more than `expected_size` bytes are read: reading fails as soon as
+    /// the reader provides more, or ends before providing `expected_size`.
+    #[must_use]
+    pub fn from_async_read(
+        reader: impl AsyncRead + Send + Unpin + 'static, data_cid: impl Into<String>,
+        expected_size: usize,
+    ) -> Self {
+        Self::Reader {
+            reader: DataReader::new(ExpectedSize {
+                reader: reader.take(expected_size as u64 + 1),
+                expected: expected_size,
+                read: 0,
+            }),
+            data_cid: data_cid.into(),
+            data_size: expected_size,
+        }
+    }
+
+    /// Convert the data into an asynchronous reader. Returns `None` for
+    /// `Data::Cid`, or when a `Data::Reader` has already been consumed.
+    #[must_use]
+    pub fn into_async_read(self) -> Option<impl AsyncRead + Send + Unpin> {
+        let reader: Box<dyn AsyncRead + Send + Unpin> = match self {
+            Self::Stream(cursor) => Box::new(AllowStdIo::new(cursor)),
+            Self::Bytes(bytes) => Box::new(AllowStdIo::new(Cursor::new(bytes))),
+            Self::Reader { reader, .. } => reader.take()?,
+            Self::Cid { .. } => return None,
+        };
+        Some(reader)
+    }
+}
+
+// Reads data from the inner reader, failing once more than `expected` bytes
+// are read, or the reader ends having read fewer.
+struct ExpectedSize<R> {
+    reader: Take<R>,
+    expected: usize,
+    read: usize,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ExpectedSize<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = ready!(Pin::new(&mut self.reader).poll_read(cx, buf))?;
+        self.read += n;
+
+        let ended = n == 0 && !buf.is_empty();
+        if self.read > self.expected || (ended && self.read < self.expected) {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("data size does not match expected size {}", self.expected),
+            )));
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
 // State 'guards' for the WriteBuilder typestate pattern.
 
 /// The WriteBuilder is in a new state.
@@ -737,7 +1152,7 @@ impl WriteBuilder<'_, New, Unattested, Unsigned> {
             message_timestamp: now,
             date_created: now,
             data: None,
-            data_format: "application/json".to_string(),
+            data_format: Some("application/json".to_string()),
             signer: Unsigned,
             attesters: Unattested,
             origin: New,
@@ -752,7 +1167,14 @@ impl WriteBuilder<'_, New, Unattested, Unsigned> {
             permission_grant_id: None,
             delegated_grant: None,
             existing: None,
+            expected_parent_cid: None,
+            create_only: None,
+            idempotency_key: None,
             encryption: None,
+            protocol_encryption: None,
+            protocol_definition: None,
+            data_reference: false,
+            owner_signer: None,
         }
     }
 }
@@ -760,6 +1182,11 @@ impl WriteBuilder<'_, New, Unattested, Unsigned> {
 /// Create a [`Write`] record from an existing record.
 impl WriteBuilder<'_, Existing, Unattested, Unsigned> {
     /// Returns a new [`WriteBuilder`] based on an existing `Write` record.
+    ///
+    /// All descriptor fields — including tags, `published`, and encryption
+    /// settings — are carried forward to the update, with only those fields
+    /// explicitly set on the builder being overridden. Tags added using
+    /// `add_tag` are merged with the existing tags.
     #[must_use]
     pub fn from(existing: Write) -> Self {
         let mut existing = existing;
@@ -771,6 +1198,7 @@ impl WriteBuilder<'_, Existing, Unattested, Unsigned> {
             date_created: existing.descriptor.date_created,
             data: None,
             data_format: existing.descriptor.data_format.clone(),
+            tags: existing.descriptor.tags.clone(),
             existing: Some(existing),
             origin: Existing,
             signer: Unsigned,
@@ -778,14 +1206,20 @@ impl WriteBuilder<'_, Existing, Unattested, Unsigned> {
             recipient: None,
             protocol: None,
             schema: None,
-            tags: None,
             record_id: None,
             published: None,
             date_published: None,
             protocol_role: None,
             permission_grant_id: None,
             delegated_grant: None,
+            expected_parent_cid: None,
+            create_only: None,
+            idempotency_key: None,
             encryption: None,
+            protocol_encryption: None,
+            protocol_definition: None,
+            data_reference: false,
+            owner_signer: None,
         }
     }
 }
@@ -801,6 +1235,27 @@ impl<'a> WriteBuilder<'a, New, Unattested, Unsigned> {
         self
     }
 
+    /// Set a protocol for the record from a protocol `definition`, checking
+    /// `protocol_path` exists in the definition's structure when the message
+    /// is built. Invalid paths are caught before the message is sent rather
+    /// than being rejected by the web node.
+    ///
+    /// The `parent_context_id` is required for records nested below the root
+    /// of the protocol structure.
+    #[must_use]
+    pub fn protocol_from(
+        mut self, definition: &'a Definition, protocol_path: &'a str,
+        parent_context_id: Option<String>,
+    ) -> Self {
+        self.protocol = Some(ProtocolBuilder {
+            protocol: &definition.protocol,
+            protocol_path,
+            parent_context_id,
+        });
+        self.protocol_definition = Some(definition);
+        self
+    }
+
     /// Specify a schema to use with the record.
     #[must_use]
     pub fn schema(mut self, schema: impl Into<String>) -> Self {
@@ -814,13 +1269,26 @@ impl<'a> WriteBuilder<'a, New, Unattested, Unsigned> {
         self.recipient = Some(recipient.into());
         self
     }
+
+    /// Reference data already stored by the web node for another record,
+    /// rather than providing the data again. The web node rejects the write
+    /// when no data matching `data_cid` and `data_size` is found.
+    #[must_use]
+    pub fn reference_data(mut self, data_cid: impl Into<String>, data_size: usize) -> Self {
+        self.data = Some(Data::Cid {
+            data_cid: data_cid.into(),
+            data_size,
+        });
+        self.data_reference = true;
+        self
+    }
 }
 
 /// State: Unattested, and Unsigned.
 ///
 ///  Mutable properties properties are able to be set for both new and existing
 /// `Write` records.
-impl<O> WriteBuilder<'_, O, Unattested, Unsigned> {
+impl<'a, O> WriteBuilder<'a, O, Unattested, Unsigned> {
     /// Entry data as a CID or raw bytes.
     #[must_use]
     pub fn data(mut self, data: Data) -> Self {
@@ -831,7 +1299,15 @@ impl<O> WriteBuilder<'_, O, Unattested, Unsigned> {
     /// The record's MIME type. Defaults to `application/json`.
     #[must_use]
     pub fn data_format(mut self, data_format: impl Into<String>) -> Self {
-        self.data_format = data_format.into();
+        self.data_format = Some(data_format.into());
+        self
+    }
+
+    /// Omit the record's MIME type. Only permitted for protocol records whose
+    /// type does not declare any `dataFormats`.
+    #[must_use]
+    pub fn omit_data_format(mut self) -> Self {
+        self.data_format = None;
         self
     }
 
@@ -842,10 +1318,17 @@ impl<O> WriteBuilder<'_, O, Unattested, Unsigned> {
         self
     }
 
+    /// Set the record's tags, replacing any previously added.
+    #[must_use]
+    pub fn tags(mut self, tags: BTreeMap<String, Tag>) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
     /// Add a tag to the record.
     #[must_use]
     pub fn add_tag(mut self, name: impl Into<String>, tag: Tag) -> Self {
-        self.tags.get_or_insert_with(HashMap::new).insert(name.into(), tag);
+        self.tags.get_or_insert_with(BTreeMap::new).insert(name.into(), tag);
         self
     }
 
@@ -877,6 +1360,32 @@ impl<O> WriteBuilder<'_, O, Unattested, Unsigned> {
         self
     }
 
+    /// The CID of the write this update is derived from. The update will be
+    /// rejected with a conflict unless this is the record's latest write.
+    #[must_use]
+    pub fn expected_parent_cid(mut self, expected_parent_cid: impl Into<String>) -> Self {
+        self.expected_parent_cid = Some(expected_parent_cid.into());
+        self
+    }
+
+    /// Only create the record: the write will be rejected with a conflict
+    /// if a record with the same ID already exists.
+    #[must_use]
+    pub const fn create_only(mut self, create_only: bool) -> Self {
+        self.create_only = Some(create_only);
+        self
+    }
+
+    /// An application-level key identifying the write. Should the write be
+    /// retried (with a new timestamp) using the same key, the retry is
+    /// answered with the original write's reply without creating a new
+    /// record.
+    #[must_use]
+    pub fn idempotency_key(mut self, idempotency_key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(idempotency_key.into());
+        self
+    }
+
     /// The encryption properties for the record.
     #[must_use]
     pub fn encryption(mut self, encryption: EncryptionProperty) -> Self {
@@ -884,6 +1393,21 @@ impl<O> WriteBuilder<'_, O, Unattested, Unsigned> {
         self
     }
 
+    /// Encrypt the record's data using the encryption declared by the
+    /// protocol `definition` for the record's protocol path.
+    ///
+    /// When built, the data is encrypted for both the protocol path derived
+    /// key and the protocol context derived key, the latter derived from the
+    /// `keyring`'s root key for the protocol path. Replaces any encryption
+    /// properties set using [`WriteBuilder::encryption`].
+    #[must_use]
+    pub const fn encrypt_for_protocol(
+        mut self, definition: &'a Definition, keyring: &'a Keyring,
+    ) -> Self {
+        self.protocol_encryption = Some((definition, keyring));
+        self
+    }
+
     // ----------------------------------------------------------------
     // Methods enabled soley for testing
     // ----------------------------------------------------------------
@@ -914,11 +1438,12 @@ impl<O> WriteBuilder<'_, O, Unattested, Unsigned> {
 
 /// State: Unencrypted and Unsigned.
 impl<'a, O, A> WriteBuilder<'a, O, A, Unsigned> {
-    /// Logically (from user POV), have an attester sign the record.
+    /// Logically (from user POV), have one or more attesters sign the record.
     ///
-    /// At this point, the builder simply captures the attester for use in the
-    /// final build step. Can only be done if the content hasn't been signed
-    /// or encrypted.
+    /// At this point, the builder simply captures the attesters for use in the
+    /// final build step, where each attester's signature is added to the
+    /// record's attestation. Can only be done if the content hasn't been
+    /// signed or encrypted.
     #[must_use]
     pub fn attest<S: Signer>(
         self, attesters: &'a [&'a S],
@@ -939,10 +1464,17 @@ impl<'a, O, A> WriteBuilder<'a, O, A, Unsigned> {
             protocol_role: self.protocol_role,
             permission_grant_id: self.permission_grant_id,
             delegated_grant: self.delegated_grant,
+            expected_parent_cid: self.expected_parent_cid,
+            create_only: self.create_only,
+            idempotency_key: self.idempotency_key,
             encryption: self.encryption,
+            protocol_encryption: self.protocol_encryption,
+            protocol_definition: self.protocol_definition,
+            data_reference: self.data_reference,
             existing: self.existing,
             origin: self.origin,
             signer: self.signer,
+            owner_signer: self.owner_signer,
         }
     }
 }
@@ -972,15 +1504,38 @@ impl<'a, O, A> WriteBuilder<'a, O, A, Unsigned> {
             protocol_role: self.protocol_role,
             permission_grant_id: self.permission_grant_id,
             delegated_grant: self.delegated_grant,
+            expected_parent_cid: self.expected_parent_cid,
+            create_only: self.create_only,
+            idempotency_key: self.idempotency_key,
             encryption: self.encryption,
+            protocol_encryption: self.protocol_encryption,
+            protocol_definition: self.protocol_definition,
+            data_reference: self.data_reference,
             existing: self.existing,
             origin: self.origin,
             attesters: self.attesters,
+            owner_signer: None,
         }
     }
 }
 
 // State: Signed.
+impl<'a, O, A, S: Signer> WriteBuilder<'a, O, A, Signed<'a, S>> {
+    /// Counter-sign the record as the web node owner, adding the owner's
+    /// signature alongside the author's when the message is built.
+    ///
+    /// Used when the owner retains a record they did not author: the owner
+    /// signature authorizes the write, while the author remains the record's
+    /// author. Once stored, the record is read and queried with the same rules
+    /// as any other record on the owner's node. The owner can let anyone else
+    /// read it by counter-signing their `Read` or `Query` (see
+    /// [`Read::sign_as_owner`] and [`Query::sign_as_owner`]).
+    #[must_use]
+    pub const fn sign_as_owner(mut self, owner: &'a S) -> Self {
+        self.owner_signer = Some(Signed(owner));
+        self
+    }
+}
 
 /// Builder is ready to build once the `sign` step is complete (i.e. the Signer
 /// is set).
@@ -1009,6 +1564,15 @@ impl<O, A, S: Signer> WriteBuilder<'_, O, A, Signed<'_, S>> {
                 write.record_id = record_id;
             }
             if let Some(settings) = self.protocol.clone() {
+                if let Some(definition) = self.protocol_definition
+                    && protocols::rule_set(settings.protocol_path, &definition.structure).is_none()
+                {
+                    return Err(anyhow!(
+                        "protocol path {} not found in protocol {}",
+                        settings.protocol_path,
+                        definition.protocol
+                    ));
+                }
                 let normalized = utils::uri::clean(settings.protocol)?;
                 write.descriptor.protocol = Some(normalized);
                 write.descriptor.protocol_path = Some(settings.protocol_path.to_string());
@@ -1029,6 +1593,13 @@ impl<O, A, S: Signer> WriteBuilder<'_, O, A, Signed<'_, S>> {
         // mutable properties
         write.descriptor.base.message_timestamp = self.message_timestamp;
         write.descriptor.data_format.clone_from(&self.data_format);
+        write.descriptor.expected_parent_cid.clone_from(&self.expected_parent_cid);
+        write.descriptor.create_only = self.create_only;
+        write.descriptor.idempotency_key.clone_from(&self.idempotency_key);
+        write.descriptor.data_reference = self.data_reference.then_some(true);
+
+        // an attestation only applies to the descriptor it was made over
+        write.attestation = None;
 
         // tags
         if let Some(tags) = self.tags.clone() {
@@ -1039,7 +1610,8 @@ impl<O, A, S: Signer> WriteBuilder<'_, O, A, Signed<'_, S>> {
         if let Some(published) = self.published {
             write.descriptor.published = Some(published);
 
-            // set/unset `date_published`
+            // set/unset `date_published`, defaulting to the time the record
+            // transitions to published
             if published {
                 write.descriptor.date_published =
                     Some(self.date_published.unwrap_or(self.message_timestamp));
@@ -1048,7 +1620,23 @@ impl<O, A, S: Signer> WriteBuilder<'_, O, A, Signed<'_, S>> {
             }
         }
 
-        match &self.data {
+        // encrypt the data before its CID and size are calculated
+        let mut encrypted = None;
+        let ciphertext;
+        let data = if self.protocol_encryption.is_some() {
+            let Some(Data::Stream(stream)) = &self.data else {
+                return Err(anyhow!("protocol encryption requires data to be set as a stream"));
+            };
+            let mut options = EncryptOptions::new().data(stream.get_ref());
+            let encrypted_data = options.encrypt()?;
+            ciphertext = Some(Data::from(encrypted_data.ciphertext.clone()));
+            encrypted = Some(encrypted_data);
+            ciphertext.as_ref()
+        } else {
+            self.data.as_ref()
+        };
+
+        match data {
             Some(Data::Stream(stream)) => {
                 let (data_cid, data_size) = cid::from_reader(stream.clone())?;
                 write.descriptor.data_cid = data_cid;
@@ -1065,6 +1653,15 @@ impl<O, A, S: Signer> WriteBuilder<'_, O, A, Signed<'_, S>> {
                 write.descriptor.data_cid.clone_from(data_cid);
                 write.descriptor.data_size = *data_size;
             }
+            Some(Data::Reader {
+                reader,
+                data_cid,
+                data_size,
+            }) => {
+                write.descriptor.data_cid.clone_from(data_cid);
+                write.descriptor.data_size = *data_size;
+                write.data_reader = Some(reader.clone());
+            }
             None => {}
         }
 
@@ -1108,19 +1705,77 @@ impl<O, A, S: Signer> WriteBuilder<'_, O, A, Signed<'_, S>> {
             }
         }
 
+        // add recipients once the record's context is known
+        if let (Some(encrypted), Some((definition, keyring))) =
+            (encrypted, self.protocol_encryption)
+        {
+            write.encryption = Some(protocol_encryption(encrypted, &write, definition, keyring)?);
+        }
+
         Ok(write)
     }
 }
 
+// Encrypt the content encryption key for the protocol path and protocol
+// context derived keys of the record, returning the record's encryption
+// properties.
+fn protocol_encryption(
+    encrypted: Encrypted, write: &Write, definition: &Definition, keyring: &Keyring,
+) -> Result<EncryptionProperty> {
+    let Some(protocol_path) = &write.descriptor.protocol_path else {
+        return Err(anyhow!("`protocol` must be specified when using protocol encryption"));
+    };
+    let Some(encryption) = definition.encryption_for(protocol_path) else {
+        return Err(anyhow!("protocol path {protocol_path} has no encryption"));
+    };
+    let Some(private_key_jwk) = keyring.get(&encryption.root_key_id) else {
+        return Err(anyhow!("keyring has no key for {}", encryption.root_key_id));
+    };
+
+    // the protocol context key is derived from the root of the record's context
+    let context_id = write.context_id.as_deref().unwrap_or_default();
+    let root_context = context_id.split('/').next().unwrap_or_default();
+    let root_jwk = DerivedPrivateJwk {
+        root_key_id: encryption.root_key_id.clone(),
+        derivation_scheme: DerivationScheme::ProtocolContext,
+        derivation_path: None,
+        derived_private_key: private_key_jwk.clone(),
+    };
+    let path = [DerivationScheme::ProtocolContext.to_string(), root_context.to_string()];
+    let context_jwk = hd_key::derive_jwk(root_jwk, &DerivationPath::Full(&path))?;
+
+    let encryption = encrypted
+        .add_recipient(Recipient {
+            key_id: encryption.root_key_id.clone(),
+            public_key: encryption.public_key_jwk.clone(),
+            derivation_scheme: DerivationScheme::ProtocolPath,
+        })
+        .add_recipient(Recipient {
+            key_id: encryption.root_key_id.clone(),
+            public_key: context_jwk.derived_private_key.public_key,
+            derivation_scheme: DerivationScheme::ProtocolContext,
+        })
+        .finalize()?;
+    Ok(encryption)
+}
+
 impl<O, A: Signer, S: Signer> WriteBuilder<'_, O, Attested<'_, A>, Signed<'_, S>> {
     async fn attestation(self, descriptor: &WriteDescriptor) -> Result<Jws> {
-        let payload = Attestation {
-            descriptor_cid: cid::from_value(descriptor)?,
-        };
-        let Some(attester) = self.attesters.0.first() else {
-            return Err(anyhow!("attesters is empty"));
-        };
-        JwsBuilder::new().payload(payload).add_signer(*attester).build().await
+        let descriptor_cid = cid::from_value(descriptor)?;
+
+        // each attester adds their signature to the same attestation
+        let mut attestation: Option<Jws> = None;
+        for attester in self.attesters.0 {
+            let payload = Attestation {
+                descriptor_cid: descriptor_cid.clone(),
+            };
+            let jws = JwsBuilder::new().payload(payload).add_signer(*attester).build().await?;
+            match &mut attestation {
+                Some(attestation) => attestation.signatures.extend(jws.signatures),
+                None => attestation = Some(jws),
+            }
+        }
+        attestation.ok_or_else(|| anyhow!("attesters is empty"))
     }
 }
 
@@ -1140,6 +1795,9 @@ impl<O, S: Signer> WriteBuilder<'_, O, Unattested, Signed<'_, S>> {
 
         let mut write = self.to_write(&author_did)?;
         write.sign_as_author(self.permission_grant_id, self.protocol_role, self.signer.0).await?;
+        if let Some(owner) = self.owner_signer {
+            write.sign_as_owner(owner.0).await?;
+        }
         Ok(write)
     }
 }
@@ -1160,12 +1818,16 @@ impl<'a, O, A: Signer, S: Signer> WriteBuilder<'a, O, Attested<'a, A>, Signed<'a
         };
 
         let signer = self.signer.0;
+        let owner_signer = self.owner_signer.as_ref().map(|owner| owner.0);
         let protocol_role = self.protocol_role.clone();
         let permission_grant_id = self.permission_grant_id.clone();
 
         let mut write = self.to_write(&author_did)?;
         write.attestation = Some(self.attestation(&write.descriptor).await?);
         write.sign_as_author(permission_grant_id, protocol_role, signer).await?;
+        if let Some(owner) = owner_signer {
+            write.sign_as_owner(owner).await?;
+        }
         Ok(write)
     }
 }
@@ -37,7 +37,7 @@ use derive_more::Display;
 pub use http::StatusCode;
 
 pub use crate::endpoint::Message;
-pub use crate::error::Error;
+pub use crate::error::{Error, ForbiddenCode};
 pub use crate::provider::Provider;
 use crate::serde::rfc3339_micros;
 pub use crate::utils::cid;
@@ -336,4 +336,28 @@ mod serde {
         };
         rfc3339_micros(date, serializer)
     }
+
+    /// Serialize whole number floats as integers, as other DWN implementations
+    /// do, so descriptors containing them produce the same CID.
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn float<S>(n: &f64, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        whole_number(*n)
+            .map_or_else(|| serializer.serialize_f64(*n), |i| serializer.serialize_i64(i))
+    }
+
+    /// The integer value of a whole number float, when it can be represented
+    /// exactly.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss, clippy::float_cmp)]
+    pub fn whole_number(n: f64) -> Option<i64> {
+        // integers beyond 2^53 are not exactly representable as floats
+        const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+        if !n.is_finite() || n.abs() > MAX_EXACT {
+            return None;
+        }
+        let i = n as i64;
+        (i as f64 == n).then_some(i)
+    }
 }
@@ -8,7 +8,7 @@ mod subscribe;
 
 use serde::{Deserialize, Serialize};
 
-pub use self::query::{Query, QueryDescriptor};
+pub use self::query::{Query, QueryDescriptor, QueryReply};
 pub use self::read::{Read, ReadDescriptor};
 pub use self::subscribe::{Subscribe, SubscribeDescriptor};
 use crate::{DateRange, Interface, Method};
@@ -33,6 +33,10 @@ pub struct MessagesFilter {
     /// Filter messages timestamped within the specified range.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message_timestamp: Option<DateRange>,
+
+    /// The DID of the message author.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
 }
 
 /// Provide  builder-like behaviour to create a [`MessagesFilter`].
@@ -70,4 +74,11 @@ impl MessagesFilter {
         self.message_timestamp = Some(message_timestamp);
         self
     }
+
+    /// Specify an author to filter messages by.
+    #[must_use]
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
 }
@@ -10,7 +10,7 @@ use super::MessagesFilter;
 use crate::authorization::Authorization;
 use crate::endpoint::{Message, Reply, Status};
 use crate::provider::{EventLog, Provider};
-use crate::store::{self, Cursor};
+use crate::store::{self, Cursor, Entry};
 use crate::utils::cid;
 use crate::{Descriptor, Result, forbidden, grants};
 
@@ -28,7 +28,11 @@ pub async fn handle(
     let query = store::Query::from(query);
     let (events, cursor) = EventLog::query(provider, owner, &query).await?;
 
-    let events = events.iter().map(|e| e.cid().unwrap_or_default()).collect::<Vec<String>>();
+    let watermark = events.iter().filter_map(Entry::watermark).max();
+
+    // events logged for a pruning delete's descendants share its message
+    let mut events = events.iter().map(|e| e.cid().unwrap_or_default()).collect::<Vec<String>>();
+    events.dedup();
     let entries = if events.is_empty() { None } else { Some(events) };
 
     Ok(Reply {
@@ -36,7 +40,11 @@ pub async fn handle(
             code: StatusCode::OK.as_u16(),
             detail: None,
         },
-        body: Some(QueryReply { entries, cursor }),
+        body: Some(QueryReply {
+            entries,
+            cursor,
+            watermark,
+        }),
     })
 }
 
@@ -85,6 +93,15 @@ impl Query {
         };
         let grant = grants::fetch_grant(owner, grant_id, provider).await?;
         grant.verify(owner, &authzn.signer()?, self.descriptor(), provider).await?;
+        grant.verify_unexpired(provider.clock())?;
+
+        // verify filter interface
+        if let Some(interface) = grant.data.scope.message_interface()
+            && (self.descriptor.filters.is_empty()
+                || self.descriptor.filters.iter().any(|f| f.interface.as_ref() != Some(interface)))
+        {
+            return Err(forbidden!("filter and grant interfaces do not match"));
+        }
 
         // verify filter protocol
         if grant.data.scope.protocol().is_none() {
@@ -112,6 +129,11 @@ pub struct QueryReply {
     /// The message authorization.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cursor: Option<Cursor>,
+
+    /// The watermark of the last event returned. Used as the `since`
+    /// watermark of a later query to fetch only events logged after these.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<u64>,
 }
 
 /// The [`Query`] message descriptor.
@@ -128,4 +150,16 @@ pub struct QueryDescriptor {
     /// The pagination cursor.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cursor: Option<Cursor>,
+
+    /// The maximum number of events to return.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+
+    /// Return the most recently logged events first.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest: Option<bool>,
+
+    /// Only return events logged after this watermark.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<u64>,
 }
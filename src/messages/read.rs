@@ -22,7 +22,7 @@ use crate::provider::{DataStore, MessageStore, Provider};
 use crate::records::write;
 use crate::store::{Entry, EntryType};
 use crate::utils::cid;
-use crate::{Descriptor, Error, Interface, Result, forbidden, unexpected};
+use crate::{Descriptor, Error, ForbiddenCode, Interface, Result, forbidden, unexpected};
 
 /// Handle — or process — a [`Read`] message.
 ///
@@ -123,22 +123,64 @@ impl Read {
             return Ok(());
         }
 
-        // verify grant
+        // without a grant, others can only read messages they are permitted
+        // to see
         let Some(grant_id) = &authzn.payload()?.permission_grant_id else {
+            if permitted(owner, &author, entry, provider).await? {
+                return Ok(());
+            }
             return Err(forbidden!("missing grant ID"));
         };
+
+        // verify grant
         let grant = grants::fetch_grant(owner, grant_id, provider).await?;
         grant.verify(owner, &author, self.descriptor(), provider).await?;
+        grant.verify_unexpired(provider.clock())?;
         verify_scope(owner, entry, grant.data.scope, provider).await?;
 
         Ok(())
     }
 }
 
+// Whether a requestor without a grant is permitted to read the message: they
+// authored it, or it belongs to a record that is published, was authored by
+// them, or has them as the recipient.
+async fn permitted(
+    owner: &str, requestor: &str, requested: &Entry, store: &impl MessageStore,
+) -> Result<bool> {
+    let write = match &requested.message {
+        EntryType::Write(write) => write.clone(),
+        EntryType::Delete(delete) => {
+            if delete.authorization.author()? == requestor {
+                return Ok(true);
+            }
+            let entry = write::initial_write(owner, &delete.descriptor.record_id, store).await?;
+            let Some(write) = entry else {
+                return Ok(false);
+            };
+            write
+        }
+        EntryType::Configure(configure) => {
+            return Ok(configure.authorization.author()? == requestor);
+        }
+    };
+
+    Ok(write.descriptor.published.unwrap_or_default()
+        || write.authorization.author()? == requestor
+        || write.descriptor.recipient.as_deref() == Some(requestor))
+}
+
 // Verify message scope against grant scope.
 async fn verify_scope(
     owner: &str, requested: &Entry, scope: Scope, store: &impl MessageStore,
 ) -> Result<()> {
+    // ensure the message belongs to the scoped interface
+    if let Some(interface) = scope.message_interface()
+        && requested.descriptor().interface != *interface
+    {
+        return Err(ForbiddenCode::ScopeMismatch.into());
+    }
+
     // ensure read filters include scoped protocol
     let Some(protocol) = scope.protocol() else {
         return Ok(());
@@ -146,7 +188,7 @@ async fn verify_scope(
 
     if requested.descriptor().interface == Interface::Protocols {
         let Some(configure) = requested.as_configure() else {
-            return Err(forbidden!("message failed scope authorization"));
+            return Err(ForbiddenCode::ScopeMismatch.into());
         };
         if configure.descriptor.definition.protocol == protocol {
             return Ok(());
@@ -160,12 +202,12 @@ async fn verify_scope(
                 let entry =
                     write::initial_write(owner, &delete.descriptor.record_id, store).await?;
                 let Some(write) = entry else {
-                    return Err(forbidden!("message failed scope authorization"));
+                    return Err(ForbiddenCode::ScopeMismatch.into());
                 };
                 write.clone()
             }
             EntryType::Configure(_) => {
-                return Err(forbidden!("message failed scope authorization"));
+                return Err(ForbiddenCode::ScopeMismatch.into());
             }
         };
 
@@ -183,7 +225,7 @@ async fn verify_scope(
         }
     }
 
-    Err(forbidden!("message failed scope authorization"))
+    Err(ForbiddenCode::ScopeMismatch.into())
 }
 
 /// [`ReadReply`] is returned by the handler in the [`Reply`] `body` field.
@@ -9,9 +9,9 @@ use serde::{Deserialize, Serialize};
 
 use crate::authorization::Authorization;
 use crate::endpoint::{Message, Reply, Status};
-use crate::event::{SubscribeFilter, Subscriber};
+use crate::event::{SUBSCRIBER_CAPACITY, SubscribeFilter, Subscriber};
 use crate::messages::MessagesFilter;
-use crate::provider::{EventStream, MessageStore, Provider};
+use crate::provider::{EventStream, Provider};
 use crate::utils::cid;
 use crate::{Descriptor, Result, forbidden, grants};
 
@@ -38,6 +38,9 @@ pub async fn handle(
         subscriber.inner = Box::pin(filtered);
     }
 
+    // buffer events so a slow consumer cannot cause unbounded buffering
+    let subscriber = subscriber.bounded(SUBSCRIBER_CAPACITY);
+
     Ok(Reply {
         status: Status {
             code: StatusCode::OK.as_u16(),
@@ -80,7 +83,7 @@ impl Message for Subscribe {
 }
 
 impl Subscribe {
-    async fn authorize(&self, owner: &str, store: &impl MessageStore) -> Result<()> {
+    async fn authorize(&self, owner: &str, provider: &impl Provider) -> Result<()> {
         let authzn = &self.authorization;
         let author = authzn.author()?;
 
@@ -92,8 +95,17 @@ impl Subscribe {
         let Some(grant_id) = &authzn.payload()?.permission_grant_id else {
             return Err(forbidden!("missing permission grant"));
         };
-        let grant = grants::fetch_grant(owner, grant_id, store).await?;
-        grant.verify(owner, &authzn.signer()?, self.descriptor(), store).await?;
+        let grant = grants::fetch_grant(owner, grant_id, provider).await?;
+        grant.verify(owner, &authzn.signer()?, self.descriptor(), provider).await?;
+        grant.verify_unexpired(provider.clock())?;
+
+        // verify filter interface
+        if let Some(interface) = grant.data.scope.message_interface()
+            && (self.descriptor.filters.is_empty()
+                || self.descriptor.filters.iter().any(|f| f.interface.as_ref() != Some(interface)))
+        {
+            return Err(forbidden!("filter and grant interfaces do not match"));
+        }
 
         // ensure subscribe filters include scoped protocol
         if grant.data.scope.protocol().is_none() {
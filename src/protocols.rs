@@ -11,8 +11,8 @@ use std::sync::LazyLock;
 use serde::{Deserialize, Serialize};
 
 pub use self::configure::{
-    Action, ActionRule, Actor, Configure, ConfigureDescriptor, Definition, ProtocolType, RuleSet,
-    Size, validate_structure,
+    Action, ActionRule, Actor, Configure, ConfigureDescriptor, Definition, DeriveRule,
+    PathEncryption, ProtocolType, RuleSet, Size, Transform, validate_structure,
 };
 pub use self::query::{Query, QueryDescriptor};
 use crate::provider::MessageStore;
@@ -35,7 +35,12 @@ pub const REVOCATION_PATH: &str = "grant/revocation";
 #[serde(rename_all = "camelCase")]
 pub struct ProtocolsFilter {
     /// Protocol matching the specified protocol.
-    pub protocol: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+
+    /// Protocols matching the specified published state.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published: Option<bool>,
 }
 
 /// Define a default protocol definition.
@@ -84,11 +89,20 @@ pub static DEFINITION: LazyLock<Definition> = LazyLock::new(|| {
                     "revocation".to_string(),
                     RuleSet {
                         size: Some(default_size),
-                        actions: Some(vec![ActionRule {
-                            who: Some(Actor::Anyone),
-                            can: vec![Action::Read],
-                            ..ActionRule::default()
-                        }]),
+                        actions: Some(vec![
+                            ActionRule {
+                                who: Some(Actor::Anyone),
+                                can: vec![Action::Read],
+                                ..ActionRule::default()
+                            },
+                            // only the grantor may revoke a grant
+                            ActionRule {
+                                who: Some(Actor::Author),
+                                of: Some("grant".to_string()),
+                                can: vec![Action::Create],
+                                ..ActionRule::default()
+                            },
+                        ]),
                         ..RuleSet::default()
                     },
                 )]),
@@ -100,6 +114,8 @@ pub static DEFINITION: LazyLock<Definition> = LazyLock::new(|| {
     Definition {
         protocol: PROTOCOL_URI.to_string(),
         published: true,
+        version: None,
+        active: None,
         types,
         structure,
     }
@@ -9,6 +9,8 @@ use chrono::SecondsFormat::Micros;
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use vercre_did::{DidResolver, Resource, dereference};
+use vercre_infosec::Curve;
 use vercre_infosec::jose::jwk::PublicKeyJwk;
 
 use crate::authorization::Authorization;
@@ -61,6 +63,16 @@ pub async fn handle(
             return Err(Error::Conflict("message CID is smaller than existing entry".to_string()));
         }
 
+        // when incoming definition is an older version than the latest
+        if let Some(latest_version) = &latest.descriptor.definition.version
+            && let Some(version) = &configure.descriptor.definition.version
+            && semver(version)? < semver(latest_version)?
+        {
+            return Err(Error::Conflict(format!(
+                "version {version} is older than the configured version {latest_version}"
+            )));
+        }
+
         // remove existing entries
         for e in existing {
             let cid = cid::from_value(&e)?;
@@ -145,11 +157,12 @@ impl Configure {
             "messageTimestamp".to_string(),
             self.descriptor.base.message_timestamp.to_rfc3339_opts(Micros, true),
         );
+        indexes.insert("author".to_string(), self.authorization.author().unwrap_or_default());
         indexes
     }
 
     /// Check message has sufficient privileges.
-    async fn authorize(&self, owner: &str, store: &impl MessageStore) -> Result<()> {
+    async fn authorize(&self, owner: &str, provider: &impl Provider) -> Result<()> {
         let authzn = &self.authorization;
 
         if authzn.author()? == owner {
@@ -160,8 +173,8 @@ impl Configure {
         let Some(grant_id) = &authzn.payload()?.permission_grant_id else {
             return Err(forbidden!("author has no grant"));
         };
-        let grant = grants::fetch_grant(owner, grant_id, store).await?;
-        grant.verify(owner, &authzn.author()?, self.descriptor(), store).await?;
+        let grant = grants::fetch_grant(owner, grant_id, provider).await?;
+        grant.verify(owner, &authzn.author()?, self.descriptor(), provider).await?;
 
         // when the grant scope does not specify a protocol, it is an unrestricted grant
         let Some(protocol) = grant.data.scope.protocol() else {
@@ -180,13 +193,17 @@ impl Configure {
         utils::uri::validate(&self.descriptor.definition.protocol)?;
 
         // validate schemas
-        for t in self.descriptor.definition.types.values() {
+        for (name, t) in &self.descriptor.definition.types {
             if let Some(schema) = &t.schema {
                 utils::uri::validate(schema)?;
             }
+            if let Some(data_schema) = &t.data_schema {
+                jsonschema::validator_for(data_schema)
+                    .map_err(|e| unexpected!("invalid `dataSchema` for type {name}: {e}"))?;
+            }
         }
 
-        validate_structure(&self.descriptor.definition)?;
+        self.descriptor.definition.validate()?;
 
         Ok(())
     }
@@ -215,6 +232,17 @@ pub struct Definition {
     /// `ProtocolsQuery`.
     pub published: bool,
 
+    /// The definition's semantic version (`MAJOR.MINOR.PATCH`). When set, a
+    /// definition cannot be replaced by one with an older version.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// Whether the protocol accepts new writes. A deactivated protocol
+    /// rejects new records while existing records remain readable. When
+    /// unset, the protocol is active.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>,
+
     /// Protocol types.
     pub types: BTreeMap<String, ProtocolType>,
 
@@ -232,6 +260,26 @@ impl Definition {
         }
     }
 
+    /// Verify the structural integrity of the definition: every rule set is a
+    /// declared type, action rules reference existing roles and protocol
+    /// paths, and `$size`, `$tags`, `$encryption`, and recipient settings are
+    /// valid.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `BadRequest` error, naming the offending protocol path, when
+    /// the definition is invalid.
+    pub fn validate(&self) -> Result<()> {
+        validate_structure(self)
+    }
+
+    /// Set the definition's semantic version (`MAJOR.MINOR.PATCH`).
+    #[must_use]
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
     /// Whether the definition should be published.
     #[must_use]
     pub const fn published(mut self, published: bool) -> Self {
@@ -258,10 +306,23 @@ impl Definition {
     ///
     /// # Errors
     ///
-    /// This method will fail when an error occurs deriving the public key.
+    /// This method will fail when the key is not an Ed25519 key, the
+    /// definition is already encrypted using a different root key, or an
+    /// error occurs deriving the public key.
     pub fn with_encryption(
         mut self, root_key_id: &str, private_key_jwk: PrivateKeyJwk,
     ) -> Result<Self> {
+        // the protocol-path derivation scheme only supports Ed25519 keys
+        if private_key_jwk.public_key.crv != Curve::Ed25519 {
+            return Err(unexpected!("unsupported encryption key type"));
+        }
+        if !root_key_id.starts_with("did:") || !root_key_id.contains('#') {
+            return Err(unexpected!("root key ID must be a DID URL: {root_key_id}"));
+        }
+        if let Some(existing) = other_root_key(&self.structure, root_key_id) {
+            return Err(unexpected!("encryption is already configured using root key {existing}"));
+        }
+
         let root_key = DerivedPrivateJwk {
             root_key_id: root_key_id.to_string(),
             derivation_scheme: DerivationScheme::ProtocolPath,
@@ -278,6 +339,61 @@ impl Definition {
 
         Ok(self)
     }
+
+    /// Adds encryption as for [`Definition::with_encryption`], first verifying
+    /// the key is the key identified by `root_key_id` in the owner's DID
+    /// document.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail when `root_key_id` cannot be dereferenced, the
+    /// key's public component does not match the DID document, or adding
+    /// encryption fails.
+    pub async fn with_verified_encryption(
+        self, root_key_id: &str, private_key_jwk: PrivateKeyJwk, resolver: impl DidResolver,
+    ) -> Result<Self> {
+        let resp = dereference(root_key_id, None, resolver)
+            .await
+            .map_err(|e| unexpected!("issue dereferencing root key ID: {e}"))?;
+        let Some(Resource::VerificationMethod(vm)) = resp.content_stream else {
+            return Err(unexpected!("root key ID not found in DID document"));
+        };
+        let jwk = vm.method_type.jwk().map_err(|e| unexpected!("root key is not a JWK: {e}"))?;
+        if jwk.x != private_key_jwk.public_key.x {
+            return Err(unexpected!("encryption key does not match the DID document"));
+        }
+
+        self.with_encryption(root_key_id, private_key_jwk)
+    }
+
+    /// Returns the encryption settings for the specified protocol path (e.g.
+    /// `thread/message`), when the definition is encrypted.
+    #[must_use]
+    pub fn encryption_for(&self, protocol_path: &str) -> Option<&PathEncryption> {
+        let mut structure = &self.structure;
+        let mut rule_set = None;
+        for segment in protocol_path.split('/') {
+            let current = structure.get(segment)?;
+            structure = &current.structure;
+            rule_set = Some(current);
+        }
+        rule_set?.encryption.as_ref()
+    }
+}
+
+// Find a root key, other than the one specified, used by an existing
+// `$encryption` setting.
+fn other_root_key<'a>(
+    structure: &'a BTreeMap<String, RuleSet>, root_key_id: &str,
+) -> Option<&'a str> {
+    structure.values().find_map(|rule_set| {
+        rule_set
+            .encryption
+            .as_ref()
+            .map(|e| e.root_key_id.as_str())
+            .filter(|id| *id != root_key_id)
+            .or_else(|| other_root_key(&rule_set.structure, root_key_id))
+    })
 }
 
 fn add_encryption(
@@ -309,6 +425,11 @@ pub struct ProtocolType {
     /// Data formats supported by the protocol.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data_formats: Option<Vec<String>>,
+
+    /// A JSON Schema record data must conform to. Only applied to records
+    /// with a JSON data format.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_schema: Option<Value>,
 }
 
 /// Protocol rule set.
@@ -329,6 +450,17 @@ pub struct RuleSet {
     #[serde(rename = "$role")]
     pub role: Option<bool>,
 
+    /// When `true`, records at this path must have a recipient.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "$requireRecipient")]
+    pub require_recipient: Option<bool>,
+
+    /// When `true`, records at this path must not have a recipient. Useful
+    /// for published types that are not addressed to anyone in particular.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "$forbidRecipient")]
+    pub forbid_recipient: Option<bool>,
+
     /// If $size is set, the record size in bytes must be within the limits.
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "$size")]
@@ -339,6 +471,12 @@ pub struct RuleSet {
     #[serde(rename = "$tags")]
     pub tags: Option<Tags>,
 
+    /// Tags derived from the record's tags when the record is written. Derived
+    /// tags are indexed (and so queryable) but do not form part of the record.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "$derive")]
+    pub derive: Option<BTreeMap<String, DeriveRule>>,
+
     /// JSON Schema verifies that properties other than properties prefixed
     /// with $ will actually have type `ProtocolRuleSet`
     #[serde(flatten)]
@@ -473,6 +611,44 @@ pub struct Size {
     pub max: Option<usize>,
 }
 
+/// Rule for deriving an indexed tag from one of the record's tags.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeriveRule {
+    /// The name of the record tag to derive the value from.
+    pub from: String,
+
+    /// The transform to apply to the source tag value.
+    pub transform: Transform,
+}
+
+/// Deterministic transforms used to derive tag values.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Transform {
+    /// Convert the value to lowercase.
+    #[default]
+    Lowercase,
+
+    /// Convert the value to uppercase.
+    Uppercase,
+
+    /// Remove leading and trailing whitespace.
+    Trim,
+}
+
+impl Transform {
+    /// Apply the transform to a value.
+    #[must_use]
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Self::Lowercase => value.to_lowercase(),
+            Self::Uppercase => value.to_uppercase(),
+            Self::Trim => value.trim().to_string(),
+        }
+    }
+}
+
 /// Protocol tags
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -493,37 +669,77 @@ pub struct Tags {
 }
 
 /// Verify the structure (rule sets) of the protocol definition.
+///
+/// # Errors
+///
+/// Returns a `BadRequest` error, naming the offending protocol path, when the
+/// definition's structure is invalid.
 pub fn validate_structure(definition: &Definition) -> Result<()> {
+    if let Some(version) = &definition.version {
+        semver(version)?;
+    }
+
     let keys = definition.types.keys().collect::<Vec<&String>>();
 
-    // parse rule set for roles
+    // parse rule set for roles and protocol paths
     let roles = role_paths("", &definition.structure, &[])?;
+    let paths = protocol_paths("", &definition.structure);
 
     // validate rule set hierarchy
-    for rule_set in definition.structure.values() {
-        validate_rule_set(rule_set, "", &keys, &roles)?;
-    }
+    validate_rule_sets(&definition.structure, "", &keys, &roles, &paths)
+}
 
+// Validates each rule set in the structure is a declared type before
+// validating the rule set itself.
+fn validate_rule_sets(
+    structure: &BTreeMap<String, RuleSet>, parent_path: &str, types: &Vec<&String>,
+    roles: &Vec<String>, paths: &Vec<String>,
+) -> Result<()> {
+    for (set_name, rule_set) in structure {
+        if !types.contains(&set_name) {
+            return Err(unexpected!("rule set {set_name} is not declared as an allowed type"));
+        }
+        let protocol_path =
+            if parent_path.is_empty() { set_name } else { &format!("{parent_path}/{set_name}") };
+        validate_rule_set(rule_set, protocol_path, types, roles, paths)?;
+    }
     Ok(())
 }
 
 // Validates a rule set structure, recursively validating nested rule sets.
 fn validate_rule_set(
     rule_set: &RuleSet, protocol_path: &str, types: &Vec<&String>, roles: &Vec<String>,
+    paths: &Vec<String>,
 ) -> Result<()> {
     // validate size rule
     if let Some(size) = &rule_set.size {
         if size.max.is_some() && size.min > size.max {
-            return Err(unexpected!("invalid size range"));
+            return Err(unexpected!("invalid size range at {protocol_path}"));
+        }
+    }
+
+    // validate recipient rules
+    if rule_set.forbid_recipient == Some(true) {
+        if rule_set.require_recipient == Some(true) {
+            return Err(unexpected!("conflicting recipient rules at {protocol_path}"));
+        }
+        if rule_set.role.is_some() {
+            return Err(unexpected!("role records require a recipient at {protocol_path}"));
+        }
+    }
+
+    // validate encryption key
+    if let Some(encryption) = &rule_set.encryption {
+        if encryption.root_key_id.is_empty() || encryption.public_key_jwk.x.is_empty() {
+            return Err(unexpected!("invalid encryption key at {protocol_path}"));
         }
     }
 
     // validate tags schemas
     if let Some(tags) = &rule_set.tags {
-        for tag in tags.undefined.keys() {
-            let schema = serde_json::from_str(tag)?;
-            jsonschema::validator_for(&schema)
-                .map_err(|e| unexpected!("tag schema validation error: {e}"))?;
+        for (tag, schema) in &tags.undefined {
+            jsonschema::validator_for(schema)
+                .map_err(|e| unexpected!("invalid schema for tag {tag} at {protocol_path}: {e}"))?;
         }
     }
 
@@ -536,7 +752,7 @@ fn validate_rule_set(
         if let Some(role) = &action.role {
             // role must contain valid protocol paths to a role record
             if !roles.contains(role) {
-                return Err(unexpected!("missing role {role} in action"));
+                return Err(unexpected!("missing role {role} in action at {protocol_path}"));
             }
 
             // if ANY `can` actions are read-like ('read', 'query', 'subscribe')
@@ -550,6 +766,13 @@ fn validate_rule_set(
             }
         }
 
+        // `of` must reference a protocol path in the structure
+        if let Some(of) = &action.of
+            && !paths.contains(of)
+        {
+            return Err(unexpected!("invalid protocol path {of} in action at {protocol_path}"));
+        }
+
         // when `who` is `anyone`, `of` cannot be set
         if action.who.as_ref().is_some_and(|w| w == &Actor::Anyone) && action.of.is_some() {
             return Err(unexpected!("`of` must not be set when `who` is \"anyone\""));
@@ -602,19 +825,31 @@ fn validate_rule_set(
     }
 
     // verify nested rule sets
-    for (set_name, rule_set) in &rule_set.structure {
-        if !types.contains(&set_name) {
-            return Err(unexpected!("rule set {set_name} is not declared as an allowed type"));
-        }
+    validate_rule_sets(&rule_set.structure, protocol_path, types, roles, paths)
+}
+
+// Parse a `MAJOR.MINOR.PATCH` version string into comparable parts.
+fn semver(version: &str) -> Result<(u64, u64, u64)> {
+    let parts = version.split('.').map(str::parse::<u64>).collect::<Vec<_>>();
+    let [Ok(major), Ok(minor), Ok(patch)] = parts[..] else {
+        return Err(unexpected!("invalid version {version}: expected MAJOR.MINOR.PATCH"));
+    };
+    Ok((major, minor, patch))
+}
+
+// Parses the given rule set hierarchy to get all protocol paths.
+fn protocol_paths(protocol_path: &str, structure: &BTreeMap<String, RuleSet>) -> Vec<String> {
+    let mut paths = vec![];
+    for (rule_name, rule_set) in structure {
         let protocol_path = if protocol_path.is_empty() {
-            set_name
+            rule_name.clone()
         } else {
-            &format!("{protocol_path}/{set_name}")
+            format!("{protocol_path}/{rule_name}")
         };
-        validate_rule_set(rule_set, protocol_path, types, roles)?;
+        paths.extend(protocol_paths(&protocol_path, &rule_set.structure));
+        paths.push(protocol_path);
     }
-
-    Ok(())
+    paths
 }
 
 // Parses the given rule set hierarchy to get all the role protocol paths.
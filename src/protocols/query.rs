@@ -9,7 +9,7 @@ use crate::authorization::Authorization;
 use crate::endpoint::{Message, Reply, Status};
 use crate::protocols::{Configure, ProtocolsFilter};
 use crate::provider::{MessageStore, Provider};
-use crate::store::{Cursor, ProtocolsQueryBuilder};
+use crate::store::{Cursor, Pagination, ProtocolsQueryBuilder};
 use crate::utils::cid;
 use crate::{Descriptor, Result, grants, utils};
 
@@ -30,19 +30,37 @@ pub async fn handle(
     owner: &str, query: Query, provider: &impl Provider,
 ) -> Result<Reply<QueryReply>> {
     // validate query
-    if let Some(filter) = &query.descriptor.filter {
-        utils::uri::validate(&filter.protocol)?;
+    if let Some(protocol) = query.descriptor.filter.as_ref().and_then(|f| f.protocol.as_ref()) {
+        utils::uri::validate(protocol)?;
     }
 
     // build actual query
     let mut builder = ProtocolsQueryBuilder::new();
-    if let Some(filter) = &query.descriptor.filter {
-        builder = builder.protocol(&filter.protocol);
+    if let Some(protocol) = query.descriptor.filter.as_ref().and_then(|f| f.protocol.as_ref()) {
+        builder = builder.protocol(protocol);
+    }
+    if let Some(pagination) = &query.descriptor.pagination {
+        builder = builder.pagination(pagination.clone());
     }
 
     // unauthorized queries can only query for published protocols
+    let published = query.descriptor.filter.as_ref().and_then(|f| f.published);
     if query.authorize(owner, provider).await? == Access::Published {
+        if published == Some(false) {
+            return Ok(Reply {
+                status: Status {
+                    code: 200,
+                    detail: Some("OK".to_string()),
+                },
+                body: Some(QueryReply {
+                    entries: Some(vec![]),
+                    cursor: None,
+                }),
+            });
+        }
         builder = builder.published(true);
+    } else if let Some(published) = published {
+        builder = builder.published(published);
     }
 
     let (records, cursor) = MessageStore::query(provider, owner, &builder.build()).await?;
@@ -110,7 +128,7 @@ pub struct QueryReply {
 
 impl Query {
     /// Check message has sufficient privileges.
-    async fn authorize(&self, owner: &str, store: &impl MessageStore) -> Result<Access> {
+    async fn authorize(&self, owner: &str, provider: &impl Provider) -> Result<Access> {
         let Some(authzn) = &self.authorization else {
             return Ok(Access::Published);
         };
@@ -125,19 +143,22 @@ impl Query {
         };
 
         // verify permission grant
-        let grant = grants::fetch_grant(owner, grant_id, store).await?;
-        grant.verify(owner, &authzn.signer()?, self.descriptor(), store).await?;
+        let grant = grants::fetch_grant(owner, grant_id, provider).await?;
+        grant.verify(owner, &authzn.signer()?, self.descriptor(), provider).await?;
+        grant.verify_unexpired(provider.clock())?;
 
         // if set, query and grant protocols need to match
         let Some(protocol) = grant.data.scope.protocol() else {
             return Ok(Access::Unpublished);
         };
-        // has a grant but no filter: published protocols only
-        let Some(filter) = &self.descriptor.filter else {
+        // has a grant but no protocol filter: published protocols only
+        let Some(filter_protocol) =
+            self.descriptor.filter.as_ref().and_then(|f| f.protocol.as_ref())
+        else {
             return Ok(Access::Published);
         };
         // filter protocol must match grant protocol
-        if protocol != filter.protocol {
+        if protocol != filter_protocol.as_str() {
             return Ok(Access::Published);
         }
 
@@ -156,4 +177,8 @@ pub struct QueryDescriptor {
     /// Filter Records for query.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub filter: Option<ProtocolsFilter>,
+
+    /// The pagination cursor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pagination: Option<Pagination>,
 }
@@ -3,20 +3,257 @@
 //! Provider traits are required of implementers in order to provide data
 //! storage, DID resolution, and cryptographic capabilities to the library.
 
+pub mod gc;
+pub mod sync;
+mod transaction;
+
+use std::collections::{BTreeMap, HashMap};
 use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::AsyncRead;
 pub use vercre_did::{DidResolver, Document};
 pub use vercre_infosec::{Receiver, Signer};
 
+pub use self::transaction::Transaction;
+
+use crate::endpoint::{MAX_MESSAGE_SIZE, RateLimiter};
 use crate::event::{Event, Subscriber};
-use crate::store::{Cursor, Entry, Query, data, event_log, message, task};
+use crate::store::{Cursor, Entry, GroupBy, Query, StoreStats, data, event_log, message, task};
 use crate::tasks::ResumableTask;
 
 /// Provider trait.
 pub trait Provider:
     MessageStore + DataStore + TaskStore + EventLog + BlockStore + EventStream + DidResolver
 {
+    /// Reject `RecordsWrite` messages with a `message_timestamp` earlier than
+    /// the author's most recent message for the same record. Used to detect
+    /// clients that rewind their clock.
+    ///
+    /// Disabled by default.
+    fn strict_timestamps(&self) -> bool {
+        false
+    }
+
+    /// The maximum size, in bytes, of record data stored with its message as
+    /// `encoded_data`. Larger data is stored in the [`DataStore`].
+    ///
+    /// Changing the threshold does not affect records already written: data
+    /// is read from wherever it was stored at the time of writing.
+    ///
+    /// Defaults to [`MAX_ENCODED_SIZE`].
+    ///
+    /// [`MAX_ENCODED_SIZE`]: crate::store::MAX_ENCODED_SIZE
+    fn max_encoded_size(&self) -> usize {
+        data::MAX_ENCODED_SIZE
+    }
+
+    /// The maximum size, in bytes, of a JSON-encoded message accepted by
+    /// [`handle`]. Larger messages are rejected before being processed.
+    ///
+    /// Defaults to [`MAX_MESSAGE_SIZE`].
+    ///
+    /// [`handle`]: crate::endpoint::handle
+    /// [`MAX_MESSAGE_SIZE`]: crate::endpoint::MAX_MESSAGE_SIZE
+    fn max_message_size(&self) -> usize {
+        MAX_MESSAGE_SIZE
+    }
+
+    /// The number of superseded writes of a record, besides its initial write,
+    /// to retain as versions for reads as of an earlier time. The oldest
+    /// versions are removed once the limit is reached. Retained versions keep
+    /// their data only when it is encoded with the message.
+    ///
+    /// Defaults to 0: only the initial and latest writes are retained.
+    fn retained_versions(&self) -> usize {
+        0
+    }
+
+    /// The window within which a `RecordsWrite` reusing an earlier write's
+    /// idempotency key is treated as a retry of that write.
+    ///
+    /// Defaults to 24 hours.
+    fn idempotency_window(&self) -> TimeDelta {
+        TimeDelta::hours(24)
+    }
+
+    /// A cache of resolved DID documents used when authenticating message
+    /// signers. When a signature fails to verify against a cached document,
+    /// the signer's DID is re-resolved and the cached document replaced if
+    /// the signer has rotated their keys.
+    ///
+    /// Disabled by default.
+    fn resolver_cache(&self) -> Option<Arc<ResolverCache>> {
+        None
+    }
+
+    /// The rate limiter consulted before each message is processed.
+    ///
+    /// Disabled by default.
+    fn rate_limiter(&self) -> Option<&dyn RateLimiter> {
+        None
+    }
+
+    /// The clock used whenever a handler needs the current time, for example
+    /// to check a grant presented with a query has not expired, or to decide
+    /// when tombstones and orphaned data may be collected.
+    ///
+    /// Defaults to [`SystemClock`].
+    fn clock(&self) -> &dyn Clock {
+        &SystemClock
+    }
+}
+
+/// A source of the current time. Implement to run handlers against a fixed
+/// or advanceable clock.
+pub trait Clock: Send + Sync {
+    /// The current date and time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// A [`Clock`] reading the system time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Default number of DID documents held by a [`ResolverCache`].
+pub const DEFAULT_CACHE_CAPACITY: usize = 1024;
+
+/// An LRU cache of resolved DID documents, keyed by DID.
+///
+/// Entries expire after a configurable time-to-live. When the cache is full,
+/// the least recently used document is evicted to make room for a new one.
+#[derive(Debug)]
+pub struct ResolverCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    hits: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    resolved_at: Instant,
+    used_at: Instant,
+    document: Document,
+}
+
+impl ResolverCache {
+    /// Create a new `ResolverCache` holding up to `capacity` documents, each
+    /// for no longer than `ttl`.
+    #[must_use]
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicUsize::new(0),
+        }
+    }
+
+    /// Get the cached document for `did`, if present and not expired.
+    pub fn get(&self, did: &str) -> Option<Document> {
+        let Ok(mut entries) = self.entries.lock() else {
+            return None;
+        };
+        let Some(entry) = entries.get_mut(did) else {
+            return None;
+        };
+        if entry.resolved_at.elapsed() >= self.ttl {
+            entries.remove(did);
+            return None;
+        }
+
+        entry.used_at = Instant::now();
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(entry.document.clone())
+    }
+
+    /// Cache `document` for `did`, evicting the least recently used document
+    /// when the cache is full.
+    pub fn insert(&self, did: &str, document: Document) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        if !entries.contains_key(did) && entries.len() >= self.capacity {
+            let lru = entries.iter().min_by_key(|(_, entry)| entry.used_at).map(|(k, _)| k.clone());
+            if let Some(lru) = lru {
+                entries.remove(&lru);
+            }
+        }
+
+        let now = Instant::now();
+        let entry = CacheEntry {
+            resolved_at: now,
+            used_at: now,
+            document,
+        };
+        entries.insert(did.to_string(), entry);
+    }
+
+    /// Remove the cached document for `did`, returning `true` if a document
+    /// was removed.
+    ///
+    /// Used to discard a stale document once a key rotation has been
+    /// observed.
+    pub fn invalidate(&self, did: &str) -> bool {
+        self.entries.lock().is_ok_and(|mut entries| entries.remove(did).is_some())
+    }
+
+    /// The number of lookups served from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
+
+/// A [`DidResolver`] wrapper serving resolved DID documents from a
+/// [`ResolverCache`].
+///
+/// Providers can wrap their resolver to avoid resolving the same signer's
+/// DID for every message.
+#[derive(Clone)]
+pub struct CachedResolver<R: DidResolver> {
+    inner: R,
+    cache: Arc<ResolverCache>,
+}
+
+impl<R: DidResolver> CachedResolver<R> {
+    /// Create a new `CachedResolver` caching documents resolved by `inner`
+    /// for `ttl`.
+    pub fn new(inner: R, ttl: Duration) -> Self {
+        Self::with_cache(inner, Arc::new(ResolverCache::new(DEFAULT_CACHE_CAPACITY, ttl)))
+    }
+
+    /// Create a new `CachedResolver` using an existing (possibly shared)
+    /// cache.
+    pub const fn with_cache(inner: R, cache: Arc<ResolverCache>) -> Self {
+        Self { inner, cache }
+    }
+
+    /// The underlying document cache.
+    pub fn cache(&self) -> &ResolverCache {
+        &self.cache
+    }
+}
+
+impl<R: DidResolver> DidResolver for CachedResolver<R> {
+    async fn resolve(&self, url: &str) -> Result<Document> {
+        if let Some(document) = self.cache.get(url) {
+            return Ok(document);
+        }
+        let document = self.inner.resolve(url).await?;
+        self.cache.insert(url, document.clone());
+        Ok(document)
+    }
 }
 
 /// `BlockStore` is used by implementers to provide data storage
@@ -38,6 +275,40 @@ pub trait BlockStore: Send + Sync {
         &self, owner: &str, partition: &str, cid: &str,
     ) -> impl Future<Output = Result<()>> + Send;
 
+    /// Store a batch of `(cid, data)` blocks in the underlying block store.
+    ///
+    /// The default implementation stores each block using [`BlockStore::put`].
+    /// Implementers backed by a database or object store should override this
+    /// method to store the batch in a single round-trip.
+    fn put_many(
+        &self, owner: &str, partition: &str, blocks: &[(String, Vec<u8>)],
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            for (cid, data) in blocks {
+                self.put(owner, partition, cid, data).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Fetches a batch of blocks by CID, returning `None` in place of each
+    /// block not found. Blocks are returned in the order requested.
+    ///
+    /// The default implementation fetches each block using
+    /// [`BlockStore::get`]. Implementers backed by a database or object store
+    /// should override this method to fetch the batch in a single round-trip.
+    fn get_many(
+        &self, owner: &str, partition: &str, cids: &[String],
+    ) -> impl Future<Output = Result<Vec<Option<Vec<u8>>>>> + Send {
+        async move {
+            let mut blocks = Vec::with_capacity(cids.len());
+            for cid in cids {
+                blocks.push(self.get(owner, partition, cid).await?);
+            }
+            Ok(blocks)
+        }
+    }
+
     /// Purge all blocks from the store.
     fn purge(&self, owner: &str, partition: &str) -> impl Future<Output = Result<()>> + Send;
 }
@@ -58,6 +329,27 @@ pub trait MessageStore: BlockStore + Sized + Send + Sync {
         async move { message::query(owner, query, self).await.map_err(Into::into) }
     }
 
+    /// Counts the messages matching the provided query without fetching them.
+    /// Query pagination is ignored.
+    fn count(&self, owner: &str, query: &Query) -> impl Future<Output = Result<u64>> + Send {
+        async move { message::count(owner, query, self).await.map_err(Into::into) }
+    }
+
+    /// Counts the messages matching the provided query for each distinct
+    /// value of the `group_by` field. Messages without a value for the field
+    /// are not counted. Query pagination is ignored.
+    fn count_by(
+        &self, owner: &str, query: &Query, group_by: GroupBy,
+    ) -> impl Future<Output = Result<BTreeMap<String, u64>>> + Send {
+        async move { message::count_by(owner, query, group_by, self).await.map_err(Into::into) }
+    }
+
+    /// Returns statistics (message count, data bytes, and distinct protocols)
+    /// for the owner's stored messages.
+    fn stats(&self, owner: &str) -> impl Future<Output = Result<StoreStats>> + Send {
+        async move { message::stats(owner, self).await.map_err(Into::into) }
+    }
+
     /// Fetch a single message by CID from the underlying store, returning
     /// `None` if no message was found.
     fn get(
@@ -71,14 +363,51 @@ pub trait MessageStore: BlockStore + Sized + Send + Sync {
         async move { message::delete(owner, message_cid, self).await.map_err(Into::into) }
     }
 
+    /// Rebuild the owner's query indexes (`recordId`, `protocol`, `schema`,
+    /// `contextId`, `recipient`, tags, dates, etc.) by re-deriving them from
+    /// stored messages. Used to repair indexes that have drifted from the
+    /// messages they index, e.g. after a crash.
+    ///
+    /// Rebuilding is idempotent, but should only be run while the node is
+    /// quiescent as messages stored during the rebuild may not be indexed.
+    fn rebuild_indexes(&self, owner: &str) -> impl Future<Output = Result<()>> + Send {
+        async move { message::rebuild_indexes(owner, self).await.map_err(Into::into) }
+    }
+
+    /// Set a single query index field for a stored message, leaving the
+    /// message and its other index fields unchanged.
+    fn set_index(
+        &self, owner: &str, message_cid: &str, field: &str, value: &str,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            message::set_index(owner, message_cid, field, value, self).await.map_err(Into::into)
+        }
+    }
+
     /// Purge all records from the store.
     fn purge(&self) -> impl Future<Output = Result<()>> + Send {
         async move { todo!("implement purge") }
     }
+
+    /// Begin a [`Transaction`] whose store operations can be committed or
+    /// rolled back as a unit.
+    fn transaction(&self) -> Transaction<'_, Self>
+    where
+        Self: Provider,
+    {
+        Transaction::new(self)
+    }
 }
 
 /// The `DataStore` trait is used by implementers to provide data storage
 /// capability.
+///
+/// Record data (content addressed by a write's `data_cid`) is read and
+/// written exclusively through this trait, while messages go through
+/// [`MessageStore`]. The default methods store data alongside messages in the
+/// provider's [`BlockStore`]; implementers wanting to keep large objects
+/// elsewhere (e.g. an object store) override `put`, `get`, `open`,
+/// `get_range`, `delete`, and `collect`.
 pub trait DataStore: BlockStore + Sized + Send + Sync {
     // /// Open a connection to the underlying store.
     // fn open(&self) -> impl Future<Output = anyhow::Result<()>> + Send;
@@ -88,21 +417,47 @@ pub trait DataStore: BlockStore + Sized + Send + Sync {
 
     /// Store data in an underlying block store.
     ///
-    /// The default implementation uses the `BlockStore` provider for storage.
-    /// This may be overridden by implementers to provide custom storage.
+    /// The default implementation uses the `BlockStore` provider for storage,
+    /// stamping the data with the provider's [`Clock`] so it is collected once
+    /// orphaned for the provider's retention period. This may be overridden by
+    /// implementers to provide custom storage.
     fn put(
-        &self, owner: &str, record_id: &str, data_cid: &str, reader: impl Read + Send,
-    ) -> impl Future<Output = anyhow::Result<(String, usize)>> + Send {
-        async move { data::put(owner, record_id, data_cid, reader, self).await.map_err(Into::into) }
+        &self, owner: &str, record_id: &str, data_cid: &str, reader: impl AsyncRead + Send + Unpin,
+    ) -> impl Future<Output = anyhow::Result<(String, usize)>> + Send
+    where
+        Self: Provider,
+    {
+        let stored = self.clock().now();
+        async move {
+            data::put(owner, record_id, data_cid, reader, stored, self).await.map_err(Into::into)
+        }
     }
 
-    /// Fetches a single message by CID from an underlying block store.
+    /// Fetches a record's data by CID from an underlying block store.
     fn get(
         &self, owner: &str, record_id: &str, data_cid: &str,
     ) -> impl Future<Output = anyhow::Result<Option<impl Read>>> + Send {
         async move { data::get(owner, record_id, data_cid, self).await.map_err(Into::into) }
     }
 
+    /// Opens a record's data by CID for reading, fetching data blocks from an
+    /// underlying block store on demand as the data is read.
+    fn open(
+        &self, owner: &str, record_id: &str, data_cid: &str,
+    ) -> impl Future<Output = anyhow::Result<Option<impl AsyncRead + Send + Unpin>>> + Send {
+        async move { data::open(owner, record_id, data_cid, self).await.map_err(Into::into) }
+    }
+
+    /// Fetches the `[start, end)` byte range of a record's data by CID from
+    /// an underlying block store.
+    fn get_range(
+        &self, owner: &str, record_id: &str, data_cid: &str, start: usize, end: usize,
+    ) -> impl Future<Output = anyhow::Result<Option<impl Read>>> + Send {
+        async move {
+            data::get_range(owner, record_id, data_cid, start, end, self).await.map_err(Into::into)
+        }
+    }
+
     /// Delete data associated with the specified id.
     fn delete(
         &self, owner: &str, record_id: &str, data_cid: &str,
@@ -110,6 +465,21 @@ pub trait DataStore: BlockStore + Sized + Send + Sync {
         async move { data::delete(owner, record_id, data_cid, self).await.map_err(Into::into) }
     }
 
+    /// Delete data no longer referenced by the owner's records, as found by
+    /// [`gc::collect_orphans`], returning the storage reclaimed.
+    ///
+    /// The default implementation collects the blocks written by the other
+    /// default methods, retaining data stored within a short grace period so
+    /// data for writes still in flight is not collected.
+    fn collect(
+        &self, owner: &str, records: &BTreeMap<String, gc::Referenced>,
+    ) -> impl Future<Output = anyhow::Result<gc::Reclaimed>> + Send
+    where
+        Self: Provider,
+    {
+        async move { gc::collect_blocks(owner, records, self).await.map_err(Into::into) }
+    }
+
     /// Purge all data from the store.
     fn purge(&self) -> impl Future<Output = anyhow::Result<()>> + Send {
         async move { todo!("implement purge") }
@@ -200,6 +570,9 @@ pub trait EventLog: BlockStore + Sized + Send + Sync {
     /// events for a given owner and filter combo will be returned. The cursor
     /// is a `message_cid`.
     ///
+    /// When the query's `since` watermark is set (see [`Query::since`]), only
+    /// events logged after that watermark are returned.
+    ///
     /// Returns an array of `message_cid`s that represent the events.
     fn query(
         &self, owner: &str, query: &Query,
@@ -207,6 +580,15 @@ pub trait EventLog: BlockStore + Sized + Send + Sync {
         async move { event_log::query(owner, query, self).await.map_err(Into::into) }
     }
 
+    /// Returns the owner's current watermark: the sequence number assigned to
+    /// the most recently logged event, or 0 when no events have been logged.
+    ///
+    /// Watermarks increase monotonically and are persisted with the log, so
+    /// they can be used as checkpoints for incremental sync.
+    fn watermark(&self, owner: &str) -> impl Future<Output = Result<u64>> + Send {
+        async move { event_log::watermark(owner, self).await.map_err(Into::into) }
+    }
+
     /// Deletes event for the specified `message_cid`.
     fn delete(&self, owner: &str, message_cid: &str) -> impl Future<Output = Result<()>> + Send {
         async move { event_log::delete(owner, message_cid, self).await.map_err(Into::into) }
@@ -0,0 +1,254 @@
+//! # Garbage Collection
+//!
+//! Record data is stored in the [`BlockStore`] as a root block linking to
+//! chunked data blocks. Blocks are orphaned when the record is updated with
+//! new data or deleted, and can be reclaimed using [`collect_orphans`].
+//!
+//! Deleted records leave a `RecordsDelete` tombstone behind. Tombstones
+//! created with a retention period can be removed, along with the rest of the
+//! record, once the period has expired using [`collect_tombstones`]. A small
+//! marker is kept in place of each collected record so the record cannot be
+//! recreated by replaying its initial write.
+//!
+//! Orphaned data is removed using [`DataStore::collect`], so providers
+//! storing data elsewhere collect it from their own store.
+//!
+//! [`BlockStore`]: crate::provider::BlockStore
+//! [`DataStore::collect`]: crate::provider::DataStore::collect
+
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::Duration;
+
+use crate::provider::{BlockStore, DataStore, EventLog, MessageStore, Provider};
+use crate::records::RecordsFilter;
+use crate::store::{EntryType, RecordsQueryBuilder, block, data};
+use crate::utils::cid;
+use crate::{Message, Method, Result};
+
+// Time allowed between storing data and saving the message referencing it.
+const GRACE_MINUTES: i64 = 10;
+
+// Partition holding markers for records whose tombstones were collected.
+const PARTITION: &str = "TOMBSTONE";
+
+/// The blocks reclaimed by [`collect_orphans`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reclaimed {
+    /// The number of blocks deleted.
+    pub blocks: usize,
+
+    /// The total size, in bytes, of the blocks deleted.
+    pub bytes: usize,
+}
+
+/// The data referenced by a record's stored messages, used by
+/// [`DataStore::collect`] to find orphaned data.
+#[derive(Clone, Debug, Default)]
+pub struct Referenced {
+    /// The `data_cid` of each write stored for the record.
+    pub data_cids: HashSet<String>,
+
+    /// Whether the record has been deleted, in which case none of its data
+    /// is referenced.
+    pub deleted: bool,
+}
+
+/// Delete data blocks no longer referenced by a record's current write or its
+/// retained initial write.
+///
+/// Data not yet referenced by any message is retained for a grace period,
+/// unless superseded by newer data for the same record, so blocks stored by
+/// writes still in flight are not collected. The owner's data cannot be
+/// stored while collection is in progress.
+///
+/// Data stored before data was registered for collection is registered
+/// when first referenced, and so is never mistaken for an orphan.
+///
+/// # Errors
+///
+/// Returns an error when there is an issue querying the [`MessageStore`] or
+/// collecting data from the [`DataStore`].
+pub async fn collect_orphans(owner: &str, provider: &impl Provider) -> Result<Reclaimed> {
+    collect(owner, &[], provider).await
+}
+
+// Collect orphaned data blocks, treating the records in `removed` — those
+// whose messages have all been removed — as deleted.
+async fn collect(owner: &str, removed: &[String], provider: &impl Provider) -> Result<Reclaimed> {
+    let locks = data::locks(owner);
+    let _guard = locks.data.write().await;
+
+    // find the data referenced by each record's messages
+    let query = RecordsQueryBuilder::new()
+        .add_filter(RecordsFilter::new())
+        .include_archived(true)
+        .method(None)
+        .build();
+    let (entries, _) = MessageStore::query(provider, owner, &query).await?;
+
+    let mut records: BTreeMap<String, Referenced> = BTreeMap::new();
+    for entry in entries {
+        match entry.message {
+            EntryType::Write(write) => {
+                let referenced = records.entry(write.record_id).or_default();
+                referenced.data_cids.insert(write.descriptor.data_cid);
+            }
+            EntryType::Delete(delete) => {
+                records.entry(delete.descriptor.record_id).or_default().deleted = true;
+            }
+            EntryType::Configure(_) => {}
+        }
+    }
+    for record_id in removed {
+        records.entry(record_id.clone()).or_default().deleted = true;
+    }
+
+    DataStore::collect(provider, owner, &records).await.map_err(Into::into)
+}
+
+/// Delete the data blocks written by the default [`DataStore`] methods that
+/// are no longer referenced by the owner's records.
+///
+/// # Errors
+///
+/// Returns an error when there is an issue reading or deleting blocks from
+/// the block store.
+pub(crate) async fn collect_blocks(
+    owner: &str, records: &BTreeMap<String, Referenced>, provider: &impl Provider,
+) -> Result<Reclaimed> {
+    let mut records = records.clone();
+
+    // include records with recently registered data, which may have no
+    // messages yet
+    let now = provider.clock().now();
+    for record_id in data::recent_records(owner, now, provider).await? {
+        records.entry(record_id).or_default();
+    }
+
+    let cutoff = now - Duration::minutes(GRACE_MINUTES);
+    let mut orphans = BTreeMap::new();
+    let mut retained = HashSet::new();
+
+    for (record_id, referenced) in &records {
+        for data_cid in &referenced.data_cids {
+            data::register_existing(owner, record_id, data_cid, now, provider).await?;
+        }
+        let roots = data::record_roots(owner, record_id, provider).await?;
+
+        // a root is live while its block exists and its data is referenced
+        let mut live = HashSet::new();
+        let mut deleted = HashSet::new();
+        for (root_cid, root) in &roots {
+            if !data::has_root(owner, root_cid, provider).await? {
+                deleted.insert(root_cid.as_str());
+            } else if referenced.data_cids.contains(&root.data_cid) {
+                live.insert(root_cid.as_str());
+            }
+        }
+
+        for (root_cid, root) in &roots {
+            if !live.contains(root_cid.as_str()) {
+                let superseded = roots.iter().any(|(other_cid, other)| {
+                    other.stored > root.stored && live.contains(other_cid.as_str())
+                });
+                if deleted.contains(root_cid.as_str())
+                    || superseded
+                    || referenced.deleted
+                    || root.stored < cutoff
+                {
+                    orphans.insert(root_cid.clone(), root.clone());
+                    continue;
+                }
+            }
+
+            // blocks of live and in-flight roots are retained
+            retained.insert(root_cid.clone());
+            retained.extend(data::root_links(owner, root_cid, provider).await?);
+        }
+    }
+
+    let (blocks, bytes) = data::remove_roots(owner, &orphans, &retained, provider).await?;
+    data::collected_until(owner, cutoff, provider).await?;
+
+    Ok(Reclaimed { blocks, bytes })
+}
+
+/// Remove expired `RecordsDelete` tombstones, along with the initial write
+/// retained for each deleted record, returning the number of tombstones
+/// removed.
+///
+/// Until removed, a tombstone remains queryable as proof the record was
+/// deleted by an authorized author. Once removed, a marker recording the
+/// deleted `record_id` is kept so a replayed initial write is rejected. Data
+/// blocks orphaned by the removal are collected as by [`collect_orphans`].
+///
+/// # Errors
+///
+/// Returns an error when there is an issue querying or deleting messages from
+/// the [`MessageStore`] or [`EventLog`].
+pub async fn collect_tombstones(owner: &str, provider: &impl Provider) -> Result<usize> {
+    let query = RecordsQueryBuilder::new()
+        .add_filter(RecordsFilter::new())
+        .include_archived(true)
+        .method(Some(Method::Delete))
+        .build();
+    let (entries, _) = MessageStore::query(provider, owner, &query).await?;
+
+    let now = provider.clock().now();
+    let mut removed = vec![];
+
+    for entry in entries {
+        let EntryType::Delete(delete) = entry.message else {
+            continue;
+        };
+        if delete.descriptor.retain_until.is_none_or(|retain_until| retain_until > now) {
+            continue;
+        }
+
+        // mark the record as deleted, by the tombstone's CID, before removing
+        // its messages so a replayed initial write cannot recreate it
+        let record_id = &delete.descriptor.record_id;
+        let marker = block::encode(&delete.cid()?)?;
+        BlockStore::put(provider, owner, PARTITION, &marker_cid(owner, record_id)?, &marker)
+            .await?;
+
+        // remove the tombstone and every message retained for the record
+        let query = RecordsQueryBuilder::new()
+            .add_filter(RecordsFilter::new().record_id(record_id))
+            .include_archived(true)
+            .method(None)
+            .build();
+        let (messages, _) = MessageStore::query(provider, owner, &query).await?;
+        for message in messages {
+            let cid = message.cid()?;
+            EventLog::delete(provider, owner, &cid).await?;
+            MessageStore::delete(provider, owner, &cid).await?;
+        }
+        removed.push(record_id.clone());
+    }
+
+    // the removed records' data can no longer be found from their messages
+    if !removed.is_empty() {
+        collect(owner, &removed, provider).await?;
+    }
+
+    Ok(removed.len())
+}
+
+/// Whether the record's tombstone has been removed by
+/// [`collect_tombstones`], meaning the record was deleted and must not be
+/// recreated.
+///
+/// # Errors
+///
+/// Returns an error when there is an issue reading from the block store.
+pub(crate) async fn is_collected(
+    owner: &str, record_id: &str, store: &impl BlockStore,
+) -> Result<bool> {
+    Ok(store.get(owner, PARTITION, &marker_cid(owner, record_id)?).await?.is_some())
+}
+
+fn marker_cid(owner: &str, record_id: &str) -> Result<String> {
+    cid::from_value(&format!("{owner}-{record_id}-collected"))
+}
@@ -0,0 +1,226 @@
+//! # Sync
+//!
+//! Web nodes operated by the same owner replicate state by pulling messages
+//! from one another. [`pull`] queries a [`Remote`] node for the events logged
+//! since a given watermark (`MessagesQuery`), reads each referenced message
+//! (`MessagesRead`), and applies it locally.
+//!
+//! Messages are applied through the same validation and handling pipeline as
+//! messages received from clients, so signatures, authorization, and protocol
+//! rules are enforced on every pulled message.
+
+use std::future::Future;
+
+use chrono::{DateTime, Utc};
+
+use crate::endpoint::{self, Message, Reply};
+use crate::interfaces::messages::{Query, QueryBuilder, QueryReply, Read, ReadBuilder};
+use crate::provider::{EventLog, MessageStore, Provider, Signer, gc};
+use crate::records::{DataReader, RecordsFilter};
+use crate::store::{EntryType, RecordsQueryBuilder};
+use crate::{Error, Result, protocols};
+
+// The number of remote events fetched per page.
+const PAGE_SIZE: usize = 100;
+
+/// A remote web node, reached through its `Messages` interface (e.g. over
+/// HTTP).
+pub trait Remote: Send + Sync {
+    /// Send a `MessagesQuery` message to the remote node, returning its reply.
+    fn query(
+        &self, owner: &str, query: Query,
+    ) -> impl Future<Output = Result<Reply<QueryReply>>> + Send;
+
+    /// Send a `MessagesRead` message to the remote node, returning the message
+    /// read along with a reader for its data, if any. The data is streamed
+    /// from the reader as the message is applied.
+    ///
+    /// Returns [`Error::NotFound`] when the message is no longer stored by the
+    /// remote node.
+    fn read(
+        &self, owner: &str, read: Read,
+    ) -> impl Future<Output = Result<(EntryType, Option<DataReader>)>> + Send;
+}
+
+/// The progress made by a [`pull`].
+#[derive(Debug, Default)]
+pub struct Pulled {
+    /// The watermark of the last remote event pulled, to be used as `since`
+    /// for the next pull. Unchanged when there were no new events.
+    pub since: Option<u64>,
+
+    /// The local node's watermark after pulled messages were applied.
+    pub watermark: u64,
+
+    /// The number of messages applied to the local node.
+    pub applied: usize,
+
+    /// The number of messages skipped because the local node already has
+    /// them, or a more recent message for the same record or protocol, or
+    /// because they have been superseded on the remote node.
+    pub skipped: usize,
+
+    /// Messages that could not be applied, by message CID, with the error
+    /// returned for each.
+    pub failed: Vec<(String, Error)>,
+}
+
+/// Pull messages logged by the `remote` node after the `since` watermark,
+/// applying each to the `local` node. Messages sent to the remote node are
+/// signed by `signer`, who must be the owner or hold a grant to query and
+/// read the owner's messages.
+///
+/// Remote events are fetched a page at a time, in watermark order. Messages
+/// conflicting with a more recent local message are resolved using the usual
+/// timestamp/CID tie-break: the local message is retained and the pulled
+/// message is skipped. Messages failing for any other reason, including any
+/// other conflict, are recorded in [`Pulled::failed`] and the pull continues
+/// with the next message.
+///
+/// # Errors
+///
+/// Returns an error when there is an issue querying the `remote` node's
+/// event log or reading the `local` node's watermark.
+pub async fn pull(
+    owner: &str, local: &impl Provider, remote: &impl Remote, signer: &impl Signer,
+    since: Option<u64>,
+) -> Result<Pulled> {
+    let mut pulled = Pulled {
+        since,
+        ..Pulled::default()
+    };
+
+    loop {
+        let mut builder = QueryBuilder::new().limit(PAGE_SIZE);
+        if let Some(since) = pulled.since {
+            builder = builder.since(since);
+        }
+        let query = builder.sign(signer).build().await?;
+        let reply = remote.query(owner, query).await?;
+        let Some(body) = reply.body else {
+            break;
+        };
+
+        for message_cid in body.entries.unwrap_or_default() {
+            match apply(owner, &message_cid, local, remote, signer).await {
+                Ok(true) => pulled.applied += 1,
+                Ok(false) => pulled.skipped += 1,
+                Err(e) => pulled.failed.push((message_cid, e)),
+            }
+        }
+
+        // stop once the remote log is exhausted or fails to advance
+        let previous = pulled.since;
+        if let Some(watermark) = body.watermark {
+            pulled.since = Some(previous.map_or(watermark, |since| since.max(watermark)));
+        }
+        if body.cursor.is_none() || pulled.since == previous {
+            break;
+        }
+    }
+
+    pulled.watermark = EventLog::watermark(local, owner).await?;
+    Ok(pulled)
+}
+
+// Read the message referenced by a remote event and apply it to the local
+// node. Returns `false` when the message is skipped.
+async fn apply(
+    owner: &str, message_cid: &str, local: &impl Provider, remote: &impl Remote,
+    signer: &impl Signer,
+) -> Result<bool> {
+    // the local node already has the message
+    if MessageStore::get(local, owner, message_cid).await?.is_some() {
+        return Ok(false);
+    }
+
+    let read = ReadBuilder::new().message_cid(message_cid).sign(signer).build().await?;
+    let (message, data) = match remote.read(owner, read).await {
+        Ok(read) => read,
+        // superseded on the remote node since the event was logged
+        Err(Error::NotFound(_)) => return Ok(false),
+        Err(e) => return Err(e),
+    };
+
+    let (timestamp, target) = match &message {
+        EntryType::Write(write) => {
+            (write.descriptor.base.message_timestamp, Target::Record(write.record_id.clone()))
+        }
+        EntryType::Delete(delete) => (
+            delete.descriptor.base.message_timestamp,
+            Target::Record(delete.descriptor.record_id.clone()),
+        ),
+        EntryType::Configure(configure) => (
+            configure.descriptor.base.message_timestamp,
+            Target::Protocol(configure.descriptor.definition.protocol.clone()),
+        ),
+    };
+
+    let result = match message {
+        EntryType::Write(mut write) => {
+            if let Some(data) = data {
+                write.with_reader(data);
+            }
+            endpoint::handle(owner, write, local).await.map(|_| ())
+        }
+        EntryType::Delete(delete) => endpoint::handle(owner, delete, local).await.map(|_| ()),
+        EntryType::Configure(configure) => {
+            endpoint::handle(owner, configure, local).await.map(|_| ())
+        }
+    };
+
+    match result {
+        Ok(()) => Ok(true),
+        Err(Error::Conflict(e)) => {
+            if target.is_after(owner, timestamp, message_cid, local).await? {
+                Ok(false)
+            } else {
+                Err(Error::Conflict(e))
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// The record or protocol a pulled message applies to.
+enum Target {
+    Record(String),
+    Protocol(String),
+}
+
+impl Target {
+    // Whether the local node holds a message for the record or protocol that
+    // wins the timestamp/CID tie-break against the pulled message.
+    async fn is_after(
+        &self, owner: &str, timestamp: DateTime<Utc>, message_cid: &str, local: &impl Provider,
+    ) -> Result<bool> {
+        let latest = match self {
+            Self::Record(record_id) => {
+                let query = RecordsQueryBuilder::new()
+                    .add_filter(RecordsFilter::new().record_id(record_id))
+                    .include_archived(true)
+                    .method(None)
+                    .build();
+                let (entries, _) = MessageStore::query(local, owner, &query).await?;
+                let Some(latest) = entries.last() else {
+                    // a record whose tombstone has been collected was deleted
+                    return gc::is_collected(owner, record_id, local).await;
+                };
+                (latest.descriptor().message_timestamp, latest.cid()?)
+            }
+            Self::Protocol(protocol) => {
+                let configs = protocols::fetch_config(owner, Some(protocol.clone()), local).await?;
+                let Some(latest) = configs.unwrap_or_default().into_iter().max_by(|a, b| {
+                    a.descriptor.base.message_timestamp.cmp(&b.descriptor.base.message_timestamp)
+                }) else {
+                    return Ok(false);
+                };
+                (latest.descriptor.base.message_timestamp, latest.cid()?)
+            }
+        };
+
+        let (latest_ts, latest_cid) = latest;
+        Ok((latest_ts.timestamp_micros(), latest_cid.as_str())
+            >= (timestamp.timestamp_micros(), message_cid))
+    }
+}
@@ -0,0 +1,847 @@
+//! # Transaction
+//!
+//! A [`Transaction`] groups store operations so they can be committed or
+//! rolled back as a unit.
+//!
+//! Changes made within a transaction are staged rather than being written to
+//! the provider. Reads made through the transaction see the staged changes
+//! layered over the provider's stored state. On commit, staged changes are
+//! applied, in order, using the provider's own [`MessageStore`],
+//! [`DataStore`], [`EventLog`], and [`BlockStore`] implementations, and events
+//! held back while the transaction was in progress are emitted. On rollback,
+//! staged changes are discarded, so the provider is never modified.
+//!
+//! Commits are atomic: the provider's state is captured before each change is
+//! applied, and should a change fail, the changes already applied are undone,
+//! most recent first. Events restored when undoing a commit are logged anew.
+//!
+//! Record data written within a transaction is streamed into blocks staged in
+//! the provider's [`BlockStore`], under a record ID unique to the
+//! transaction, rather than held in memory. Staged blocks are removed once the
+//! transaction is committed or rolled back. Those of a transaction dropped
+//! without either are collected as orphaned data (see [`gc`]).
+//!
+//! Transactions are not isolated from concurrent writers: reads see changes
+//! committed by other writers while the transaction is in progress.
+//!
+//! [`gc`]: crate::provider::gc
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use anyhow::{Result, anyhow};
+use chrono::TimeDelta;
+use futures::AsyncRead;
+
+use crate::endpoint::RateLimiter;
+use crate::event::{Event, Subscriber};
+use crate::provider::{
+    BlockStore, Clock, DataStore, DidResolver, Document, EventLog, EventStream, MessageStore,
+    Provider, ResolverCache, TaskStore,
+};
+use crate::store::index::{self, IndexItem};
+use crate::store::{
+    Cursor, Entry, EntryType, GroupBy, Pagination, Query, StoreStats, data, event_log, message,
+};
+use crate::tasks::ResumableTask;
+
+// Record IDs used to stage data are prefixed to avoid colliding with records.
+const STAGING: &str = "transaction";
+
+// The number of stored messages fetched in each page when layering staged
+// messages over an unpaginated query.
+const PAGE_SIZE: usize = 100;
+
+/// A provider wrapper applying store operations within a transaction
+/// boundary.
+///
+/// Create a transaction using [`MessageStore::transaction`], then finish it
+/// with either [`Transaction::commit`] or [`Transaction::rollback`].
+#[derive(Clone)]
+pub struct Transaction<'a, P> {
+    provider: &'a P,
+    id: String,
+    changes: Arc<Mutex<Vec<Change>>>,
+    staging: Arc<Mutex<Vec<StagedData>>>,
+    tasks: Arc<Mutex<HashMap<(String, String), ResumableTask>>>,
+    events: Arc<Mutex<Vec<(String, Event)>>>,
+}
+
+// A change staged within a transaction. A `None` value marks a deletion.
+enum Change {
+    Block { owner: String, partition: String, cid: String, block: Option<Vec<u8>> },
+    Message { owner: String, message_cid: String, entry: Option<Entry> },
+    Data { owner: String, record_id: String, data_cid: String, staged: Option<String> },
+    Event { owner: String, message_cid: String, event: Option<Event> },
+    Indexes { owner: String },
+}
+
+// Data staged in the provider's block store, removed when the transaction
+// ends.
+struct StagedData {
+    owner: String,
+    record_id: String,
+    data_cid: String,
+}
+
+impl<'a, P: Provider> Transaction<'a, P> {
+    /// Begin a new transaction against `provider`.
+    pub fn new(provider: &'a P) -> Self {
+        Self {
+            provider,
+            id: uuid::Uuid::new_v4().to_string(),
+            changes: Arc::new(Mutex::new(vec![])),
+            staging: Arc::new(Mutex::new(vec![])),
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            events: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Commit the transaction, applying the changes staged while it was in
+    /// progress to the provider, then emitting any events held back.
+    ///
+    /// Should a change fail to apply, the changes already applied are undone
+    /// before the error is returned, leaving the provider unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when a staged change cannot be applied (or undone) or
+    /// a held back event cannot be emitted.
+    pub async fn commit(self) -> Result<()> {
+        let changes = std::mem::take(&mut *self.changes.lock().map_err(|_| poisoned())?);
+
+        let mut undo = vec![];
+        for change in changes {
+            let applied = match self.inverse(&change).await {
+                Ok(inverse) => {
+                    undo.extend(inverse);
+                    self.apply(change).await
+                }
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = applied {
+                // undo the changes applied so far, most recent first
+                for inverse in undo.into_iter().rev() {
+                    self.apply(inverse)
+                        .await
+                        .map_err(|undo_err| anyhow!("{e}: issue undoing commit: {undo_err}"))?;
+                }
+                self.discard().await?;
+                return Err(e);
+            }
+        }
+        self.discard().await?;
+
+        let events = std::mem::take(&mut *self.events.lock().map_err(|_| poisoned())?);
+        for (owner, event) in events {
+            self.provider.emit(&owner, &event).await?;
+        }
+        Ok(())
+    }
+
+    /// Roll back the transaction, discarding the changes staged while it was
+    /// in progress. The provider is left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the transaction's state cannot be accessed or its
+    /// staged data cannot be removed.
+    pub async fn rollback(self) -> Result<()> {
+        self.changes.lock().map_err(|_| poisoned())?.clear();
+        self.events.lock().map_err(|_| poisoned())?.clear();
+        self.discard().await
+    }
+
+    // Apply a staged change to the provider.
+    async fn apply(&self, change: Change) -> Result<()> {
+        let provider = self.provider;
+        match change {
+            Change::Block {
+                owner,
+                partition,
+                cid,
+                block,
+            } => match block {
+                Some(block) => BlockStore::put(provider, &owner, &partition, &cid, &block).await,
+                None => BlockStore::delete(provider, &owner, &partition, &cid).await,
+            },
+            Change::Message {
+                owner,
+                message_cid,
+                entry,
+            } => match entry {
+                Some(entry) => MessageStore::put(provider, &owner, &entry).await,
+                None => MessageStore::delete(provider, &owner, &message_cid).await,
+            },
+            Change::Data {
+                owner,
+                record_id,
+                data_cid,
+                staged,
+            } => match staged {
+                Some(staged) => {
+                    let Some(reader) = data::open(&owner, &staged, &data_cid, provider).await?
+                    else {
+                        return Err(anyhow!("staged data not found"));
+                    };
+                    DataStore::put(provider, &owner, &record_id, &data_cid, reader).await?;
+                    Ok(())
+                }
+                None => DataStore::delete(provider, &owner, &record_id, &data_cid).await,
+            },
+            Change::Event {
+                owner,
+                message_cid,
+                event,
+            } => match event {
+                Some(event) => EventLog::append(provider, &owner, &event).await,
+                None => EventLog::delete(provider, &owner, &message_cid).await,
+            },
+            Change::Indexes { owner } => MessageStore::rebuild_indexes(provider, &owner).await,
+        }
+    }
+
+    // The change undoing `change`, captured from the provider's current
+    // state. Returns `None` when applying `change` leaves the state as is.
+    async fn inverse(&self, change: &Change) -> Result<Option<Change>> {
+        let provider = self.provider;
+        let inverse = match change {
+            Change::Block {
+                owner,
+                partition,
+                cid,
+                ..
+            } => Change::Block {
+                owner: owner.clone(),
+                partition: partition.clone(),
+                cid: cid.clone(),
+                block: BlockStore::get(provider, owner, partition, cid).await?,
+            },
+            Change::Message {
+                owner, message_cid, ..
+            } => Change::Message {
+                owner: owner.clone(),
+                message_cid: message_cid.clone(),
+                entry: self.stored_message(owner, message_cid).await?,
+            },
+            Change::Data {
+                owner,
+                record_id,
+                data_cid,
+                staged,
+            } => {
+                let stored = DataStore::open(provider, owner, record_id, data_cid).await?;
+                let staged = match (stored, staged) {
+                    (None, _) => None,
+                    // data is content addressed, so storing it again changes nothing
+                    (Some(_), Some(_)) => return Ok(None),
+                    (Some(reader), None) => {
+                        let undo_id = format!("undo/{record_id}");
+                        Some(self.stage_data(owner, &undo_id, data_cid, reader).await?.0)
+                    }
+                };
+                Change::Data {
+                    owner: owner.clone(),
+                    record_id: record_id.clone(),
+                    data_cid: data_cid.clone(),
+                    staged,
+                }
+            }
+            Change::Event {
+                owner, message_cid, ..
+            } => Change::Event {
+                owner: owner.clone(),
+                message_cid: message_cid.clone(),
+                event: event_log::get(owner, message_cid, provider).await?,
+            },
+            // indexes are derived from stored messages
+            Change::Indexes { .. } => return Ok(None),
+        };
+        Ok(Some(inverse))
+    }
+
+    // Stream data into blocks staged in the provider's block store under a
+    // record ID unique to the transaction. Returns the staging record ID,
+    // along with the data's CID and size.
+    async fn stage_data(
+        &self, owner: &str, record_id: &str, data_cid: &str, reader: impl AsyncRead + Unpin,
+    ) -> Result<(String, String, usize)> {
+        let staged_id = format!("{STAGING}/{}/{record_id}", self.id);
+        let stored = self.provider.clock().now();
+        let (actual_cid, data_size) =
+            data::put(owner, &staged_id, data_cid, reader, stored, self.provider).await?;
+
+        self.staging.lock().map_err(|_| poisoned())?.push(StagedData {
+            owner: owner.to_string(),
+            record_id: staged_id.clone(),
+            data_cid: data_cid.to_string(),
+        });
+        Ok((staged_id, actual_cid, data_size))
+    }
+
+    // Remove the data staged by the transaction. Blocks shared with stored
+    // data are left for collection.
+    async fn discard(&self) -> Result<()> {
+        let staging = std::mem::take(&mut *self.staging.lock().map_err(|_| poisoned())?);
+        for staged in staging {
+            data::delete(&staged.owner, &staged.record_id, &staged.data_cid, self.provider).await?;
+        }
+        Ok(())
+    }
+
+    // Stage a change to be applied on commit.
+    fn stage(&self, change: Change) -> Result<()> {
+        self.changes.lock().map_err(|_| poisoned())?.push(change);
+        Ok(())
+    }
+
+    // The most recently staged state of a block, if changed.
+    fn staged_block(&self, owner: &str, partition: &str, cid: &str) -> Result<Option<Staged>> {
+        let changes = self.changes.lock().map_err(|_| poisoned())?;
+        Ok(changes.iter().rev().find_map(|change| match change {
+            Change::Block {
+                owner: o,
+                partition: p,
+                cid: c,
+                block,
+            } if o == owner && p == partition && c == cid => Some(block.clone()),
+            _ => None,
+        }))
+    }
+
+    // The most recently staged state of each message changed for `owner`,
+    // keyed by message CID.
+    fn staged_messages(&self, owner: &str) -> Result<HashMap<String, Option<Entry>>> {
+        let changes = self.changes.lock().map_err(|_| poisoned())?;
+        let mut messages = HashMap::new();
+        for change in changes.iter() {
+            if let Change::Message {
+                owner: o,
+                message_cid,
+                entry,
+            } = change
+                && o == owner
+            {
+                messages.insert(message_cid.clone(), entry.clone());
+            }
+        }
+        Ok(messages)
+    }
+
+    // The most recently staged state of a record's data, if changed: the
+    // record ID the data is staged under, or `None` when deleted.
+    fn staged_data(
+        &self, owner: &str, record_id: &str, data_cid: &str,
+    ) -> Result<Option<Option<String>>> {
+        let changes = self.changes.lock().map_err(|_| poisoned())?;
+        Ok(changes.iter().rev().find_map(|change| match change {
+            Change::Data {
+                owner: o,
+                record_id: r,
+                data_cid: d,
+                staged,
+            } if o == owner && r == record_id && d == data_cid => Some(staged.clone()),
+            _ => None,
+        }))
+    }
+
+    // A message stored by the provider, with its index fields.
+    async fn stored_message(&self, owner: &str, message_cid: &str) -> Result<Option<Entry>> {
+        let Some(mut entry) = message::get_indexed(owner, message_cid, self.provider).await? else {
+            return Ok(None);
+        };
+        // providers not indexing messages in the block store
+        if entry.indexes().is_empty() {
+            for (field, value) in fields(&entry) {
+                entry.add_index(field, value);
+            }
+        }
+        Ok(Some(entry))
+    }
+
+    // Whether a stored message matches `query`, returning its index fields
+    // when it does.
+    async fn stored_match(
+        &self, owner: &str, message_cid: &str, query: &Query,
+    ) -> Result<Option<HashMap<String, String>>> {
+        let Some(entry) = self.stored_message(owner, message_cid).await? else {
+            return Ok(None);
+        };
+        if query.is_match(entry.indexes())? {
+            return Ok(Some(entry.indexes().clone()));
+        }
+        Ok(None)
+    }
+
+    // Find the messages matching `query`, with staged messages layered over
+    // those stored by the provider. Each message is returned with the index
+    // fields used to sort it.
+    async fn matching(
+        &self, owner: &str, query: &Query, staged: HashMap<String, Option<Entry>>,
+    ) -> Result<Vec<(IndexItem, Entry)>> {
+        let mut matches = vec![];
+        for entry in self.stored(owner, query, staged.len()).await? {
+            let message_cid = entry.cid()?;
+            if staged.contains_key(&message_cid) {
+                continue;
+            }
+            let item = IndexItem {
+                fields: fields(&entry),
+                message_cid,
+            };
+            matches.push((item, entry));
+        }
+        for (message_cid, entry) in staged {
+            let Some(entry) = entry else {
+                continue;
+            };
+            if query.is_match(entry.indexes())? {
+                let item = IndexItem {
+                    fields: entry.indexes().clone(),
+                    message_cid,
+                };
+                matches.push((item, entry));
+            }
+        }
+
+        Ok(matches)
+    }
+
+    // Page through the stored messages matching `query`. For a paginated
+    // query, only enough messages are fetched to fill the page (and find the
+    // next cursor) once `hidden` messages are replaced by staged changes.
+    async fn stored(&self, owner: &str, query: &Query, hidden: usize) -> Result<Vec<Entry>> {
+        let pagination = query.pagination.clone().unwrap_or_default();
+        let wanted = pagination.limit.map(|limit| limit + hidden + 1);
+
+        let mut page = Query {
+            pagination: Some(Pagination {
+                limit: Some(wanted.unwrap_or(PAGE_SIZE)),
+                cursor: pagination.cursor,
+            }),
+            ..query.clone()
+        };
+
+        let mut stored = vec![];
+        loop {
+            let (entries, cursor) = MessageStore::query(self.provider, owner, &page).await?;
+            stored.extend(entries);
+
+            match cursor {
+                Some(cursor) if wanted.is_none() => {
+                    if let Some(pagination) = &mut page.pagination {
+                        pagination.cursor = Some(cursor);
+                    }
+                }
+                _ => return Ok(stored),
+            }
+        }
+    }
+}
+
+// The staged state of a block: `None` when deleted.
+type Staged = Option<Vec<u8>>;
+
+// The index fields of a message returned by the provider. Fields the
+// provider did not return are re-derived from the message.
+fn fields(entry: &Entry) -> HashMap<String, String> {
+    let derived = match &entry.message {
+        EntryType::Write(write) => Entry::from(write),
+        EntryType::Delete(delete) => Entry::from(delete),
+        EntryType::Configure(configure) => Entry::from(configure),
+    };
+    let mut fields = entry.indexes().clone();
+    for (field, value) in derived.indexes() {
+        fields.entry(field.clone()).or_insert_with(|| value.clone());
+    }
+    if entry.is_pinned() {
+        fields.entry("pinned".to_string()).or_insert_with(|| true.to_string());
+    }
+    fields
+}
+
+fn poisoned() -> anyhow::Error {
+    anyhow!("transaction lock poisoned")
+}
+
+impl<P: Provider> Provider for Transaction<'_, P> {
+    fn strict_timestamps(&self) -> bool {
+        self.provider.strict_timestamps()
+    }
+
+    fn max_encoded_size(&self) -> usize {
+        self.provider.max_encoded_size()
+    }
+
+    fn max_message_size(&self) -> usize {
+        self.provider.max_message_size()
+    }
+
+    fn retained_versions(&self) -> usize {
+        self.provider.retained_versions()
+    }
+
+    fn idempotency_window(&self) -> TimeDelta {
+        self.provider.idempotency_window()
+    }
+
+    fn resolver_cache(&self) -> Option<Arc<ResolverCache>> {
+        self.provider.resolver_cache()
+    }
+
+    fn rate_limiter(&self) -> Option<&dyn RateLimiter> {
+        self.provider.rate_limiter()
+    }
+
+    fn clock(&self) -> &dyn Clock {
+        self.provider.clock()
+    }
+}
+
+impl<P: Provider> BlockStore for Transaction<'_, P> {
+    async fn put(&self, owner: &str, partition: &str, cid: &str, data: &[u8]) -> Result<()> {
+        self.stage(Change::Block {
+            owner: owner.to_string(),
+            partition: partition.to_string(),
+            cid: cid.to_string(),
+            block: Some(data.to_vec()),
+        })
+    }
+
+    async fn get(&self, owner: &str, partition: &str, cid: &str) -> Result<Option<Vec<u8>>> {
+        if let Some(block) = self.staged_block(owner, partition, cid)? {
+            return Ok(block);
+        }
+        BlockStore::get(self.provider, owner, partition, cid).await
+    }
+
+    async fn delete(&self, owner: &str, partition: &str, cid: &str) -> Result<()> {
+        self.stage(Change::Block {
+            owner: owner.to_string(),
+            partition: partition.to_string(),
+            cid: cid.to_string(),
+            block: None,
+        })
+    }
+
+    async fn purge(&self, _owner: &str, _partition: &str) -> Result<()> {
+        Err(anyhow!("purge is not supported within a transaction"))
+    }
+}
+
+impl<P: Provider> MessageStore for Transaction<'_, P> {
+    async fn put(&self, owner: &str, entry: &Entry) -> Result<()> {
+        self.stage(Change::Message {
+            owner: owner.to_string(),
+            message_cid: entry.cid()?,
+            entry: Some(entry.clone()),
+        })
+    }
+
+    async fn query(&self, owner: &str, query: &Query) -> Result<(Vec<Entry>, Option<Cursor>)> {
+        let staged = self.staged_messages(owner)?;
+        if staged.is_empty() {
+            return MessageStore::query(self.provider, owner, query).await;
+        }
+
+        let (items, mut entries): (Vec<_>, HashMap<_, _>) = self
+            .matching(owner, query, staged)
+            .await?
+            .into_iter()
+            .map(|(item, entry)| (item.clone(), (item.message_cid, entry)))
+            .unzip();
+        let mut items = index::sort_and_paginate(items, query);
+        let cursor = message::next_cursor(&mut items, query);
+
+        let entries =
+            items.iter().filter_map(|item| entries.remove(&item.message_cid)).collect::<Vec<_>>();
+        Ok((entries, cursor))
+    }
+
+    async fn count(&self, owner: &str, query: &Query) -> Result<u64> {
+        let staged = self.staged_messages(owner)?;
+        let mut count = MessageStore::count(self.provider, owner, query).await?;
+
+        // adjust the stored count for messages replaced by staged changes
+        for (message_cid, entry) in &staged {
+            if self.stored_match(owner, message_cid, query).await?.is_some() {
+                count = count.saturating_sub(1);
+            }
+            if let Some(entry) = entry
+                && query.is_match(entry.indexes())?
+            {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    async fn count_by(
+        &self, owner: &str, query: &Query, group_by: GroupBy,
+    ) -> Result<BTreeMap<String, u64>> {
+        let field = group_by.to_string();
+        let staged = self.staged_messages(owner)?;
+        let mut groups = MessageStore::count_by(self.provider, owner, query, group_by).await?;
+
+        // adjust the stored counts for messages replaced by staged changes
+        for (message_cid, entry) in &staged {
+            if let Some(fields) = self.stored_match(owner, message_cid, query).await?
+                && let Some(value) = fields.get(&field)
+                && let Some(count) = groups.get_mut(value)
+            {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    groups.remove(value);
+                }
+            }
+            if let Some(entry) = entry
+                && query.is_match(entry.indexes())?
+                && let Some(value) = entry.indexes().get(&field)
+            {
+                *groups.entry(value.clone()).or_insert(0) += 1;
+            }
+        }
+        Ok(groups)
+    }
+
+    // Statistics are computed by the provider, so do not include staged
+    // changes.
+    async fn stats(&self, owner: &str) -> Result<StoreStats> {
+        MessageStore::stats(self.provider, owner).await
+    }
+
+    async fn get(&self, owner: &str, message_cid: &str) -> Result<Option<Entry>> {
+        let staged = self.staged_messages(owner)?.remove(message_cid);
+        if let Some(entry) = staged {
+            return Ok(entry);
+        }
+        MessageStore::get(self.provider, owner, message_cid).await
+    }
+
+    async fn delete(&self, owner: &str, message_cid: &str) -> Result<()> {
+        self.stage(Change::Message {
+            owner: owner.to_string(),
+            message_cid: message_cid.to_string(),
+            entry: None,
+        })
+    }
+
+    // Indexes are rebuilt by the provider on commit.
+    async fn rebuild_indexes(&self, owner: &str) -> Result<()> {
+        self.stage(Change::Indexes {
+            owner: owner.to_string(),
+        })
+    }
+
+    async fn set_index(
+        &self, owner: &str, message_cid: &str, field: &str, value: &str,
+    ) -> Result<()> {
+        let entry = match self.staged_messages(owner)?.remove(message_cid) {
+            Some(entry) => entry,
+            None => self.stored_message(owner, message_cid).await?,
+        };
+        let Some(mut entry) = entry else {
+            return Err(anyhow!("message not found"));
+        };
+        entry.set_index(field, value);
+        MessageStore::put(self, owner, &entry).await
+    }
+
+    async fn purge(&self) -> Result<()> {
+        Err(anyhow!("purge is not supported within a transaction"))
+    }
+}
+
+impl<P: Provider> DataStore for Transaction<'_, P> {
+    async fn put(
+        &self, owner: &str, record_id: &str, data_cid: &str, reader: impl AsyncRead + Send + Unpin,
+    ) -> Result<(String, usize)> {
+        let (staged, actual_cid, data_size) =
+            self.stage_data(owner, record_id, data_cid, reader).await?;
+
+        self.stage(Change::Data {
+            owner: owner.to_string(),
+            record_id: record_id.to_string(),
+            data_cid: data_cid.to_string(),
+            staged: Some(staged),
+        })?;
+        Ok((actual_cid, data_size))
+    }
+
+    async fn get(&self, owner: &str, record_id: &str, data_cid: &str) -> Result<Option<impl Read>> {
+        if let Some(staged) = self.staged_data(owner, record_id, data_cid)? {
+            let Some(staged) = staged else {
+                return Ok(None);
+            };
+            let reader = data::get(owner, &staged, data_cid, self.provider).await?;
+            return Ok(reader.map(StagedReader::Staged));
+        }
+        let reader = DataStore::get(self.provider, owner, record_id, data_cid).await?;
+        Ok(reader.map(StagedReader::Stored))
+    }
+
+    async fn open(
+        &self, owner: &str, record_id: &str, data_cid: &str,
+    ) -> Result<Option<impl AsyncRead + Send + Unpin>> {
+        if let Some(staged) = self.staged_data(owner, record_id, data_cid)? {
+            let Some(staged) = staged else {
+                return Ok(None);
+            };
+            let reader = data::open(owner, &staged, data_cid, self.provider).await?;
+            return Ok(reader.map(StagedReader::Staged));
+        }
+        let reader = DataStore::open(self.provider, owner, record_id, data_cid).await?;
+        Ok(reader.map(StagedReader::Stored))
+    }
+
+    async fn get_range(
+        &self, owner: &str, record_id: &str, data_cid: &str, start: usize, end: usize,
+    ) -> Result<Option<impl Read>> {
+        if let Some(staged) = self.staged_data(owner, record_id, data_cid)? {
+            let Some(staged) = staged else {
+                return Ok(None);
+            };
+            let reader =
+                data::get_range(owner, &staged, data_cid, start, end, self.provider).await?;
+            return Ok(reader.map(StagedReader::Staged));
+        }
+        let reader =
+            DataStore::get_range(self.provider, owner, record_id, data_cid, start, end).await?;
+        Ok(reader.map(StagedReader::Stored))
+    }
+
+    async fn delete(&self, owner: &str, record_id: &str, data_cid: &str) -> Result<()> {
+        self.stage(Change::Data {
+            owner: owner.to_string(),
+            record_id: record_id.to_string(),
+            data_cid: data_cid.to_string(),
+            staged: None,
+        })
+    }
+
+    async fn purge(&self) -> Result<()> {
+        Err(anyhow!("purge is not supported within a transaction"))
+    }
+}
+
+// Record data read from either the transaction's staged data or the provider.
+enum StagedReader<S, R> {
+    Staged(S),
+    Stored(R),
+}
+
+impl<S: Read, R: Read> Read for StagedReader<S, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Staged(staged) => staged.read(buf),
+            Self::Stored(stored) => stored.read(buf),
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin, R: AsyncRead + Unpin> AsyncRead for StagedReader<S, R> {
+    fn poll_read(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Staged(staged) => Pin::new(staged).poll_read(cx, buf),
+            Self::Stored(stored) => Pin::new(stored).poll_read(cx, buf),
+        }
+    }
+}
+
+// Events logged within the transaction are appended to the provider's log on
+// commit, so are not returned by `events` or `query` until then.
+impl<P: Provider> EventLog for Transaction<'_, P> {
+    async fn append(&self, owner: &str, event: &Event) -> Result<()> {
+        self.stage(Change::Event {
+            owner: owner.to_string(),
+            message_cid: event.event_cid()?,
+            event: Some(event.clone()),
+        })
+    }
+
+    async fn events(
+        &self, owner: &str, cursor: Option<Cursor>,
+    ) -> Result<(Vec<Event>, Option<Cursor>)> {
+        EventLog::events(self.provider, owner, cursor).await
+    }
+
+    async fn query(&self, owner: &str, query: &Query) -> Result<(Vec<Event>, Option<Cursor>)> {
+        EventLog::query(self.provider, owner, query).await
+    }
+
+    async fn watermark(&self, owner: &str) -> Result<u64> {
+        EventLog::watermark(self.provider, owner).await
+    }
+
+    async fn delete(&self, owner: &str, message_cid: &str) -> Result<()> {
+        self.stage(Change::Event {
+            owner: owner.to_string(),
+            message_cid: message_cid.to_string(),
+            event: None,
+        })
+    }
+
+    async fn purge(&self) -> Result<()> {
+        Err(anyhow!("purge is not supported within a transaction"))
+    }
+}
+
+// Tasks registered within the transaction run to completion before it is
+// committed, so are tracked by the transaction rather than persisted.
+impl<P: Provider> TaskStore for Transaction<'_, P> {
+    async fn register(&self, owner: &str, task: &ResumableTask, _timeout_secs: u64) -> Result<()> {
+        let key = (owner.to_string(), task.task_id.clone());
+        self.tasks.lock().map_err(|_| poisoned())?.insert(key, task.clone());
+        Ok(())
+    }
+
+    async fn grab(&self, owner: &str, count: u64) -> Result<Vec<ResumableTask>> {
+        TaskStore::grab(self.provider, owner, count).await
+    }
+
+    async fn read(&self, owner: &str, task_id: &str) -> Result<Option<ResumableTask>> {
+        let key = (owner.to_string(), task_id.to_string());
+        let task = self.tasks.lock().map_err(|_| poisoned())?.get(&key).cloned();
+        if task.is_some() {
+            return Ok(task);
+        }
+        TaskStore::read(self.provider, owner, task_id).await
+    }
+
+    async fn extend(&self, _owner: &str, _task_id: &str, _timeout_secs: u64) -> Result<()> {
+        Ok(())
+    }
+
+    async fn delete(&self, owner: &str, task_id: &str) -> Result<()> {
+        let key = (owner.to_string(), task_id.to_string());
+        self.tasks.lock().map_err(|_| poisoned())?.remove(&key);
+        Ok(())
+    }
+
+    async fn purge(&self, _owner: &str) -> Result<()> {
+        Err(anyhow!("purge is not supported within a transaction"))
+    }
+}
+
+impl<P: Provider> EventStream for Transaction<'_, P> {
+    async fn subscribe(&self, owner: &str) -> Result<Subscriber> {
+        self.provider.subscribe(owner).await
+    }
+
+    async fn emit(&self, owner: &str, event: &Event) -> Result<()> {
+        self.events.lock().map_err(|_| poisoned())?.push((owner.to_string(), event.clone()));
+        Ok(())
+    }
+}
+
+impl<P: Provider> DidResolver for Transaction<'_, P> {
+    async fn resolve(&self, url: &str) -> Result<Document> {
+        self.provider.resolve(url).await
+    }
+}
@@ -4,25 +4,35 @@
 
 mod delete;
 mod encryption;
+mod export;
 mod integrity;
+mod pin;
 mod protocol;
 mod query;
 mod read;
 mod subscribe;
 pub mod write;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
 
 use serde::{Deserialize, Serialize};
 
-pub use self::delete::{Delete, DeleteDescriptor};
-pub use self::encryption::{EncryptOptions, EncryptionProperty, Recipient, decrypt};
+pub use self::delete::{Delete, DeleteDescriptor, DeletePreview, preview as preview_delete};
+pub use self::encryption::{
+    EncryptOptions, Encrypted, EncryptionProperty, Keyring, Recipient, decrypt, decrypt_stream,
+    decrypt_with_keyring,
+};
+pub use self::export::{Bundle, export, export_record, import, verify_write};
+pub(crate) use self::pin::is_pinned;
+pub use self::pin::pin;
 pub use self::query::{Query, QueryDescriptor};
-pub use self::read::{Read, ReadDescriptor};
+pub use self::read::{ByteRange, Read, ReadBatch, ReadDescriptor};
 pub use self::subscribe::{Subscribe, SubscribeDescriptor};
-pub use self::write::{Attestation, DelegatedGrant, SignaturePayload, Tag, Write, WriteDescriptor};
-use crate::{DateRange, OneOrMany, Range, Result, utils};
+pub use self::write::{
+    Attestation, DataReader, DelegatedGrant, SignaturePayload, Tag, Write, WriteDescriptor,
+};
+use crate::{DateRange, OneOrMany, Range, Result, unexpected, utils};
 
 /// The Records filter is used when querying for records.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -33,6 +43,10 @@ pub struct RecordsFilter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub record_id: Option<String>,
 
+    /// Find the records matching any of the specified IDs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_ids: Option<Vec<String>>,
+
     /// Records matching the specified author.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub author: Option<OneOrMany<String>>,
@@ -45,7 +59,9 @@ pub struct RecordsFilter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub recipient: Option<OneOrMany<String>>,
 
-    /// Records with the specified context.
+    /// Records with the specified context, including records nested within
+    /// it. Combined with `protocol_path`, selects records of a given type
+    /// within a single context (e.g. the `thread/chat` records of a thread).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context_id: Option<String>,
 
@@ -65,6 +81,14 @@ pub struct RecordsFilter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub schema: Option<String>,
 
+    /// Exclude records written under the specified protocol.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_protocol: Option<String>,
+
+    /// Exclude records with the specified schema.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_schema: Option<String>,
+
     /// The MIME type of the requested data. For example, `application/json`.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data_format: Option<String>,
@@ -85,6 +109,15 @@ pub struct RecordsFilter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub published: Option<bool>,
 
+    /// Whether the record has been pinned by the owner.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<bool>,
+
+    /// Whether the record has been updated, i.e. earlier revisions of the
+    /// record are retained.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revised: Option<bool>,
+
     /// Filter messages published within the specified range.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub date_published: Option<DateRange>,
@@ -96,6 +129,20 @@ pub struct RecordsFilter {
     /// Match messages updated within the specified range.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub date_updated: Option<DateRange>,
+
+    /// Records written under the protocol configuration with the specified
+    /// CID.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol_config: Option<String>,
+
+    /// Full-text search over indexed string fields.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<TextFilter>,
+
+    /// Match records satisfying at least one of the sub-filters, in addition
+    /// to this filter's own conditions. Sub-filters cannot be nested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub any_of: Option<Vec<RecordsFilter>>,
 }
 
 impl RecordsFilter {
@@ -115,9 +162,32 @@ impl RecordsFilter {
         };
         filter.schema =
             if let Some(schema) = &self.schema { Some(utils::uri::clean(schema)?) } else { None };
+        if let Some(not_protocol) = &self.not_protocol {
+            filter.not_protocol = Some(utils::uri::clean(not_protocol)?);
+        }
+        if let Some(not_schema) = &self.not_schema {
+            filter.not_schema = Some(utils::uri::clean(not_schema)?);
+        }
+        if let Some(any_of) = &self.any_of {
+            filter.any_of = Some(any_of.iter().map(Self::normalize).collect::<Result<_>>()?);
+        }
         Ok(filter)
     }
 
+    /// Verify `any_of` sub-filters are present and not themselves nested.
+    pub(crate) fn validate(&self) -> Result<()> {
+        let Some(any_of) = &self.any_of else {
+            return Ok(());
+        };
+        if any_of.is_empty() {
+            return Err(unexpected!("`any_of` requires at least one filter"));
+        }
+        if any_of.iter().any(|filter| filter.any_of.is_some()) {
+            return Err(unexpected!("nested `any_of` filters are not supported"));
+        }
+        Ok(())
+    }
+
     /// Check whether the filter will return a concise set of results.
     pub(crate) const fn is_concise(&self) -> bool {
         self.record_id.is_some()
@@ -214,14 +284,50 @@ impl RecordsFilter {
         self
     }
 
-    /// Add a published flag to the filter.
+    /// Exclude records written under the specified protocol. Records without
+    /// a protocol are not excluded.
+    #[must_use]
+    pub fn not_protocol(mut self, protocol: impl Into<String>) -> Self {
+        self.not_protocol = Some(protocol.into());
+        self
+    }
+
+    /// Exclude records with the specified schema. Records without a schema
+    /// are not excluded.
+    #[must_use]
+    pub fn not_schema(mut self, schema: impl Into<String>) -> Self {
+        self.not_schema = Some(schema.into());
+        self
+    }
+
+    /// Filter records by published state: `true` for published records only,
+    /// `false` for unpublished records only, or `None` for both (subject to
+    /// the requestor's authorization).
+    #[must_use]
+    pub fn published(mut self, published: impl Into<Option<bool>>) -> Self {
+        self.published = published.into();
+        self
+    }
+
+    /// Filter records by pin status: `true` for records pinned by the owner
+    /// (see [`pin`]), `false` for records not pinned.
     #[must_use]
-    pub const fn published(mut self, published: bool) -> Self {
-        self.published = Some(published);
+    pub const fn pinned(mut self, pinned: bool) -> Self {
+        self.pinned = Some(pinned);
         self
     }
 
-    /// Add a context ID to the filter.
+    /// Filter records by revision: `true` for records with more than one
+    /// retained revision (the initial write and at least one update), `false`
+    /// for records never updated.
+    #[must_use]
+    pub const fn revised(mut self, revised: bool) -> Self {
+        self.revised = Some(revised);
+        self
+    }
+
+    /// Add a context ID to the filter. Matching is by prefix, so the context
+    /// ID of a root record selects that record and all of its descendants.
     #[must_use]
     pub fn context_id(mut self, context_id: impl Into<String>) -> Self {
         self.context_id = Some(context_id.into());
@@ -235,6 +341,14 @@ impl RecordsFilter {
         self
     }
 
+    /// Add a set of record IDs to the filter, matching records with any of
+    /// the IDs.
+    #[must_use]
+    pub fn record_ids(mut self, record_ids: Vec<String>) -> Self {
+        self.record_ids = Some(record_ids);
+        self
+    }
+
     /// Add a parent ID to the filter.
     #[must_use]
     pub fn parent_id(mut self, parent_id: impl Into<String>) -> Self {
@@ -262,13 +376,51 @@ impl RecordsFilter {
         self
     }
 
-    /// Add a data size to the filter.
+    /// Add a data size range (in bytes) to the filter. Use `ge`/`le` for
+    /// inclusive bounds and `gt`/`lt` for exclusive bounds.
     #[must_use]
     pub const fn data_size(mut self, data_size: Range<usize>) -> Self {
         self.data_size = Some(data_size);
         self
     }
 
+    /// Add a protocol configuration CID to the filter, matching records
+    /// written while that configuration was in effect.
+    #[must_use]
+    pub fn protocol_config(mut self, protocol_config: impl Into<String>) -> Self {
+        self.protocol_config = Some(protocol_config.into());
+        self
+    }
+
+    /// Search indexed string fields for the whitespace-separated terms in
+    /// `query`. Fields default to [`TextFilter::DEFAULT_FIELDS`].
+    #[must_use]
+    pub fn text(mut self, query: impl Into<String>) -> Self {
+        let fields = self.text.and_then(|text| text.fields);
+        self.text = Some(TextFilter {
+            query: query.into(),
+            fields,
+        });
+        self
+    }
+
+    /// Restrict a text search to the specified index fields. A trailing `*`
+    /// matches every field with that prefix (e.g. `tag.*`).
+    #[must_use]
+    pub fn text_fields(mut self, fields: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let text = self.text.get_or_insert_with(TextFilter::default);
+        text.fields = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Match records satisfying any one of the specified filters. Sub-filters
+    /// are 'OR-ed' together and 'AND-ed' with this filter.
+    #[must_use]
+    pub fn any_of(mut self, filters: Vec<Self>) -> Self {
+        self.any_of = Some(filters);
+        self
+    }
+
     /// Add a data CID to the filter.
     #[must_use]
     pub fn data_cid(mut self, data_cid: impl Into<String>) -> Self {
@@ -296,6 +448,15 @@ impl RecordsFilter {
         self.date_updated = Some(date_updated);
         self
     }
+
+    /// Add a message timestamp range to the filter. As a record's latest
+    /// `message_timestamp` is the time it was last updated, this is
+    /// equivalent to `date_updated`.
+    #[must_use]
+    pub const fn message_timestamp(mut self, message_timestamp: DateRange) -> Self {
+        self.date_updated = Some(message_timestamp);
+        self
+    }
 }
 
 /// Specifies the way that `RecordsQuery`results should be sorted.
@@ -346,20 +507,206 @@ impl Display for Sort {
     }
 }
 
+/// A key used in a composite sort. Keys are applied in order, with each
+/// subsequent key used to order records equal on all preceding keys.
+///
+/// Records missing the key's field are ordered last, regardless of sort
+/// direction. Numeric values are ordered numerically, before any other values.
+///
+/// A composite index is built for each combination of keys the first time it
+/// is used, and maintained from then on.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SortKey {
+    /// The indexed field to sort on. For example, `dateCreated` or, for a
+    /// tag, `tag.priority`.
+    pub field: String,
+
+    /// Sort from highest to lowest.
+    pub descending: bool,
+}
+
+impl SortKey {
+    /// Sort on the specified field from lowest to highest.
+    #[must_use]
+    pub fn ascending(field: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            descending: false,
+        }
+    }
+
+    /// Sort on the specified field from highest to lowest.
+    #[must_use]
+    pub fn descending(field: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            descending: true,
+        }
+    }
+}
+
+impl From<Sort> for SortKey {
+    fn from(sort: Sort) -> Self {
+        Self {
+            field: sort.to_string(),
+            descending: !sort.is_ascending(),
+        }
+    }
+}
+
+/// The record field used to group a `RecordsQuery`'s matching records,
+/// returning a count per distinct value rather than the records themselves.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GroupBy {
+    /// Group by `protocol`.
+    Protocol,
+
+    /// Group by `schema`.
+    Schema,
+
+    /// Group by `data_format`.
+    DataFormat,
+
+    /// Group by `recipient`.
+    Recipient,
+}
+
+impl Display for GroupBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Protocol => write!(f, "protocol"),
+            Self::Schema => write!(f, "schema"),
+            Self::DataFormat => write!(f, "dataFormat"),
+            Self::Recipient => write!(f, "recipient"),
+        }
+    }
+}
+
 /// A tag filter is used when filter records by tag.
+///
+/// Serializes as a bare value for equality, `{"startsWith": "..."}` for
+/// prefix matching, or a range object (`gt`, `gte`, `lt`, `lte`) for numeric
+/// comparison. Records without the tag never match.
 #[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(untagged)]
 pub enum TagFilter {
     /// Match tags starting with a string value.
-    StartsWith(String),
+    StartsWith(StartsWith),
 
     /// Filter tags by range.
     Range(Range<usize>),
 
+    /// Filter numeric tags, including floats, by range.
+    FloatRange(Range<f64>),
+
     /// Filter by a specific value.
     Equal(Tag),
 }
 
+/// A case-insensitive, tokenized search over a record's indexed string
+/// fields.
+///
+/// Every term in the query must be contained in at least one searched field.
+/// Matching records are ranked by the number of field hits.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextFilter {
+    /// Whitespace-separated search terms.
+    pub query: String,
+
+    /// The index fields to search. A trailing `*` matches every field with
+    /// that prefix.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<String>>,
+}
+
+impl TextFilter {
+    /// Fields searched when none are specified.
+    pub const DEFAULT_FIELDS: [&str; 4] = ["schema", "protocol", "protocolPath", "tag.*"];
+
+    /// The lowercased search terms.
+    pub(crate) fn terms(&self) -> Vec<String> {
+        self.query.split_whitespace().map(str::to_lowercase).collect()
+    }
+
+    /// The number of (field, term) hits in `indexes`, or `None` when a term
+    /// is not found in any searched field.
+    pub(crate) fn hits(&self, indexes: &HashMap<String, String>) -> Option<usize> {
+        let terms = self.terms();
+        if terms.is_empty() {
+            return None;
+        }
+
+        let values = indexes
+            .iter()
+            .filter(|(name, _)| self.searches(name))
+            .map(|(_, value)| {
+                Tag::decode_float(value).map_or_else(|| value.to_lowercase(), |n| n.to_string())
+            })
+            .collect::<Vec<_>>();
+
+        let mut hits = 0;
+        for term in &terms {
+            let count = values.iter().filter(|value| value.contains(term.as_str())).count();
+            if count == 0 {
+                return None;
+            }
+            hits += count;
+        }
+        Some(hits)
+    }
+
+    // Whether the named index field is searched.
+    fn searches(&self, name: &str) -> bool {
+        let matches = |field: &str| {
+            field.strip_suffix('*').map_or(field == name, |prefix| name.starts_with(prefix))
+        };
+        self.fields.as_ref().map_or_else(
+            || Self::DEFAULT_FIELDS.iter().any(|field| matches(field)),
+            |fields| fields.iter().any(|field| matches(field)),
+        )
+    }
+}
+
+/// Prefix used by [`TagFilter::StartsWith`] to match string tags.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartsWith {
+    /// The prefix the tag value must start with.
+    pub starts_with: String,
+}
+
+impl TagFilter {
+    /// Match tags starting with the specified string.
+    #[must_use]
+    pub fn starts_with(value: impl Into<String>) -> Self {
+        Self::StartsWith(StartsWith {
+            starts_with: value.into(),
+        })
+    }
+
+    /// Match numeric tags between `gte` and `lte` (inclusive).
+    #[must_use]
+    pub fn range(gte: usize, lte: usize) -> Self {
+        Self::Range(Range::new().ge(gte).le(lte))
+    }
+
+    /// Match numeric tags, including signed and floating point tags, between
+    /// `gte` and `lte` (inclusive).
+    #[must_use]
+    pub fn float_range(gte: f64, lte: f64) -> Self {
+        Self::FloatRange(Range::new().ge(gte).le(lte))
+    }
+
+    /// Match tags equal to the specified value.
+    #[must_use]
+    pub const fn equal(value: Tag) -> Self {
+        Self::Equal(value)
+    }
+}
+
 impl Default for TagFilter {
     fn default() -> Self {
         Self::Equal(Tag::Empty)
@@ -12,6 +12,7 @@ use std::collections::HashMap;
 
 use async_recursion::async_recursion;
 use chrono::SecondsFormat::Micros;
+use chrono::{DateTime, Utc};
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +20,7 @@ use crate::authorization::Authorization;
 use crate::endpoint::{Message, Reply, Status};
 use crate::provider::{DataStore, EventLog, EventStream, MessageStore, Provider};
 use crate::records::{RecordsFilter, Write, protocol};
+use crate::serde::rfc3339_micros_opt;
 use crate::store::{Entry, EntryType, RecordsQueryBuilder};
 use crate::tasks::{self, Task, TaskType};
 use crate::utils::cid;
@@ -64,6 +66,13 @@ pub async fn handle(
     // authorize the delete message
     delete.authorize(owner, &Write::try_from(latest)?, provider).await?;
 
+    // a pruning delete must be permitted to delete every descendant
+    if delete.descriptor.prune {
+        for write in descendants(owner, &delete.descriptor.record_id, provider).await? {
+            delete.authorize_descendant(owner, &write, provider).await?;
+        }
+    }
+
     // ensure the delete request does not pre-date the latest existing version
     if delete.descriptor().message_timestamp.timestamp_micros()
         < latest.descriptor().message_timestamp.timestamp_micros()
@@ -83,6 +92,60 @@ pub async fn handle(
     })
 }
 
+/// Preview the records that would be removed by the [`Delete`] message
+/// without performing the deletion.
+///
+/// The preview includes the record being deleted and, when the delete is set
+/// to prune, all of the record's descendants.
+///
+/// # Errors
+///
+/// The function will return an error when the record cannot be found, has
+/// already been deleted, or the delete message is not authorized.
+pub async fn preview(
+    owner: &str, delete: &Delete, provider: &impl Provider,
+) -> Result<DeletePreview> {
+    let query = RecordsQueryBuilder::new()
+        .method(None)
+        .add_filter(RecordsFilter::new().record_id(&delete.descriptor.record_id))
+        .build();
+    let (entries, _) = MessageStore::query(provider, owner, &query).await?;
+    if entries.is_empty() {
+        return Err(Error::NotFound("no matching record found".to_string()));
+    }
+    if entries[0].descriptor().method == Method::Delete {
+        return Err(Error::NotFound("cannot delete a `RecordsDelete` record".to_string()));
+    }
+
+    let write = Write::try_from(&entries[0])?;
+    delete.authorize(owner, &write, provider).await?;
+
+    let mut preview = DeletePreview {
+        record_ids: vec![write.record_id.clone()],
+        total_bytes: write.descriptor.data_size,
+    };
+    if delete.descriptor.prune {
+        for write in descendants(owner, &write.record_id, provider).await? {
+            delete.authorize_descendant(owner, &write, provider).await?;
+            preview.record_ids.push(write.record_id.clone());
+            preview.total_bytes += write.descriptor.data_size;
+        }
+    }
+
+    Ok(preview)
+}
+
+/// The records that would be removed by a [`Delete`] message.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletePreview {
+    /// The IDs of the record and any descendants that would be deleted.
+    pub record_ids: Vec<String>,
+
+    /// The total size, in bytes, of the data that would be deleted.
+    pub total_bytes: usize,
+}
+
 /// The [`Delete`] message expected by the handler.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -164,14 +227,14 @@ impl Delete {
     }
 
     /// Authorize the delete message.
-    async fn authorize(&self, owner: &str, write: &Write, store: &impl MessageStore) -> Result<()> {
+    async fn authorize(&self, owner: &str, write: &Write, provider: &impl Provider) -> Result<()> {
         let authzn = &self.authorization;
         let author = &authzn.author()?;
 
         // when signed by delegate, authorize delegate
         if let Some(delegated_grant) = &authzn.author_delegated_grant {
             let grant = delegated_grant.to_grant()?;
-            grant.permit_delete(author, &authzn.signer()?, self, write, store).await?;
+            grant.permit_delete(author, &authzn.signer()?, self, write, provider).await?;
         }
 
         if author == owner {
@@ -182,11 +245,21 @@ impl Delete {
             let protocol = protocol::Authorizer::new(protocol)
                 .context_id(write.context_id.as_ref())
                 .initial_write(write);
-            return protocol.permit_delete(owner, self, store).await;
+            return protocol.permit_delete(owner, self, provider).await;
         }
 
         Err(forbidden!("delete request failed authorization"))
     }
+
+    /// Authorize a pruning delete message against one of the descendants of
+    /// the record being deleted.
+    async fn authorize_descendant(
+        &self, owner: &str, write: &Write, provider: &impl Provider,
+    ) -> Result<()> {
+        let mut delete = self.clone();
+        delete.descriptor.record_id.clone_from(&write.record_id);
+        delete.authorize(owner, write, provider).await
+    }
 }
 
 /// The [`Delete`] message descriptor.
@@ -202,6 +275,16 @@ pub struct DeleteDescriptor {
 
     /// Specifies whether descendent records should be pruned or not.
     pub prune: bool,
+
+    /// The time until which the delete's tombstone is retained. Once expired,
+    /// the tombstone, and with it the record, can be removed using
+    /// [`collect_tombstones`]. Tombstones without a retention period are
+    /// retained indefinitely.
+    ///
+    /// [`collect_tombstones`]: crate::provider::gc::collect_tombstones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "rfc3339_micros_opt")]
+    pub retain_until: Option<DateTime<Utc>>,
 }
 
 async fn delete(owner: &str, delete: &Delete, provider: &impl Provider) -> Result<()> {
@@ -216,7 +299,7 @@ async fn delete(owner: &str, delete: &Delete, provider: &impl Provider) -> Resul
     if entries.is_empty() {
         return Err(Error::NotFound("no matching records found".to_string()));
     }
-    if entries.len() > 2 {
+    if entries.len() > provider.retained_versions() + 2 {
         return Err(unexpected!("multiple messages exist"));
     }
 
@@ -244,7 +327,7 @@ async fn delete(owner: &str, delete: &Delete, provider: &impl Provider) -> Resul
 
     // purge/hard-delete all descendent records
     if delete.descriptor.prune {
-        delete_children(owner, &delete.descriptor.record_id, provider).await?;
+        delete_children(owner, delete, &delete.descriptor.record_id, provider).await?;
     }
 
     // delete all messages except initial write and most recent
@@ -253,9 +336,16 @@ async fn delete(owner: &str, delete: &Delete, provider: &impl Provider) -> Resul
     Ok(())
 }
 
-// Purge a record's descendant records and data.
+// Purge a record's descendant records and data, logging a delete event for
+// each descendant.
+//
+// Descendants are removed as store operations rather than by creating new
+// delete messages: each descendant's event carries the signed, pruning
+// `RecordsDelete` of the ancestor, indexed with the descendant's fields.
 #[async_recursion]
-async fn delete_children(owner: &str, record_id: &str, provider: &impl Provider) -> Result<()> {
+async fn delete_children(
+    owner: &str, delete: &Delete, record_id: &str, provider: &impl Provider,
+) -> Result<()> {
     // fetch child records
     let query =
         RecordsQueryBuilder::new().add_filter(RecordsFilter::new().parent_id(record_id)).build();
@@ -265,33 +355,60 @@ async fn delete_children(owner: &str, record_id: &str, provider: &impl Provider)
     }
 
     // group by `record_id` (a record can have multiple children)
-    let mut record_id_map = HashMap::<&str, Vec<Entry>>::new();
+    let mut record_id_map = HashMap::<String, Vec<Entry>>::new();
     for entry in children {
         let record_id = if let Some(write) = entry.as_write() {
-            &write.record_id
+            write.record_id.clone()
         } else {
             let Some(delete) = entry.as_delete() else {
                 return Err(unexpected!("unexpected message type"));
             };
-            &delete.descriptor.record_id
+            delete.descriptor.record_id.clone()
         };
-
-        record_id_map
-            .get_mut(record_id.as_str())
-            .unwrap_or(&mut Vec::<Entry>::new())
-            .push(entry.clone());
+        record_id_map.entry(record_id).or_default().push(entry);
     }
 
     for (record_id, entries) in record_id_map {
         // purge child's descendants
-        delete_children(owner, record_id, provider).await?;
+        delete_children(owner, delete, &record_id, provider).await?;
         // purge child's entries
         purge(owner, &entries, provider).await?;
+
+        // log the child's deletion as its own event
+        let mut event = Entry::from(delete);
+        event.set_index("recordId", &record_id);
+        if let Some(write) = entries.iter().find_map(Entry::as_write) {
+            for (key, value) in write.build_indexes() {
+                event.add_index(key, value);
+            }
+        }
+        EventLog::append(provider, owner, &event).await?;
+        EventStream::emit(provider, owner, &event).await?;
     }
 
     Ok(())
 }
 
+// Fetch the latest `RecordsWrite` of each of a record's descendants.
+#[async_recursion]
+async fn descendants(
+    owner: &str, record_id: &str, store: &impl MessageStore,
+) -> Result<Vec<Write>> {
+    let query =
+        RecordsQueryBuilder::new().add_filter(RecordsFilter::new().parent_id(record_id)).build();
+    let (children, _) = MessageStore::query(store, owner, &query).await?;
+
+    let mut writes = vec![];
+    for entry in children {
+        let write = Write::try_from(entry)?;
+        let record_id = write.record_id.clone();
+        writes.push(write);
+        writes.extend(descendants(owner, &record_id, store).await?);
+    }
+
+    Ok(writes)
+}
+
 // Purge record's specified records and data.
 async fn purge(owner: &str, records: &[Entry], provider: &impl Provider) -> Result<()> {
     // filter out `RecordsDelete` messages
@@ -326,24 +443,33 @@ async fn purge(owner: &str, records: &[Entry], provider: &impl Provider) -> Resu
 async fn delete_earlier(
     owner: &str, latest: &Entry, existing: &[Entry], provider: &impl Provider,
 ) -> Result<()> {
-    // N.B. typically there will only be, at most, two existing records per
-    // `record_id` (initial + a potential subsequent write/delete),
-    for entry in existing {
-        if entry.descriptor().message_timestamp < latest.descriptor().message_timestamp {
-            delete_data(owner, entry, latest, provider).await?;
-
-            // when the existing message is the initial write, retain it BUT,
-            // ensure the message is marked as `archived`
-            let write = Write::try_from(entry)?;
-            if write.is_initial()? {
-                let mut entry = Entry::from(&write);
-                entry.add_index("initial", true.to_string());
-                MessageStore::put(provider, owner, &entry).await?;
-            } else {
-                let cid = entry.cid()?;
-                MessageStore::delete(provider, owner, &cid).await?;
-                EventLog::delete(provider, owner, &cid).await?;
+    let earlier = existing
+        .iter()
+        .filter(|entry| {
+            entry.descriptor().message_timestamp < latest.descriptor().message_timestamp
+        })
+        .collect::<Vec<_>>();
+
+    for entry in &earlier {
+        delete_data(owner, entry, latest, provider).await?;
+
+        // when the existing message is the initial write, retain it BUT,
+        // ensure the message is marked as `archived`
+        let write = Write::try_from(*entry)?;
+        if write.is_initial()? {
+            let mut entry = Entry::from(&write);
+            entry.add_index("initial", true.to_string());
+
+            // the initial write is the version superseded by `latest` only
+            // when it has not been updated
+            if earlier.len() == 1 {
+                entry.add_index("supersededBy", latest.cid()?);
             }
+            MessageStore::put(provider, owner, &entry).await?;
+        } else {
+            let cid = entry.cid()?;
+            MessageStore::delete(provider, owner, &cid).await?;
+            EventLog::delete(provider, owner, &cid).await?;
         }
     }
 
@@ -3,7 +3,14 @@
 //! This module provides data structures and functions used in the encrypting
 //! and decrypting of [`Write`] data.
 
+use std::io;
+
+use aes_gcm::Aes256Gcm;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
 use base64ct::{Base64UrlUnpadded, Encoding};
+use chacha20poly1305::XChaCha20Poly1305;
+use futures::future::Either;
+use futures::{AsyncRead, AsyncReadExt, TryStreamExt, stream};
 use serde::{Deserialize, Serialize};
 use vercre_infosec::Receiver;
 use vercre_infosec::jose::jwe::{
@@ -12,7 +19,7 @@ use vercre_infosec::jose::jwe::{
 use vercre_infosec::jose::{Curve, Jwe, PublicKeyJwk};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
-use crate::hd_key::{self, DerivationPath, DerivationScheme, DerivedPrivateJwk};
+use crate::hd_key::{self, DerivationPath, DerivationScheme, DerivedPrivateJwk, PrivateKeyJwk};
 use crate::records::Write;
 use crate::{Result, unexpected};
 
@@ -32,6 +39,10 @@ pub struct EncryptOptions<'a> {
     /// An array of inputs specifying how the CEK key is to be encrypted. Each
     /// entry in the array will result in a unique ciphertext for the CEK.
     recipients: Vec<Recipient>,
+
+    /// When set, the data is encrypted in chunks of this many bytes so it can
+    /// be decrypted as it is streamed.
+    chunk_size: Option<usize>,
 }
 
 /// Encrypted data. Intermediate work product.
@@ -62,6 +73,10 @@ pub struct Encrypted {
 
     /// The ciphertext.
     pub ciphertext: Vec<u8>,
+
+    /// The number of plaintext bytes encrypted in each chunk, when chunked.
+    #[zeroize(skip)]
+    chunk_size: Option<usize>,
 }
 
 /// Encryption key settings.
@@ -87,6 +102,7 @@ impl<'a> EncryptOptions<'a> {
             key_algorithm: KeyAlgorithm::EcdhEsA256Kw,
             data: &[],
             recipients: vec![],
+            chunk_size: None,
         }
     }
 
@@ -111,6 +127,15 @@ impl<'a> EncryptOptions<'a> {
         self
     }
 
+    /// Encrypt the data in chunks of `chunk_size` bytes, each authenticated
+    /// separately, so that it can be decrypted using [`decrypt_stream`]
+    /// without first buffering the whole ciphertext.
+    #[must_use]
+    pub const fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
     /// Add a recipient to the encryption options.
     #[must_use]
     pub fn with_recipient(mut self, recipient: Recipient) -> Self {
@@ -127,9 +152,6 @@ impl<'a> EncryptOptions<'a> {
     /// if the provided data cannot be encrypted using the specified content
     /// encryption algorithm.
     pub fn encrypt(&mut self) -> Result<Encrypted> {
-        use aes_gcm::Aes256Gcm;
-        use aes_gcm::aead::KeyInit;
-
         let cek = Aes256Gcm::generate_key(&mut rand::thread_rng());
         let protected = Protected {
             enc: self.content_algorithm.clone(),
@@ -137,13 +159,35 @@ impl<'a> EncryptOptions<'a> {
         };
         let aad = serde_json::to_vec(&protected)?;
 
+        // chunked data is encrypted using a stream key, itself encrypted using
+        // the CEK and prepended to the encrypted chunks
+        let mut stream_key = Aes256Gcm::generate_key(&mut rand::thread_rng()).to_vec();
+        let content = if self.chunk_size.is_some() { &stream_key } else { self.data };
+
         let encrypted = match self.content_algorithm {
-            ContentAlgorithm::A256Gcm => jwe::a256gcm(self.data, &cek.into(), &aad)?,
+            ContentAlgorithm::A256Gcm => jwe::a256gcm(content, &cek.into(), &aad)?,
             ContentAlgorithm::XChaCha20Poly1305 => {
-                jwe::xchacha20_poly1305(self.data, &cek.into(), &aad)?
+                jwe::xchacha20_poly1305(content, &cek.into(), &aad)?
             }
         };
 
+        let mut ciphertext = encrypted.ciphertext;
+        if let Some(chunk_size) = self.chunk_size {
+            if chunk_size == 0 {
+                return Err(unexpected!("chunk size must be greater than 0"));
+            }
+            let cipher = ChunkCipher::new(&self.content_algorithm, &stream_key, &aad);
+            let chunks: Vec<&[u8]> = if self.data.is_empty() {
+                vec![&[]]
+            } else {
+                self.data.chunks(chunk_size).collect()
+            };
+            for (index, chunk) in chunks.iter().enumerate() {
+                ciphertext.extend(cipher.seal(index, index + 1 == chunks.len(), chunk)?);
+            }
+        }
+        stream_key.zeroize();
+
         Ok(Encrypted {
             content_algorithm: self.content_algorithm.clone(),
             key_algorithm: self.key_algorithm.clone(),
@@ -151,7 +195,8 @@ impl<'a> EncryptOptions<'a> {
             cek: cek.to_vec(),
             iv: encrypted.iv,
             tag: encrypted.tag,
-            ciphertext: encrypted.ciphertext,
+            ciphertext,
+            chunk_size: self.chunk_size,
         })
     }
 }
@@ -164,6 +209,13 @@ impl Encrypted {
         self
     }
 
+    /// Encrypt the content encryption key (CEK) for each recipient, returning
+    /// the encryption properties to add to the `Write` message.
+    ///
+    /// # Errors
+    ///
+    /// Will fail if a recipient's public key is invalid or the CEK cannot be
+    /// encrypted for a recipient.
     pub fn finalize(self) -> Result<EncryptionProperty> {
         // encryption property
         let mut encryption = EncryptionProperty {
@@ -171,6 +223,7 @@ impl Encrypted {
             initialization_vector: self.iv.clone(),
             message_authentication_code: Some(self.tag.clone()),
             key_encryption: vec![],
+            chunk_size: self.chunk_size,
         };
 
         // add `EncryptedKey` for each recipient
@@ -253,6 +306,13 @@ pub struct EncryptionProperty {
     /// Equivalent to the JWE Authentication Tag (JWE `tag` property).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message_authentication_code: Option<String>,
+
+    /// The number of plaintext bytes in each encrypted chunk, when the data
+    /// was encrypted in chunks. The data is then the 32-byte stream key,
+    /// encrypted using the CEK, followed by each chunk encrypted using the
+    /// stream key and authenticated by its own (16-byte) tag.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<usize>,
 }
 
 /// The encrypted Content Encryption Key (CEK). Equivalent to the JWE
@@ -300,7 +360,14 @@ pub struct EncryptedKey {
 /// # Errors
 ///
 /// Will fail if the encryption properties are not set or if the data cannot be
-/// decrypted using the provided encryption properties.
+/// decrypted using the provided encryption properties. Failures are
+/// classified so callers can tell a wrong key from corrupt data:
+///
+/// - "no matching recipient key" when the key's ID and derivation scheme
+///   match no encryption recipient, or the key is not an ancestor of the
+///   recipient's derivation path. Another key may succeed.
+/// - "decryption failed" when the data fails authenticated decryption, i.e.
+///   the ciphertext or its encryption properties have been tampered with.
 pub async fn decrypt(
     data: &[u8], write: &Write, ancestor_jwk: &DerivedPrivateJwk, _: &impl Receiver,
 ) -> Result<Vec<u8>> {
@@ -311,15 +378,167 @@ pub async fn decrypt(
         k.root_key_id == ancestor_jwk.root_key_id
             && k.derivation_scheme == ancestor_jwk.derivation_scheme
     }) else {
-        return Err(unexpected!("encryption key not found"));
+        return Err(unexpected!("no matching recipient key"));
+    };
+
+    decrypt_for(data, write, encryption, recipient, ancestor_jwk).await
+}
+
+/// Decrypt data read from the provided reader using the encryption properties
+/// specified in the `Write` message, returning a reader over the plaintext.
+///
+/// Data encrypted in chunks (see [`EncryptOptions::chunk_size`]) is decrypted
+/// chunk by chunk as the returned reader is consumed, each chunk released once
+/// authenticated. Data encrypted in a single AEAD operation is authenticated
+/// by one tag, so is read in full and decrypted before any plaintext is
+/// released. Either way, the plaintext is identical to that returned by
+/// [`decrypt`].
+///
+/// # Errors
+///
+/// Will fail if the ciphertext cannot be read, or for any of the reasons
+/// [`decrypt`] fails. Reading from the returned reader fails with
+/// "decryption failed" when a chunk fails authenticated decryption.
+pub async fn decrypt_stream(
+    mut reader: impl AsyncRead + Send + Unpin, write: &Write, ancestor_jwk: &DerivedPrivateJwk,
+    receiver: &impl Receiver,
+) -> Result<impl AsyncRead + Send + Unpin> {
+    let Some(encryption) = &write.encryption else {
+        return Err(unexpected!("encryption parameter not set"));
+    };
+    let Some(chunk_size) = encryption.chunk_size else {
+        let mut data = vec![];
+        reader.read_to_end(&mut data).await.map_err(|e| unexpected!("issue reading data: {e}"))?;
+        let plaintext = decrypt(&data, write, ancestor_jwk, receiver).await?;
+        return Ok(Either::Left(futures::io::Cursor::new(plaintext)));
+    };
+    let Some(recipient) = encryption.key_encryption.iter().find(|k| {
+        k.root_key_id == ancestor_jwk.root_key_id
+            && k.derivation_scheme == ancestor_jwk.derivation_scheme
+    }) else {
+        return Err(unexpected!("no matching recipient key"));
+    };
+
+    // decrypt the stream key preceding the encrypted chunks
+    let mut stream_key = vec![0; STREAM_KEY_LEN];
+    reader.read_exact(&mut stream_key).await.map_err(|e| unexpected!("issue reading data: {e}"))?;
+    let mut stream_key =
+        decrypt_content(&stream_key, write, encryption, recipient, ancestor_jwk).await?;
+    let cipher = ChunkCipher::new(&encryption.algorithm, &stream_key, &aad(encryption)?);
+    stream_key.zeroize();
+
+    let sealed_len = chunk_size + TAG_LEN;
+    let chunks = stream::try_unfold(
+        (reader, cipher, 0, vec![], false),
+        move |(mut reader, cipher, index, mut pending, done)| async move {
+            if done {
+                return Ok::<_, io::Error>(None);
+            }
+
+            // read one byte past the chunk to find whether it is the last
+            let start = pending.len();
+            pending.resize(sealed_len + 1, 0);
+            let read = read_full(&mut reader, &mut pending[start..]).await?;
+            pending.truncate(start + read);
+
+            let last = pending.len() <= sealed_len;
+            let next = if last { vec![] } else { pending.split_off(sealed_len) };
+            let plaintext = cipher
+                .open(index, last, &pending)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+            Ok(Some((plaintext, (reader, cipher, index + 1, next, last))))
+        },
+    );
+
+    Ok(Either::Right(Box::pin(chunks).into_async_read()))
+}
+
+/// The owner's root private keys, indexed by key ID, used to derive the key
+/// needed to decrypt a record.
+#[derive(Clone, Debug, Default)]
+pub struct Keyring {
+    keys: Vec<(String, PrivateKeyJwk)>,
+}
+
+impl Keyring {
+    /// Returns a new, empty [`Keyring`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a root private key, identified by its key ID (e.g. a DID URL).
+    #[must_use]
+    pub fn add_key(mut self, key_id: impl Into<String>, private_key_jwk: PrivateKeyJwk) -> Self {
+        self.keys.push((key_id.into(), private_key_jwk));
+        self
+    }
+
+    pub(crate) fn get(&self, key_id: &str) -> Option<&PrivateKeyJwk> {
+        self.keys.iter().find(|(id, _)| id == key_id).map(|(_, jwk)| jwk)
+    }
+}
+
+/// Decrypt the provided data using the first key in the keyring able to
+/// decrypt it. The key for each of the `Write` message's encryption
+/// recipients is derived from the matching root key, using the recipient's
+/// derivation scheme.
+///
+/// # Errors
+///
+/// Will fail if the encryption properties are not set, the keyring holds no
+/// root key for any recipient, or the data cannot be decrypted with any of the
+/// derived keys.
+pub async fn decrypt_with_keyring(
+    data: &[u8], write: &Write, keyring: &Keyring,
+) -> Result<Vec<u8>> {
+    let Some(encryption) = &write.encryption else {
+        return Err(unexpected!("encryption parameter not set"));
     };
 
+    let mut last_err = None;
+    for recipient in &encryption.key_encryption {
+        let Some(private_key_jwk) = keyring.get(&recipient.root_key_id) else {
+            continue;
+        };
+        let ancestor_jwk = DerivedPrivateJwk {
+            root_key_id: recipient.root_key_id.clone(),
+            derivation_scheme: recipient.derivation_scheme.clone(),
+            derivation_path: None,
+            derived_private_key: private_key_jwk.clone(),
+        };
+        match decrypt_for(data, write, encryption, recipient, &ancestor_jwk).await {
+            Ok(plaintext) => return Ok(plaintext),
+            Err(e) => last_err = Some(e),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| unexpected!("no keyring key matches an encryption recipient")))
+}
+
+async fn decrypt_for(
+    data: &[u8], write: &Write, encryption: &EncryptionProperty, recipient: &EncryptedKey,
+    ancestor_jwk: &DerivedPrivateJwk,
+) -> Result<Vec<u8>> {
+    if let Some(chunk_size) = encryption.chunk_size {
+        return decrypt_chunked(data, chunk_size, write, encryption, recipient, ancestor_jwk).await;
+    }
+    decrypt_content(data, write, encryption, recipient, ancestor_jwk).await
+}
+
+// Decrypt content encrypted in a single AEAD operation using the CEK.
+async fn decrypt_content(
+    data: &[u8], write: &Write, encryption: &EncryptionProperty, recipient: &EncryptedKey,
+    ancestor_jwk: &DerivedPrivateJwk,
+) -> Result<Vec<u8>> {
     // ------------------------------------------------------------------------
     // TODO: move this code to Provider
     // ------------------------------------------------------------------------
     // derive path-appropriate JWK from ancestor
     let path = derivation_path(recipient, write)?;
-    let derived_jwk = hd_key::derive_jwk(ancestor_jwk.clone(), &DerivationPath::Full(&path))?;
+    let derived_jwk = hd_key::derive_jwk(ancestor_jwk.clone(), &DerivationPath::Full(&path))
+        .map_err(|_| unexpected!("no matching recipient key"))?;
     let receiver = ReceiverImpl(derived_jwk.derived_private_key.d.clone());
     // ------------------------------------------------------------------------
 
@@ -328,7 +547,7 @@ pub async fn decrypt(
         enc: encryption.algorithm.clone(),
         alg: None,
     };
-    let aad = serde_json::to_vec(&protected)?;
+    let aad = aad(encryption)?;
 
     let jwe = Jwe {
         protected,
@@ -350,21 +569,152 @@ pub async fn decrypt(
     };
 
     let plaintext: Vec<u8> =
-        jwe::decrypt(&jwe, &receiver).await.map_err(|e| unexpected!("failed to decrypt: {e}"))?;
+        jwe::decrypt(&jwe, &receiver).await.map_err(|_| unexpected!("decryption failed"))?;
 
     Ok(plaintext)
 }
 
+// Decrypt data encrypted in chunks: the stream key, decrypted using the CEK,
+// followed by chunks decrypted using the stream key.
+async fn decrypt_chunked(
+    data: &[u8], chunk_size: usize, write: &Write, encryption: &EncryptionProperty,
+    recipient: &EncryptedKey, ancestor_jwk: &DerivedPrivateJwk,
+) -> Result<Vec<u8>> {
+    let Some((stream_key, chunks)) = data.split_at_checked(STREAM_KEY_LEN) else {
+        return Err(unexpected!("decryption failed"));
+    };
+    let mut stream_key =
+        decrypt_content(stream_key, write, encryption, recipient, ancestor_jwk).await?;
+    let cipher = ChunkCipher::new(&encryption.algorithm, &stream_key, &aad(encryption)?);
+    stream_key.zeroize();
+
+    let count = chunks.len().div_ceil(chunk_size + TAG_LEN);
+    if count == 0 {
+        return Err(unexpected!("decryption failed"));
+    }
+    let mut plaintext = Vec::with_capacity(chunks.len());
+    for (index, chunk) in chunks.chunks(chunk_size + TAG_LEN).enumerate() {
+        plaintext.extend(cipher.open(index, index + 1 == count, chunk)?);
+    }
+    Ok(plaintext)
+}
+
+// The length of the key used to encrypt chunked data.
+const STREAM_KEY_LEN: usize = 32;
+
+// The length of the tag authenticating each encrypted chunk.
+const TAG_LEN: usize = 16;
+
+// The additional authenticated data used when encrypting a record's data.
+fn aad(encryption: &EncryptionProperty) -> Result<Vec<u8>> {
+    let protected = Protected {
+        enc: encryption.algorithm.clone(),
+        alg: None,
+    };
+    Ok(serde_json::to_vec(&protected)?)
+}
+
+// Encrypts or decrypts the chunks of a data stream using the STREAM
+// construction: each chunk's nonce is derived from its index, with the final
+// byte flagging the last chunk, so chunks cannot be reordered, dropped, or
+// truncated without detection. A fresh stream key is used for every record,
+// so nonces are never reused with the same key.
+struct ChunkCipher {
+    algorithm: ContentAlgorithm,
+    key: Vec<u8>,
+    aad: Vec<u8>,
+}
+
+impl ChunkCipher {
+    fn new(algorithm: &ContentAlgorithm, key: &[u8], aad: &[u8]) -> Self {
+        Self {
+            algorithm: algorithm.clone(),
+            key: key.to_vec(),
+            aad: aad.to_vec(),
+        }
+    }
+
+    fn nonce(&self, index: usize, last: bool) -> Result<Vec<u8>> {
+        let len = match self.algorithm {
+            ContentAlgorithm::A256Gcm => 12,
+            ContentAlgorithm::XChaCha20Poly1305 => 24,
+        };
+        let index = u32::try_from(index).map_err(|_| unexpected!("too many chunks"))?;
+
+        let mut nonce = vec![0; len - 5];
+        nonce.extend(index.to_be_bytes());
+        nonce.push(u8::from(last));
+        Ok(nonce)
+    }
+
+    fn seal(&self, index: usize, last: bool, chunk: &[u8]) -> Result<Vec<u8>> {
+        let nonce = self.nonce(index, last)?;
+        let payload = Payload {
+            msg: chunk,
+            aad: &self.aad,
+        };
+        let sealed = match self.algorithm {
+            ContentAlgorithm::A256Gcm => Aes256Gcm::new_from_slice(&self.key)
+                .map_err(|_| unexpected!("invalid stream key"))?
+                .encrypt(nonce.as_slice().into(), payload),
+            ContentAlgorithm::XChaCha20Poly1305 => XChaCha20Poly1305::new_from_slice(&self.key)
+                .map_err(|_| unexpected!("invalid stream key"))?
+                .encrypt(nonce.as_slice().into(), payload),
+        };
+        sealed.map_err(|_| unexpected!("encryption failed"))
+    }
+
+    fn open(&self, index: usize, last: bool, chunk: &[u8]) -> Result<Vec<u8>> {
+        let nonce = self.nonce(index, last)?;
+        let payload = Payload {
+            msg: chunk,
+            aad: &self.aad,
+        };
+        let opened = match self.algorithm {
+            ContentAlgorithm::A256Gcm => Aes256Gcm::new_from_slice(&self.key)
+                .map_err(|_| unexpected!("invalid stream key"))?
+                .decrypt(nonce.as_slice().into(), payload),
+            ContentAlgorithm::XChaCha20Poly1305 => XChaCha20Poly1305::new_from_slice(&self.key)
+                .map_err(|_| unexpected!("invalid stream key"))?
+                .decrypt(nonce.as_slice().into(), payload),
+        };
+        opened.map_err(|_| unexpected!("decryption failed"))
+    }
+}
+
+impl Drop for ChunkCipher {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+// Read until `buf` is full or the reader is exhausted, returning the number of
+// bytes read.
+async fn read_full(reader: &mut (impl AsyncRead + Unpin), buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        let n = reader.read(&mut buf[read..]).await?;
+        if n == 0 {
+            break;
+        }
+        read += n;
+    }
+    Ok(read)
+}
+
 fn derivation_path(encrypted_key: &EncryptedKey, write: &Write) -> Result<Vec<String>> {
     let descriptor = &write.descriptor;
 
     let derivation_path = match &encrypted_key.derivation_scheme {
         DerivationScheme::DataFormats => {
+            let Some(data_format) = &descriptor.data_format else {
+                return Err(unexpected!("`data_format` not set"));
+            };
             let scheme = DerivationScheme::DataFormats.to_string();
             if let Some(schema) = &descriptor.schema {
-                vec![scheme, schema.clone(), descriptor.data_format.clone()]
+                vec![scheme, schema.clone(), data_format.clone()]
             } else {
-                vec![scheme, descriptor.data_format.clone()]
+                vec![scheme, data_format.clone()]
             }
         }
         DerivationScheme::ProtocolPath => {
@@ -0,0 +1,508 @@
+//! # Records Export
+//!
+//! Export a single record as a self-contained [`Bundle`] that can be shared
+//! out-of-band and verified without access to the originating DWN, or as a
+//! CAR (Content Addressable aRchive) for use with IPFS tooling.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Cursor, Read};
+use std::str::FromStr;
+
+use ::cid::Cid;
+use base64ct::{Base64UrlUnpadded, Encoding};
+use ciborium::Value;
+use ipld_core::ipld::Ipld;
+use serde::{Deserialize, Serialize};
+
+use crate::endpoint::{self, Status};
+use crate::provider::{BlockStore, DataStore, DidResolver, MessageStore, Provider};
+use crate::records::{Delete, RecordsFilter, SignaturePayload, Write, write};
+use crate::store::{Entry, EntryType, RecordsQueryBuilder, block, data};
+use crate::utils::cid;
+use crate::{Error, Method, Result, unexpected};
+
+// Data blocks are staged under this partition while an archive is imported.
+const IMPORT_PARTITION: &str = "IMPORT";
+
+// The largest archive section (CID and block) accepted on import.
+const MAX_SECTION_SIZE: usize = 1_048_576;
+
+/// A self-contained, content-verifiable export of a single record.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bundle {
+    /// The latest `RecordsWrite` message for the record.
+    pub records_write: Write,
+
+    /// The record's initial write, when `records_write` is an update.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_write: Option<Write>,
+
+    /// The base64url encoded record data.
+    pub encoded_data: String,
+}
+
+/// Export the latest version of the specified record, along with its data and
+/// initial write (when the latest write is an update).
+///
+/// # Errors
+///
+/// Returns an error when the record cannot be found, has been deleted, or its
+/// data cannot be retrieved.
+pub async fn export_record(
+    owner: &str, record_id: &str, store: &(impl MessageStore + DataStore),
+) -> Result<Bundle> {
+    let latest = latest_entry(owner, record_id, store).await?;
+    let mut records_write = Write::try_from(&latest)?;
+
+    // move data out of the message so CIDs are computed as originally signed
+    let encoded_data = if let Some(encoded) = records_write.encoded_data.take() {
+        encoded
+    } else {
+        let Some(mut reader) = DataStore::get(
+            store,
+            owner,
+            &records_write.record_id,
+            &records_write.descriptor.data_cid,
+        )
+        .await?
+        else {
+            return Err(Error::NotFound("data not found".to_string()));
+        };
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        Base64UrlUnpadded::encode_string(&buf)
+    };
+
+    let initial_write = if records_write.is_initial()? {
+        None
+    } else {
+        let Some(mut initial) = write::initial_write(owner, record_id, store).await? else {
+            return Err(unexpected!("initial write not found"));
+        };
+        initial.encoded_data = None;
+        Some(initial)
+    };
+
+    Ok(Bundle {
+        records_write,
+        initial_write,
+        encoded_data,
+    })
+}
+
+/// Export the latest version of the specified record as a CAR (Content
+/// Addressable aRchive) with the `RecordsWrite` message's CID as its root.
+///
+/// The archive holds the message block (including its authorization), the
+/// initial write's message block when the latest write is an update, and
+/// the data blocks referenced by `data_cid`. Each block holds the bytes its
+/// CID is computed over, so blocks can be verified by hashing: messages are
+/// encoded without `encoded_data`, with data encoded in a message carried as
+/// data blocks instead.
+///
+/// # Errors
+///
+/// Returns an error when the record cannot be found, has been deleted, or its
+/// data cannot be retrieved.
+pub async fn export(
+    owner: &str, record_id: &str, store: &(impl MessageStore + DataStore),
+) -> Result<Cursor<Vec<u8>>> {
+    let latest = latest_entry(owner, record_id, store).await?;
+    let write = Write::try_from(&latest)?;
+    let root = write.cid()?;
+
+    let mut blocks = message_blocks(&write).await?;
+    if write.encoded_data.is_none() {
+        let Some(reader) =
+            DataStore::open(store, owner, record_id, &write.descriptor.data_cid).await?
+        else {
+            return Err(Error::NotFound("data not found".to_string()));
+        };
+        blocks.extend(data::cid_blocks(reader).await?);
+    }
+    if !write.is_initial()? {
+        let Some(initial) = write::initial_write(owner, record_id, store).await? else {
+            return Err(unexpected!("initial write not found"));
+        };
+        blocks.extend(message_blocks(&initial).await?);
+    }
+
+    Ok(Cursor::new(encode_car(&root, &blocks)?))
+}
+
+// The blocks for a `RecordsWrite` message: the message without its encoded
+// data, followed by the blocks of any data encoded with the message.
+async fn message_blocks(write: &Write) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut message = write.clone();
+    let encoded_data = message.encoded_data.take();
+
+    let bytes = cid::to_cbor(&message)?;
+    let mut blocks = vec![(cid::from_bytes(&bytes), bytes)];
+    if let Some(encoded_data) = encoded_data {
+        let data = Base64UrlUnpadded::decode_vec(&encoded_data)?;
+        blocks.extend(data::cid_blocks(data.as_slice()).await?);
+    }
+    Ok(blocks)
+}
+
+/// Import records from a CAR (Content Addressable aRchive), such as one
+/// produced by [`export`], by replaying the archived `RecordsWrite` and
+/// `RecordsDelete` messages through the normal message pipeline.
+///
+/// The archive is read a block at a time and each block is verified against
+/// its CID before anything is replayed. Data blocks are staged in the block
+/// store until the archive has been read, and are removed once the messages
+/// have been replayed.
+///
+/// Messages are replayed oldest first, with each message's signature and
+/// `data_cid` re-validated. A message failing validation does not abort the
+/// import: a [`Status`] is returned for each message, in the order replayed.
+///
+/// # Errors
+///
+/// Returns an error when the archive itself is malformed or any of its blocks
+/// do not match their CIDs.
+pub async fn import(
+    owner: &str, mut car: impl Read, provider: &impl Provider,
+) -> Result<Vec<Status>> {
+    let root = read_header(&mut car)?;
+    let partition = format!("{IMPORT_PARTITION}/{root}");
+
+    // message blocks are held for replay while data blocks are staged
+    let mut staged = HashSet::new();
+    let result = async {
+        let mut entries = vec![];
+        while let Some((block_cid, bytes)) = read_block(&mut car)? {
+            if let Some(entry) = decode_message(&bytes) {
+                entries.push(entry);
+            } else if staged.insert(block_cid.clone()) {
+                BlockStore::put(provider, owner, &partition, &block_cid, &bytes).await?;
+            }
+        }
+        replay(owner, entries, &partition, provider).await
+    }
+    .await;
+
+    for block_cid in &staged {
+        BlockStore::delete(provider, owner, &partition, block_cid).await?;
+    }
+    result
+}
+
+// Replay archived messages, oldest first.
+async fn replay(
+    owner: &str, mut entries: Vec<Entry>, partition: &str, provider: &impl Provider,
+) -> Result<Vec<Status>> {
+    entries.sort_by(|a, b| a.descriptor().message_timestamp.cmp(&b.descriptor().message_timestamp));
+
+    let mut statuses = vec![];
+    for entry in entries {
+        let result = match entry.message {
+            EntryType::Write(write) => replay_write(owner, write, partition, provider).await,
+            EntryType::Delete(delete) => {
+                endpoint::handle(owner, delete, provider).await.map(|reply| reply.status)
+            }
+            EntryType::Configure(_) => {
+                Err(Error::Unimplemented("only records messages can be imported".to_string()))
+            }
+        };
+        statuses.push(result.unwrap_or_else(|e| Status {
+            code: e.code().as_u16(),
+            detail: Some(e.detail().to_string()),
+        }));
+    }
+
+    Ok(statuses)
+}
+
+// Replay a `RecordsWrite` message, attaching its data when the archive holds
+// its data blocks.
+async fn replay_write(
+    owner: &str, mut write: Write, partition: &str, provider: &impl Provider,
+) -> Result<Status> {
+    if let Some(data) = staged_data(owner, partition, &write.descriptor.data_cid, provider).await? {
+        write.with_stream(Cursor::new(data));
+    }
+    Ok(endpoint::handle(owner, write, provider).await?.status)
+}
+
+// Reassemble data from staged data blocks, returning `None` when any block is
+// missing.
+async fn staged_data(
+    owner: &str, partition: &str, data_cid: &str, store: &impl BlockStore,
+) -> Result<Option<Vec<u8>>> {
+    let Some(root) = store.get(owner, partition, data_cid).await? else {
+        return Ok(None);
+    };
+
+    let mut data = Vec::new();
+    for link in decode_links(&root)? {
+        let Some(bytes) = store.get(owner, partition, &link).await? else {
+            return Ok(None);
+        };
+        let Ok(Value::Bytes(bytes)) = ciborium::from_reader(bytes.as_slice()) else {
+            return Err(unexpected!("invalid data block"));
+        };
+        data.extend(bytes);
+    }
+
+    Ok(Some(data))
+}
+
+// Decode a message block, returning `None` for data blocks.
+fn decode_message(bytes: &[u8]) -> Option<Entry> {
+    if let Ok(write) = ciborium::from_reader::<Write, _>(bytes)
+        && write.descriptor.base.method == Method::Write
+    {
+        return Some(Entry::from(&write));
+    }
+    if let Ok(delete) = ciborium::from_reader::<Delete, _>(bytes)
+        && delete.descriptor.base.method == Method::Delete
+    {
+        return Some(Entry::from(&delete));
+    }
+    None
+}
+
+// Decode the links held by a data root block.
+fn decode_links(root: &[u8]) -> Result<Vec<String>> {
+    let Ok(Value::Array(links)) = ciborium::from_reader(root) else {
+        return Err(unexpected!("invalid data root block"));
+    };
+
+    let mut cids = vec![];
+    for link in links {
+        let bytes = match link {
+            Value::Bytes(bytes) => bytes,
+            Value::Tag(42, link) => match *link {
+                Value::Bytes(bytes) => bytes,
+                _ => return Err(unexpected!("invalid link")),
+            },
+            _ => return Err(unexpected!("invalid link")),
+        };
+        // links encoded as DAG-CBOR carry a leading multibase identity byte
+        let bytes = bytes.strip_prefix(&[0]).unwrap_or(&bytes);
+        let cid = Cid::try_from(bytes).map_err(|e| unexpected!("invalid link: {e}"))?;
+        cids.push(cid.to_string());
+    }
+    Ok(cids)
+}
+
+/// Verify an exported [`Bundle`] without reference to the originating DWN.
+///
+/// Verification checks message signatures, that each signature covers the
+/// message it is attached to, that the bundled data matches the signed
+/// `data_cid` and `data_size`, and that any initial write is consistent with
+/// the latest write.
+///
+/// # Errors
+///
+/// Returns an error describing the first verification check to fail.
+pub async fn verify_write(bundle: &Bundle, resolver: impl DidResolver + Clone) -> Result<()> {
+    let write = &bundle.records_write;
+    verify_signature(write, resolver.clone()).await?;
+
+    // data integrity
+    let data = Base64UrlUnpadded::decode_vec(&bundle.encoded_data)?;
+    let (data_cid, data_size) = cid::from_reader(Cursor::new(data))?;
+    if data_cid != write.descriptor.data_cid {
+        return Err(unexpected!("bundle data CID does not match message `data_cid`"));
+    }
+    if data_size != write.descriptor.data_size {
+        return Err(unexpected!("bundle data size does not match message `data_size`"));
+    }
+
+    // the initial write is required when the latest write is an update
+    match &bundle.initial_write {
+        None if !write.is_initial()? => Err(unexpected!("initial write is missing from bundle")),
+        None => Ok(()),
+        Some(initial) => {
+            verify_signature(initial, resolver).await?;
+            if !initial.is_initial()? {
+                return Err(unexpected!("bundled initial write is not an initial write"));
+            }
+            if initial.record_id != write.record_id {
+                return Err(unexpected!("initial write and latest write record IDs do not match"));
+            }
+            write.verify_immutable(initial)
+        }
+    }
+}
+
+// Get the latest, undeleted message for the specified record.
+async fn latest_entry(owner: &str, record_id: &str, store: &impl MessageStore) -> Result<Entry> {
+    let query = RecordsQueryBuilder::new()
+        .add_filter(RecordsFilter::new().record_id(record_id))
+        .include_archived(true)
+        .method(None)
+        .build();
+    let (entries, _) = MessageStore::query(store, owner, &query).await?;
+
+    let Some(latest) = entries.last() else {
+        return Err(Error::NotFound("no matching record".to_string()));
+    };
+    if latest.descriptor().method == Method::Delete {
+        return Err(Error::NotFound("record is deleted".to_string()));
+    }
+    Ok(latest.clone())
+}
+
+// Encode blocks as a CARv1 archive with a single root. Each section is the
+// varint-prefixed concatenation of the block's CID and data. Blocks repeated
+// (e.g. data shared by the latest and initial writes) are written once.
+fn encode_car(root: &str, blocks: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let header = Ipld::Map(BTreeMap::from([
+        (String::from("roots"), Ipld::List(vec![Ipld::Link(parse_cid(root)?)])),
+        (String::from("version"), Ipld::Integer(1)),
+    ]));
+    let header = block::encode(&header)?;
+
+    let mut car = Vec::new();
+    write_varint(&mut car, header.len());
+    car.extend(header);
+
+    let mut written = HashSet::new();
+    for (cid, data) in blocks {
+        if !written.insert(cid) {
+            continue;
+        }
+        let cid = parse_cid(cid)?.to_bytes();
+        write_varint(&mut car, cid.len() + data.len());
+        car.extend(cid);
+        car.extend(data);
+    }
+
+    Ok(car)
+}
+
+// Read a CARv1 header, returning the archive's single root.
+fn read_header(car: &mut impl Read) -> Result<String> {
+    let Some(header) = read_section(car)? else {
+        return Err(unexpected!("archive header is missing"));
+    };
+
+    let Ipld::Map(header) = block::decode(&header)? else {
+        return Err(unexpected!("invalid archive header"));
+    };
+    if header.get("version") != Some(&Ipld::Integer(1)) {
+        return Err(unexpected!("unsupported archive version"));
+    }
+    let Some(Ipld::List(roots)) = header.get("roots") else {
+        return Err(unexpected!("archive header has no roots"));
+    };
+    let [Ipld::Link(root)] = roots.as_slice() else {
+        return Err(unexpected!("archive must have a single root"));
+    };
+
+    Ok(root.to_string())
+}
+
+// Read the next block from a CARv1 archive, returning `None` at the end of the
+// archive. The block's data is verified against the hash held by its CID.
+fn read_block(car: &mut impl Read) -> Result<Option<(String, Vec<u8>)>> {
+    let Some(mut section) = read_section(car)? else {
+        return Ok(None);
+    };
+
+    // a CIDv1 is the version, codec, hash code, and digest length (all
+    // varints) followed by the digest
+    let mut cursor = Cursor::new(section.as_slice());
+    let mut digest_len = 0;
+    for _ in 0..4 {
+        let Some(value) = read_varint(&mut cursor)? else {
+            return Err(unexpected!("archive block CID is truncated"));
+        };
+        digest_len = value;
+    }
+    let cid_len = usize::try_from(cursor.position())
+        .map_err(|e| unexpected!("archive block CID is too large: {e}"))?
+        .saturating_add(digest_len);
+
+    let Some(cid) = section.get(..cid_len) else {
+        return Err(unexpected!("archive block CID is truncated"));
+    };
+    let cid = Cid::try_from(cid).map_err(|e| unexpected!("issue parsing CID: {e}"))?;
+    let data = section.split_off(cid_len);
+
+    if parse_cid(&cid::from_bytes(&data))?.hash() != cid.hash() {
+        return Err(unexpected!("archive block does not match its CID"));
+    }
+    Ok(Some((cid.to_string(), data)))
+}
+
+// Read the next varint-prefixed section of a CARv1 archive, returning `None`
+// at the end of the archive.
+fn read_section(car: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let Some(len) = read_varint(car)? else {
+        return Ok(None);
+    };
+    if len > MAX_SECTION_SIZE {
+        return Err(unexpected!("archive section is too large"));
+    }
+
+    let mut section = vec![0; len];
+    car.read_exact(&mut section).map_err(|_| unexpected!("archive section is truncated"))?;
+    Ok(Some(section))
+}
+
+fn parse_cid(cid: &str) -> Result<Cid> {
+    Cid::from_str(cid).map_err(|e| unexpected!("issue parsing CID: {e}"))
+}
+
+// Append an unsigned LEB128 varint.
+#[allow(clippy::cast_possible_truncation)]
+fn write_varint(buf: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+// Read an unsigned LEB128 varint, returning `None` when the reader is
+// exhausted before the varint starts.
+fn read_varint(reader: &mut impl Read) -> Result<Option<usize>> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0; 1];
+        if reader.read(&mut byte)? == 0 {
+            if shift == 0 {
+                return Ok(None);
+            }
+            return Err(unexpected!("varint is truncated"));
+        }
+        if shift >= usize::BITS {
+            return Err(unexpected!("varint is too large"));
+        }
+        value |= usize::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+        shift += 7;
+    }
+}
+
+// Verify the message's signatures and that the signed payload matches the
+// message.
+async fn verify_signature(write: &Write, resolver: impl DidResolver) -> Result<()> {
+    write.authorization.verify(resolver).await?;
+
+    let decoded = Base64UrlUnpadded::decode_vec(&write.authorization.signature.payload)
+        .map_err(|e| unexpected!("issue decoding header: {e}"))?;
+    let payload: SignaturePayload = serde_json::from_slice(&decoded)
+        .map_err(|e| unexpected!("issue deserializing header: {e}"))?;
+
+    if payload.base.descriptor_cid != cid::from_value(&write.descriptor)? {
+        return Err(unexpected!("message and authorization descriptor CIDs do not match"));
+    }
+    if payload.record_id != write.record_id {
+        return Err(unexpected!("message and authorization record IDs do not match"));
+    }
+    if payload.context_id != write.context_id {
+        return Err(unexpected!("message and authorization context IDs do not match"));
+    }
+
+    Ok(())
+}
@@ -4,15 +4,17 @@
 //! protocol.
 
 use std::collections::BTreeMap;
+use std::io::Read;
 
-use serde_json::json;
+use base64ct::{Base64UrlUnpadded, Encoding};
+use serde_json::{Value, json};
 
 use crate::grants::{self, GrantData, RequestData, RevocationData, Scope};
 use crate::protocols::{self, GRANT_PATH, ProtocolType, REQUEST_PATH, REVOCATION_PATH, RuleSet};
-use crate::provider::MessageStore;
+use crate::provider::{DataStore, MessageStore, Provider};
 use crate::records::{RecordsFilter, Write};
 use crate::store::RecordsQueryBuilder;
-use crate::{Result, forbidden, schema, unexpected};
+use crate::{ForbiddenCode, Result, forbidden, schema, unexpected};
 
 impl Write {
     /// Verify the integrity of `RecordsWrite` messages using a protocol.
@@ -21,6 +23,9 @@ impl Write {
             return Err(forbidden!("missing protocol"));
         };
         let definition = protocols::definition(owner, protocol, store).await?;
+        if definition.active == Some(false) {
+            return Err(ForbiddenCode::ProtocolDeactivated.into());
+        }
         let Some(protocol_path) = &self.descriptor.protocol_path else {
             return Err(forbidden!("missing protocol"));
         };
@@ -33,6 +38,7 @@ impl Write {
         if rule_set.role.is_some() {
             self.verify_role_record(owner, store).await?;
         }
+        self.verify_recipient(&rule_set)?;
         self.verify_size_limit(&rule_set)?;
         self.verify_tags(&rule_set)?;
         self.verify_revoke(owner, store).await?;
@@ -65,6 +71,51 @@ impl Write {
         }
     }
 
+    /// Verify JSON record data conforms to the JSON Schema declared by the
+    /// record's protocol type. Records without a protocol, types without a
+    /// `dataSchema`, and non-JSON data formats are not validated.
+    pub(crate) async fn verify_data_schema(
+        &self, owner: &str, provider: &impl Provider,
+    ) -> Result<()> {
+        let (Some(protocol), Some(protocol_path)) =
+            (&self.descriptor.protocol, &self.descriptor.protocol_path)
+        else {
+            return Ok(());
+        };
+        if !self.descriptor.data_format.as_deref().is_some_and(is_json) {
+            return Ok(());
+        }
+        let definition = protocols::definition(owner, protocol, provider).await?;
+        let type_name = protocol_path.split('/').next_back().unwrap_or_default();
+        let Some(data_schema) =
+            definition.types.get(type_name).and_then(|t| t.data_schema.as_ref())
+        else {
+            return Ok(());
+        };
+
+        let data = if let Some(encoded) = &self.encoded_data {
+            Base64UrlUnpadded::decode_vec(encoded)?
+        } else {
+            let (record_id, data_cid) = (&self.record_id, &self.descriptor.data_cid);
+            let Some(mut reader) = DataStore::get(provider, owner, record_id, data_cid).await?
+            else {
+                return Err(unexpected!("data not found"));
+            };
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf)?;
+            buf
+        };
+
+        let validator = jsonschema::validator_for(data_schema)
+            .map_err(|e| unexpected!("invalid `dataSchema` for type {type_name}: {e}"))?;
+        let conforms = serde_json::from_slice::<Value>(&data)
+            .is_ok_and(|instance| validator.is_valid(&instance));
+        if !conforms {
+            return Err(unexpected!("data does not conform to schema"));
+        }
+        Ok(())
+    }
+
     /// Verifies the `data_format` and `schema` parameters .
     fn verify_type(&self, types: &BTreeMap<String, ProtocolType>) -> Result<()> {
         let Some(protocol_path) = &self.descriptor.protocol_path else {
@@ -82,8 +133,11 @@ impl Write {
         }
 
         if let Some(data_formats) = &protocol_type.data_formats {
-            if !data_formats.contains(&self.descriptor.data_format) {
-                return Err(forbidden!("invalid data format"));
+            let Some(data_format) = &self.descriptor.data_format else {
+                return Err(unexpected!("data format required by protocol"));
+            };
+            if !data_formats.contains(data_format) {
+                return Err(unexpected!("data format not allowed by protocol"));
             }
         }
 
@@ -133,7 +187,7 @@ impl Write {
             .build();
         let (entries, _) = store.query(owner, &query).await?;
         if entries.is_empty() {
-            return Err(forbidden!("unable to find parent record"));
+            return Err(unexpected!("parent record not found"));
         }
         let Some(record) = &entries.first() else {
             return Err(forbidden!("expected to find parent message"));
@@ -159,9 +213,9 @@ impl Write {
         let Some(context_id) = &self.context_id else {
             return Err(forbidden!("missing `context_id`"));
         };
-        // compare the parent segment of `context_id` with `parent_context_id`
-        if context_id[..parent_context_id.len()] != *parent_context_id {
-            return Err(forbidden!("incorrect parent `context_id`"));
+        // `context_id` must be the parent's `context_id` followed by `record_id`
+        if *context_id != format!("{parent_context_id}/{}", self.record_id) {
+            return Err(unexpected!("invalid context ID"));
         }
 
         Ok(())
@@ -179,7 +233,8 @@ impl Write {
             return Err(unexpected!("missing protocol_path"));
         };
 
-        // if this is not the root record, add a prefix filter to the query
+        // if this is not the root record, add a prefix filter to the query;
+        // root-level roles must be unique for the recipient across the protocol
         let mut filter = RecordsFilter::new()
             .protocol(protocol)
             .protocol_path(protocol_path)
@@ -205,6 +260,19 @@ impl Write {
         Ok(())
     }
 
+    // Verify write record adheres to the `$requireRecipient` and
+    // `$forbidRecipient` constraints.
+    fn verify_recipient(&self, rule_set: &RuleSet) -> Result<()> {
+        let has_recipient = self.descriptor.recipient.is_some();
+        if rule_set.require_recipient == Some(true) && !has_recipient {
+            return Err(unexpected!("protocol type requires a recipient"));
+        }
+        if rule_set.forbid_recipient == Some(true) && has_recipient {
+            return Err(unexpected!("protocol type does not allow a recipient"));
+        }
+        Ok(())
+    }
+
     // Verify write record adheres to the $size constraints.
     fn verify_size_limit(&self, rule_set: &RuleSet) -> Result<()> {
         let data_size = self.descriptor.data_size;
@@ -214,12 +282,12 @@ impl Write {
         };
         if let Some(start) = range.min {
             if data_size < start {
-                return Err(forbidden!("data size is less than allowed"));
+                return Err(unexpected!("data size outside protocol bounds"));
             }
         }
         if let Some(end) = range.max {
             if data_size > end {
-                return Err(forbidden!("data size is greater than allowed"));
+                return Err(unexpected!("data size outside protocol bounds"));
             }
         }
         Ok(())
@@ -240,7 +308,7 @@ impl Write {
 
         // validate tags against schema
         if !jsonschema::is_valid(&schema, &serde_json::to_value(&self.descriptor.tags)?) {
-            return Err(forbidden!("tags do not match schema"));
+            return Err(unexpected!("tags do not match schema"));
         }
 
         Ok(())
@@ -280,3 +348,9 @@ impl Write {
         Ok(())
     }
 }
+
+// Whether the data format is JSON (e.g. `application/json` or
+// `application/ld+json`).
+fn is_json(data_format: &str) -> bool {
+    data_format == "application/json" || data_format.ends_with("+json")
+}
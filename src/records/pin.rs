@@ -0,0 +1,49 @@
+//! # Records Pinning
+//!
+//! Owners can pin records they want to keep track of (e.g. when managing
+//! storage). Pinning is local to the owner's DWN: it is not a message, is not
+//! replicated, and is carried forward when a pinned record is updated. The pin
+//! is stored with the record's latest message, so it survives rebuilding the
+//! owner's indexes. Pinned records are selected using
+//! [`RecordsFilter::pinned`].
+
+use crate::provider::MessageStore;
+use crate::records::RecordsFilter;
+use crate::store::RecordsQueryBuilder;
+use crate::{Error, Method, Result};
+
+/// Pin (or unpin) the latest version of the specified record.
+///
+/// # Errors
+///
+/// Returns an error when the record cannot be found or has been deleted, or
+/// when the store cannot be updated.
+pub async fn pin(
+    owner: &str, record_id: &str, pinned: bool, store: &impl MessageStore,
+) -> Result<()> {
+    let query = RecordsQueryBuilder::new()
+        .add_filter(RecordsFilter::new().record_id(record_id))
+        .include_archived(true)
+        .method(None)
+        .build();
+    let (entries, _) = store.query(owner, &query).await?;
+
+    let Some(latest) = entries.last() else {
+        return Err(Error::NotFound("no matching record".to_string()));
+    };
+    if latest.descriptor().method == Method::Delete {
+        return Err(Error::NotFound("record is deleted".to_string()));
+    }
+
+    store.set_index(owner, &latest.cid()?, "pinned", &pinned.to_string()).await.map_err(Into::into)
+}
+
+// Whether the latest version of the specified record is pinned.
+pub(crate) async fn is_pinned(
+    owner: &str, record_id: &str, store: &impl MessageStore,
+) -> Result<bool> {
+    let query = RecordsQueryBuilder::new()
+        .add_filter(RecordsFilter::new().record_id(record_id).pinned(true))
+        .build();
+    Ok(store.count(owner, &query).await? > 0)
+}
@@ -10,7 +10,7 @@ use crate::protocols::{self, Action, ActionRule, Actor, RuleSet};
 use crate::provider::MessageStore;
 use crate::records::{Delete, Query, Read, RecordsFilter, Subscribe, Write, write};
 use crate::store::RecordsQueryBuilder;
-use crate::{Result, forbidden};
+use crate::{ForbiddenCode, Result, forbidden};
 
 /// [`Authorizer`] holds protocol-related information required during the process
 /// of verifying an incoming message's protocol-based authorization.
@@ -95,6 +95,52 @@ impl Authorizer<'_> {
     }
 }
 
+impl Authorizer<'_> {
+    /// Determine whether the requestor of the [`Read`] would be permitted to
+    /// both update and delete the record by the protocol's action rules,
+    /// including any protocol role invoked by the read.
+    pub async fn permit_modify(
+        &self, owner: &str, read: &Read, write: &Write, store: &impl MessageStore,
+    ) -> Result<bool> {
+        let record: Record = read.into();
+        if self.permit_role(owner, &record, store).await.is_err() {
+            return Ok(false);
+        }
+        let Some(authzn) = record.authorization() else {
+            return Ok(false);
+        };
+        let author = authzn.author()?;
+        let invoked_role = authzn.payload()?.protocol_role;
+
+        let write_record: Record = write.into();
+        let rule_set = write_record.rule_set(owner, store).await?;
+        let ancestor_chain = self.ancestor_chain(owner, &write.record_id, store).await?;
+        let Some(initial) = ancestor_chain.last() else {
+            return Ok(false);
+        };
+
+        let mut update_actions = vec![Action::CoUpdate];
+        let mut delete_actions = vec![Action::CoDelete];
+        if author == initial.authorization.author()? {
+            update_actions.push(Action::Update);
+            delete_actions.push(Action::Delete);
+        }
+
+        let permitted = |actions: &[Action]| {
+            permit_rules(
+                &rule_set,
+                &author,
+                invoked_role.as_ref(),
+                actions,
+                Some(write),
+                &ancestor_chain,
+            )
+            .is_ok()
+        };
+        Ok(permitted(&update_actions) && permitted(&delete_actions))
+    }
+}
+
 impl Authorizer<'_> {
     // Check if the incoming message is invoking a role. If so, verify the invoked role.
     async fn permit_role(
@@ -183,48 +229,21 @@ impl Authorizer<'_> {
         let author = authzn.author()?;
         let invoked_role = authzn.payload()?.protocol_role;
         let permitted_actions = self.permitted_actions(owner, record, store).await?;
-        let Some(action_rules) = &rule_set.actions else {
-            return Err(forbidden!("no rule defined for action"));
-        };
 
-        // find a rule that authorizes the incoming message
-        for rule in action_rules {
-            if !rule.can.iter().any(|action| permitted_actions.contains(action)) {
-                continue;
-            }
-            if rule.who == Some(Actor::Anyone) {
-                return Ok(());
-            }
-            if invoked_role.is_some() {
-                if rule.role == invoked_role {
-                    return Ok(());
-                }
-                continue;
-            }
-
-            // validate actor
-            if rule.who == Some(Actor::Recipient) && rule.of.is_none() {
-                let message = if let Record::Write(write) = &record {
-                    write
-                } else {
-                    // the incoming message must be a `RecordsDelete` because only
-                    // `co-update`, `co-delete`, `co-prune` are allowed recipient actions,
-                    &ancestor_chain[ancestor_chain.len() - 1]
-                };
-
-                if message.descriptor.recipient.as_ref() == Some(&author) {
-                    return Ok(());
-                }
-                continue;
-            }
-
-            // is actor allowed by the current action rule?
-            if permit_actor(&author, rule, &ancestor_chain)? {
-                return Ok(());
-            }
-        }
-
-        Err(forbidden!("action not permitted"))
+        // a `RecordsDelete` is checked against the recipient of the record
+        // being deleted because only `co-update`, `co-delete`, `co-prune` are
+        // allowed recipient actions
+        let recipient_write =
+            if let Record::Write(write) = &record { Some(write) } else { ancestor_chain.last() };
+
+        permit_rules(
+            &rule_set,
+            &author,
+            invoked_role.as_ref(),
+            &permitted_actions,
+            recipient_write,
+            &ancestor_chain,
+        )
     }
 
     // Constructs a chain of ancestor `initial_write` records starting from
@@ -303,6 +322,49 @@ impl Authorizer<'_> {
     }
 }
 
+// Find an action rule in the rule set that authorizes the `author` to
+// undertake one of the `actions`, either directly or by the invoked role.
+fn permit_rules(
+    rule_set: &RuleSet, author: &str, invoked_role: Option<&String>, actions: &[Action],
+    recipient_write: Option<&Write>, ancestor_chain: &[Write],
+) -> Result<()> {
+    let Some(action_rules) = &rule_set.actions else {
+        return Err(forbidden!("no rule defined for action"));
+    };
+
+    for rule in action_rules {
+        if !rule.can.iter().any(|action| actions.contains(action)) {
+            continue;
+        }
+        if rule.who == Some(Actor::Anyone) {
+            return Ok(());
+        }
+        if invoked_role.is_some() {
+            if rule.role.as_ref() == invoked_role {
+                return Ok(());
+            }
+            continue;
+        }
+
+        // validate actor
+        if rule.who == Some(Actor::Recipient) && rule.of.is_none() {
+            if let Some(write) = recipient_write
+                && write.descriptor.recipient.as_deref() == Some(author)
+            {
+                return Ok(());
+            }
+            continue;
+        }
+
+        // is actor allowed by the current action rule?
+        if permit_actor(author, rule, ancestor_chain)? {
+            return Ok(());
+        }
+    }
+
+    Err(ForbiddenCode::ActionNotPermitted.into())
+}
+
 // Checks for a match with the `who` rule in record chain.
 fn permit_actor(author: &str, action_rule: &ActionRule, ancestor_chain: &[Write]) -> Result<bool> {
     // find a message with matching protocolPath
@@ -4,17 +4,20 @@
 //! to query the [`MessageStore`] for matching [`Write`] (and possibly
 //! [`Delete`]) messages.
 
+use std::collections::BTreeMap;
+
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
+use vercre_infosec::Signer;
+use vercre_infosec::jose::JwsBuilder;
 
-use crate::authorization::Authorization;
+use crate::authorization::{Authorization, JwsPayload};
 use crate::endpoint::{Message, Reply, Status};
-use crate::grants::Grant;
 use crate::provider::{MessageStore, Provider};
-use crate::records::{RecordsFilter, Write, protocol};
-use crate::store::{self, Cursor, Pagination, RecordsQueryBuilder, Sort};
+use crate::records::{GroupBy, RecordsFilter, Write, protocol};
+use crate::store::{self, Cursor, Pagination, RecordsQueryBuilder, Sort, SortKey};
 use crate::utils::cid;
-use crate::{Descriptor, Result, forbidden, unexpected, utils};
+use crate::{Descriptor, Result, unauthorized, unexpected, utils};
 
 /// Handle — or process — a [`Query`] message.
 ///
@@ -27,6 +30,11 @@ pub async fn handle(
 ) -> Result<Reply<QueryReply>> {
     query.validate()?;
 
+    let query_max_bytes = query.descriptor.max_response_bytes;
+    let count_only = query.descriptor.count_only.unwrap_or_default();
+    let group_by = query.descriptor.group_by;
+    let include_initial = query.descriptor.include_initial_write.unwrap_or(true);
+    let no_data = query.descriptor.no_data.unwrap_or_default();
     let store_query = if query.only_published() {
         // correct filter when querying soley for published records
         let mut query = query;
@@ -35,16 +43,52 @@ pub async fn handle(
     } else {
         query.authorize(owner, provider).await?;
         let Some(authzn) = &query.authorization else {
-            return Err(forbidden!("missing authorization"));
+            return Err(unauthorized!("missing authorization"));
         };
 
-        if authzn.author()? == owner {
+        // a query counter-signed by the owner is authorized as the owner
+        let descriptor_cid = cid::from_value(&query.descriptor)?;
+        if authzn.author()? == owner || authzn.owner_signed(owner, &descriptor_cid)? {
             store::Query::from(query)
         } else {
             query.into_non_owner()?
         }
     };
 
+    // count matching records without fetching them
+    if count_only {
+        let count = MessageStore::count(provider, owner, &store_query).await?;
+        return Ok(Reply {
+            status: Status {
+                code: StatusCode::OK.as_u16(),
+                detail: None,
+            },
+            body: Some(QueryReply {
+                entries: None,
+                cursor: None,
+                count: Some(count),
+                groups: None,
+            }),
+        });
+    }
+
+    // count matching records per value of the grouping field
+    if let Some(group_by) = group_by {
+        let groups = MessageStore::count_by(provider, owner, &store_query, group_by).await?;
+        return Ok(Reply {
+            status: Status {
+                code: StatusCode::OK.as_u16(),
+                detail: None,
+            },
+            body: Some(QueryReply {
+                entries: None,
+                cursor: None,
+                count: None,
+                groups: Some(groups),
+            }),
+        });
+    }
+
     // fetch records matching query criteria
     let (records, cursor) = MessageStore::query(provider, owner, &store_query).await?;
 
@@ -61,32 +105,51 @@ pub async fn handle(
 
     // build reply
     let mut entries = vec![];
+    let mut cursor = cursor;
+    let mut response_bytes = 0;
 
     for record in records {
-        let write: Write = record.try_into()?;
-
-        // short-circuit when the record is an initial write
-        if write.is_initial()? {
-            entries.push(QueryReplyEntry {
-                write,
-                initial_write: None,
-            });
-            continue;
+        let mut write: Write = record.try_into()?;
+        if no_data {
+            write.encoded_data = None;
         }
 
-        // get the initial write for the returned `RecordsWrite`
-        let query = RecordsQueryBuilder::new()
-            .add_filter(RecordsFilter::new().record_id(&write.record_id))
-            .include_archived(true)
-            .build();
-        let (results, _) = MessageStore::query(provider, owner, &query).await?;
-        let mut initial_write: Write = (&results[0]).try_into()?;
-        initial_write.encoded_data = None;
-
-        entries.push(QueryReplyEntry {
-            write,
-            initial_write: Some(initial_write),
-        });
+        // get the initial write when the returned `RecordsWrite` is an update
+        let initial_write = if !include_initial || write.is_initial()? {
+            None
+        } else {
+            let query = RecordsQueryBuilder::new()
+                .add_filter(RecordsFilter::new().record_id(&write.record_id))
+                .include_archived(true)
+                .build();
+            let (results, _) = MessageStore::query(provider, owner, &query).await?;
+            let mut initial_write: Write = (&results[0]).try_into()?;
+            initial_write.encoded_data = None;
+            Some(initial_write)
+        };
+
+        let entry = QueryReplyEntry { write, initial_write };
+
+        // stop adding entries once the response byte budget would be exceeded,
+        // always returning at least one entry
+        if let Some(max_bytes) = query_max_bytes {
+            let entry_bytes = serde_json::to_vec(&entry)?.len();
+            if !entries.is_empty() && response_bytes + entry_bytes > max_bytes {
+                // continue from this entry in the next page
+                let indexes = entry.write.build_indexes();
+                let Some(value) = indexes.get(&store_query.sort.to_string()) else {
+                    return Err(unexpected!("missing sort field index"));
+                };
+                cursor = Some(Cursor {
+                    message_cid: entry.write.cid()?,
+                    value: value.clone(),
+                });
+                break;
+            }
+            response_bytes += entry_bytes;
+        }
+
+        entries.push(entry);
     }
 
     Ok(Reply {
@@ -97,6 +160,8 @@ pub async fn handle(
         body: Some(QueryReply {
             entries: Some(entries),
             cursor,
+            count: None,
+            groups: None,
         }),
     })
 }
@@ -144,6 +209,15 @@ pub struct QueryReply {
     /// Pagination cursor.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cursor: Option<Cursor>,
+
+    /// The number of matching records, returned for count-only queries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+
+    /// The number of matching records for each value of the `group_by`
+    /// field, returned for grouped queries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<BTreeMap<String, u64>>,
 }
 
 /// [`QueryReplyEntry`] represents a [`Write`] entry returned by the query.
@@ -161,15 +235,39 @@ pub struct QueryReplyEntry {
 }
 
 impl Query {
+    /// Counter-signs the `Query` as the web node owner, authorizing the
+    /// message's author to query records on the owner's web node as if they
+    /// were the owner.
+    ///
+    /// N.B. requires the `Query` to have previously been signed by the author.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail when the message has not been previously signed
+    /// by the author or there is an issue signing the message.
+    pub async fn sign_as_owner(&mut self, signer: &impl Signer) -> Result<()> {
+        let payload = JwsPayload {
+            descriptor_cid: cid::from_value(&self.descriptor)?,
+            ..JwsPayload::default()
+        };
+        let Some(authzn) = &mut self.authorization else {
+            return Err(unexpected!("message signature is required in order to sign as owner"));
+        };
+        authzn.owner_signature =
+            Some(JwsBuilder::new().payload(payload).add_signer(signer).build().await?);
+        Ok(())
+    }
+
     async fn authorize(&self, owner: &str, provider: &impl Provider) -> Result<()> {
         let Some(authzn) = &self.authorization else {
-            return Err(forbidden!("missing authorization"));
+            return Err(unauthorized!("missing authorization"));
         };
 
         // verify grant
         if let Some(delegated_grant) = &authzn.author_delegated_grant {
-            let grant: Grant = delegated_grant.try_into()?;
+            let grant = delegated_grant.to_grant()?;
             grant.permit_query(&authzn.author()?, &authzn.signer()?, self, provider).await?;
+            grant.verify_unexpired(provider.clock())?;
         }
 
         // verify protocol when request invokes a protocol role
@@ -194,6 +292,13 @@ impl Query {
     }
 
     fn validate(&self) -> Result<()> {
+        if self.descriptor.date_sort.is_some() && self.descriptor.sort.is_some() {
+            return Err(unexpected!("cannot specify both `date_sort` and `sort`"));
+        }
+        if self.descriptor.count_only == Some(true) && self.descriptor.group_by.is_some() {
+            return Err(unexpected!("cannot specify both `count_only` and `group_by`"));
+        }
+
         if let Some(protocol) = &self.descriptor.filter.protocol {
             utils::uri::validate(protocol)?;
         }
@@ -202,6 +307,14 @@ impl Query {
             utils::uri::validate(schema)?;
         }
 
+        self.descriptor.filter.validate()?;
+
+        if let Some(text) = &self.descriptor.filter.text
+            && text.terms().is_empty()
+        {
+            return Err(unexpected!("text search requires at least one term"));
+        }
+
         let Some(published) = self.descriptor.filter.published else {
             return Ok(());
         };
@@ -248,12 +361,15 @@ impl Query {
         if let Some(date_sort) = self.descriptor.date_sort {
             store_query = store_query.sort(date_sort);
         }
+        for sort_key in self.descriptor.sort.iter().flatten() {
+            store_query = store_query.add_sort(sort_key.clone());
+        }
         if let Some(pagination) = self.descriptor.pagination {
             store_query = store_query.pagination(pagination);
         }
 
         let Some(authzn) = &self.authorization else {
-            return Err(forbidden!("missing authorization"));
+            return Err(unauthorized!("missing authorization"));
         };
         let author = authzn.author()?;
 
@@ -299,7 +415,36 @@ pub struct QueryDescriptor {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub date_sort: Option<Sort>,
 
+    /// Composite sort keys, applied in order. Cannot be used with
+    /// `date_sort`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<Vec<SortKey>>,
+
     /// The pagination cursor.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pagination: Option<Pagination>,
+
+    /// The maximum number of (serialized) bytes to return in a page of
+    /// results. A single entry exceeding the limit is returned on its own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_response_bytes: Option<usize>,
+
+    /// Return only the number of matching records, omitting entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count_only: Option<bool>,
+
+    /// Return the number of matching records for each value of the field,
+    /// omitting entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<GroupBy>,
+
+    /// Include the initial write of updated records alongside the latest
+    /// write. Defaults to `true`; set to `false` to return only the latest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_initial_write: Option<bool>,
+
+    /// Return only record metadata, omitting any `encoded_data` from
+    /// returned entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_data: Option<bool>,
 }
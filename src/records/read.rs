@@ -6,16 +6,22 @@
 use std::io::Cursor;
 
 use base64ct::{Base64UrlUnpadded, Encoding};
+use chrono::{DateTime, Utc};
+use futures::future::Either;
+use futures::{AsyncRead, Stream, StreamExt, io, stream};
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
+use vercre_infosec::Signer;
+use vercre_infosec::jose::JwsBuilder;
 
-use crate::authorization::Authorization;
-use crate::endpoint::{Message, Reply, Status};
+use crate::authorization::{Authorization, JwsPayload};
+use crate::endpoint::{self, Message, Reply, Status};
 use crate::provider::{DataStore, MessageStore, Provider};
-use crate::records::{Delete, RecordsFilter, Write, protocol, write};
-use crate::store::{self, RecordsQueryBuilder};
+use crate::records::{Delete, RecordsFilter, Sort, Write, protocol, write};
+use crate::serde::rfc3339_micros_opt;
+use crate::store::{self, Entry, MatchOn, MatchSet, Matcher, RecordsQueryBuilder};
 use crate::utils::cid;
-use crate::{Descriptor, Error, Method, Result, forbidden, grants, unexpected};
+use crate::{Descriptor, Error, ForbiddenCode, Method, Result, grants, unauthorized, unexpected};
 
 /// Handle — or process — a [`Read`] message.
 ///
@@ -25,6 +31,8 @@ use crate::{Descriptor, Error, Method, Result, forbidden, grants, unexpected};
 /// an issue occurs attempting to retrieve the specified message from the
 /// [`MessageStore`].
 pub async fn handle(owner: &str, read: Read, provider: &impl Provider) -> Result<Reply<ReadReply>> {
+    read.validate()?;
+
     // get the latest active `RecordsWrite` and `RecordsDelete` messages
     let query = store::Query::from(read.clone());
 
@@ -36,9 +44,23 @@ pub async fn handle(owner: &str, read: Read, provider: &impl Provider) -> Result
         return Err(unexpected!("multiple messages exist"));
     }
 
+    // when reading as of an earlier time, use the version of the record
+    // current at that time if the latest message is more recent
+    let mut entry = entries[0].clone();
+    if let Some(as_of) = &read.descriptor.as_of
+        && entry.descriptor().message_timestamp > *as_of
+    {
+        let record_id = match (entry.as_write(), entry.as_delete()) {
+            (Some(write), _) => write.record_id.clone(),
+            (_, Some(delete)) => delete.descriptor.record_id.clone(),
+            _ => return Err(unexpected!("unexpected message type")),
+        };
+        entry = version_as_of(owner, &record_id, as_of, provider).await?;
+    }
+
     // if record is deleted, return as NotFound
-    if entries[0].descriptor().method == Method::Delete {
-        let Some(delete) = entries[0].as_delete() else {
+    if entry.descriptor().method == Method::Delete {
+        let Some(delete) = entry.as_delete() else {
             return Err(unexpected!("expected `RecordsDelete` message"));
         };
 
@@ -65,30 +87,70 @@ pub async fn handle(owner: &str, read: Read, provider: &impl Provider) -> Result
                     records_write: None,
                     data: None,
                 },
+                can_write: None,
+                data_size: None,
             }),
         });
     }
 
-    let mut write = Write::try_from(&entries[0])?;
+    let mut write = Write::try_from(&entry)?;
 
     // verify the fetched message can be safely returned to the requestor
     read.authorize(owner, &write, provider).await?;
 
+    // evaluate whether the requestor could update and delete the record
+    let can_write = if read.descriptor.include_permissions.unwrap_or_default() {
+        Some(read.can_write(owner, &write, provider).await?)
+    } else {
+        None
+    };
+
+    // resolve any requested range to offsets within the data
+    let bounds = read.descriptor.range.as_ref().map(|r| r.bounds(write.descriptor.data_size));
+
+    let verify = read.descriptor.verify_integrity.unwrap_or_default();
+
     let data = if let Some(encoded) = write.encoded_data {
         write.encoded_data = None;
-        let buffer = Base64UrlUnpadded::decode_vec(&encoded)?;
+        let mut buffer = Base64UrlUnpadded::decode_vec(&encoded)?;
+        if verify {
+            verify_data(&buffer, &write.descriptor.data_cid)?;
+        }
+        if let Some((start, end)) = bounds {
+            buffer = buffer.get(start..end).unwrap_or_default().to_vec();
+        }
         Some(Cursor::new(buffer))
+    } else if read.defer_data && bounds.is_none() && !verify {
+        // data is fetched on demand using `ReadReplyEntry::open_data`
+        None
     } else {
         use std::io::Read;
 
-        let Some(mut read) =
-            DataStore::get(provider, owner, &write.record_id, &write.descriptor.data_cid).await?
-        else {
-            return Err(Error::NotFound("data not found".to_string()));
-        };
-
+        let (record_id, data_cid) = (&write.record_id, &write.descriptor.data_cid);
         let mut buf = Vec::new();
-        read.read_to_end(&mut buf)?;
+        if let Some((start, end)) = bounds
+            && !verify
+        {
+            let Some(mut read) =
+                DataStore::get_range(provider, owner, record_id, data_cid, start, end).await?
+            else {
+                return Err(Error::NotFound("data not found".to_string()));
+            };
+            read.read_to_end(&mut buf)?;
+        } else {
+            let Some(mut read) = DataStore::get(provider, owner, record_id, data_cid).await? else {
+                return Err(Error::NotFound("data not found".to_string()));
+            };
+            read.read_to_end(&mut buf)?;
+
+            // the full data is required to verify its integrity
+            if verify {
+                verify_data(&buf, data_cid)?;
+                if let Some((start, end)) = bounds {
+                    buf = buf.get(start..end).unwrap_or_default().to_vec();
+                }
+            }
+        }
         Some(Cursor::new(buf))
     };
 
@@ -115,9 +177,16 @@ pub async fn handle(owner: &str, read: Read, provider: &impl Provider) -> Result
         Some(initial_write)
     };
 
+    // a range read returns partial content
+    let (code, data_size) = if bounds.is_some() {
+        (StatusCode::PARTIAL_CONTENT, Some(write.descriptor.data_size))
+    } else {
+        (StatusCode::OK, None)
+    };
+
     Ok(Reply {
         status: Status {
-            code: StatusCode::OK.as_u16(),
+            code: code.as_u16(),
             detail: None,
         },
         body: Some(ReadReply {
@@ -127,6 +196,8 @@ pub async fn handle(owner: &str, read: Read, provider: &impl Provider) -> Result
                 initial_write,
                 data,
             },
+            can_write,
+            data_size,
         }),
     })
 }
@@ -141,6 +212,12 @@ pub struct Read {
     /// Message authorization.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub authorization: Option<Authorization>,
+
+    /// Leave data stored in the block store out of the reply so it can be
+    /// read on demand using [`ReadReplyEntry::open_data`]. Ignored for range
+    /// reads and reads verifying data integrity.
+    #[serde(skip)]
+    pub defer_data: bool,
 }
 
 impl Message for Read {
@@ -163,12 +240,55 @@ impl Message for Read {
     }
 }
 
+/// A batch of [`Read`] messages, one for each record to read.
+#[derive(Clone, Debug, Default)]
+pub struct ReadBatch {
+    /// The `Read` messages making up the batch.
+    pub reads: Vec<Read>,
+}
+
+impl ReadBatch {
+    /// Process each read in the batch, yielding the record ID and the outcome
+    /// of the read as each record is resolved.
+    ///
+    /// Each read is authenticated and authorized exactly as a single
+    /// [`Read`] would be, so a deleted, missing, or forbidden record produces
+    /// an error for that record only.
+    pub fn stream<'a>(
+        self, owner: &'a str, provider: &'a impl Provider,
+    ) -> impl Stream<Item = (String, Result<ReadReplyEntry>)> + 'a {
+        stream::iter(self.reads).then(move |read| async move {
+            let record_id = read.descriptor.filter.record_id.clone().unwrap_or_default();
+            let result = match endpoint::handle(owner, read, provider).await {
+                Ok(reply) if reply.status.code == StatusCode::NOT_FOUND => {
+                    Err(Error::NotFound("record is deleted".to_string()))
+                }
+                Ok(reply) => reply.body.map(|body| body.entry).ok_or_else(|| {
+                    Error::InternalServerError("read reply is missing body".to_string())
+                }),
+                Err(e) => Err(e),
+            };
+            (record_id, result)
+        })
+    }
+}
+
 /// [`ReadReply`] is returned by the handler in the [`Reply`] `body` field.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ReadReply {
     /// The read reply entry.
     pub entry: ReadReplyEntry,
+
+    /// Whether the requestor is authorized to update and delete the record.
+    /// Only set when requested using `include_permissions`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub can_write: Option<bool>,
+
+    /// The total size of the record's data. Only set when a `range` of the
+    /// data is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_size: Option<usize>,
 }
 
 /// [`ReadReplyEntry`] represents the [`Write`] entry returned for a successful
@@ -195,15 +315,175 @@ pub struct ReadReplyEntry {
     pub data: Option<Cursor<Vec<u8>>>,
 }
 
+impl ReadReplyEntry {
+    /// Open the record's data for reading.
+    ///
+    /// Data returned in the reply (including encoded data) is read directly.
+    /// Otherwise, data blocks are fetched from the provider's [`DataStore`]
+    /// as the data is read, keeping memory use bounded for large records.
+    ///
+    /// Returns `None` when the entry has no data, e.g. the record is deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the record's data cannot be opened.
+    pub async fn open_data<'a>(
+        &'a self, owner: &'a str, provider: &'a impl Provider,
+    ) -> Result<Option<impl AsyncRead + Send + Unpin + 'a>> {
+        if let Some(data) = &self.data {
+            let reader = io::Cursor::new(data.get_ref().as_slice());
+            return Ok(Some(Either::Left(reader)));
+        }
+        let Some(write) = &self.records_write else {
+            return Ok(None);
+        };
+        let (record_id, data_cid) = (&write.record_id, &write.descriptor.data_cid);
+        let reader = DataStore::open(provider, owner, record_id, data_cid).await?;
+        Ok(reader.map(Either::Right))
+    }
+}
+
+// Find the version of a record current as of `as_of`: the message preceding
+// the earliest message logged after `as_of`. The version cannot be determined
+// unless it is known to have been superseded by that message, as superseded
+// writes were not always retained.
+async fn version_as_of(
+    owner: &str, record_id: &str, as_of: &DateTime<Utc>, provider: &impl Provider,
+) -> Result<Entry> {
+    let query = RecordsQueryBuilder::new()
+        .add_filter(RecordsFilter::new().record_id(record_id))
+        .include_archived(true)
+        .method(None)
+        .build();
+    let (entries, _) = MessageStore::query(provider, owner, &query).await?;
+
+    let Some(next) = entries.iter().position(|e| e.descriptor().message_timestamp > *as_of) else {
+        return entries.last().cloned().ok_or_else(|| unexpected!("record not found"));
+    };
+    let Some(version) = next.checked_sub(1).map(|index| &entries[index]) else {
+        return Err(Error::NotFound("no version of record as of time".to_string()));
+    };
+
+    // verify the version was superseded by the next message
+    let superseded_by = entries[next].cid()?;
+    let query = store::Query {
+        match_sets: vec![MatchSet {
+            inner: vec![Matcher {
+                field: "supersededBy".to_string(),
+                value: MatchOn::Equal(superseded_by.clone()),
+            }],
+            index: Some(("supersededBy".to_string(), superseded_by)),
+        }],
+        ..store::Query::default()
+    };
+    let (superseded, _) = MessageStore::query(provider, owner, &query).await?;
+    if superseded.first().map(Entry::cid).transpose()? != Some(version.cid()?) {
+        return Err(Error::NotFound("version of record as of time not retained".to_string()));
+    }
+
+    Ok(version.clone())
+}
+
+// Verify the data matches the CID it was stored under.
+fn verify_data(data: &[u8], data_cid: &str) -> Result<()> {
+    let (actual, _) = cid::from_reader(data)?;
+    if actual != data_cid {
+        return Err(unexpected!("data integrity check failed"));
+    }
+    Ok(())
+}
+
 impl Read {
-    async fn authorize(&self, owner: &str, write: &Write, store: &impl MessageStore) -> Result<()> {
+    /// Counter-signs the `Read` as the web node owner, authorizing the
+    /// message's author to read any record on the owner's web node as if they
+    /// were the owner. The author remains the reader: the author's signature
+    /// authenticates the request, the owner's authorizes it.
+    ///
+    /// N.B. requires the `Read` to have previously been signed by the author.
+    ///
+    /// # Errors
+    ///
+    /// This method will fail when the message has not been previously signed
+    /// by the author or there is an issue signing the message.
+    pub async fn sign_as_owner(&mut self, signer: &impl Signer) -> Result<()> {
+        let payload = JwsPayload {
+            descriptor_cid: cid::from_value(&self.descriptor)?,
+            ..JwsPayload::default()
+        };
+        let Some(authzn) = &mut self.authorization else {
+            return Err(unexpected!("message signature is required in order to sign as owner"));
+        };
+        authzn.owner_signature =
+            Some(JwsBuilder::new().payload(payload).add_signer(signer).build().await?);
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.descriptor.filter.validate()?;
+
+        // only published records are guaranteed to have a `date_published`
+        if (self.descriptor.date_sort == Some(Sort::PublishedAsc)
+            || self.descriptor.date_sort == Some(Sort::PublishedDesc))
+            && self.descriptor.filter.published != Some(true)
+        {
+            return Err(unexpected!("sorting by `date_published` requires a published filter"));
+        }
+        if let Some(ByteRange {
+            start,
+            end: Some(end),
+        }) = &self.descriptor.range
+            && start > end
+        {
+            return Err(unexpected!("range `start` must not be after `end`"));
+        }
+        Ok(())
+    }
+
+    // Determine whether the requestor would be authorized to update and delete
+    // the record, without performing either action.
+    async fn can_write(
+        &self, owner: &str, write: &Write, provider: &impl Provider,
+    ) -> Result<bool> {
+        let Some(authzn) = &self.authorization else {
+            return Ok(false);
+        };
+        let author = authzn.author()?;
+
+        // owner can update and delete any record on their DWN
+        if author == owner {
+            return Ok(true);
+        }
+
+        // grants held by the requestor permitting them to update and delete
+        let grants = grants::fetch_grants(owner, &author, provider).await?;
+        let (mut can_update, mut can_delete) = (false, false);
+        for grant in &grants {
+            can_update |=
+                grant.permit_modify(owner, &author, Method::Write, write, provider).await.is_ok();
+            can_delete |=
+                grant.permit_modify(owner, &author, Method::Delete, write, provider).await.is_ok();
+        }
+        if can_update && can_delete {
+            return Ok(true);
+        }
+
+        // protocol rules, including any role invoked by the read
+        let Some(protocol) = &write.descriptor.protocol else {
+            return Ok(false);
+        };
+        let authorizer = protocol::Authorizer::new(protocol).context_id(write.context_id.as_ref());
+        authorizer.permit_modify(owner, self, write, provider).await
+    }
+
+    async fn authorize(&self, owner: &str, write: &Write, provider: &impl Provider) -> Result<()> {
         // authorization not required for published data
         if write.descriptor.published.unwrap_or_default() {
             return Ok(());
         }
 
+        // anonymous requests are unauthenticated rather than forbidden
         let Some(authzn) = &self.authorization else {
-            return Err(forbidden!("read not authorized"));
+            return Err(unauthorized!("read not authorized"));
         };
         let author = authzn.author()?;
 
@@ -212,6 +492,12 @@ impl Read {
             return Ok(());
         }
 
+        // a read counter-signed by the owner is authorized as the owner, so
+        // the owner can let a non-author read any record on their DWN
+        if authzn.owner_signed(owner, &cid::from_value(&self.descriptor)?)? {
+            return Ok(());
+        }
+
         // recipient can read records they received
         if let Some(recipient) = &write.descriptor.recipient {
             if &author == recipient {
@@ -231,8 +517,9 @@ impl Read {
 
         // verify grant
         if let Some(grant_id) = &authzn.payload()?.permission_grant_id {
-            let grant = grants::fetch_grant(owner, grant_id, store).await?;
-            grant.permit_read(owner, &author, self, write, store).await?;
+            let grant = grants::fetch_grant(owner, grant_id, provider).await?;
+            grant.permit_read(owner, &author, self, write, provider).await?;
+            grant.verify_unexpired(provider.clock())?;
             return Ok(());
         }
 
@@ -241,11 +528,11 @@ impl Read {
             let protocol = protocol::Authorizer::new(protocol)
                 .context_id(write.context_id.as_ref())
                 .initial_write(write);
-            protocol.permit_read(owner, self, store).await?;
+            protocol.permit_read(owner, self, provider).await?;
             return Ok(());
         }
 
-        Err(forbidden!("read cannot be authorized"))
+        Err(ForbiddenCode::ReadNotAuthorized.into())
     }
 }
 
@@ -259,4 +546,55 @@ pub struct ReadDescriptor {
 
     /// Defines the filter for the read.
     pub filter: RecordsFilter,
+
+    /// When set, the first record matching the filter (using the specified
+    /// sort) is returned rather than requiring the filter to match a single
+    /// record. Ties are broken using the message CID.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_sort: Option<Sort>,
+
+    /// Include whether the requestor is authorized to update and delete the
+    /// record in the reply.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_permissions: Option<bool>,
+
+    /// When set, only the specified range of the record's data is returned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<ByteRange>,
+
+    /// Recompute the CID of the record's data as it is read, failing the read
+    /// when it does not match the record's `data_cid`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_integrity: Option<bool>,
+
+    /// Return the version of the record current as of the specified time,
+    /// rather than the latest version.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "rfc3339_micros_opt")]
+    pub as_of: Option<DateTime<Utc>>,
+}
+
+/// A range of bytes to read from a record's data. As for HTTP range requests,
+/// both `start` and `end` are inclusive. When `end` is not set, or is beyond
+/// the end of the data, the range extends to the end of the data.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ByteRange {
+    /// The offset of the first byte to return.
+    pub start: u64,
+
+    /// The offset of the last byte to return.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<u64>,
+}
+
+impl ByteRange {
+    // Resolve the range to `[start, end)` offsets within data of the specified
+    // size, clamping both to the end of the data.
+    fn bounds(&self, data_size: usize) -> (usize, usize) {
+        let start = usize::try_from(self.start).unwrap_or(usize::MAX).min(data_size);
+        let end = self.end.map_or(data_size, |end| {
+            usize::try_from(end).unwrap_or(usize::MAX).saturating_add(1).min(data_size)
+        });
+        (start, end.max(start))
+    }
 }
@@ -9,12 +9,11 @@ use serde::{Deserialize, Serialize};
 
 use crate::authorization::Authorization;
 use crate::endpoint::{Message, Reply, Status};
-use crate::event::{SubscribeFilter, Subscriber};
-use crate::grants::Grant;
+use crate::event::{SUBSCRIBER_CAPACITY, Subscriber};
 use crate::provider::{EventStream, Provider};
 use crate::records::{RecordsFilter, protocol};
 use crate::utils::cid;
-use crate::{Descriptor, OneOrMany, Result, forbidden};
+use crate::{Descriptor, Result, unauthorized, unexpected};
 
 /// Handle — or process — a [`Subscribe`] message.
 ///
@@ -33,24 +32,18 @@ pub async fn handle(
     let mut subscriber = EventStream::subscribe(provider, owner).await?;
 
     // apply filtering before returning
-    let mut filter = subscribe.descriptor.filter.clone();
-
-    let authzn =
-        subscribe.authorization.as_ref().ok_or_else(|| forbidden!("missing authorization"))?;
-    let author = authzn.author()?;
-    if author != owner {
-        // non-owners can only see records they created or received
-        filter.author = Some(OneOrMany::One(author.clone()));
-        filter.recipient = Some(OneOrMany::One(author));
-    }
-
-    let filter = SubscribeFilter::Records(filter);
-    let filtered = subscriber.inner.filter(move |event| future::ready(filter.is_match(event)));
+    let filters = subscribe.filters(owner)?;
+    let filtered = subscriber
+        .inner
+        .filter(move |event| future::ready(filters.iter().any(|filter| filter.is_match(event))));
     subscriber.inner = Box::pin(filtered);
 
+    // buffer events so a slow consumer cannot cause unbounded buffering
+    let subscriber = subscriber.bounded(SUBSCRIBER_CAPACITY);
+
     Ok(Reply {
         status: Status {
-            code: StatusCode::OK.as_u16(),
+            code: StatusCode::ACCEPTED.as_u16(),
             detail: None,
         },
         body: Some(SubscribeReply {
@@ -110,33 +103,78 @@ impl Subscribe {
         }
 
         let Some(authzn) = &self.authorization else {
-            return Err(forbidden!("missing authorization"));
+            return Err(unauthorized!("missing authorization"));
         };
-        let author = authzn.author()?;
 
         // verify grant
         if let Some(delegated_grant) = &authzn.author_delegated_grant {
-            let grant: Grant = delegated_grant.try_into()?;
-            grant.permit_subscribe(&author, &authzn.signer()?, self, provider).await?;
+            let grant = delegated_grant.to_grant()?;
+            grant.permit_subscribe(&authzn.author()?, &authzn.signer()?, self, provider).await?;
+            grant.verify_unexpired(provider.clock())?;
         }
 
         // verify protocol when request invokes a protocol role
-        if let Some(protocol) = &authzn.payload()?.protocol_role {
-            let protocol = protocol::Authorizer::new(protocol)
+        if authzn.payload()?.protocol_role.is_some() {
+            let Some(protocol) = &self.descriptor.filter.protocol else {
+                return Err(unexpected!("missing protocol"));
+            };
+            let Some(protocol_path) = &self.descriptor.filter.protocol_path else {
+                return Err(unexpected!("missing `protocol_path`"));
+            };
+            if protocol_path.contains('/') && self.descriptor.filter.context_id.is_none() {
+                return Err(unexpected!("missing `context_id`"));
+            }
+
+            let verifier = protocol::Authorizer::new(protocol)
                 .context_id(self.descriptor.filter.context_id.as_ref());
-            return protocol.permit_subscribe(owner, self, provider).await;
+            return verifier.permit_subscribe(owner, self, provider).await;
         }
 
-        // when filter.protocol_role is set, set method to be RecordsWrite or RecordsDelete
+        Ok(())
+    }
+
+    // Build the set of filters a record event must match one of to be sent to
+    // the subscriber. Mirrors the filters used when querying records: the
+    // owner sees all matching records while other subscribers only see
+    // published records and those they authored or received (or any, when
+    // authorized by a protocol role).
+    fn filters(&self, owner: &str) -> Result<Vec<RecordsFilter>> {
+        let filter = self.descriptor.filter.normalize()?;
+
         let Some(authzn) = &self.authorization else {
-            return Err(forbidden!("missing authorization"));
+            return Ok(vec![filter.published(true)]);
         };
+        let author = authzn.author()?;
+        if author == owner {
+            return Ok(vec![filter]);
+        }
+
+        let mut filters = vec![];
+        if filter.published != Some(false) {
+            filters.push(filter.clone().published(true));
+        }
+        if filter.published == Some(true) {
+            return Ok(filters);
+        }
+
+        // the subscriber's own records, narrowed to those the filter selects
+        if filter.author.as_ref().is_none_or(|authors| authors.to_vec().contains(&author)) {
+            let mut authored = filter.clone();
+            authored.author = None;
+            filters.push(authored.add_author(&author).published(false));
+        }
+        if filter.recipient.as_ref().is_none_or(|recipients| recipients.to_vec().contains(&author))
+        {
+            let mut received = filter.clone();
+            received.recipient = None;
+            filters.push(received.add_recipient(&author).published(false));
+        }
+
         if authzn.payload()?.protocol_role.is_some() {
-            // TODO: determine whether this is required
-            // filter.method = OneOrMany::Many(vec![Method::Write, Method::Delete]);
+            filters.push(filter.published(false));
         }
 
-        Ok(())
+        Ok(filters)
     }
 }
 
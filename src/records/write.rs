@@ -3,27 +3,31 @@
 //! The records write endpoint handles `RecordsWrite` messages —
 //! requests to write to records to the DWN's [`MessageStore`].
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
 use std::io::{Cursor, Read};
+use std::sync::{Arc, Mutex};
 
 use base64ct::{Base64UrlUnpadded, Encoding};
 use chrono::format::SecondsFormat::Micros;
 use chrono::{DateTime, Utc};
+use futures::io::AllowStdIo;
+use futures::{AsyncRead, AsyncReadExt};
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
 use vercre_infosec::Signer;
 use vercre_infosec::jose::{Jws, JwsBuilder};
 
-use crate::authorization::{Authorization, JwsPayload};
-use crate::endpoint::{Message, Reply, Status};
+use crate::authorization::{self, Authorization, JwsPayload};
+use crate::endpoint::{self, Message, Reply, Status};
 use crate::grants::{self, Grant};
-use crate::protocols::{PROTOCOL_URI, REVOCATION_PATH};
-use crate::provider::{DataStore, EventLog, EventStream, MessageStore, Provider};
-use crate::records::{DateRange, EncryptionProperty, RecordsFilter, protocol};
-use crate::serde::{rfc3339_micros, rfc3339_micros_opt};
-use crate::store::{Entry, EntryType, GrantedQueryBuilder, RecordsQueryBuilder, data};
+use crate::protocols::{self, PROTOCOL_URI, REVOCATION_PATH};
+use crate::provider::{BlockStore, DataStore, EventLog, EventStream, MessageStore, Provider, gc};
+use crate::records::{DateRange, EncryptionProperty, RecordsFilter, is_pinned, protocol};
+use crate::serde::{float, rfc3339_micros, rfc3339_micros_opt, whole_number};
+use crate::store::{Entry, EntryType, GrantedQueryBuilder, RecordsQueryBuilder, block};
 use crate::utils::cid;
-use crate::{Descriptor, Error, Method, Result, forbidden, unexpected};
+use crate::{Descriptor, Error, Method, Result, forbidden, unexpected, utils};
 
 /// Handle — or process — a [`Write`] message.
 ///
@@ -32,22 +36,61 @@ use crate::{Descriptor, Error, Method, Result, forbidden, unexpected};
 /// The endpoint will return an error when message authorization fails or when
 /// an issue occurs attempting to save the [`Write`] message or attendant data.
 pub async fn handle(
-    owner: &str, write: Write, provider: &impl Provider,
+    owner: &str, mut write: Write, provider: &impl Provider,
 ) -> Result<Reply<WriteReply>> {
     write.authorize(owner, provider).await?;
     write.verify_integrity(owner, provider).await?;
 
+    // a data reader can only be read once, so is taken from the write here
+    // and a write whose reader has already been consumed is rejected
+    let data_reader = match write.data_reader.take() {
+        Some(reader) => {
+            Some(reader.take().ok_or_else(|| unexpected!("data reader has already been read"))?)
+        }
+        None => None,
+    };
+
+    // a write reusing the idempotency key of an earlier write is a retry,
+    // answered with the earlier write's reply without creating a new record
+    if let Some(status) = write.idempotent_reply(owner, provider).await? {
+        return status.replay();
+    }
+
+    let reply = process(owner, &mut write, data_reader, provider).await;
+    write.record_reply(owner, &reply, provider).await?;
+    reply
+}
+
+// Process a write, returning the reply to send to the requestor.
+async fn process(
+    owner: &str, write: &mut Write, data_reader: Option<Box<dyn AsyncRead + Send + Unpin>>,
+    provider: &impl Provider,
+) -> Result<Reply<WriteReply>> {
     let is_initial = write.is_initial()?;
 
     // find any existing entries for the `record_id`
     let existing = existing_entries(owner, &write.record_id, provider).await?;
     let (initial_entry, latest_entry) = earliest_and_latest(&existing);
 
+    // a record whose tombstone has been collected remains deleted
+    if initial_entry.is_none() && gc::is_collected(owner, &write.record_id, provider).await? {
+        return Err(Error::Conflict("record has been deleted".to_string()));
+    }
+
     // when no existing entries, verify this write is the initial write
     if initial_entry.is_none() && !is_initial {
         return Err(unexpected!("initial write not found"));
     }
 
+    // a create-only write must not overwrite an existing record, though a
+    // retry of the create itself is still acknowledged
+    if write.descriptor.create_only == Some(true)
+        && let Some(initial_entry) = &initial_entry
+        && initial_entry.cid()? != write.cid()?
+    {
+        return Err(Error::Conflict("record already exists".to_string()));
+    }
+
     // when message is an update, verify 'immutable' properties are unchanged
     if let Some(initial_entry) = &initial_entry {
         let earliest = Write::try_from(initial_entry)?;
@@ -55,10 +98,59 @@ pub async fn handle(
             return Err(unexpected!("initial write is not the earliest message"));
         }
         write.verify_immutable(&earliest)?;
+
+        // only the write the `record_id` is derived from can take the initial
+        // write's place, otherwise a different descriptor could stand in for it
+        if !is_initial
+            && write.descriptor.base.message_timestamp == earliest.descriptor.base.message_timestamp
+        {
+            return Err(unexpected!("record_id does not match descriptor"));
+        }
+    }
+
+    // a retried write (same CID as the latest write) is acknowledged with its
+    // original status without being reprocessed or logged a second time,
+    // unless it provides the data the earlier write was stored without
+    let mut is_retry = false;
+    if let Some(latest_entry) = &latest_entry
+        && latest_entry.cid()? == write.cid()?
+    {
+        let stored_data = has_stored_data(owner, latest_entry, provider).await?;
+        let has_data = write.data_stream.is_some() || data_reader.is_some() || write.is_reference();
+        if stored_data || !has_data {
+            let code = if stored_data || !is_initial {
+                StatusCode::ACCEPTED
+            } else {
+                StatusCode::NO_CONTENT
+            };
+            return Ok(Reply {
+                status: Status {
+                    code: code.as_u16(),
+                    detail: None,
+                },
+                body: None,
+            });
+        }
+        is_retry = true;
+    }
+
+    // when enabled, reject writes from an author whose clock has gone backwards
+    if provider.strict_timestamps() && !is_retry {
+        write.verify_timestamp_order(&existing)?;
+    }
+
+    // when set, the update must be derived from the latest stored write
+    if let Some(expected) = &write.descriptor.expected_parent_cid {
+        let latest_cid = latest_entry.as_ref().map(Entry::cid).transpose()?;
+        if latest_cid.as_ref() != Some(expected) {
+            return Err(Error::Conflict("latest write does not match expected parent".to_string()));
+        }
     }
 
     // check message is the most recent AND most recent has not been deleted
-    if let Some(latest_entry) = &latest_entry {
+    if let Some(latest_entry) = &latest_entry
+        && !is_retry
+    {
         let write_ts = write.descriptor.base.message_timestamp.timestamp_micros();
         let latest_ts = latest_entry.descriptor().message_timestamp.timestamp_micros();
         if write_ts < latest_ts {
@@ -73,9 +165,20 @@ pub async fn handle(
     }
 
     // process data stream
-    let mut write = write;
-    if let Some(mut data) = write.data_stream.clone() {
+    let has_data = write.data_stream.is_some() || data_reader.is_some() || write.is_reference();
+
+    // data already stored for the record (e.g. by the latest write) is not
+    // removed should this write's data fail validation
+    let (record_id, data_cid) = (&write.record_id, &write.descriptor.data_cid);
+    let data_stored =
+        has_data && DataStore::open(provider, owner, record_id, data_cid).await?.is_some();
+
+    if let Some(reader) = data_reader {
+        write.stream_data(owner, reader, provider).await?;
+    } else if let Some(mut data) = write.data_stream.clone() {
         write.update_data(owner, &mut data, provider).await?;
+    } else if write.is_reference() {
+        write.reference_data(owner, provider).await?;
     } else if !is_initial {
         // no data AND NOT an initial write
         let Some(existing) = &latest_entry else {
@@ -84,8 +187,17 @@ pub async fn handle(
         write.clone_data(owner, existing, provider).await?;
     }
 
+    // validate JSON data against the protocol type's schema, when declared
+    if has_data && let Err(e) = write.verify_data_schema(owner, provider).await {
+        if write.encoded_data.is_none() && !data_stored {
+            let (record_id, data_cid) = (&write.record_id, &write.descriptor.data_cid);
+            DataStore::delete(provider, owner, record_id, data_cid).await?;
+        }
+        return Err(e);
+    }
+
     // response codes
-    let code = if write.data_stream.is_some() || !is_initial {
+    let code = if has_data || !is_initial {
         // queryable writes
         StatusCode::ACCEPTED
     } else {
@@ -95,42 +207,75 @@ pub async fn handle(
 
     // set `archive` flag is set when the intial write has no data
     // N.B. this is used to prevent malicious access to another record's data
-    let mut entry = Entry::from(&write);
+    let mut entry = Entry::from(&*write);
     entry.add_index("initial", (code == StatusCode::NO_CONTENT).to_string());
+    if let Some(date_published) = write.carried_date_published(&existing)? {
+        entry.set_index("datePublished", date_published.to_rfc3339_opts(Micros, true));
+    }
+    write.add_derived_tags(owner, &mut entry, provider).await?;
+    write.add_protocol_config(owner, &mut entry, provider).await?;
+
+    // an owner's pin is carried forward to the record's latest write
+    if latest_entry.is_some() && is_pinned(owner, &write.record_id, provider).await? {
+        entry.set_index("pinned", true.to_string());
+    }
 
     // save the message and log the event
     MessageStore::put(provider, owner, &entry).await?;
     EventLog::append(provider, owner, &entry).await?;
     EventStream::emit(provider, owner, &entry).await?;
 
-    // when this is an update, archive the initial write (and delete its data?)
-    if let Some(entry) = initial_entry {
-        let initial = Write::try_from(&entry)?;
+    // when this is an update, archive the write it supersedes: the initial
+    // write is always retained, while later writes are only retained (as
+    // versions of the record for reads as of an earlier time) up to the
+    // provider's limit
+    if let Some(entry) = latest_entry
+        && !is_retry
+    {
+        let earlier = Write::try_from(&entry)?;
 
-        // HACK: rebuild entry's indexes
-        let mut entry = Entry::from(&initial);
-        entry.add_index("initial", true.to_string());
+        // superseded writes other than the initial write, oldest first
+        let versions = &existing[1..];
+        let pruned = versions.len().saturating_sub(provider.retained_versions());
+        for entry in &versions[..pruned] {
+            MessageStore::delete(provider, owner, &entry.cid()?).await?;
+        }
 
-        MessageStore::put(provider, owner, &entry).await?;
-        EventLog::append(provider, owner, &entry).await?;
+        if earlier.is_initial()? || pruned < versions.len() {
+            // the protocol configuration that admitted the write is carried
+            // over rather than recomputed against the current configuration
+            let protocol_config = entry.indexes().get("protocolConfig").cloned();
+
+            // HACK: rebuild entry's indexes
+            let mut entry = Entry::from(&earlier);
+            entry.add_index("initial", true.to_string());
+            entry.add_index("supersededBy", write.cid()?);
+            if let Some(date_published) = earlier.carried_date_published(&existing)? {
+                entry.set_index("datePublished", date_published.to_rfc3339_opts(Micros, true));
+            }
+            earlier.add_derived_tags(owner, &mut entry, provider).await?;
+            if let Some(protocol_config) = protocol_config {
+                entry.add_index("protocolConfig", protocol_config);
+            }
+            MessageStore::put(provider, owner, &entry).await?;
+            if earlier.is_initial()? {
+                EventLog::append(provider, owner, &entry).await?;
+            }
+        }
+        if !earlier.is_initial()? {
+            EventLog::delete(provider, owner, &earlier.cid()?).await?;
+        }
 
-        if !initial.descriptor.data_cid.is_empty() && write.data_stream.is_some() {
-            DataStore::delete(provider, owner, &initial.record_id, &initial.descriptor.data_cid)
+        // data shared with the new write is retained
+        if !earlier.descriptor.data_cid.is_empty()
+            && has_data
+            && earlier.descriptor.data_cid != write.descriptor.data_cid
+        {
+            DataStore::delete(provider, owner, &earlier.record_id, &earlier.descriptor.data_cid)
                 .await?;
         }
     }
 
-    // delete any previous messages with the same `record_id` EXCEPT initial write
-    let mut deletable = VecDeque::from(existing);
-    let _ = deletable.pop_front(); // retain initial write (first entry)
-    for entry in deletable {
-        let write = Write::try_from(entry)?;
-        let cid = write.cid()?;
-        MessageStore::delete(provider, owner, &cid).await?;
-        DataStore::delete(provider, owner, &write.record_id, &write.descriptor.data_cid).await?;
-        EventLog::delete(provider, owner, &cid).await?;
-    }
-
     // when message is a grant revocation, delete grant-authorized messages
     // created after revocation
     if write.descriptor.protocol == Some(PROTOCOL_URI.to_string())
@@ -148,6 +293,32 @@ pub async fn handle(
     })
 }
 
+// Partition holding the replies to writes made using an idempotency key.
+const IDEMPOTENCY_PARTITION: &str = "IDEMPOTENCY";
+
+// The reply to a write made using an idempotency key.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct IdempotentReply {
+    message_cid: String,
+    message_timestamp: DateTime<Utc>,
+    status: Status,
+}
+
+impl IdempotentReply {
+    // Replay the recorded reply.
+    fn replay(self) -> Result<Reply<WriteReply>> {
+        if self.status.code >= StatusCode::BAD_REQUEST.as_u16() {
+            let detail = self.status.detail.unwrap_or_default();
+            return Err(Error::from_status(self.status.code, detail));
+        }
+        Ok(Reply {
+            status: self.status,
+            body: None,
+        })
+    }
+}
+
 /// The [`Write`] message expected by the handler.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -181,6 +352,37 @@ pub struct Write {
     /// The data stream of the record if the data associated with the record
     #[serde(skip)]
     pub data_stream: Option<Cursor<Vec<u8>>>,
+
+    /// A reader used to stream the record's data into the `DataStore`
+    /// without buffering it in memory.
+    #[serde(skip)]
+    pub data_reader: Option<DataReader>,
+}
+
+/// An asynchronous reader used to stream a [`Write`] message's data.
+///
+/// The reader can only be consumed once. Clones share the underlying reader,
+/// so consuming one clone consumes them all.
+#[derive(Clone, Default)]
+pub struct DataReader(Arc<Mutex<Option<Box<dyn AsyncRead + Send + Unpin>>>>);
+
+impl DataReader {
+    /// Create a new `DataReader` from the provided reader.
+    #[must_use]
+    pub fn new(reader: impl AsyncRead + Send + Unpin + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Some(Box::new(reader)))))
+    }
+
+    // Take the underlying reader, leaving `None` in its place.
+    pub(crate) fn take(&self) -> Option<Box<dyn AsyncRead + Send + Unpin>> {
+        self.0.lock().ok()?.take()
+    }
+}
+
+impl std::fmt::Debug for DataReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DataReader")
+    }
 }
 
 impl Message for Write {
@@ -200,6 +402,12 @@ impl Message for Write {
         Some(&self.authorization)
     }
 
+    // record data, streamed or carried inline as `encoded_data`, is governed
+    // by the `data_size` checks rather than the maximum message size
+    fn encoded_size(&self) -> Result<usize> {
+        Ok(endpoint::json_size(&self.descriptor)? + endpoint::json_size(&self.authorization)?)
+    }
+
     async fn handle(self, owner: &str, provider: &impl Provider) -> Result<Reply<Self::Reply>> {
         handle(owner, self, provider).await
     }
@@ -252,7 +460,10 @@ impl Write {
         );
         indexes
             .insert("published".to_string(), descriptor.published.unwrap_or_default().to_string());
-        indexes.insert("dataFormat".to_string(), descriptor.data_format.clone());
+        indexes.insert("revised".to_string(), (!self.is_initial().unwrap_or_default()).to_string());
+        if let Some(data_format) = &descriptor.data_format {
+            indexes.insert("dataFormat".to_string(), data_format.clone());
+        }
         indexes.insert("dataCid".to_string(), descriptor.data_cid.clone());
         indexes.insert("dataSize".to_string(), format!("{:0>10}", descriptor.data_size));
         indexes.insert(
@@ -274,7 +485,10 @@ impl Write {
         if let Some(parent_id) = &descriptor.parent_id {
             indexes.insert("parentId".to_string(), parent_id.clone());
         }
-        if let Some(date_published) = &descriptor.date_published {
+        if let Some(idempotency_key) = &descriptor.idempotency_key {
+            indexes.insert("idempotencyKey".to_string(), idempotency_key.clone());
+        }
+        if let Some(date_published) = self.date_published() {
             indexes
                 .insert("datePublished".to_string(), date_published.to_rfc3339_opts(Micros, true));
         }
@@ -286,27 +500,98 @@ impl Write {
                 indexes.insert("permissionGrantId".to_string(), grant_id.clone());
             }
         }
-        if let Some(attestation) = &self.attestation {
-            let attester = attestation.did().unwrap_or_default();
-            indexes.insert("attester".to_string(), attester);
+        // every attester is indexed, separated by spaces (DIDs cannot
+        // contain whitespace)
+        if let Ok(attesters) = self.attesters() {
+            if !attesters.is_empty() {
+                indexes.insert("attester".to_string(), attesters.join(" "));
+            }
         }
 
-        // TODO: convert all tags to String (not Value)
         // flatten tags for indexing
         if let Some(tags) = &self.descriptor.tags {
             for (k, v) in tags {
-                indexes.insert(format!("tag.{k}"), v.as_str().unwrap_or_default().to_string());
+                indexes.insert(format!("tag.{k}"), v.index_value());
             }
         }
 
         indexes
     }
 
+    // Add indexes for any tags derived by the protocol rule set. Derived tags
+    // are index-only so do not affect the message CID or data.
+    async fn add_derived_tags(
+        &self, owner: &str, entry: &mut Entry, store: &impl MessageStore,
+    ) -> Result<()> {
+        let (Some(protocol), Some(protocol_path)) =
+            (&self.descriptor.protocol, &self.descriptor.protocol_path)
+        else {
+            return Ok(());
+        };
+        let definition = protocols::definition(owner, protocol, store).await?;
+        let Some(rule_set) = protocols::rule_set(protocol_path, &definition.structure) else {
+            return Ok(());
+        };
+        let (Some(derive), Some(tags)) = (&rule_set.derive, &self.descriptor.tags) else {
+            return Ok(());
+        };
+
+        for (name, rule) in derive {
+            if let Some(value) = tags.get(&rule.from).and_then(Tag::as_str) {
+                entry.add_index(format!("tag.{name}"), rule.transform.apply(value));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Index the CID of the protocol configuration the write was validated
+    // against so records can be filtered by the protocol version that
+    // admitted them.
+    //
+    // Only new writes are indexed: when a write is superseded, the value
+    // indexed when it was admitted is carried over.
+    async fn add_protocol_config(
+        &self, owner: &str, entry: &mut Entry, store: &impl MessageStore,
+    ) -> Result<()> {
+        let Some(protocol) = &self.descriptor.protocol else {
+            return Ok(());
+        };
+        let protocol = utils::uri::clean(protocol)?;
+        if let Some(configs) = protocols::fetch_config(owner, Some(protocol), store).await?
+            && let Some(config) = configs.first()
+        {
+            entry.add_index("protocolConfig", config.cid()?);
+        }
+        Ok(())
+    }
+
     /// Add a data stream to the write message.
     pub fn with_stream(&mut self, data_stream: Cursor<Vec<u8>>) {
         self.data_stream = Some(data_stream);
     }
 
+    /// Add an asynchronous data reader to the write message.
+    pub fn with_reader(&mut self, data_reader: DataReader) {
+        self.data_reader = Some(data_reader);
+    }
+
+    /// The DIDs of the record's attesters, in signing order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an attester's DID cannot be determined from their
+    /// signature.
+    pub fn attesters(&self) -> Result<Vec<String>> {
+        let Some(attestation) = &self.attestation else {
+            return Ok(vec![]);
+        };
+        attestation_signatures(attestation)
+            .iter()
+            .map(|jws| jws.did().map_err(|e| unexpected!("issue getting attester's DID: {e}")))
+            .collect()
+    }
+
     /// Computes the deterministic Entry ID (Record ID) of the message.
     ///
     /// # Errors
@@ -325,7 +610,7 @@ impl Write {
         })
     }
 
-    async fn authorize(&self, owner: &str, store: &impl MessageStore) -> Result<()> {
+    async fn authorize(&self, owner: &str, provider: &impl Provider) -> Result<()> {
         let authzn = &self.authorization;
         let record_owner = authzn.owner()?;
 
@@ -340,7 +625,7 @@ impl Write {
         if let Some(delegated_grant) = &authzn.author_delegated_grant {
             let signer = authzn.signer()?;
             let grant = delegated_grant.to_grant()?;
-            grant.permit_write(&author, &signer, self, store).await?;
+            grant.permit_write(&author, &signer, self, provider).await?;
         }
 
         // authorize owner delegate
@@ -350,7 +635,7 @@ impl Write {
             };
             let signer = authzn.owner_signer()?;
             let grant = delegated_grant.to_grant()?;
-            grant.permit_write(owner, &signer, self, store).await?;
+            grant.permit_write(owner, &signer, self, provider).await?;
         }
 
         // when record owner is set, we can directly grant access
@@ -368,14 +653,14 @@ impl Write {
         let decoded = Base64UrlUnpadded::decode_vec(&authzn.signature.payload)?;
         let payload: SignaturePayload = serde_json::from_slice(&decoded)?;
         if let Some(permission_grant_id) = &payload.base.permission_grant_id {
-            let grant = grants::fetch_grant(owner, permission_grant_id, store).await?;
-            return grant.permit_write(owner, &author, self, store).await;
+            let grant = grants::fetch_grant(owner, permission_grant_id, provider).await?;
+            return grant.permit_write(owner, &author, self, provider).await;
         }
 
         // protocol-specific authorization
         if let Some(protocol) = &self.descriptor.protocol {
             let protocol = protocol::Authorizer::new(protocol).context_id(self.context_id.as_ref());
-            return protocol.permit_write(owner, self, store).await;
+            return protocol.permit_write(owner, self, provider).await;
         }
 
         Err(forbidden!("message failed authorization"))
@@ -401,6 +686,8 @@ impl Write {
         // verify integrity of messages with protocol
         if self.descriptor.protocol.is_some() {
             self.verify(owner, provider).await?;
+        } else if self.descriptor.data_format.is_none() {
+            return Err(unexpected!("missing data format"));
         }
 
         let decoded = Base64UrlUnpadded::decode_vec(&self.authorization.signature.payload)
@@ -428,11 +715,51 @@ impl Write {
             }
         }
 
+        // verify every attester signed this message's descriptor
+        if let Some(attestation) = &self.attestation {
+            for jws in attestation_signatures(attestation) {
+                authorization::verify_jws(&jws, provider.clone())
+                    .await
+                    .map_err(|e| unexpected!("invalid attestation signature: {e}"))?;
+            }
+
+            let decoded = Base64UrlUnpadded::decode_vec(&attestation.payload)
+                .map_err(|e| unexpected!("issue decoding attestation: {e}"))?;
+            let payload: Attestation = serde_json::from_slice(&decoded)
+                .map_err(|e| unexpected!("issue deserializing attestation: {e}"))?;
+            if payload.descriptor_cid != cid::from_value(&self.descriptor)? {
+                return Err(unexpected!("attestation does not match message descriptor"));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Verify the message timestamp is not earlier than any existing message
+    // for the record by the same author.
+    fn verify_timestamp_order(&self, existing: &[Entry]) -> Result<()> {
+        let author = self.authorization.author()?;
+        let timestamp = self.descriptor.base.message_timestamp;
+
+        for entry in existing {
+            let entry_author = match &entry.message {
+                EntryType::Write(write) => write.authorization.author()?,
+                EntryType::Delete(delete) => delete.authorization.author()?,
+                EntryType::Configure(_) => continue,
+            };
+            if entry_author == author && timestamp < entry.descriptor().message_timestamp {
+                return Err(Error::BadRequest(
+                    "message timestamp is earlier than the author's most recent message"
+                        .to_string(),
+                ));
+            }
+        }
+
         Ok(())
     }
 
     // Verify immutable properties of two records are identical.
-    fn verify_immutable(&self, other: &Self) -> Result<()> {
+    pub(crate) fn verify_immutable(&self, other: &Self) -> Result<()> {
         let self_desc = &self.descriptor;
         let other_desc = &other.descriptor;
 
@@ -452,6 +779,120 @@ impl Write {
         Ok(())
     }
 
+    // The date the record was published. A record published without an
+    // explicit `date_published` (the descriptor is signed, so cannot be
+    // amended) is published as of the write's `message_timestamp`.
+    pub(crate) fn date_published(&self) -> Option<DateTime<Utc>> {
+        let descriptor = &self.descriptor;
+        descriptor.date_published.or_else(|| {
+            descriptor.published.unwrap_or_default().then_some(descriptor.base.message_timestamp)
+        })
+    }
+
+    // The date the record was published, taking account of the record's
+    // `existing` writes. An update leaving the record published without an
+    // explicit `date_published` keeps the date of the earlier write that
+    // published it, rather than moving the date to the time of the update.
+    fn carried_date_published(&self, existing: &[Entry]) -> Result<Option<DateTime<Utc>>> {
+        let Some(mut date_published) = self.date_published() else {
+            return Ok(None);
+        };
+        if self.descriptor.date_published.is_some() {
+            return Ok(Some(date_published));
+        }
+
+        // walk back through the unbroken run of published writes
+        let cid = self.cid()?;
+        let timestamp = self.descriptor.base.message_timestamp;
+        for entry in existing.iter().rev() {
+            if entry.descriptor().message_timestamp > timestamp || entry.cid()? == cid {
+                continue;
+            }
+            let Ok(earlier) = Self::try_from(entry) else {
+                break;
+            };
+            if !earlier.descriptor.published.unwrap_or_default() {
+                break;
+            }
+            if let Some(explicit) = earlier.descriptor.date_published {
+                date_published = explicit;
+                break;
+            }
+            date_published = earlier.descriptor.base.message_timestamp;
+        }
+
+        Ok(Some(date_published))
+    }
+
+    // The reply to an earlier write by the same author, within the provider's
+    // idempotency window, that used the same idempotency key. The window is
+    // measured on the provider's clock so a retry cannot extend it by
+    // backdating its own timestamp. Writes with the same CID are left to the
+    // handler's retry check.
+    async fn idempotent_reply(
+        &self, owner: &str, provider: &impl Provider,
+    ) -> Result<Option<IdempotentReply>> {
+        let Some(cid) = self.idempotency_cid(owner)? else {
+            return Ok(None);
+        };
+        let Some(bytes) = BlockStore::get(provider, owner, IDEMPOTENCY_PARTITION, &cid).await?
+        else {
+            return Ok(None);
+        };
+        let earlier: IdempotentReply = block::decode(&bytes)?;
+
+        if earlier.message_cid == self.cid()?
+            || provider.clock().now() - earlier.message_timestamp > provider.idempotency_window()
+        {
+            return Ok(None);
+        }
+        Ok(Some(earlier))
+    }
+
+    // Record the reply to a write using an idempotency key so it can be
+    // replayed for retries. Server errors are not recorded, leaving the
+    // write free to be retried.
+    async fn record_reply(
+        &self, owner: &str, reply: &Result<Reply<WriteReply>>, provider: &impl Provider,
+    ) -> Result<()> {
+        let Some(cid) = self.idempotency_cid(owner)? else {
+            return Ok(());
+        };
+        let status = match reply {
+            Ok(reply) => reply.status.clone(),
+            Err(e) if e.code().is_server_error() => return Ok(()),
+            Err(e) => Status {
+                code: e.code().as_u16(),
+                detail: Some(e.detail().to_string()),
+            },
+        };
+
+        let recorded = IdempotentReply {
+            message_cid: self.cid()?,
+            message_timestamp: self.descriptor.base.message_timestamp,
+            status,
+        };
+        let bytes = block::encode(&recorded)?;
+        BlockStore::put(provider, owner, IDEMPOTENCY_PARTITION, &cid, &bytes).await?;
+        Ok(())
+    }
+
+    // The CID of the block recording the reply to the author's write using
+    // the write's idempotency key, if any.
+    fn idempotency_cid(&self, owner: &str) -> Result<Option<String>> {
+        let Some(key) = &self.descriptor.idempotency_key else {
+            return Ok(None);
+        };
+        let author = self.authorization.author()?;
+        Ok(Some(cid::from_value(&format!("{owner}-{author}-{key}-idempotency"))?))
+    }
+
+    // Determine whether the write references data already stored for another
+    // record rather than providing it.
+    fn is_reference(&self) -> bool {
+        self.descriptor.data_reference == Some(true)
+    }
+
     // Determine whether the record is the initial write.
     pub(crate) fn is_initial(&self) -> Result<bool> {
         let entry_id = self.entry_id(&self.authorization.author()?)?;
@@ -459,10 +900,10 @@ impl Write {
     }
 
     async fn update_data(
-        &mut self, owner: &str, stream: &mut Cursor<Vec<u8>>, store: &impl DataStore,
+        &mut self, owner: &str, stream: &mut Cursor<Vec<u8>>, provider: &impl Provider,
     ) -> Result<()> {
         // when data is below the threshold, store it within MessageStore
-        if self.descriptor.data_size <= data::MAX_ENCODED_SIZE {
+        if self.descriptor.data_size <= provider.max_encoded_size() {
             // verify data integrity
             let (data_cid, data_size) = cid::from_reader(stream.clone())?;
             if self.descriptor.data_cid != data_cid {
@@ -483,8 +924,9 @@ impl Write {
             }
         } else {
             // store data in DataStore
+            let stream = AllowStdIo::new(stream);
             let (data_cid, data_size) =
-                DataStore::put(store, owner, &self.record_id, &self.descriptor.data_cid, stream)
+                DataStore::put(provider, owner, &self.record_id, &self.descriptor.data_cid, stream)
                     .await?;
 
             // verify integrity of stored data
@@ -499,6 +941,41 @@ impl Write {
         Ok(())
     }
 
+    // Stream data from an asynchronous reader, computing its CID and size
+    // incrementally as it is chunked into the `DataStore`.
+    async fn stream_data(
+        &mut self, owner: &str, reader: Box<dyn AsyncRead + Send + Unpin>, provider: &impl Provider,
+    ) -> Result<()> {
+        // small data is stored with the message
+        let max_encoded_size = provider.max_encoded_size();
+        if self.descriptor.data_size <= max_encoded_size {
+            let mut data_bytes = Vec::new();
+            reader
+                .take(max_encoded_size as u64 + 1)
+                .read_to_end(&mut data_bytes)
+                .await
+                .map_err(|e| unexpected!("issue reading data: {e}"))?;
+            return self.update_data(owner, &mut Cursor::new(data_bytes), provider).await;
+        }
+
+        // read errors are returned as `BadRequest` by the default `DataStore`
+        let (data_cid, data_size) =
+            DataStore::put(provider, owner, &self.record_id, &self.descriptor.data_cid, reader)
+                .await
+                .map_err(|e| e.downcast::<Error>().unwrap_or_else(Error::from))?;
+
+        // verify integrity of stored data
+        if self.descriptor.data_cid != data_cid || self.descriptor.data_size != data_size {
+            DataStore::delete(provider, owner, &self.record_id, &self.descriptor.data_cid).await?;
+            if self.descriptor.data_cid != data_cid {
+                return Err(unexpected!("actual data CID does not match message `data_cid`"));
+            }
+            return Err(unexpected!("actual data size does not match message `data_size`"));
+        }
+
+        Ok(())
+    }
+
     // Write message has no data and is not an 'initial write':
     //  1. verify the new message's data integrity
     //  2. copy stored `encoded_data` to the new  message.
@@ -516,8 +993,9 @@ impl Write {
             return Err(unexpected!("data size does not match descriptor `data_size`"));
         }
 
-        // if bigger than encoding threshold, ensure data exists for this record
-        if latest.descriptor.data_size > data::MAX_ENCODED_SIZE {
+        // data not encoded with the message was stored in the `DataStore`,
+        // regardless of the current encoding threshold
+        if latest.encoded_data.is_none() {
             let result =
                 DataStore::get(store, owner, &self.record_id, &self.descriptor.data_cid).await?;
             if result.is_none() {
@@ -527,14 +1005,59 @@ impl Write {
         }
 
         // otherwise, copy `encoded_data` to the new message
-        if latest.encoded_data.is_none() {
-            return Err(unexpected!("referenced data does not exist"));
-        }
         self.encoded_data = latest.encoded_data;
 
         Ok(())
     }
 
+    // Share data already stored for another record with the same `data_cid`
+    // and `data_size`. Only data the author has written (or any data, for the
+    // owner) can be referenced.
+    async fn reference_data(&mut self, owner: &str, provider: &impl Provider) -> Result<()> {
+        let query = RecordsQueryBuilder::new()
+            .add_filter(RecordsFilter::new().data_cid(&self.descriptor.data_cid))
+            .build();
+        let (entries, _) = MessageStore::query(provider, owner, &query).await?;
+
+        let author = self.authorization.author()?;
+        let mut source = None;
+        for entry in &entries {
+            let write = Self::try_from(entry)?;
+            if write.record_id != self.record_id
+                && write.descriptor.data_size == self.descriptor.data_size
+                && (author == owner || write.authorization.author()? == author)
+            {
+                source = Some(write);
+                break;
+            }
+        }
+        let Some(source) = source else {
+            return Err(unexpected!("referenced data not found"));
+        };
+
+        // small data is stored with the message
+        if source.encoded_data.is_some() {
+            self.encoded_data = source.encoded_data;
+            return Ok(());
+        }
+
+        // blocks already stored are shared rather than stored again
+        let Some(reader) =
+            DataStore::open(provider, owner, &source.record_id, &source.descriptor.data_cid)
+                .await?
+        else {
+            return Err(unexpected!("referenced data not found"));
+        };
+        let (data_cid, data_size) =
+            DataStore::put(provider, owner, &self.record_id, &self.descriptor.data_cid, reader)
+                .await?;
+        if self.descriptor.data_cid != data_cid || self.descriptor.data_size != data_size {
+            return Err(unexpected!("referenced data not found"));
+        }
+
+        Ok(())
+    }
+
     // Delete any grant-authorized messages created after grant revocation.
     async fn revoke_grants(&self, owner: &str, provider: &impl Provider) -> Result<()> {
         // verify revocation message matches grant being revoked
@@ -702,8 +1225,21 @@ pub struct SignaturePayload {
     pub encryption_cid: Option<String>,
 }
 
+// Split an attestation into a JWS per attester signature.
+fn attestation_signatures(attestation: &Jws) -> Vec<Jws> {
+    attestation
+        .signatures
+        .iter()
+        .map(|signature| {
+            let mut jws = attestation.clone();
+            jws.signatures = vec![signature.clone()];
+            jws
+        })
+        .collect()
+}
+
 /// Attestation payload.
-#[derive(Default, Serialize)]
+#[derive(Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Attestation {
     /// The attestation's descriptor CID.
@@ -736,8 +1272,14 @@ pub struct DelegatedGrant {
 
 impl DelegatedGrant {
     /// Convert [`DelegatedGrant`] to `permissions::Grant`.
+    ///
+    /// The grant must have been issued as a delegated grant.
     pub(crate) fn to_grant(&self) -> Result<Grant> {
-        self.try_into()
+        let grant: Grant = self.try_into()?;
+        if grant.data.delegated != Some(true) {
+            return Err(forbidden!("grant is not a delegated grant"));
+        }
+        Ok(grant)
     }
 }
 
@@ -779,7 +1321,7 @@ pub struct WriteDescriptor {
 
     /// Tags associated with the record
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tags: Option<HashMap<String, Tag>>,
+    pub tags: Option<BTreeMap<String, Tag>>,
 
     /// The CID of the record's parent (if exists).
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -791,8 +1333,10 @@ pub struct WriteDescriptor {
     /// The record's size in bytes.
     pub data_size: usize,
 
-    /// The record's MIME type. For example, `application/json`.
-    pub data_format: String,
+    /// The record's MIME type. For example, `application/json`. May only be
+    /// omitted for protocol records whose type does not declare `dataFormats`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_format: Option<String>,
 
     /// The datatime the record was created.
     #[serde(serialize_with = "rfc3339_micros")]
@@ -806,10 +1350,32 @@ pub struct WriteDescriptor {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(serialize_with = "rfc3339_micros_opt")]
     pub date_published: Option<DateTime<Utc>>,
+
+    /// The CID of the write this update was derived from. When set, the
+    /// update is rejected unless it is the record's latest stored write.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_parent_cid: Option<String>,
+
+    /// When set, the write is rejected with a conflict if the record already
+    /// exists rather than being treated as an update.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub create_only: Option<bool>,
+
+    /// An application-level key identifying the write. A later write by the
+    /// same author with the same key, within the provider's idempotency
+    /// window, is treated as a retry: it is answered with the earlier
+    /// write's reply and does not create a new record.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+
+    /// When set, the record's data is not provided, but references data (by
+    /// `data_cid` and `data_size`) already stored for another record.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_reference: Option<bool>,
 }
 
 /// Tag value types.
-#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 // #[serde(rename_all = "camelCase")]
 #[serde(untagged)]
 pub enum Tag {
@@ -823,6 +1389,15 @@ pub enum Tag {
     /// Number tag value.
     Number(u64),
 
+    /// Signed integer tag value. Non-negative integers deserialize as
+    /// [`Tag::Number`].
+    Integer(i64),
+
+    /// Floating point tag value. Whole numbers serialize as integers so
+    /// deserialize as [`Tag::Number`] or [`Tag::Integer`].
+    #[serde(serialize_with = "float")]
+    Float(f64),
+
     /// Boolean tag value.
     Boolean(bool),
 }
@@ -846,6 +1421,28 @@ impl Tag {
         }
     }
 
+    /// Attempt to convert the tag value to an i64.
+    #[must_use]
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Number(n) => i64::try_from(*n).ok(),
+            Self::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Attempt to convert the tag value to an f64.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub const fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Number(n) => Some(*n as f64),
+            Self::Integer(n) => Some(*n as f64),
+            Self::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+
     /// Attempt to convert the tag value to a bool.
     #[must_use]
     pub const fn as_bool(&self) -> Option<bool> {
@@ -854,6 +1451,70 @@ impl Tag {
             _ => None,
         }
     }
+
+    /// The value used to index the tag. Fractional floats are encoded so
+    /// their index values sort in numeric order; all other tags, including
+    /// whole number floats, are indexed as their string value.
+    pub(crate) fn index_value(&self) -> String {
+        let Self::Float(n) = self else {
+            return self.to_string();
+        };
+        if let Some(i) = whole_number(*n) {
+            return i.to_string();
+        }
+
+        // flip the sign bit of positive numbers and every bit of negative
+        // numbers so the bits order as unsigned integers (adding 0.0
+        // normalizes -0.0)
+        let bits = (n + 0.0).to_bits();
+        let ordered = if bits & SIGN_BIT == 0 { bits | SIGN_BIT } else { !bits };
+        format!("{FLOAT_PREFIX}{ordered:016x}")
+    }
+
+    /// Decode the index value of a fractional float tag. Returns `None` for
+    /// the index values of other tag types.
+    pub(crate) fn decode_float(value: &str) -> Option<f64> {
+        let hex = value.strip_prefix(FLOAT_PREFIX).filter(|hex| hex.len() == 16)?;
+        let ordered = u64::from_str_radix(hex, 16).ok()?;
+        let bits = if ordered & SIGN_BIT == 0 { !ordered } else { ordered & !SIGN_BIT };
+        Some(f64::from_bits(bits))
+    }
+
+    /// The numeric value of a tag's index value, if it was indexed from a
+    /// numeric tag.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn decode_number(value: &str) -> Option<f64> {
+        if let Some(n) = Self::decode_float(value) {
+            return Some(n);
+        }
+        let digits = value.strip_prefix('-').unwrap_or(value);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        value
+            .parse::<i64>()
+            .map(|n| n as f64)
+            .or_else(|_| value.parse::<u64>().map(|n| n as f64))
+            .ok()
+    }
+}
+
+// Marks the index value of a fractional float tag, distinguishing it from
+// string tags.
+const FLOAT_PREFIX: char = '\u{1}';
+const SIGN_BIT: u64 = 1 << 63;
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => Ok(()),
+            Self::String(s) => write!(f, "{s}"),
+            Self::Number(n) => write!(f, "{n}"),
+            Self::Integer(n) => write!(f, "{n}"),
+            Self::Float(n) => write!(f, "{n}"),
+            Self::Boolean(b) => write!(f, "{b}"),
+        }
+    }
 }
 
 // Fetch previous entries for this record, ordered from earliest to latest.
@@ -892,3 +1553,14 @@ pub async fn initial_write(
 fn earliest_and_latest(entries: &[Entry]) -> (Option<Entry>, Option<Entry>) {
     entries.first().map_or((None, None), |first| (Some(first.clone()), entries.last().cloned()))
 }
+
+// Whether a stored write's data has been received, either encoded in the
+// message or held in the `DataStore`.
+async fn has_stored_data(owner: &str, entry: &Entry, store: &impl DataStore) -> Result<bool> {
+    let write = Write::try_from(entry)?;
+    if write.encoded_data.is_some() {
+        return Ok(true);
+    }
+    let data = DataStore::get(store, owner, &write.record_id, &write.descriptor.data_cid).await?;
+    Ok(data.is_some())
+}
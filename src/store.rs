@@ -12,10 +12,12 @@
 //! [`Query`] wraps store-specific query options for querying the underlying
 //! store.
 
-mod block;
+pub(crate) mod block;
 pub mod data;
 pub(crate) mod event_log;
 pub(crate) mod index;
+#[cfg(test)]
+pub(crate) mod memory;
 pub(crate) mod message;
 pub(crate) mod task;
 
@@ -27,8 +29,9 @@ use serde::{Deserialize, Serialize};
 pub use self::data::MAX_ENCODED_SIZE;
 use crate::endpoint::Message;
 use crate::protocols::Configure;
-pub use crate::records::Sort;
-use crate::records::{self, Delete, RecordsFilter, TagFilter, Write};
+use crate::records::{self, Delete, RecordsFilter, Tag, TagFilter, TextFilter, Write};
+pub use crate::records::{GroupBy, Sort, SortKey};
+use crate::utils::cid;
 use crate::{
     DateRange, Descriptor, Interface, Method, Range, Result, messages, protocols, unexpected,
 };
@@ -55,6 +58,11 @@ pub struct Entry {
     /// in the underlying store.
     #[serde(skip)]
     indexes: HashMap<String, String>,
+
+    /// Whether the owner has pinned the record. Stored with the message so
+    /// the `pinned` index can be rebuilt from stored messages.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pinned: bool,
 }
 
 impl Entry {
@@ -67,12 +75,32 @@ impl Entry {
         self.indexes.insert(key, value.into());
     }
 
+    // Sets an index item, replacing any existing value.
+    pub(crate) fn set_index(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.indexes.insert(key.into(), value.into());
+    }
+
     /// Indexes for this entry.
     #[must_use]
     pub const fn indexes(&self) -> &HashMap<String, String> {
         &self.indexes
     }
 
+    /// Whether the owner has pinned the record (see [`records::pin`]).
+    #[must_use]
+    pub fn is_pinned(&self) -> bool {
+        self.pinned || self.indexes.get("pinned").is_some_and(|pinned| pinned == "true")
+    }
+
+    /// The watermark assigned to the entry when it was logged as an event.
+    /// Only set for entries returned from the [`EventLog`].
+    ///
+    /// [`EventLog`]: crate::provider::EventLog
+    #[must_use]
+    pub fn watermark(&self) -> Option<u64> {
+        self.indexes.get("watermark").and_then(|watermark| watermark.parse().ok())
+    }
+
     /// The message's CID.
     ///
     /// # Errors
@@ -87,6 +115,28 @@ impl Entry {
         }
     }
 
+    /// The CID the entry is logged under as an event.
+    ///
+    /// This is the message's CID, except for the events logged for each
+    /// descendant purged by a pruning `RecordsDelete`. These carry the
+    /// ancestor's delete message and are distinguished by the descendant's
+    /// record ID.
+    ///
+    /// # Errors
+    ///
+    /// The underlying CID computation is not infallible and may fail if the
+    /// message cannot be serialized to CBOR.
+    pub fn event_cid(&self) -> Result<String> {
+        let message_cid = self.cid()?;
+        if let EntryType::Delete(delete) = &self.message
+            && let Some(record_id) = self.indexes.get("recordId")
+            && *record_id != delete.descriptor.record_id
+        {
+            return cid::from_value(&(message_cid, record_id));
+        }
+        Ok(message_cid)
+    }
+
     /// The message's CID.
     #[must_use]
     pub fn descriptor(&self) -> &Descriptor {
@@ -130,6 +180,7 @@ impl From<&Write> for Entry {
         Self {
             message: EntryType::Write(write.clone()),
             indexes: write.build_indexes(),
+            pinned: false,
         }
     }
 }
@@ -139,6 +190,7 @@ impl From<&Delete> for Entry {
         Self {
             message: EntryType::Delete(delete.clone()),
             indexes: delete.build_indexes(),
+            pinned: false,
         }
     }
 }
@@ -148,6 +200,7 @@ impl From<&Configure> for Entry {
         Self {
             message: EntryType::Configure(configure.clone()),
             indexes: configure.build_indexes(),
+            pinned: false,
         }
     }
 }
@@ -189,11 +242,40 @@ pub struct Query {
     /// Sort options.
     pub(crate) sort: Sort,
 
+    /// Composite sort keys. When set, these take precedence over `sort`.
+    pub(crate) sort_keys: Vec<SortKey>,
+
     /// Pagination options.
     pub(crate) pagination: Option<Pagination>,
+
+    /// Only match events logged after this watermark. Only used when
+    /// querying the [`EventLog`].
+    ///
+    /// [`EventLog`]: crate::provider::EventLog
+    pub(crate) since: Option<u64>,
 }
 
 impl Query {
+    /// Only match events logged after the specified watermark (see
+    /// [`EventLog::watermark`]).
+    ///
+    /// [`EventLog::watermark`]: crate::provider::EventLog::watermark
+    #[must_use]
+    pub const fn since(mut self, watermark: u64) -> Self {
+        self.since = Some(watermark);
+        self
+    }
+
+    /// The text search filter used to rank matching items, if any.
+    pub(crate) fn text(&self) -> Option<&TextFilter> {
+        self.match_sets.iter().flat_map(|match_set| &match_set.inner).find_map(|matcher| {
+            match &matcher.value {
+                MatchOn::Text(text) => Some(text),
+                _ => None,
+            }
+        })
+    }
+
     /// Determine whether the query can be expressed in a concise form.
     #[must_use]
     pub(crate) fn is_concise(&self) -> bool {
@@ -212,43 +294,95 @@ impl Query {
 
         true
     }
+
+    /// Determine whether an item's indexed fields satisfy the query, as they
+    /// would when matched against the index.
+    ///
+    /// # Errors
+    ///
+    /// See [`Matcher::is_match`].
+    pub(crate) fn is_match(&self, fields: &HashMap<String, String>) -> Result<bool> {
+        if self.match_sets.is_empty() {
+            return Ok(true);
+        }
+
+        // match sets are 'OR-ed' together
+        'next_set: for match_set in &self.match_sets {
+            if let Some((field, value)) = &match_set.index
+                && !fields.get(field).is_some_and(|indexed| indexed.starts_with(value.as_str()))
+            {
+                continue;
+            }
+            // a set of matchers are 'AND-ed' together
+            for matcher in &match_set.inner {
+                if !matcher.is_match_fields(fields)? {
+                    continue 'next_set;
+                }
+            }
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
 }
 
 impl From<records::Query> for Query {
     fn from(query: records::Query) -> Self {
-        let mut match_set = MatchSet::from(&query.descriptor.filter);
-
-        match_set.inner.insert(
-            0,
-            Matcher {
-                field: "method".to_string(),
-                value: MatchOn::Equal(Method::Write.to_string()),
-            },
-        );
-        match_set.inner.push(Matcher {
-            field: "initial".to_string(),
-            value: MatchOn::Equal(false.to_string()),
-        });
+        let mut match_sets = MatchSet::any_of(&query.descriptor.filter);
+
+        for match_set in &mut match_sets {
+            match_set.inner.insert(
+                0,
+                Matcher {
+                    field: "method".to_string(),
+                    value: MatchOn::Equal(Method::Write.to_string()),
+                },
+            );
+            match_set.inner.push(Matcher {
+                field: "initial".to_string(),
+                value: MatchOn::Equal(false.to_string()),
+            });
+        }
 
         Self {
-            match_sets: vec![match_set],
+            match_sets,
             sort: query.descriptor.date_sort.unwrap_or_default(),
+            sort_keys: query.descriptor.sort.unwrap_or_default(),
             pagination: query.descriptor.pagination,
+            since: None,
         }
     }
 }
 
 impl From<records::Read> for Query {
     fn from(read: records::Read) -> Self {
-        let mut match_set = MatchSet::from(&read.descriptor.filter);
+        let mut match_sets = MatchSet::any_of(&read.descriptor.filter);
 
-        match_set.inner.push(Matcher {
-            field: "initial".to_string(),
-            value: MatchOn::Equal(false.to_string()),
-        });
+        for match_set in &mut match_sets {
+            match_set.inner.push(Matcher {
+                field: "initial".to_string(),
+                value: MatchOn::Equal(false.to_string()),
+            });
+        }
+
+        // return the first of the (sorted) `RecordsWrite` messages
+        if let Some(sort) = read.descriptor.date_sort {
+            for match_set in &mut match_sets {
+                match_set.inner.push(Matcher {
+                    field: "method".to_string(),
+                    value: MatchOn::Equal(Method::Write.to_string()),
+                });
+            }
+            return Self {
+                match_sets,
+                sort,
+                pagination: Some(Pagination::new().limit(1)),
+                ..Self::default()
+            };
+        }
 
         Self {
-            match_sets: vec![match_set],
+            match_sets,
             ..Self::default()
         }
     }
@@ -279,6 +413,12 @@ impl From<messages::Query> for Query {
                     value: MatchOn::DateRange(message_timestamp.clone()),
                 });
             }
+            if let Some(author) = &filter.author {
+                match_set.inner.push(Matcher {
+                    field: "author".to_string(),
+                    value: MatchOn::Equal(author.clone()),
+                });
+            }
 
             // match on `protocol` OR `tag.protocol`
             if let Some(protocol) = &filter.protocol {
@@ -299,10 +439,25 @@ impl From<messages::Query> for Query {
             match_sets.push(match_set);
         }
 
+        // events are ordered by watermark, newest first when `latest` is set
+        let descriptor = query.descriptor;
+        let sort_key = if descriptor.latest.unwrap_or_default() {
+            SortKey::descending("watermark")
+        } else {
+            SortKey::ascending("watermark")
+        };
+        let pagination =
+            (descriptor.limit.is_some() || descriptor.cursor.is_some()).then(|| Pagination {
+                limit: descriptor.limit,
+                cursor: descriptor.cursor,
+            });
+
         Self {
             match_sets,
             sort: Sort::TimestampAsc,
-            pagination: None,
+            sort_keys: vec![sort_key],
+            pagination,
+            since: descriptor.since,
         }
     }
 }
@@ -339,21 +494,72 @@ impl Matcher {
     pub(crate) fn is_match(&self, value: &str) -> Result<bool> {
         let matched = match &self.value {
             MatchOn::Equal(filter_val) => value == filter_val,
+            MatchOn::NotEqual(filter_val) => value != filter_val,
             MatchOn::StartsWith(filter_val) => value.starts_with(filter_val),
             MatchOn::OneOf(values) => values.contains(&value.to_string()),
+            MatchOn::Includes(filter_val) => value.split(' ').any(|v| v == filter_val),
             MatchOn::Range(range) => {
-                let int_val: usize =
-                    value.parse().map_err(|e| unexpected!("issue parsing usize: {e}"))?;
+                // non-numeric values (e.g. string tags) are outside any range
+                let Ok(int_val) = value.parse::<usize>() else {
+                    return Ok(false);
+                };
                 range.contains(&int_val)
             }
+            MatchOn::FloatRange(range) => {
+                Tag::decode_number(value).is_some_and(|n| range.contains(&n))
+            }
             MatchOn::DateRange(range) => {
                 let date_val = DateTime::parse_from_rfc3339(value)
                     .map_err(|e| unexpected!("issue parsing date: {e}"))?;
                 range.contains(&date_val.into())
             }
+            MatchOn::Text(text) => {
+                let value = value.to_lowercase();
+                let terms = text.terms();
+                !terms.is_empty() && terms.iter().all(|term| value.contains(term.as_str()))
+            }
         };
         Ok(matched)
     }
+
+    /// Check if an item's indexed fields match the filter value. Text
+    /// matchers search across fields, other matchers compare their own
+    /// field and fail when it is missing.
+    ///
+    /// # Errors
+    ///
+    /// See [`Matcher::is_match`].
+    pub(crate) fn is_match_fields(&self, fields: &HashMap<String, String>) -> Result<bool> {
+        if let MatchOn::Text(text) = &self.value {
+            return Ok(text.hits(fields).is_some());
+        }
+        let Some(value) = fields.get(&self.field) else {
+            return Ok(matches!(self.value, MatchOn::NotEqual(_)));
+        };
+        self.is_match(value)
+    }
+}
+
+impl MatchSet {
+    /// Expand a filter into 'OR-ed' match sets, one for each of its `any_of`
+    /// sub-filters, combined with the filter's own conditions.
+    pub(crate) fn any_of(filter: &RecordsFilter) -> Vec<Self> {
+        let base = Self::from(filter);
+        let Some(any_of) = &filter.any_of else {
+            return vec![base];
+        };
+
+        any_of
+            .iter()
+            .map(|sub_filter| {
+                let sub_set = Self::from(sub_filter);
+                let mut match_set = base.clone();
+                match_set.inner.extend(sub_set.inner);
+                match_set.index = base.index.clone().or(sub_set.index);
+                match_set
+            })
+            .collect()
+    }
 }
 
 /// The [`MatchOn`] enum is used to specify the matching strategy to be
@@ -363,17 +569,30 @@ pub enum MatchOn {
     /// The match must be equal.
     Equal(String),
 
+    /// The match must not be equal. Items without the field also match.
+    NotEqual(String),
+
     /// The match must start with the specified value.
     StartsWith(String),
 
     /// The match must be with at least one of the items specified.
     OneOf(Vec<String>),
 
+    /// One of the field's space-separated values must be equal.
+    Includes(String),
+
     /// The match must be in the specified range.
     Range(Range<usize>),
 
+    /// The match must be a numeric tag, including floats, in the specified
+    /// range.
+    FloatRange(Range<f64>),
+
     /// The match must be in the specified date range.
     DateRange(DateRange),
+
+    /// Every search term must be found in one of the searched fields.
+    Text(TextFilter),
 }
 
 impl From<&RecordsFilter> for MatchSet {
@@ -396,12 +615,36 @@ impl From<&RecordsFilter> for MatchSet {
                 value: MatchOn::Equal(record_id.to_string()),
             });
         }
+        if let Some(record_ids) = &filter.record_ids {
+            match_set.inner.push(Matcher {
+                field: "recordId".to_string(),
+                value: MatchOn::OneOf(record_ids.clone()),
+            });
+        }
         if let Some(published) = &filter.published {
             match_set.inner.push(Matcher {
                 field: "published".to_string(),
                 value: MatchOn::Equal(published.to_string()),
             });
         }
+        if let Some(pinned) = &filter.pinned {
+            // unpinned records may have never been pinned (no `pinned` field)
+            let value = if *pinned {
+                MatchOn::Equal(true.to_string())
+            } else {
+                MatchOn::NotEqual(true.to_string())
+            };
+            match_set.inner.push(Matcher {
+                field: "pinned".to_string(),
+                value,
+            });
+        }
+        if let Some(revised) = &filter.revised {
+            match_set.inner.push(Matcher {
+                field: "revised".to_string(),
+                value: MatchOn::Equal(revised.to_string()),
+            });
+        }
         if let Some(author) = &filter.author {
             match_set.inner.push(Matcher {
                 field: "author".to_string(),
@@ -438,6 +681,18 @@ impl From<&RecordsFilter> for MatchSet {
                 value: MatchOn::Equal(schema.to_string()),
             });
         }
+        if let Some(not_protocol) = &filter.not_protocol {
+            match_set.inner.push(Matcher {
+                field: "protocol".to_string(),
+                value: MatchOn::NotEqual(not_protocol.to_string()),
+            });
+        }
+        if let Some(not_schema) = &filter.not_schema {
+            match_set.inner.push(Matcher {
+                field: "schema".to_string(),
+                value: MatchOn::NotEqual(not_schema.to_string()),
+            });
+        }
         if let Some(parent_id) = &filter.parent_id {
             match_set.inner.push(Matcher {
                 field: "parentId".to_string(),
@@ -462,6 +717,12 @@ impl From<&RecordsFilter> for MatchSet {
                 value: MatchOn::Equal(data_cid.to_string()),
             });
         }
+        if let Some(protocol_config) = &filter.protocol_config {
+            match_set.inner.push(Matcher {
+                field: "protocolConfig".to_string(),
+                value: MatchOn::Equal(protocol_config.to_string()),
+            });
+        }
         if let Some(date_created) = &filter.date_created {
             match_set.inner.push(Matcher {
                 field: "dateCreated".to_string(),
@@ -483,7 +744,14 @@ impl From<&RecordsFilter> for MatchSet {
         if let Some(attester) = &filter.attester {
             match_set.inner.push(Matcher {
                 field: "attester".to_string(),
-                value: MatchOn::Equal(attester.to_string()),
+                value: MatchOn::Includes(attester.to_string()),
+            });
+        }
+
+        if let Some(text) = &filter.text {
+            match_set.inner.push(Matcher {
+                field: "text".to_string(),
+                value: MatchOn::Text(text.clone()),
             });
         }
 
@@ -491,17 +759,15 @@ impl From<&RecordsFilter> for MatchSet {
             for (property, tag_filter) in tags {
                 match tag_filter {
                     TagFilter::Equal(value) => {
-                        if let Some(val_str) = value.as_str() {
-                            match_set.inner.push(Matcher {
-                                field: format!("tag.{property}"),
-                                value: MatchOn::Equal(val_str.to_string()),
-                            });
-                        }
+                        match_set.inner.push(Matcher {
+                            field: format!("tag.{property}"),
+                            value: MatchOn::Equal(value.index_value()),
+                        });
                     }
                     TagFilter::StartsWith(value) => {
                         match_set.inner.push(Matcher {
                             field: format!("tag.{property}"),
-                            value: MatchOn::Equal(value.to_string()),
+                            value: MatchOn::StartsWith(value.starts_with.clone()),
                         });
                     }
                     TagFilter::Range(range) => {
@@ -510,6 +776,12 @@ impl From<&RecordsFilter> for MatchSet {
                             value: MatchOn::Range(range.clone()),
                         });
                     }
+                    TagFilter::FloatRange(range) => {
+                        match_set.inner.push(Matcher {
+                            field: format!("tag.{property}"),
+                            value: MatchOn::FloatRange(range.clone()),
+                        });
+                    }
                 }
             }
         }
@@ -523,6 +795,7 @@ impl From<&RecordsFilter> for MatchSet {
 pub struct ProtocolsQueryBuilder {
     protocol: Option<String>,
     published: Option<bool>,
+    pagination: Option<Pagination>,
 }
 
 impl ProtocolsQueryBuilder {
@@ -546,6 +819,13 @@ impl ProtocolsQueryBuilder {
         self
     }
 
+    /// Set the pagination options.
+    #[must_use]
+    pub fn pagination(mut self, pagination: Pagination) -> Self {
+        self.pagination = Some(pagination);
+        self
+    }
+
     /// Build the `RecordsQuery`.
     #[must_use]
     pub fn build(self) -> Query {
@@ -574,6 +854,7 @@ impl ProtocolsQueryBuilder {
 
         Query {
             match_sets: vec![match_set],
+            pagination: self.pagination,
             ..Query::default()
         }
     }
@@ -586,6 +867,7 @@ pub struct RecordsQueryBuilder {
     method: Option<Method>,
     include_archived: bool,
     sort: Sort,
+    sort_keys: Vec<SortKey>,
     pagination: Option<Pagination>,
 }
 
@@ -627,6 +909,13 @@ impl RecordsQueryBuilder {
         self
     }
 
+    /// Add a key to the composite sort order of the returned records.
+    #[must_use]
+    pub fn add_sort(mut self, sort_key: SortKey) -> Self {
+        self.sort_keys.push(sort_key);
+        self
+    }
+
     /// Set the pagination options.
     #[must_use]
     pub fn pagination(mut self, pagination: Pagination) -> Self {
@@ -656,11 +945,12 @@ impl RecordsQueryBuilder {
                 });
             }
 
-            let ms = MatchSet::from(filter);
-            match_set.inner.extend(ms.inner);
-            match_set.index = ms.index;
-
-            match_sets.push(match_set);
+            for ms in MatchSet::any_of(filter) {
+                let mut match_set = match_set.clone();
+                match_set.inner.extend(ms.inner);
+                match_set.index = ms.index;
+                match_sets.push(match_set);
+            }
 
             if is_concise {
                 is_concise = filter.is_concise();
@@ -670,7 +960,9 @@ impl RecordsQueryBuilder {
         Query {
             match_sets,
             sort: self.sort,
+            sort_keys: self.sort_keys,
             pagination: self.pagination,
+            since: None,
         }
     }
 }
@@ -795,3 +1087,17 @@ pub struct Cursor {
     /// results.
     pub value: String,
 }
+
+/// Statistics describing the messages stored for an owner.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreStats {
+    /// The number of stored messages, including archived writes.
+    pub messages: u64,
+
+    /// The total size, in bytes, of the latest data of each record.
+    pub data_bytes: u64,
+
+    /// The number of distinct protocols referenced by stored messages.
+    pub protocols: u64,
+}
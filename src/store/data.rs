@@ -1,55 +1,124 @@
 //! Data record handling.
 
-use std::collections::BTreeMap;
-use std::io::{Cursor, Read, Write};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{self, Cursor, Read, Write};
 use std::str::FromStr;
+use std::sync::{Arc, LazyLock, PoisonError};
 
+use chrono::{DateTime, Utc};
 use cid::Cid;
+use futures::{AsyncRead, AsyncReadExt, StreamExt, TryStreamExt, stream};
 use ipld_core::ipld::Ipld;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock};
 
 use crate::provider::BlockStore;
 use crate::store::block::{self, Block};
+use crate::utils::cid;
 use crate::{Result, unexpected};
 
-/// The maximum size of a message.
+/// The default maximum size of data encoded with a message. Providers can
+/// override the threshold using [`Provider::max_encoded_size`].
+///
+/// [`Provider::max_encoded_size`]: crate::provider::Provider::max_encoded_size
 pub const MAX_ENCODED_SIZE: usize = 30000;
 
 /// The maximum size of a block.
 pub(crate) const CHUNK_SIZE: usize = 64;
+/// The number of data blocks stored or fetched in a single batch.
+const BATCH_SIZE: usize = 64;
 const PARTITION: &str = "DATA";
+/// The period of time covered by each bucket of recently registered roots.
+const BUCKET_SECS: i64 = 600;
+
+static LOCKS: LazyLock<std::sync::Mutex<HashMap<String, Arc<Locks>>>> =
+    LazyLock::new(Default::default);
+
+/// Locks coordinating the storage of an owner's data with the collection of
+/// their orphaned data blocks.
+#[derive(Default)]
+pub(crate) struct Locks {
+    /// Held (shared) while data is being stored and (exclusively) while
+    /// orphaned data blocks are being collected.
+    pub data: RwLock<()>,
+
+    // Serializes updates to the owner's registry of stored data roots.
+    registry: Mutex<()>,
+}
+
+/// Get the locks for `owner`'s data. Owners are locked independently of one
+/// another.
+pub(crate) fn locks(owner: &str) -> Arc<Locks> {
+    let mut locks = LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // prune locks no longer held outside the map
+    locks.retain(|_, locks| Arc::strong_count(locks) > 1);
+    Arc::clone(locks.entry(owner.to_string()).or_default())
+}
+
+/// A data root registered when data is stored, used to find blocks orphaned
+/// when the data is no longer referenced.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Root {
+    /// The ID of the record the data was stored for.
+    pub record_id: String,
+
+    /// The CID of the stored data.
+    pub data_cid: String,
+
+    /// When the data was stored.
+    pub stored: DateTime<Utc>,
+}
 
 /// Put a data record into the block store.
+///
+/// Data is read in fixed-size chunks, each stored as an IPLD block. Should
+/// reading or storing fail part way through, blocks added by this call are
+/// removed before returning the error.
+///
+/// The stored data is registered, as stored at `stored`, so that its blocks
+/// can be collected once no longer referenced by any message.
 pub(crate) async fn put(
-    owner: &str, record_id: &str, data_cid: &str, reader: impl Read, store: &impl BlockStore,
+    owner: &str, record_id: &str, data_cid: &str, reader: impl AsyncRead + Unpin,
+    stored: DateTime<Utc>, store: &impl BlockStore,
 ) -> Result<(String, usize)> {
-    let mut links = vec![];
-    let mut byte_count = 0;
-    let mut reader = reader;
+    let locks = locks(owner);
+    let _guard = locks.data.read().await;
 
-    // read data stream in chunks, storing each chunk as an IPLD block
-    loop {
-        let mut buffer = [0u8; CHUNK_SIZE];
-        if let Ok(bytes_read) = reader.read(&mut buffer[..]) {
-            if bytes_read == 0 {
-                break;
+    let (root, byte_count) = put_unregistered(owner, record_id, data_cid, reader, store).await?;
+
+    // keep a copy of the root's links, outliving the root, to find the data
+    // blocks once orphaned
+    let root_cid = root_cid(record_id, data_cid)?;
+    store.put(owner, PARTITION, &links_cid(&root_cid)?, root.data()).await?;
+
+    let root_ref = Root {
+        record_id: record_id.to_string(),
+        data_cid: data_cid.to_string(),
+        stored,
+    };
+    register(owner, &root_cid, &root_ref, &locks, store).await?;
+
+    Ok((root.cid().to_string(), byte_count))
+}
+
+/// Put a data record into the block store without registering it for
+/// collection. Returns the root block and the number of bytes stored.
+pub(crate) async fn put_unregistered(
+    owner: &str, record_id: &str, data_cid: &str, reader: impl AsyncRead + Unpin,
+    store: &impl BlockStore,
+) -> Result<(Block, usize)> {
+    let mut added = vec![];
+    let (links, byte_count) = match put_blocks(owner, reader, &mut added, store).await {
+        Ok(result) => result,
+        Err(e) => {
+            for cid in &added {
+                let _ = store.delete(owner, PARTITION, cid).await;
             }
-            // encode buffer to IPLD block
-            let ipld = Ipld::Bytes(buffer[..bytes_read].to_vec());
-            let block = Block::encode(&ipld)?;
-
-            // insert into the blockstore
-            let cid = block.cid();
-            store
-                .put(owner, PARTITION, cid, block.data())
-                .await
-                .map_err(|e| unexpected!("issue storing data: {e}"))?;
-
-            // save link to block
-            let cid = Cid::from_str(cid).map_err(|e| unexpected!("issue parsing CID: {e}"))?;
-            links.push(Ipld::Link(cid));
-            byte_count += bytes_read;
+            return Err(e);
         }
-    }
+    };
 
     // the root block links the data blocks — yields the `data_cid`
     let root = Block::encode(&Ipld::List(links))?;
@@ -59,13 +128,180 @@ pub(crate) async fn put(
     let root_cid = root_cid(record_id, data_cid)?;
     store.put(owner, PARTITION, &root_cid, root.data()).await?;
 
-    Ok((root.cid().to_string(), byte_count))
+    Ok((root, byte_count))
+}
+
+// Read the data stream in chunks, storing each chunk as an IPLD block. The
+// CIDs of blocks not previously in the store are added to `added`.
+async fn put_blocks(
+    owner: &str, mut reader: impl AsyncRead + Unpin, added: &mut Vec<String>,
+    store: &impl BlockStore,
+) -> Result<(Vec<Ipld>, usize)> {
+    let mut links = vec![];
+    let mut byte_count = 0;
+    let mut batch = vec![];
+
+    loop {
+        let mut buffer = [0u8; CHUNK_SIZE];
+        let bytes_read = read_chunk(&mut reader, &mut buffer)
+            .await
+            .map_err(|e| unexpected!("issue reading data: {e}"))?;
+        if bytes_read == 0 {
+            break;
+        }
+
+        // encode buffer to IPLD block
+        let ipld = Ipld::Bytes(buffer[..bytes_read].to_vec());
+        let block = Block::encode(&ipld)?;
+
+        // save link to block
+        let cid = Cid::from_str(block.cid()).map_err(|e| unexpected!("issue parsing CID: {e}"))?;
+        links.push(Ipld::Link(cid));
+        byte_count += bytes_read;
+
+        batch.push(block);
+        if batch.len() == BATCH_SIZE {
+            put_batch(owner, &mut batch, added, store).await?;
+        }
+    }
+    put_batch(owner, &mut batch, added, store).await?;
+
+    Ok((links, byte_count))
+}
+
+// Insert a batch of blocks into the block store, skipping blocks already
+// stored. The CIDs of blocks not previously in the store are added to `added`.
+async fn put_batch(
+    owner: &str, batch: &mut Vec<Block>, added: &mut Vec<String>, store: &impl BlockStore,
+) -> Result<()> {
+    let mut blocks: Vec<(String, Vec<u8>)> = vec![];
+    for block in batch.drain(..) {
+        if !blocks.iter().any(|(cid, _)| cid == block.cid()) {
+            blocks.push((block.cid().to_string(), block.data().to_vec()));
+        }
+    }
+    if blocks.is_empty() {
+        return Ok(());
+    }
+
+    let cids = blocks.iter().map(|(cid, _)| cid.clone()).collect::<Vec<_>>();
+    let existing = store
+        .get_many(owner, PARTITION, &cids)
+        .await
+        .map_err(|e| unexpected!("issue fetching data: {e}"))?;
+    let mut existing = existing.into_iter();
+    blocks.retain(|_| matches!(existing.next(), Some(None)));
+    if blocks.is_empty() {
+        return Ok(());
+    }
+
+    // record blocks before storing so a partially stored batch is removed
+    added.extend(blocks.iter().map(|(cid, _)| cid.clone()));
+    store
+        .put_many(owner, PARTITION, &blocks)
+        .await
+        .map_err(|e| unexpected!("issue storing data: {e}"))
+}
+
+// Fill the buffer, only returning a partial chunk at the end of the stream.
+// Chunk boundaries must not depend on how the reader splits reads, otherwise
+// the same data could yield different CIDs.
+async fn read_chunk(reader: &mut (impl AsyncRead + Unpin), buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]).await {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
 }
 
 /// Get a data record from the block store.
 pub(crate) async fn get(
     owner: &str, record_id: &str, data_cid: &str, store: &impl BlockStore,
 ) -> Result<Option<impl Read>> {
+    let Some(links) = links(owner, record_id, data_cid, store).await? else {
+        return Ok(None);
+    };
+
+    // fetch the data blocks in batches
+    let mut buf = Cursor::new(vec![]);
+
+    for batch in links.chunks(BATCH_SIZE) {
+        let Some(blocks) = get_blocks(owner, batch, store).await? else {
+            return Ok(None);
+        };
+        for bytes in blocks {
+            buf.write_all(&bytes)?;
+        }
+    }
+
+    buf.set_position(0);
+    Ok(Some(buf))
+}
+
+/// Open a data record in the block store for reading. Rather than reading
+/// the record's data up front, each data block is fetched as it is read, so
+/// blocks are not batched.
+pub(crate) async fn open<'a>(
+    owner: &'a str, record_id: &str, data_cid: &str, store: &'a impl BlockStore,
+) -> Result<Option<impl AsyncRead + Send + Unpin + 'a>> {
+    let Some(links) = links(owner, record_id, data_cid, store).await? else {
+        return Ok(None);
+    };
+
+    let blocks = stream::iter(links).then(move |link| async move {
+        match get_block(owner, &link, store).await {
+            Ok(Some(bytes)) => Ok(bytes),
+            Ok(None) => Err(io::Error::new(io::ErrorKind::NotFound, "data block not found")),
+            Err(e) => Err(io::Error::other(e.to_string())),
+        }
+    });
+    Ok(Some(Box::pin(blocks).into_async_read()))
+}
+
+/// Get the `[start, end)` byte range of a data record from the block store.
+///
+/// Data is stored in fixed-size chunks, so only the blocks spanning the range
+/// are fetched.
+pub(crate) async fn get_range(
+    owner: &str, record_id: &str, data_cid: &str, start: usize, end: usize, store: &impl BlockStore,
+) -> Result<Option<impl Read>> {
+    let Some(links) = links(owner, record_id, data_cid, store).await? else {
+        return Ok(None);
+    };
+
+    let mut buf = Cursor::new(vec![]);
+
+    if start < end {
+        let first = start / CHUNK_SIZE;
+        let last = (end - 1) / CHUNK_SIZE;
+
+        let span = links.get(first..=last.min(links.len().saturating_sub(1))).unwrap_or_default();
+        let Some(blocks) = get_blocks(owner, span, store).await? else {
+            return Ok(None);
+        };
+
+        for (index, bytes) in (first..).zip(blocks) {
+            // trim the first and last blocks to the range
+            let offset = index * CHUNK_SIZE;
+            let from = start.saturating_sub(offset).min(bytes.len());
+            let to = (end - offset).min(bytes.len());
+            buf.write_all(&bytes[from..to])?;
+        }
+    }
+
+    buf.set_position(0);
+    Ok(Some(buf))
+}
+
+// Get the links to a data record's blocks from its root block.
+async fn links(
+    owner: &str, record_id: &str, data_cid: &str, store: &impl BlockStore,
+) -> Result<Option<Vec<Ipld>>> {
     // get the root block using the partition CID
     let root_cid = root_cid(record_id, data_cid)?;
     let Some(bytes) = store.get(owner, PARTITION, &root_cid).await? else {
@@ -76,31 +312,83 @@ pub(crate) async fn get(
     let Ipld::List(links) = block::decode(&bytes)? else {
         return Ok(None);
     };
+    Ok(Some(links))
+}
 
-    // TODO: optimize by streaming the data blocks as fetched
-    // fetch each data block
-    let mut buf = Cursor::new(vec![]);
+// Get the payload of the data block referenced by `link`.
+async fn get_block(owner: &str, link: &Ipld, store: &impl BlockStore) -> Result<Option<Vec<u8>>> {
+    let Ipld::Link(link_cid) = link else {
+        return Err(unexpected!("invalid link"));
+    };
+    let Some(bytes) = store.get(owner, PARTITION, &link_cid.to_string()).await? else {
+        return Ok(None);
+    };
 
-    for link in links {
-        // get data block
-        let Ipld::Link(link_cid) = link else {
-            return Err(unexpected!("invalid link"));
-        };
-        let Some(bytes) = store.get(owner, PARTITION, &link_cid.to_string()).await? else {
-            return Ok(None);
-        };
+    // get data block's payload
+    let Ipld::Bytes(bytes) = block::decode(&bytes)? else {
+        return Ok(None);
+    };
+    Ok(Some(bytes))
+}
+
+// Get the payloads of the data blocks referenced by `links` in a single
+// batch, returning `None` if any block is missing.
+async fn get_blocks(
+    owner: &str, links: &[Ipld], store: &impl BlockStore,
+) -> Result<Option<Vec<Vec<u8>>>> {
+    let cids = links
+        .iter()
+        .map(|link| match link {
+            Ipld::Link(link_cid) => Ok(link_cid.to_string()),
+            _ => Err(unexpected!("invalid link")),
+        })
+        .collect::<Result<Vec<_>>>()?;
 
+    let mut payloads = Vec::with_capacity(cids.len());
+    for bytes in store.get_many(owner, PARTITION, &cids).await? {
         // get data block's payload
-        let ipld_bytes = block::decode(&bytes)?;
-        let Ipld::Bytes(bytes) = ipld_bytes else {
+        let Some(bytes) = bytes else {
+            return Ok(None);
+        };
+        let Ipld::Bytes(bytes) = block::decode(&bytes)? else {
             return Ok(None);
         };
+        payloads.push(bytes);
+    }
+    Ok(Some(payloads))
+}
 
-        buf.write_all(&bytes)?;
+/// Read data in chunks, returning the blocks its `data_cid` is computed from,
+/// root block first. Each block is encoded as the bytes its CID is computed
+/// over, so may be verified by hashing.
+pub(crate) async fn cid_blocks(
+    mut reader: impl AsyncRead + Unpin,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut links = vec![];
+    let mut blocks = vec![];
+    let mut seen = HashSet::new();
+
+    loop {
+        let mut buffer = [0u8; CHUNK_SIZE];
+        let bytes_read = read_chunk(&mut reader, &mut buffer)
+            .await
+            .map_err(|e| unexpected!("issue reading data: {e}"))?;
+        if bytes_read == 0 {
+            break;
+        }
+
+        let bytes = cid::to_cbor(&Ipld::Bytes(buffer[..bytes_read].to_vec()))?;
+        let block_cid = cid::from_bytes(&bytes);
+        let link = Cid::from_str(&block_cid).map_err(|e| unexpected!("issue parsing CID: {e}"))?;
+        links.push(Ipld::Link(link));
+        if seen.insert(block_cid.clone()) {
+            blocks.push((block_cid, bytes));
+        }
     }
 
-    buf.set_position(0);
-    Ok(Some(buf))
+    let root = cid::to_cbor(&Ipld::List(links))?;
+    blocks.insert(0, (cid::from_bytes(&root), root));
+    Ok(blocks)
 }
 
 pub(crate) async fn delete(
@@ -110,6 +398,234 @@ pub(crate) async fn delete(
     Ok(store.delete(owner, PARTITION, &root_cid).await?)
 }
 
+/// Get the data roots registered for a record, keyed by the CID each root
+/// block is stored under.
+pub(crate) async fn record_roots(
+    owner: &str, record_id: &str, store: &impl BlockStore,
+) -> Result<BTreeMap<String, Root>> {
+    let root_cids: BTreeSet<String> =
+        load(owner, &record_cid(owner, record_id)?, store).await?.unwrap_or_default();
+
+    let mut roots = BTreeMap::new();
+    for root_cid in root_cids {
+        if let Some(root) = load(owner, &entry_cid(&root_cid)?, store).await? {
+            roots.insert(root_cid, root);
+        }
+    }
+    Ok(roots)
+}
+
+/// Get the IDs of the records with data roots registered in buckets not yet
+/// collected, up to and including the bucket holding `now`.
+///
+/// Data is registered before the message referencing it is saved, so these
+/// include records with data in flight and records without any messages.
+pub(crate) async fn recent_records(
+    owner: &str, now: DateTime<Utc>, store: &impl BlockStore,
+) -> Result<BTreeSet<String>> {
+    let mut record_ids = BTreeSet::new();
+    let Some(from) = load::<i64>(owner, &from_cid(owner)?, store).await? else {
+        return Ok(record_ids);
+    };
+
+    for bucket in from..=bucket(now) {
+        let root_cids: BTreeSet<String> =
+            load(owner, &bucket_cid(owner, bucket)?, store).await?.unwrap_or_default();
+        for root_cid in root_cids {
+            if let Some(root) = load::<Root>(owner, &entry_cid(&root_cid)?, store).await? {
+                record_ids.insert(root.record_id);
+            }
+        }
+    }
+    Ok(record_ids)
+}
+
+/// Remove the buckets of roots registered wholly before `cutoff`, once
+/// collected. Roots still referenced remain registered by their record.
+pub(crate) async fn collected_until(
+    owner: &str, cutoff: DateTime<Utc>, store: &impl BlockStore,
+) -> Result<()> {
+    let from_cid = from_cid(owner)?;
+    let Some(from) = load::<i64>(owner, &from_cid, store).await? else {
+        return Ok(());
+    };
+    let until = bucket(cutoff);
+    for bucket in from..until {
+        store.delete(owner, PARTITION, &bucket_cid(owner, bucket)?).await?;
+    }
+    save(owner, &from_cid, &until.max(from), store).await
+}
+
+/// Register data stored before data roots were registered, so its blocks are
+/// retained while referenced and collected once orphaned. Data already
+/// registered, or no longer stored, is ignored.
+pub(crate) async fn register_existing(
+    owner: &str, record_id: &str, data_cid: &str, stored: DateTime<Utc>, store: &impl BlockStore,
+) -> Result<()> {
+    let root_cid = root_cid(record_id, data_cid)?;
+    if store.get(owner, PARTITION, &entry_cid(&root_cid)?).await?.is_some() {
+        return Ok(());
+    }
+    let Some(root) = store.get(owner, PARTITION, &root_cid).await? else {
+        return Ok(());
+    };
+    store.put(owner, PARTITION, &links_cid(&root_cid)?, &root).await?;
+
+    let root_ref = Root {
+        record_id: record_id.to_string(),
+        data_cid: data_cid.to_string(),
+        stored,
+    };
+    register(owner, &root_cid, &root_ref, &locks(owner), store).await
+}
+
+/// Whether the root block stored under `root_cid` still exists.
+pub(crate) async fn has_root(owner: &str, root_cid: &str, store: &impl BlockStore) -> Result<bool> {
+    Ok(store.get(owner, PARTITION, root_cid).await?.is_some())
+}
+
+/// Get the CIDs of the data blocks linked to by the root stored under
+/// `root_cid`.
+pub(crate) async fn root_links(
+    owner: &str, root_cid: &str, store: &impl BlockStore,
+) -> Result<Vec<String>> {
+    let Some(bytes) = store.get(owner, PARTITION, &links_cid(root_cid)?).await? else {
+        return Ok(vec![]);
+    };
+    let Ipld::List(links) = block::decode(&bytes)? else {
+        return Ok(vec![]);
+    };
+    links
+        .iter()
+        .map(|link| match link {
+            Ipld::Link(link_cid) => Ok(link_cid.to_string()),
+            _ => Err(unexpected!("invalid link")),
+        })
+        .collect()
+}
+
+/// Remove the `orphans` roots, keyed by the CID each root block is stored
+/// under, along with any of their data blocks not in `retained`. Returns the
+/// number and total size of the blocks removed.
+pub(crate) async fn remove_roots(
+    owner: &str, orphans: &BTreeMap<String, Root>, retained: &HashSet<String>,
+    store: &impl BlockStore,
+) -> Result<(usize, usize)> {
+    let mut count = 0;
+    let mut size = 0;
+
+    for root_cid in orphans.keys() {
+        let mut cids = root_links(owner, root_cid, store).await?;
+        cids.push(root_cid.clone());
+        cids.push(links_cid(root_cid)?);
+
+        for cid in cids.iter().filter(|cid| !retained.contains(*cid)) {
+            // blocks may be shared between roots, so may already be removed
+            let Some(bytes) = store.get(owner, PARTITION, cid).await? else {
+                continue;
+            };
+            store.delete(owner, PARTITION, cid).await?;
+            count += 1;
+            size += bytes.len();
+        }
+    }
+
+    // unregister removed roots
+    let locks = locks(owner);
+    let _guard = locks.registry.lock().await;
+    for (root_cid, root) in orphans {
+        store.delete(owner, PARTITION, &entry_cid(root_cid)?).await?;
+
+        let record_cid = record_cid(owner, &root.record_id)?;
+        let mut root_cids: BTreeSet<String> =
+            load(owner, &record_cid, store).await?.unwrap_or_default();
+        root_cids.remove(root_cid);
+        if root_cids.is_empty() {
+            store.delete(owner, PARTITION, &record_cid).await?;
+        } else {
+            save(owner, &record_cid, &root_cids, store).await?;
+        }
+    }
+
+    Ok((count, size))
+}
+
+// Register a stored data root: an entry is kept for the root, listed by the
+// record the data was stored for and by the bucket of recently registered
+// roots. Each list only grows with the record's roots or the roots
+// registered in the bucket's period.
+async fn register(
+    owner: &str, root_cid: &str, root: &Root, locks: &Locks, store: &impl BlockStore,
+) -> Result<()> {
+    save(owner, &entry_cid(root_cid)?, root, store).await?;
+
+    let _guard = locks.registry.lock().await;
+
+    let record_cid = record_cid(owner, &root.record_id)?;
+    let mut root_cids: BTreeSet<String> =
+        load(owner, &record_cid, store).await?.unwrap_or_default();
+    if root_cids.insert(root_cid.to_string()) {
+        save(owner, &record_cid, &root_cids, store).await?;
+    }
+
+    let bucket = bucket(root.stored);
+    let bucket_cid = bucket_cid(owner, bucket)?;
+    let mut root_cids: BTreeSet<String> =
+        load(owner, &bucket_cid, store).await?.unwrap_or_default();
+    if root_cids.insert(root_cid.to_string()) {
+        save(owner, &bucket_cid, &root_cids, store).await?;
+    }
+
+    // collection starts from the earliest bucket registered
+    let from_cid = from_cid(owner)?;
+    if load::<i64>(owner, &from_cid, store).await?.is_none_or(|from| bucket < from) {
+        save(owner, &from_cid, &bucket, store).await?;
+    }
+
+    Ok(())
+}
+
+async fn load<T>(owner: &str, cid: &str, store: &impl BlockStore) -> Result<Option<T>>
+where
+    T: Serialize + for<'a> Deserialize<'a>,
+{
+    let Some(bytes) = store.get(owner, PARTITION, cid).await? else {
+        return Ok(None);
+    };
+    Ok(Some(block::decode(&bytes)?))
+}
+
+async fn save<T: Serialize>(
+    owner: &str, cid: &str, value: &T, store: &impl BlockStore,
+) -> Result<()> {
+    store.delete(owner, PARTITION, cid).await?;
+    Ok(store.put(owner, PARTITION, cid, &block::encode(value)?).await?)
+}
+
+const fn bucket(stored: DateTime<Utc>) -> i64 {
+    stored.timestamp().div_euclid(BUCKET_SECS)
+}
+
+fn entry_cid(root_cid: &str) -> Result<String> {
+    cid::from_value(&format!("{root_cid}-entry"))
+}
+
+fn record_cid(owner: &str, record_id: &str) -> Result<String> {
+    cid::from_value(&format!("{owner}-{record_id}-data-roots"))
+}
+
+fn bucket_cid(owner: &str, bucket: i64) -> Result<String> {
+    cid::from_value(&format!("{owner}-{bucket}-data-roots"))
+}
+
+fn from_cid(owner: &str) -> Result<String> {
+    cid::from_value(&format!("{owner}-data-roots-from"))
+}
+
+fn links_cid(root_cid: &str) -> Result<String> {
+    cid::from_value(&format!("{root_cid}-links"))
+}
+
 fn root_cid(record_id: &str, data_cid: &str) -> Result<String> {
     let root = Block::encode(&Ipld::Map(BTreeMap::from([
         (String::from("record_id"), Ipld::String(record_id.to_string())),
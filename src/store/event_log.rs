@@ -1,26 +1,126 @@
 //! # Event Log
+//!
+//! Each event is assigned a watermark when logged: a sequence number,
+//! persisted alongside the log, that increases monotonically for each owner.
+//! Watermarks let a replica pull only the events logged since it last synced.
+//!
+//! The owner's counter is rebuilt from the log's watermark index whenever it
+//! is missing — for example, when an update of the counter was interrupted.
+//! Events logged before watermarks were sequence numbers carry ULID
+//! watermarks. These are assigned sequence numbers, in ULID order, when the
+//! counter is rebuilt.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, PoisonError};
+
+use serde::Serialize;
+use tokio::sync::Mutex;
 
 use super::Pagination;
 use crate::event::Event;
 use crate::provider::BlockStore;
-use crate::store::{Cursor, Entry, Query, Sort, block, index};
-use crate::{Result, unexpected};
+use crate::store::{Cursor, Entry, MatchOn, MatchSet, Matcher, Query, Sort, block, index};
+use crate::utils::cid;
+use crate::{Lower, Range, Result, unexpected};
 
 const PARTITION: &str = "EVENTLOG";
 
+// Serializes watermark assignment for each owner.
+static LOCKS: LazyLock<std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+#[derive(Serialize)]
+struct Cid(String);
+
 /// Adds a message event to a owner's event log.
 pub async fn append(owner: &str, event: &Event, store: &impl BlockStore) -> Result<()> {
     // store entry block
-    let message_cid = event.cid()?;
+    let message_cid = event.event_cid()?;
     store.delete(owner, PARTITION, &message_cid).await?;
     store.put(owner, PARTITION, &message_cid, &block::encode(event)?).await?;
 
     // add a 'watermark' index entry for sorting and pagination
+    let lock = lock(owner);
+    let _guard = lock.lock().await;
+
+    let watermark = counter(owner, store).await? + 1;
+    save_counter(owner, watermark, store).await?;
+
     let mut event = event.clone();
-    let watermark = ulid::Ulid::new().to_string();
-    event.indexes.insert("watermark".to_string(), watermark);
+    event.indexes.insert("watermark".to_string(), format_watermark(watermark));
+    index::insert_as(owner, PARTITION, &message_cid, &event, store).await
+}
+
+/// The watermark of the most recently logged event, or 0 when no events have
+/// been logged.
+pub async fn watermark(owner: &str, store: &impl BlockStore) -> Result<u64> {
+    let lock = lock(owner);
+    let _guard = lock.lock().await;
+    counter(owner, store).await
+}
+
+fn lock(owner: &str) -> Arc<Mutex<()>> {
+    let mut locks = LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // prune locks no longer held outside the map
+    locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+    Arc::clone(locks.entry(owner.to_string()).or_default())
+}
+
+// Read the owner's watermark counter, rebuilding it from the log when
+// missing. Must be called while holding the owner's lock.
+async fn counter(owner: &str, store: &impl BlockStore) -> Result<u64> {
+    if let Some(bytes) = store.get(owner, PARTITION, &watermark_cid(owner)?).await? {
+        return block::decode(&bytes).map_err(Into::into);
+    }
+
+    // continue from the highest sequence number logged
+    let mut sequenced = 0;
+    let mut unsequenced = vec![];
+    for item in index::items(owner, PARTITION, store).await? {
+        let Some(watermark) = item.fields.get("watermark") else {
+            continue;
+        };
+        match watermark.parse::<u64>() {
+            Ok(watermark) => sequenced = sequenced.max(watermark),
+            Err(_) => unsequenced.push((watermark.clone(), item)),
+        }
+    }
+
+    // sequence events carrying (time-ordered) ULID watermarks
+    unsequenced.sort_by(|a, b| a.0.cmp(&b.0));
+    for (_, item) in unsequenced {
+        let Some(bytes) = store.get(owner, PARTITION, &item.message_cid).await? else {
+            return Err(unexpected!("missing block for message cid"));
+        };
+        let mut entry: Entry = block::decode(&bytes)?;
+        entry.indexes = item.fields;
+
+        sequenced += 1;
+        entry.indexes.insert("watermark".to_string(), format_watermark(sequenced));
+        index::insert_as(owner, PARTITION, &item.message_cid, &entry, store).await?;
+    }
+
+    save_counter(owner, sequenced, store).await?;
+    Ok(sequenced)
+}
 
-    index::insert(owner, PARTITION, &event, store).await
+// Should saving fail after the previous counter is removed, the counter is
+// rebuilt from the log when next read.
+async fn save_counter(owner: &str, watermark: u64, store: &impl BlockStore) -> Result<()> {
+    let watermark_cid = watermark_cid(owner)?;
+    store.delete(owner, PARTITION, &watermark_cid).await?;
+    store.put(owner, PARTITION, &watermark_cid, &block::encode(&watermark)?).await?;
+    Ok(())
+}
+
+fn watermark_cid(owner: &str) -> Result<String> {
+    cid::from_value(&Cid(format!("{owner}-watermark")))
+}
+
+// Zero-pad watermarks so they sort lexically in sequence order.
+fn format_watermark(watermark: u64) -> String {
+    format!("{watermark:020}")
 }
 
 pub async fn events(
@@ -33,6 +133,8 @@ pub async fn events(
             cursor,
         }),
         sort: Sort::TimestampAsc,
+        sort_keys: vec![],
+        since: None,
     };
 
     query(owner, &q, store).await
@@ -43,11 +145,37 @@ pub async fn events(
 /// events for a given owner and filter combo will be returned. The cursor
 /// is a `message_cid`.
 ///
+/// When the query's `since` watermark is set, only events logged after the
+/// watermark are returned.
+///
 /// Returns an array of `message_cid`s that represent the events.
 pub async fn query(
     owner: &str, query: &Query, store: &impl BlockStore,
 ) -> Result<(Vec<Entry>, Option<Cursor>)> {
-    let mut results = index::query(owner, PARTITION, query, store).await?;
+    let mut results = if let Some(since) = query.since {
+        // ensure events logged with ULID watermarks have been sequenced
+        watermark(owner, store).await?;
+
+        let since = usize::try_from(since).map_err(|e| unexpected!("invalid watermark: {e}"))?;
+        let matcher = Matcher {
+            field: "watermark".to_string(),
+            value: MatchOn::Range(Range {
+                lower: Some(Lower::Exclusive(since)),
+                upper: None,
+            }),
+        };
+
+        let mut query = query.clone();
+        if query.match_sets.is_empty() {
+            query.match_sets.push(MatchSet::default());
+        }
+        for match_set in &mut query.match_sets {
+            match_set.inner.push(matcher.clone());
+        }
+        index::query(owner, PARTITION, &query, store).await?
+    } else {
+        index::query(owner, PARTITION, query, store).await?
+    };
 
     // return cursor when paging is used
     let limit = query.pagination.as_ref().map_or(0, |p| p.limit.unwrap_or(0));
@@ -66,14 +194,95 @@ pub async fn query(
         let Some(bytes) = store.get(owner, PARTITION, &item.message_cid).await? else {
             return Err(unexpected!("missing block for message cid"));
         };
-        entries.push(block::decode(&bytes)?);
+        let mut entry: Entry = block::decode(&bytes)?;
+        entry.indexes = item.fields;
+        entries.push(entry);
     }
 
     Ok((entries, cursor))
 }
 
+/// Get the event logged for the specified `message_cid`, along with its
+/// index fields.
+pub(crate) async fn get(
+    owner: &str, message_cid: &str, store: &impl BlockStore,
+) -> Result<Option<Event>> {
+    let Some(bytes) = store.get(owner, PARTITION, message_cid).await? else {
+        return Ok(None);
+    };
+    // events are always logged with a watermark
+    let Some(item) = index::item(owner, PARTITION, message_cid, store)
+        .await?
+        .filter(|item| item.fields.contains_key("watermark"))
+    else {
+        return Ok(None);
+    };
+    let mut event: Event = block::decode(&bytes)?;
+    event.indexes = item.fields;
+    Ok(Some(event))
+}
+
 /// Deletes event for the specified `message_cid`.
 pub async fn delete(owner: &str, message_cid: &str, store: &impl BlockStore) -> Result<()> {
     index::delete(owner, PARTITION, message_cid, store).await?;
     store.delete(owner, PARTITION, message_cid).await.map_err(Into::into)
 }
+
+#[cfg(test)]
+mod tests {
+    use test_node::key_store;
+
+    use super::*;
+    use crate::interfaces::records::{Data, WriteBuilder};
+    use crate::store::memory::BlockStoreImpl;
+
+    // Events logged with ULID watermarks are sequenced when the counter is
+    // rebuilt.
+    #[tokio::test]
+    async fn sequence_ulids() {
+        let block_store = BlockStoreImpl::new();
+        let alice = key_store::new_keyring();
+
+        // log events with ULID watermarks, the later event first
+        let mut message_cids = vec![];
+        for (data, ulid) in
+            [("later", "01ARZ3NDEKTSV4RRFFQ69G5FAW"), ("earlier", "01ARZ3NDEKTSV4RRFFQ69G5FAV")]
+        {
+            let write = WriteBuilder::new()
+                .data(Data::from(data.as_bytes().to_vec()))
+                .sign(&alice)
+                .build()
+                .await
+                .unwrap();
+            let mut entry = Entry::from(&write);
+            let message_cid = entry.cid().unwrap();
+            block_store
+                .put(&alice.did, PARTITION, &message_cid, &block::encode(&entry).unwrap())
+                .await
+                .unwrap();
+
+            entry.indexes.insert("watermark".to_string(), ulid.to_string());
+            index::insert(&alice.did, PARTITION, &entry, &block_store).await.unwrap();
+            message_cids.push(message_cid);
+        }
+
+        // the counter is rebuilt, sequencing events in ULID order
+        assert_eq!(watermark(&alice.did, &block_store).await.unwrap(), 2);
+
+        let (events, _) =
+            query(&alice.did, &Query::default().since(1), &block_store).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].cid().unwrap(), message_cids[0]);
+        assert_eq!(events[0].watermark(), Some(2));
+
+        // new events continue the sequence
+        let write = WriteBuilder::new()
+            .data(Data::from(b"latest".to_vec()))
+            .sign(&alice)
+            .build()
+            .await
+            .unwrap();
+        append(&alice.did, &Entry::from(&write), &block_store).await.unwrap();
+        assert_eq!(watermark(&alice.did, &block_store).await.unwrap(), 3);
+    }
+}
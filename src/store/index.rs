@@ -5,15 +5,18 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 
+use std::cmp::{Ordering, Reverse};
 use std::collections::btree_map::Range;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
 use std::ops::Bound::{self, Excluded, Included, Unbounded};
 
 use serde::{Deserialize, Serialize};
 
 use crate::Result;
 use crate::provider::BlockStore;
-use crate::store::{Entry, Query, block};
+use crate::records::Tag;
+use crate::store::{Entry, Pagination, Query, SortKey, block};
 use crate::utils::cid;
 
 // const NULL: u8 = 0x00;
@@ -21,11 +24,28 @@ use crate::utils::cid;
 const NULL: char = '\u{100000}';
 const MAX: char = '\u{10ffff}';
 
+// Composite index values: each sort key's value is terminated so that a
+// shorter value orders before (ascending) or after (descending) a longer one
+// it prefixes, and a missing value orders after any present value.
+const ASCENDING_END: char = '!';
+const DESCENDING_END: char = '~';
+const MISSING: char = '~';
+
+// The block listing the composite indexes maintained for the partition.
+const COMPOSITES: &str = "composite_indexes";
+
 /// Insert an entry's queryable fields into indexes.
 pub async fn insert(
     owner: &str, partition: &str, entry: &Entry, store: &impl BlockStore,
 ) -> Result<()> {
-    let message_cid = entry.cid()?;
+    insert_as(owner, partition, &entry.cid()?, entry, store).await
+}
+
+/// Insert an entry's queryable fields into indexes under the specified CID.
+pub async fn insert_as(
+    owner: &str, partition: &str, message_cid: &str, entry: &Entry, store: &impl BlockStore,
+) -> Result<()> {
+    let message_cid = message_cid.to_string();
 
     let fields = &entry.indexes;
     let indexes = IndexesBuilder::new().owner(owner).partition(partition).store(store).build();
@@ -33,6 +53,18 @@ pub async fn insert(
     // remove the previous index entries for message
     delete(owner, partition, &message_cid, store).await?;
 
+    // add reverse lookup to use when message is updated or deleted — added
+    // first so index entries added before a failure can still be removed
+    let mut index = indexes.get("message_cid").await?;
+    index.items.insert(
+        message_cid.clone(),
+        IndexItem {
+            fields: entry.indexes.clone(),
+            message_cid: message_cid.clone(),
+        },
+    );
+    indexes.put(index).await?;
+
     for (field, value) in &entry.indexes {
         let mut index = indexes.get(field).await?;
         index.insert(
@@ -45,16 +77,17 @@ pub async fn insert(
         indexes.put(index).await?;
     }
 
-    // add reverse lookup to use when message is updated or deleted
-    let mut index = indexes.get("message_cid").await?;
-    index.items.insert(
-        message_cid.clone(),
-        IndexItem {
-            fields: entry.indexes.clone(),
-            message_cid,
-        },
-    );
-    indexes.put(index).await?;
+    for sort_keys in indexes.composites().await? {
+        let mut index = indexes.get(&composite_field(&sort_keys)).await?;
+        index.insert(
+            &composite_value(fields, &sort_keys),
+            IndexItem {
+                fields: entry.indexes.clone(),
+                message_cid: message_cid.clone(),
+            },
+        );
+        indexes.put(index).await?;
+    }
 
     Ok(())
 }
@@ -65,12 +98,28 @@ pub async fn query(
 ) -> Result<Vec<IndexItem>> {
     let indexes = IndexesBuilder::new().owner(owner).partition(partition).store(store).build();
 
+    if query.text().is_some() {
+        return indexes.query_ranked(query).await;
+    }
+    if !query.sort_keys.is_empty() {
+        return indexes.query_composite(query).await;
+    }
     if query.is_concise() {
         return indexes.query_concise(query).await;
     }
     indexes.query_full(query).await
 }
 
+/// Return the index item for the entry specified by `message_cid`, read from
+/// the reverse lookup index.
+pub async fn item(
+    owner: &str, partition: &str, message_cid: &str, store: &impl BlockStore,
+) -> Result<Option<IndexItem>> {
+    let indexes = IndexesBuilder::new().owner(owner).partition(partition).store(store).build();
+    let mut messages = indexes.get("message_cid").await?;
+    Ok(messages.items.remove(message_cid))
+}
+
 /// Delete entry specified by `message_cid` from indexes.
 pub async fn delete(
     owner: &str, partition: &str, message_cid: &str, store: &impl BlockStore,
@@ -85,11 +134,72 @@ pub async fn delete(
             let x = index.remove(value, message_cid);
             indexes.put(index).await?;
         }
+        for sort_keys in indexes.composites().await? {
+            let mut index = indexes.get(&composite_field(&sort_keys)).await?;
+            index.remove(&composite_value(&item.fields, &sort_keys), message_cid);
+            indexes.put(index).await?;
+        }
     }
 
     Ok(())
 }
 
+/// Return the index items for every entry in the partition, read from the
+/// reverse lookup index rather than from the entries themselves.
+pub async fn items(
+    owner: &str, partition: &str, store: &impl BlockStore,
+) -> Result<Vec<IndexItem>> {
+    let indexes = IndexesBuilder::new().owner(owner).partition(partition).store(store).build();
+    let messages = indexes.get("message_cid").await?;
+    Ok(messages.items.into_values().collect())
+}
+
+/// Replace the partition's indexes with indexes built from the provided
+/// entries. Indexes for fields no longer used by any entry are emptied.
+pub async fn rebuild(
+    owner: &str, partition: &str, entries: &[Entry], store: &impl BlockStore,
+) -> Result<()> {
+    let indexes = IndexesBuilder::new().owner(owner).partition(partition).store(store).build();
+
+    // start with an empty index for every field currently indexed
+    let mut rebuilt = BTreeMap::new();
+    for item in indexes.get("message_cid").await?.items.into_values() {
+        for field in item.fields.into_keys() {
+            rebuilt.entry(field.clone()).or_insert_with(|| Index::new(field));
+        }
+    }
+
+    let composites = indexes.composites().await?;
+    for sort_keys in &composites {
+        let field = composite_field(sort_keys);
+        rebuilt.insert(field.clone(), Index::new(field));
+    }
+
+    let mut messages = Index::new("message_cid");
+    for entry in entries {
+        let message_cid = entry.cid()?;
+        let item = IndexItem {
+            fields: entry.indexes.clone(),
+            message_cid: message_cid.clone(),
+        };
+        for (field, value) in &entry.indexes {
+            let index = rebuilt.entry(field.clone()).or_insert_with(|| Index::new(field));
+            index.insert(value, item.clone());
+        }
+        for sort_keys in &composites {
+            if let Some(index) = rebuilt.get_mut(&composite_field(sort_keys)) {
+                index.insert(&composite_value(&entry.indexes, sort_keys), item.clone());
+            }
+        }
+        messages.items.insert(message_cid, item);
+    }
+
+    for index in rebuilt.into_values() {
+        indexes.put(index).await?;
+    }
+    indexes.put(messages).await
+}
+
 #[derive(Serialize)]
 struct Cid(String);
 
@@ -124,6 +234,40 @@ impl<S: BlockStore> Indexes<'_, S> {
             .map_err(Into::into)
     }
 
+    /// The sort keys of each composite index maintained for the partition.
+    async fn composites(&self) -> Result<Vec<Vec<SortKey>>> {
+        let composites_cid = cid::from_value(&Cid(format!("{}-{COMPOSITES}", self.owner)))?;
+        let Some(data) = self.store.get(self.owner, self.partition, &composites_cid).await? else {
+            return Ok(vec![]);
+        };
+        block::decode(&data).map_err(Into::into)
+    }
+
+    // Get the composite index for the sort keys, building it from the reverse
+    // lookup index when first used. Once built, the index is maintained as
+    // entries are inserted and deleted.
+    async fn composite(&self, sort_keys: &[SortKey]) -> Result<Index> {
+        let mut composites = self.composites().await?;
+        if composites.iter().any(|keys| keys == sort_keys) {
+            return self.get(&composite_field(sort_keys)).await;
+        }
+
+        let mut index = Index::new(composite_field(sort_keys));
+        for item in self.get("message_cid").await?.items.into_values() {
+            index.insert(&composite_value(&item.fields, sort_keys), item);
+        }
+
+        composites.push(sort_keys.to_vec());
+        let composites_cid = cid::from_value(&Cid(format!("{}-{COMPOSITES}", self.owner)))?;
+        self.store.delete(self.owner, self.partition, &composites_cid).await?;
+        self.store
+            .put(self.owner, self.partition, &composites_cid, &block::encode(&composites)?)
+            .await?;
+
+        self.put(index.clone()).await?;
+        Ok(index)
+    }
+
     // This query strategy is used when the filter contains a property that
     // leads to a concise, or small, set of results that can be sorted and
     // paged in memory.
@@ -155,10 +299,7 @@ impl<S: BlockStore> Indexes<'_, S> {
 
                 // a set of matchers are 'AND-ed' together
                 for matcher in &match_set.inner {
-                    let Some(index_value) = item.fields.get(&matcher.field) else {
-                        continue 'next_item;
-                    };
-                    if !matcher.is_match(index_value)? {
+                    if !matcher.is_match_fields(&item.fields)? {
                         continue 'next_item;
                     }
                 }
@@ -180,16 +321,69 @@ impl<S: BlockStore> Indexes<'_, S> {
 
         // paginate results
         if let Some(pagination) = &query.pagination {
-            let limit = pagination.limit.unwrap_or(items.len());
-            let start = pagination.cursor.as_ref().map_or(0, |cursor| {
-                items.iter().position(|item| item.message_cid == cursor.message_cid).unwrap_or(0)
-            });
-
-            let mut end = start + limit + 1;
-            if end > items.len() {
-                end = items.len();
+            items = paginate(items, pagination);
+        }
+
+        Ok(items)
+    }
+
+    // This query strategy is used when the query specifies composite sort
+    // keys. Small result sets (see `query_concise`) are sorted and paged in
+    // memory, otherwise the composite index for the keys is read in order,
+    // starting from the cursor.
+    async fn query_composite(&self, query: &Query) -> Result<Vec<IndexItem>> {
+        if query.is_concise() {
+            let unsorted = Query {
+                sort_keys: vec![],
+                pagination: None,
+                ..query.clone()
+            };
+            let mut items = self.query_concise(&unsorted).await?;
+            items.sort_by(|a, b| compare(a, b, &query.sort_keys));
+            if let Some(pagination) = &query.pagination {
+                items = paginate(items, pagination);
             }
-            items = items[start..end].to_vec();
+            return Ok(items);
+        }
+
+        let (limit, cursor) =
+            query.pagination.as_ref().map_or((None, None), |p| (p.limit, p.cursor.as_ref()));
+
+        // the location in the index to begin querying from: the cursor item's
+        // composite value is found using the reverse lookup index
+        let mut start_key = Unbounded;
+        if let Some(cursor) = cursor {
+            let messages = self.get("message_cid").await?;
+            if let Some(item) = messages.items.get(&cursor.message_cid) {
+                let value = composite_value(&item.fields, &query.sort_keys);
+                start_key = Included(format!("{value}{NULL}{}", cursor.message_cid));
+            }
+        }
+        let index = self.composite(&query.sort_keys).await?;
+
+        select(index.lower_bound(start_key).map(|(_, item)| item), query, limit)
+    }
+
+    // Text search results are ranked by the number of field hits. Every match
+    // is ranked before paginating so each page holds the best remaining
+    // matches.
+    async fn query_ranked(&self, query: &Query) -> Result<Vec<IndexItem>> {
+        let unpaged = Query {
+            pagination: None,
+            ..query.clone()
+        };
+        let mut items = if !unpaged.sort_keys.is_empty() {
+            self.query_composite(&unpaged).await?
+        } else if unpaged.is_concise() {
+            self.query_concise(&unpaged).await?
+        } else {
+            self.query_full(&unpaged).await?
+        };
+
+        rank(&mut items, query);
+
+        if let Some(pagination) = &query.pagination {
+            items = paginate(items, pagination);
         }
 
         Ok(items)
@@ -198,9 +392,6 @@ impl<S: BlockStore> Indexes<'_, S> {
     // This query strategy is used when the filter will return a larger set of
     // results.
     async fn query_full(&self, query: &Query) -> Result<Vec<IndexItem>> {
-        let mut items = Vec::new();
-        let mut matches = HashSet::new();
-
         let (limit, cursor) =
             query.pagination.as_ref().map_or((None, None), |p| (p.limit, p.cursor.as_ref()));
 
@@ -209,47 +400,161 @@ impl<S: BlockStore> Indexes<'_, S> {
             cursor.map_or(Unbounded, |c| Included(format!("{}{NULL}{}", c.value, c.message_cid)));
         let index = self.get(&query.sort.to_string()).await?;
 
-        // starting from `start_key`, select matching index items until limit
-        for (value, item) in index.lower_bound(start_key) {
-            // stop when page limit + 1 is reached
-            if let Some(lim) = limit {
-                if items.len() == lim + 1 {
-                    break;
-                }
-            }
+        select(index.lower_bound(start_key).map(|(_, item)| item), query, limit)
+    }
+}
 
-            if matches.contains(&item.message_cid) {
-                continue;
+// Select matching index items, in order, until the page limit (plus one item
+// to use as the next page's cursor) is reached.
+fn select<'a>(
+    items: impl Iterator<Item = &'a IndexItem>, query: &Query, limit: Option<usize>,
+) -> Result<Vec<IndexItem>> {
+    let mut selected = Vec::new();
+    let mut matches = HashSet::new();
+
+    for item in items {
+        // stop when page limit + 1 is reached
+        if let Some(lim) = limit {
+            if selected.len() == lim + 1 {
+                break;
             }
+        }
 
-            if query.match_sets.is_empty() {
-                matches.insert(item.message_cid.clone());
-                items.push(item.clone());
-                continue;
-            }
+        if matches.contains(&item.message_cid) {
+            continue;
+        }
 
-            // match sets are 'OR-ed' together
-            'next_set: for match_set in &query.match_sets {
-                // a set of matchers are 'AND-ed' together
-                for matcher in &match_set.inner {
-                    let Some(index_value) = item.fields.get(&matcher.field) else {
-                        continue 'next_set;
-                    };
-                    if !matcher.is_match(index_value)? {
-                        continue 'next_set;
-                    }
+        if query.match_sets.is_empty() {
+            matches.insert(item.message_cid.clone());
+            selected.push(item.clone());
+            continue;
+        }
+
+        // match sets are 'OR-ed' together
+        'next_set: for match_set in &query.match_sets {
+            // a set of matchers are 'AND-ed' together
+            for matcher in &match_set.inner {
+                if !matcher.is_match_fields(&item.fields)? {
+                    continue 'next_set;
                 }
-                matches.insert(item.message_cid.clone());
-                items.push(item.clone());
             }
+            matches.insert(item.message_cid.clone());
+            selected.push(item.clone());
         }
+    }
 
-        Ok(items)
+    Ok(selected)
+}
+
+/// Sort and page items held in memory, rather than read from an index, in
+/// the order a query of the index would return them.
+pub fn sort_and_paginate(mut items: Vec<IndexItem>, query: &Query) -> Vec<IndexItem> {
+    if query.sort_keys.is_empty() {
+        let field = query.sort.to_string();
+        items.sort_by(|a, b| {
+            let a_val = a.fields.get(&field).map_or("", String::as_str);
+            let b_val = b.fields.get(&field).map_or("", String::as_str);
+            a_val.cmp(b_val).then_with(|| a.message_cid.cmp(&b.message_cid))
+        });
+        if !query.sort.is_ascending() {
+            items.reverse();
+        }
+    } else {
+        items.sort_by(|a, b| compare(a, b, &query.sort_keys));
+    }
+    rank(&mut items, query);
+
+    if let Some(pagination) = &query.pagination {
+        items = paginate(items, pagination);
+    }
+    items
+}
+
+// Order text search results by the number of field hits, highest first. The
+// sort is stable so equally ranked items keep the query's sort order.
+fn rank(items: &mut [IndexItem], query: &Query) {
+    if let Some(text) = query.text() {
+        items.sort_by_key(|item| Reverse(text.hits(&item.fields).unwrap_or_default()));
     }
 }
 
+// Select a page of items starting from the cursor, including one extra item
+// to use as the cursor for the next page.
+fn paginate(items: Vec<IndexItem>, pagination: &Pagination) -> Vec<IndexItem> {
+    let limit = pagination.limit.unwrap_or(items.len());
+    let start = pagination.cursor.as_ref().map_or(0, |cursor| {
+        items.iter().position(|item| item.message_cid == cursor.message_cid).unwrap_or(0)
+    });
+
+    let mut end = start + limit + 1;
+    if end > items.len() {
+        end = items.len();
+    }
+    items[start..end].to_vec()
+}
+
+// Compare index items using each sort key in turn, in the order the composite
+// index for the keys holds them. Items missing a sort field are ordered last
+// and `message_cid` is used as a tie-breaker.
+fn compare(a: &IndexItem, b: &IndexItem, sort_keys: &[SortKey]) -> Ordering {
+    composite_value(&a.fields, sort_keys)
+        .cmp(&composite_value(&b.fields, sort_keys))
+        .then_with(|| a.message_cid.cmp(&b.message_cid))
+}
+
+// The name of the composite index for the sort keys.
+fn composite_field(sort_keys: &[SortKey]) -> String {
+    let keys = sort_keys
+        .iter()
+        .map(|key| format!("{}{}", key.field, if key.descending { '-' } else { '+' }))
+        .collect::<Vec<_>>();
+    format!("sort:{}", keys.join(","))
+}
+
+// Encode the item's sort field values so the encoded values order as the sort
+// keys specify. Numeric values (e.g. number tags) are ordered numerically and
+// before other values, which are ordered by their bytes.
+#[allow(clippy::cast_precision_loss)]
+fn composite_value(fields: &HashMap<String, String>, sort_keys: &[SortKey]) -> String {
+    let mut composite = String::new();
+    for key in sort_keys {
+        let Some(value) = fields.get(&key.field) else {
+            composite.push(MISSING);
+            continue;
+        };
+
+        let encoded = match (value.parse::<u64>(), Tag::decode_number(value)) {
+            (Ok(n), _) => format!("1{:016x}{n:016x}", ordered_bits(n as f64)),
+            (Err(_), Some(n)) => format!("1{:016x}{:016x}", ordered_bits(n), 0),
+            (Err(_), None) => value.bytes().fold(String::from("2"), |mut hex, b| {
+                let _ = write!(hex, "{b:02x}");
+                hex
+            }),
+        };
+
+        // descending values are complemented digit by digit
+        if key.descending {
+            composite.extend(encoded.chars().map(|c| {
+                let digit = c.to_digit(16).unwrap_or_default();
+                char::from_digit(15 - digit, 16).unwrap_or(c)
+            }));
+            composite.push(DESCENDING_END);
+        } else {
+            composite.push_str(&encoded);
+            composite.push(ASCENDING_END);
+        }
+    }
+    composite
+}
+
+// Map a float to bits that order as `f64::total_cmp` orders floats.
+const fn ordered_bits(n: f64) -> u64 {
+    let bits = n.to_bits();
+    if bits >> 63 == 1 { !bits } else { bits | (1 << 63) }
+}
+
 /// `Index` wraps a physical index, providing helper methods.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Index {
     field: String,
     items: BTreeMap<String, IndexItem>,
@@ -379,16 +684,14 @@ impl<'a, S: BlockStore> IndexesBuilder<Owner<'a>, Partition<'a>, Store<'a, S>> {
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
-    use std::str::FromStr;
 
-    use anyhow::Result;
-    use blockstore::{Blockstore as _, InMemoryBlockstore};
     use rand::RngCore;
     use test_node::key_store;
 
     use super::*;
     use crate::interfaces::protocols::{ConfigureBuilder, Definition};
     use crate::interfaces::records::{Data, WriteBuilder};
+    use crate::store::memory::BlockStoreImpl;
     use crate::store::{ProtocolsQueryBuilder, RecordsFilter, RecordsQueryBuilder};
 
     const PARTITION: &str = "TEST";
@@ -432,6 +735,71 @@ mod tests {
         let items = super::query(&alice.did, PARTITION, &query, &block_store).await.unwrap();
     }
 
+    // Composite sorts are read in order from a composite index, which is
+    // maintained as entries are added once built.
+    #[tokio::test]
+    async fn query_composite() {
+        let block_store = BlockStoreImpl::new();
+        let alice = key_store::new_keyring();
+
+        let mut message_cids = vec![];
+        for (i, priority) in [Some("1"), Some("3"), None, Some("3"), Some("20")].iter().enumerate()
+        {
+            let write = WriteBuilder::new()
+                .data(Data::from(vec![u8::try_from(i).unwrap()]))
+                .sign(&alice)
+                .build()
+                .await
+                .unwrap();
+            let mut entry = Entry::from(&write);
+            entry.indexes.insert("dateCreated".to_string(), format!("2025-01-0{}", i + 1));
+            if let Some(priority) = priority {
+                entry.indexes.insert("tag.priority".to_string(), (*priority).to_string());
+            }
+            message_cids.push(entry.cid().unwrap());
+            super::insert(&alice.did, PARTITION, &entry, &block_store).await.unwrap();
+
+            // the composite index is built by the first query using it
+            if i == 2 {
+                let query = Query {
+                    sort_keys: vec![
+                        SortKey::descending("tag.priority"),
+                        SortKey::ascending("dateCreated"),
+                    ],
+                    ..Query::default()
+                };
+                let items =
+                    super::query(&alice.did, PARTITION, &query, &block_store).await.unwrap();
+                assert_eq!(items.len(), 3);
+            }
+        }
+
+        // priority descending (numerically), then date created ascending, with
+        // the record missing a priority last
+        let expected = [4, 1, 3, 0, 2].map(|i| message_cids[i].clone());
+
+        let mut query = Query {
+            sort_keys: vec![SortKey::descending("tag.priority"), SortKey::ascending("dateCreated")],
+            pagination: Some(Pagination::new().limit(2)),
+            ..Query::default()
+        };
+        let mut received = vec![];
+        loop {
+            let mut items =
+                super::query(&alice.did, PARTITION, &query, &block_store).await.unwrap();
+            let cursor = (items.len() > 2).then(|| items.pop()).flatten();
+            received.extend(items.into_iter().map(|item| item.message_cid));
+            let Some(cursor) = cursor else {
+                break;
+            };
+            query.pagination = Some(Pagination::new().limit(2).cursor(crate::store::Cursor {
+                message_cid: cursor.message_cid,
+                value: String::new(),
+            }));
+        }
+        assert_eq!(received, expected);
+    }
+
     #[tokio::test]
     async fn query_protocols() {
         let block_store = BlockStoreImpl::new();
@@ -458,43 +826,4 @@ mod tests {
         let query = ProtocolsQueryBuilder::new().protocol("http://minimal.xyz").build();
         let items = super::query(&alice.did, PARTITION, &query, &block_store).await.unwrap();
     }
-
-    struct BlockStoreImpl {
-        blockstore: InMemoryBlockstore<64>,
-    }
-
-    impl BlockStoreImpl {
-        pub fn new() -> Self {
-            Self {
-                blockstore: InMemoryBlockstore::<64>::new(),
-            }
-        }
-    }
-
-    impl BlockStore for BlockStoreImpl {
-        async fn put(&self, owner: &str, partition: &str, cid: &str, data: &[u8]) -> Result<()> {
-            // HACK: convert libipld CID to blockstore CID
-            let block_cid = ::cid::Cid::from_str(cid)?;
-            self.blockstore.put_keyed(&block_cid, data).await.map_err(Into::into)
-        }
-
-        async fn get(&self, owner: &str, partition: &str, cid: &str) -> Result<Option<Vec<u8>>> {
-            // HACK: convert libipld CID to blockstore CID
-            let block_cid = ::cid::Cid::try_from(cid)?;
-            let Some(bytes) = self.blockstore.get(&block_cid).await? else {
-                return Ok(None);
-            };
-            Ok(Some(bytes))
-        }
-
-        async fn delete(&self, owner: &str, partition: &str, cid: &str) -> Result<()> {
-            let cid = ::cid::Cid::from_str(cid)?;
-            self.blockstore.remove(&cid).await?;
-            Ok(())
-        }
-
-        async fn purge(&self, owner: &str, partition: &str) -> Result<()> {
-            unimplemented!()
-        }
-    }
 }
@@ -0,0 +1,54 @@
+//! # In-memory Block Store
+//!
+//! A [`BlockStore`] holding blocks in memory, shared by the store's unit
+//! tests.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::Result;
+
+use crate::provider::BlockStore;
+
+// Blocks keyed by owner, partition, and CID.
+type Blocks = HashMap<(String, String, String), Vec<u8>>;
+
+#[derive(Default)]
+pub struct BlockStoreImpl {
+    blocks: Mutex<Blocks>,
+}
+
+impl BlockStoreImpl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn blocks(&self) -> std::sync::MutexGuard<'_, Blocks> {
+        self.blocks.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl BlockStore for BlockStoreImpl {
+    async fn put(&self, owner: &str, partition: &str, cid: &str, data: &[u8]) -> Result<()> {
+        self.blocks().insert(key(owner, partition, cid), data.to_vec());
+        Ok(())
+    }
+
+    async fn get(&self, owner: &str, partition: &str, cid: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.blocks().get(&key(owner, partition, cid)).cloned())
+    }
+
+    async fn delete(&self, owner: &str, partition: &str, cid: &str) -> Result<()> {
+        self.blocks().remove(&key(owner, partition, cid));
+        Ok(())
+    }
+
+    async fn purge(&self, owner: &str, partition: &str) -> Result<()> {
+        self.blocks().retain(|(o, p, _), _| o != owner || p != partition);
+        Ok(())
+    }
+}
+
+fn key(owner: &str, partition: &str, cid: &str) -> (String, String, String) {
+    (owner.to_string(), partition.to_string(), cid.to_string())
+}
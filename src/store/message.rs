@@ -1,20 +1,26 @@
 //! # Message Store
 
+use std::collections::{BTreeMap, HashSet};
+
 use crate::provider::BlockStore;
-use crate::store::{Cursor, Entry, Query, block, index};
-use crate::{Result, unexpected};
+use crate::store::index::IndexItem;
+use crate::store::{Cursor, Entry, EntryType, GroupBy, Query, StoreStats, block, index};
+use crate::{Method, Result, unexpected};
 
 const PARTITION: &str = "MESSAGE";
 
 /// Store a message in the underlying store.
 pub async fn put(owner: &str, entry: &Entry, store: &impl BlockStore) -> Result<()> {
-    // store entry block
+    // store entry block, along with its pin state
+    let mut entry = entry.clone();
+    entry.pinned = entry.indexes.get("pinned").is_some_and(|pinned| pinned == "true");
+
     let message_cid = entry.cid()?;
     store.delete(owner, PARTITION, &message_cid).await?;
-    store.put(owner, PARTITION, &message_cid, &block::encode(entry)?).await?;
+    store.put(owner, PARTITION, &message_cid, &block::encode(&entry)?).await?;
 
     // index entry
-    index::insert(owner, PARTITION, entry, store).await
+    index::insert(owner, PARTITION, &entry, store).await
 }
 
 /// Queries the underlying store for matches to the provided query.
@@ -22,20 +28,7 @@ pub async fn query(
     owner: &str, query: &Query, store: &impl BlockStore,
 ) -> Result<(Vec<Entry>, Option<Cursor>)> {
     let mut results = index::query(owner, PARTITION, query, store).await?;
-
-    // return cursor when paging is used
-    let limit = query.pagination.as_ref().map_or(0, |p| p.limit.unwrap_or(0));
-    let cursor = if limit > 0 && limit < results.len() {
-        let sort_field = query.sort.to_string();
-
-        // set cursor to the last item remaining after the spliced result.
-        results.pop().map(|item| Cursor {
-            message_cid: item.message_cid.clone(),
-            value: item.fields[&sort_field].clone(),
-        })
-    } else {
-        None
-    };
+    let cursor = next_cursor(&mut results, query);
 
     let mut entries = Vec::new();
     for item in results {
@@ -48,6 +41,80 @@ pub async fn query(
     Ok((entries, cursor))
 }
 
+/// Remove the extra item included in a page of results, returning it as the
+/// cursor for the next page. Returns `None` when paging is not used or there
+/// are no more results.
+pub(crate) fn next_cursor(results: &mut Vec<IndexItem>, query: &Query) -> Option<Cursor> {
+    let limit = query.pagination.as_ref().map_or(0, |p| p.limit.unwrap_or(0));
+    if limit == 0 || limit >= results.len() {
+        return None;
+    }
+
+    // set cursor to the last item remaining after the spliced result.
+    let sort_field = query.sort.to_string();
+    results.pop().map(|item| Cursor {
+        value: item.fields.get(&sort_field).cloned().unwrap_or_default(),
+        message_cid: item.message_cid,
+    })
+}
+
+/// Count the messages matching the provided query. Pagination is ignored and
+/// only index items are read, so no message blocks are fetched.
+pub async fn count(owner: &str, query: &Query, store: &impl BlockStore) -> Result<u64> {
+    let mut query = query.clone();
+    query.pagination = None;
+    let results = index::query(owner, PARTITION, &query, store).await?;
+    Ok(results.len() as u64)
+}
+
+/// Count the messages matching the query for each value of the `group_by`
+/// field. Only index items are read, so no message blocks are fetched.
+pub async fn count_by(
+    owner: &str, query: &Query, group_by: GroupBy, store: &impl BlockStore,
+) -> Result<BTreeMap<String, u64>> {
+    let mut query = query.clone();
+    query.pagination = None;
+    let results = index::query(owner, PARTITION, &query, store).await?;
+
+    let field = group_by.to_string();
+    let mut groups = BTreeMap::new();
+    for item in results {
+        if let Some(value) = item.fields.get(&field) {
+            *groups.entry(value.clone()).or_insert(0) += 1;
+        }
+    }
+    Ok(groups)
+}
+
+/// Compute statistics for the owner's stored messages. Only index items are
+/// read, so no message blocks are fetched.
+pub async fn stats(owner: &str, store: &impl BlockStore) -> Result<StoreStats> {
+    let items = index::items(owner, PARTITION, store).await?;
+
+    let mut stats = StoreStats {
+        messages: items.len() as u64,
+        ..StoreStats::default()
+    };
+    let mut protocols = HashSet::new();
+
+    for item in &items {
+        if let Some(protocol) = item.fields.get("protocol") {
+            protocols.insert(protocol.as_str());
+        }
+
+        // count data for the latest write of each record only
+        let is_write = item.fields.get("method").is_some_and(|m| *m == Method::Write.to_string());
+        let is_initial = item.fields.get("initial").is_some_and(|i| i == "true");
+        if is_write && !is_initial {
+            let data_size = item.fields.get("dataSize").and_then(|s| s.parse::<u64>().ok());
+            stats.data_bytes += data_size.unwrap_or_default();
+        }
+    }
+    stats.protocols = protocols.len() as u64;
+
+    Ok(stats)
+}
+
 /// Fetch a single message by CID from the underlying store, returning
 /// `None` if no message was found.
 pub async fn get(owner: &str, message_cid: &str, store: &impl BlockStore) -> Result<Option<Entry>> {
@@ -57,6 +124,67 @@ pub async fn get(owner: &str, message_cid: &str, store: &impl BlockStore) -> Res
     Ok(Some(block::decode(&bytes)?))
 }
 
+/// Get a stored message along with its index fields.
+pub(crate) async fn get_indexed(
+    owner: &str, message_cid: &str, store: &impl BlockStore,
+) -> Result<Option<Entry>> {
+    let Some(mut entry) = get(owner, message_cid, store).await? else {
+        return Ok(None);
+    };
+    if let Some(item) = index::item(owner, PARTITION, message_cid, store).await? {
+        entry.indexes = item.fields;
+    }
+    Ok(Some(entry))
+}
+
+/// Rebuild the owner's indexes from stored messages.
+///
+/// The reverse lookup index is used to enumerate stored messages. Index
+/// fields are re-derived from each message and its stored pin state, with
+/// fields added when the message was handled (e.g. `initial`) carried over.
+/// Messages no longer stored are dropped from the indexes.
+pub async fn rebuild_indexes(owner: &str, store: &impl BlockStore) -> Result<()> {
+    let mut entries = Vec::new();
+
+    for item in index::items(owner, PARTITION, store).await? {
+        let Some(bytes) = store.get(owner, PARTITION, &item.message_cid).await? else {
+            continue;
+        };
+        let stored: Entry = block::decode(&bytes)?;
+        let mut entry = match &stored.message {
+            EntryType::Write(write) => Entry::from(write),
+            EntryType::Delete(delete) => Entry::from(delete),
+            EntryType::Configure(configure) => Entry::from(configure),
+        };
+        if stored.pinned {
+            entry.set_index("pinned", true.to_string());
+        }
+        for (field, value) in item.fields {
+            entry.add_index(field, value);
+        }
+        entries.push(entry);
+    }
+
+    index::rebuild(owner, PARTITION, &entries, store).await
+}
+
+/// Set a single index field for a stored message, keeping its other index
+/// fields. Used for fields that are not derived from the message itself
+/// (e.g. `pinned`).
+pub async fn set_index(
+    owner: &str, message_cid: &str, field: &str, value: &str, store: &impl BlockStore,
+) -> Result<()> {
+    let Some(mut entry) = get(owner, message_cid, store).await? else {
+        return Err(unexpected!("message not found"));
+    };
+    let Some(item) = index::item(owner, PARTITION, message_cid, store).await? else {
+        return Err(unexpected!("message is not indexed"));
+    };
+    entry.indexes = item.fields;
+    entry.set_index(field, value);
+    put(owner, &entry, store).await
+}
+
 /// Delete message associated with the specified id.
 pub async fn delete(owner: &str, message_cid: &str, store: &impl BlockStore) -> Result<()> {
     index::delete(owner, PARTITION, message_cid, store).await?;
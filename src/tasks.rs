@@ -26,7 +26,6 @@
 //!    expired tasks for distributed processing when there are no resumable
 //!    tasks in the queue.
 
-use chrono::Utc;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use tokio::time::{Duration, sleep};
@@ -42,7 +41,7 @@ const EXTEND_SECS: u64 = 30;
 /// Runs a resumable task with automatic timeout extension.
 pub async fn run(owner: &str, task: TaskType, provider: &impl Provider) -> Result<()> {
     // register the task
-    let timeout = (Utc::now() + Duration::from_secs(EXTEND_SECS * 2)).timestamp();
+    let timeout = (provider.clock().now() + Duration::from_secs(EXTEND_SECS * 2)).timestamp();
     let timeout =
         u64::try_from(timeout).map_err(|e| unexpected!("issue converting timeout: {e}"))?;
 
@@ -32,7 +32,9 @@ pub mod cid {
     use std::io::Read;
 
     use cid::Cid;
+    use futures::AsyncRead;
     use futures::executor::block_on;
+    use futures::io::AllowStdIo;
     use multihash_codetable::MultihashDigest;
     use serde::Serialize;
 
@@ -49,10 +51,26 @@ pub mod cid {
     ///
     /// Fails when the payload cannot be serialized to CBOR.
     pub fn from_value<T: Serialize>(payload: &T) -> Result<String> {
+        Ok(from_bytes(&to_cbor(payload)?))
+    }
+
+    /// Serialize the payload to the CBOR its CID is computed from.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the payload cannot be serialized to CBOR.
+    pub fn to_cbor<T: Serialize>(payload: &T) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
         ciborium::into_writer(payload, &mut buf)?;
-        let hash = multihash_codetable::Code::Sha2_256.digest(&buf);
-        Ok(Cid::new_v1(RAW, hash).to_string())
+        Ok(buf)
+    }
+
+    /// Compute the CID of the provided bytes, such as those serialized by
+    /// [`to_cbor`].
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8]) -> String {
+        let hash = multihash_codetable::Code::Sha2_256.digest(bytes);
+        Cid::new_v1(RAW, hash).to_string()
     }
 
     /// Compute a CID for the provided data reader.
@@ -63,7 +81,21 @@ pub mod cid {
     /// mock [`BlockStore`].
     pub fn from_reader(reader: impl Read) -> Result<(String, usize)> {
         // use the default storage algorithm to compute CID and size
-        block_on(async { data::put("owner", "record_id", "data_cid", reader, &MockStore).await })
+        block_on(from_async_reader(AllowStdIo::new(reader)))
+    }
+
+    /// Compute a CID for the provided asynchronous data reader, reading the
+    /// data in chunks rather than buffering it in memory.
+    ///
+    /// # Errors
+    ///
+    /// Fails when there is an issue reading the data or processing it using
+    /// the mock [`BlockStore`].
+    pub async fn from_async_reader(reader: impl AsyncRead + Unpin) -> Result<(String, usize)> {
+        // use the default storage algorithm to compute CID and size
+        let (root, size) =
+            data::put_unregistered("owner", "record_id", "data_cid", reader, &MockStore).await?;
+        Ok((root.cid().to_string(), size))
     }
 
     struct MockStore;
@@ -73,11 +105,12 @@ pub mod cid {
         }
 
         async fn get(&self, _: &str, _: &str, _: &str) -> anyhow::Result<Option<Vec<u8>>> {
-            unimplemented!("MockStore::get")
+            Ok(None)
         }
 
+        // nothing is stored, so blocks removed after a read error are ignored
         async fn delete(&self, _: &str, _: &str, _: &str) -> anyhow::Result<()> {
-            unimplemented!("MockStore::delete")
+            Ok(())
         }
 
         async fn purge(&self, _: &str, _: &str) -> anyhow::Result<()> {
@@ -0,0 +1,683 @@
+//! Endpoint
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use dwn_node::endpoint::{self, CONTENT_TYPE_CBOR, CONTENT_TYPE_JSON, Request};
+use dwn_node::interfaces::protocols::{ConfigureBuilder, Definition};
+use dwn_node::interfaces::records::{
+    Data, DeleteBuilder, ProtocolBuilder, QueryBuilder, ReadBuilder, RecordsFilter, Tag,
+    WriteBuilder,
+};
+use dwn_node::provider::{
+    CachedResolver, DidResolver, Document, EventLog, MessageStore, ResolverCache,
+};
+use dwn_node::store::MAX_ENCODED_SIZE;
+use dwn_node::{Error, Message, StatusCode, cid};
+use serde_json::json;
+use test_node::key_store;
+use test_node::provider::ProviderImpl;
+use tracing_test::traced_test;
+
+static ALICE: LazyLock<key_store::Keyring> = LazyLock::new(|| key_store::new_keyring());
+
+// Should decode a write encoded as DAG-CBOR with an unchanged descriptor.
+#[tokio::test]
+async fn cbor_round_trip() {
+    let write = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    // --------------------------------------------------
+    // Encode the write as DAG-CBOR and decode it.
+    // --------------------------------------------------
+    let bytes = serde_ipld_dagcbor::to_vec(&write).expect("should encode");
+    let Request::RecordsWrite(decoded) =
+        endpoint::decode_message(&bytes, CONTENT_TYPE_CBOR).expect("should decode")
+    else {
+        panic!("should be RecordsWrite");
+    };
+
+    // --------------------------------------------------
+    // Verify the descriptor (and message) are unchanged.
+    // --------------------------------------------------
+    let expected = serde_ipld_dagcbor::to_vec(&write.descriptor).expect("should encode");
+    let actual = serde_ipld_dagcbor::to_vec(&decoded.descriptor).expect("should encode");
+    assert_eq!(actual, expected);
+    assert_eq!(decoded.cid().unwrap(), write.cid().unwrap());
+}
+
+// Should decode a write encoded as JSON.
+#[tokio::test]
+async fn json_round_trip() {
+    let write = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    let bytes = serde_json::to_vec(&write).expect("should encode");
+    let Request::RecordsWrite(decoded) =
+        endpoint::decode_message(&bytes, "application/json; charset=utf-8").expect("should decode")
+    else {
+        panic!("should be RecordsWrite");
+    };
+    assert_eq!(
+        cid::from_value(&decoded.descriptor).unwrap(),
+        cid::from_value(&write.descriptor).unwrap()
+    );
+}
+
+// Should accept a write referencing stored data after decoding it from JSON.
+#[tokio::test]
+async fn reference_data_round_trip() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes record A with data.
+    // --------------------------------------------------
+    let data = vec![1u8; MAX_ENCODED_SIZE + 10];
+    let write_a = WriteBuilder::new()
+        .data(Data::from(data.clone()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, write_a.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Record B, referencing A's data, is sent as JSON.
+    // --------------------------------------------------
+    let write_b = WriteBuilder::new()
+        .reference_data(&write_a.descriptor.data_cid, write_a.descriptor.data_size)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    let bytes = serde_json::to_vec(&write_b).expect("should encode");
+    let Request::RecordsWrite(decoded) =
+        endpoint::decode_message(&bytes, CONTENT_TYPE_JSON).expect("should decode")
+    else {
+        panic!("should be RecordsWrite");
+    };
+    let reply = endpoint::handle(&ALICE.did, decoded, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // B's data can be read.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&write_b.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let read_stream = body.entry.data.expect("should have data");
+    assert_eq!(read_stream.into_inner(), data);
+}
+
+// Should reject malformed messages and unsupported content.
+#[tokio::test]
+async fn invalid_message() {
+    // --------------------------------------------------
+    // Unknown interface and method combination.
+    // --------------------------------------------------
+    let message = json!({
+        "descriptor": {
+            "interface": "Protocols",
+            "method": "Read",
+            "messageTimestamp": "2025-01-01T00:00:00.000000Z"
+        }
+    });
+    let bytes = serde_json::to_vec(&message).unwrap();
+    let Err(Error::BadRequest(e)) = endpoint::decode_message(&bytes, CONTENT_TYPE_JSON) else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "unsupported message: ProtocolsRead");
+
+    // --------------------------------------------------
+    // Missing descriptor.
+    // --------------------------------------------------
+    let bytes = serde_json::to_vec(&json!({"recordId": "1234"})).unwrap();
+    let Err(Error::BadRequest(e)) = endpoint::decode_message(&bytes, CONTENT_TYPE_JSON) else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "message is missing `descriptor`");
+
+    // --------------------------------------------------
+    // Malformed authorization.
+    // --------------------------------------------------
+    let message = json!({
+        "descriptor": {
+            "interface": "Records",
+            "method": "Query",
+            "messageTimestamp": "2025-01-01T00:00:00.000000Z"
+        },
+        "authorization": "not an authorization"
+    });
+    let bytes = serde_json::to_vec(&message).unwrap();
+    let Err(Error::BadRequest(e)) = endpoint::decode_message(&bytes, CONTENT_TYPE_JSON) else {
+        panic!("should be BadRequest");
+    };
+    assert!(e.starts_with("invalid authorization"));
+
+    // --------------------------------------------------
+    // Unsupported content type.
+    // --------------------------------------------------
+    let Err(Error::BadRequest(e)) = endpoint::decode_message(&bytes, "text/plain") else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "unsupported content type: text/plain");
+}
+
+// Should encode a reply as either JSON or DAG-CBOR.
+#[tokio::test]
+async fn encode_reply() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let write = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+
+    let bytes = endpoint::encode_reply(&reply, CONTENT_TYPE_JSON).expect("should encode");
+    let value: serde_json::Value = serde_json::from_slice(&bytes).expect("should decode");
+    assert_eq!(value["status"]["code"], StatusCode::ACCEPTED.as_u16());
+
+    let bytes = endpoint::encode_reply(&reply, CONTENT_TYPE_CBOR).expect("should encode");
+    let value: serde_json::Value = serde_ipld_dagcbor::from_slice(&bytes).expect("should decode");
+    assert_eq!(value["status"]["code"], StatusCode::ACCEPTED.as_u16());
+}
+
+// Should reject a message when the provider's rate limiter denies it.
+#[tokio::test]
+async fn rate_limit() {
+    let mut provider = ProviderImpl::new().await.expect("should create provider");
+    provider.rate_limit = Some(Duration::from_secs(60));
+
+    // --------------------------------------------------
+    // The first write is processed.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // A second write within the window is rejected.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"another write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::TooManyRequests(e)) = endpoint::handle(&ALICE.did, write, &provider).await
+    else {
+        panic!("should be TooManyRequests");
+    };
+    assert_eq!(e, "rate limit exceeded, retry after 60s");
+}
+
+// Should reject a message larger than the provider's maximum message size
+// before anything is stored.
+#[tokio::test]
+async fn message_too_large() {
+    let provider =
+        ProviderImpl::new().await.expect("should create provider").with_max_message_size(4096);
+
+    // --------------------------------------------------
+    // Alice writes a record with an enormous tag.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
+        .add_tag("notes", Tag::String("x".repeat(8192)))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write.clone(), &provider).await
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "message too large");
+
+    // --------------------------------------------------
+    // Nothing was stored.
+    // --------------------------------------------------
+    let cid = write.cid().expect("should compute CID");
+    let entry = MessageStore::get(&provider, &ALICE.did, &cid).await.expect("should get");
+    assert!(entry.is_none());
+    let watermark = EventLog::watermark(&provider, &ALICE.did).await.expect("should get");
+    assert_eq!(watermark, 0);
+}
+
+// Should reject a message when the signer's DID cannot be resolved.
+#[tokio::test]
+async fn unresolvable_signer() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let mut write = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    // --------------------------------------------------
+    // Replace the signer's key ID with one that cannot be resolved.
+    // --------------------------------------------------
+    let header = json!({"alg": "EdDSA", "typ": "jwt", "kid": "did:web:unknown.example#key-0"});
+    let protected = Base64UrlUnpadded::encode_string(header.to_string().as_bytes());
+
+    let mut value = serde_json::to_value(&write).expect("should serialize");
+    value["authorization"]["signature"]["signatures"][0]["protected"] = json!(protected);
+    write = serde_json::from_value(value).expect("should deserialize");
+
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, write, &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "unable to resolve signer DID");
+}
+
+// Should forbid a message signed with a key no longer in the signer's DID
+// document, such as a key that has been rotated out.
+#[tokio::test]
+async fn retired_signing_key() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let mut write = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    // --------------------------------------------------
+    // Replace the signer's key ID with one missing from Alice's DID document.
+    // --------------------------------------------------
+    let kid = format!("{}#retired-key", ALICE.did);
+    let header = json!({"alg": "EdDSA", "typ": "jwt", "kid": kid});
+    let protected = Base64UrlUnpadded::encode_string(header.to_string().as_bytes());
+
+    let mut value = serde_json::to_value(&write).expect("should serialize");
+    value["authorization"]["signature"]["signatures"][0]["protected"] = json!(protected);
+    write = serde_json::from_value(value).expect("should deserialize");
+
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, write, &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "signing key not found in the signer's DID document");
+}
+
+// Should only resolve a DID once within the cache's time-to-live.
+#[tokio::test]
+async fn cached_resolver() {
+    #[derive(Clone, Default)]
+    struct CountingResolver(Arc<AtomicUsize>);
+
+    impl DidResolver for CountingResolver {
+        async fn resolve(&self, _url: &str) -> anyhow::Result<Document> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(Document::default())
+        }
+    }
+
+    let counter = CountingResolver::default();
+    let resolver = CachedResolver::new(counter.clone(), Duration::from_secs(60));
+
+    resolver.resolve("did:web:example.com").await.expect("should resolve");
+    resolver.resolve("did:web:example.com").await.expect("should resolve");
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    assert_eq!(resolver.cache().hits(), 1);
+
+    resolver.resolve("did:web:example.org").await.expect("should resolve");
+    assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+}
+
+// Should evict the least recently used document and re-resolve invalidated
+// documents.
+#[tokio::test]
+async fn resolver_cache() {
+    #[derive(Clone, Default)]
+    struct CountingResolver(Arc<AtomicUsize>);
+
+    impl DidResolver for CountingResolver {
+        async fn resolve(&self, _url: &str) -> anyhow::Result<Document> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(Document::default())
+        }
+    }
+
+    let counter = CountingResolver::default();
+    let cache = Arc::new(ResolverCache::new(2, Duration::from_secs(60)));
+    let resolver = CachedResolver::with_cache(counter.clone(), cache.clone());
+
+    // --------------------------------------------------
+    // Fill the cache, then touch the first DID so the second is evicted.
+    // --------------------------------------------------
+    resolver.resolve("did:web:one.example").await.expect("should resolve");
+    resolver.resolve("did:web:two.example").await.expect("should resolve");
+    resolver.resolve("did:web:one.example").await.expect("should resolve");
+    resolver.resolve("did:web:three.example").await.expect("should resolve");
+    assert_eq!(counter.0.load(Ordering::SeqCst), 3);
+
+    resolver.resolve("did:web:two.example").await.expect("should resolve");
+    assert_eq!(counter.0.load(Ordering::SeqCst), 4);
+    assert_eq!(cache.hits(), 1);
+
+    // --------------------------------------------------
+    // Invalidate a document following key rotation.
+    // --------------------------------------------------
+    assert!(cache.invalidate("did:web:two.example"));
+    assert!(!cache.invalidate("did:web:two.example"));
+    resolver.resolve("did:web:two.example").await.expect("should resolve");
+    assert_eq!(counter.0.load(Ordering::SeqCst), 5);
+}
+
+// Should roll back every write in a batch when any one of them fails.
+#[tokio::test]
+async fn batch_rollback() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a batch of three records, the last of which is invalid.
+    // --------------------------------------------------
+    let mut writes = vec![];
+    for data in [b"first".to_vec(), b"second".to_vec(), b"third".to_vec()] {
+        let write = WriteBuilder::new()
+            .data(Data::from(data))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        writes.push(write);
+    }
+    writes[2].record_id = "invalid".to_string();
+
+    let Err(e) = endpoint::handle_batch(&ALICE.did, writes.clone(), &provider).await else {
+        panic!("should fail");
+    };
+    assert!(e.detail().starts_with("message 2: "));
+
+    // --------------------------------------------------
+    // None of the records were persisted.
+    // --------------------------------------------------
+    for write in &writes {
+        let query = QueryBuilder::new()
+            .filter(RecordsFilter::new().record_id(&write.record_id))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create query");
+        let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+        assert!(reply.body.is_none());
+    }
+
+    // --------------------------------------------------
+    // Without the invalid write, the batch is committed.
+    // --------------------------------------------------
+    writes.pop();
+    let replies =
+        endpoint::handle_batch(&ALICE.did, writes.clone(), &provider).await.expect("should write");
+    assert_eq!(replies.len(), 2);
+
+    for write in &writes {
+        let query = QueryBuilder::new()
+            .filter(RecordsFilter::new().record_id(&write.record_id))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create query");
+        let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+        let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+        assert_eq!(entries.len(), 1);
+    }
+}
+
+// Should undo the changes already applied when committing a batch fails part
+// way through.
+#[tokio::test]
+async fn batch_commit_undone() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a record outside of any batch.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"existing".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let (events, _) = EventLog::events(&provider, &ALICE.did, None).await.expect("should get");
+    assert_eq!(events.len(), 1);
+
+    // --------------------------------------------------
+    // Alice writes a batch of two records, one too large to encode in its
+    // message, with the block store failing at each point in turn until the
+    // batch is committed.
+    // --------------------------------------------------
+    let mut failures = 0;
+    let writes = loop {
+        let mut writes = vec![];
+        for data in [b"small".to_vec(), vec![1; MAX_ENCODED_SIZE + 10]] {
+            let write = WriteBuilder::new()
+                .data(Data::from(data))
+                .sign(&*ALICE)
+                .build()
+                .await
+                .expect("should create write");
+            writes.push(write);
+        }
+
+        *provider.fail_after_puts.lock().unwrap() = Some(failures);
+        let result = endpoint::handle_batch(&ALICE.did, writes.clone(), &provider).await;
+        *provider.fail_after_puts.lock().unwrap() = None;
+        if result.is_ok() {
+            break writes;
+        }
+        failures += 1;
+
+        // neither record, nor its event, remains
+        for write in &writes {
+            let query = QueryBuilder::new()
+                .filter(RecordsFilter::new().record_id(&write.record_id))
+                .sign(&*ALICE)
+                .build()
+                .await
+                .expect("should create query");
+            let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+            assert!(reply.body.is_none());
+        }
+        let (events, _) = EventLog::events(&provider, &ALICE.did, None).await.expect("should get");
+        assert_eq!(events.len(), 1);
+    };
+    assert!(failures > 0);
+
+    // --------------------------------------------------
+    // Once committed, both records can be read.
+    // --------------------------------------------------
+    for write in &writes {
+        let read = ReadBuilder::new()
+            .filter(RecordsFilter::new().record_id(&write.record_id))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create read");
+        let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+        assert_eq!(reply.status.code, StatusCode::OK);
+    }
+    let (events, _) = EventLog::events(&provider, &ALICE.did, None).await.expect("should get");
+    assert_eq!(events.len(), 3);
+}
+
+// Should emit a span carrying the message CID, recording the status of
+// successful messages and the error of failed ones.
+#[tokio::test]
+#[traced_test]
+async fn trace_span() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a record.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let message_cid = write.cid().expect("should compute CID");
+    let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    assert!(logs_contain(&format!("message_cid={message_cid}")));
+    assert!(logs_contain("status=202"));
+
+    // --------------------------------------------------
+    // Alice writes an invalid record.
+    // --------------------------------------------------
+    let mut write = WriteBuilder::new()
+        .data(Data::from(b"an invalid write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    write.record_id = "invalid".to_string();
+    let message_cid = write.cid().expect("should compute CID");
+    let Err(Error::BadRequest(_)) = endpoint::handle(&ALICE.did, write, &provider).await else {
+        panic!("should be BadRequest");
+    };
+
+    assert!(logs_contain(&format!("message_cid={message_cid}")));
+    assert!(logs_contain("error=BadRequest("));
+}
+
+// Should report message, data, and protocol statistics reflecting the current
+// state of the store.
+#[tokio::test]
+async fn store_stats() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures two protocols.
+    // --------------------------------------------------
+    let minimal = include_bytes!("protocols/minimal.json");
+    let allow_any = include_bytes!("protocols/allow-any.json");
+    for bytes in [&minimal[..], &allow_any[..]] {
+        let definition: Definition = serde_json::from_slice(bytes).expect("should deserialize");
+        let configure = ConfigureBuilder::new()
+            .definition(definition)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should build");
+        let reply = endpoint::handle(&ALICE.did, configure, &provider)
+            .await
+            .expect("should configure protocol");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // --------------------------------------------------
+    // Alice writes two records to the first protocol and one to the second.
+    // --------------------------------------------------
+    let mut writes = vec![];
+    for data in [vec![0u8; 10], vec![0u8; 20]] {
+        let write = WriteBuilder::new()
+            .data(Data::from(data))
+            .protocol(ProtocolBuilder {
+                protocol: "http://minimal.xyz",
+                protocol_path: "foo",
+                parent_context_id: None,
+            })
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        writes.push(write);
+    }
+
+    let write = WriteBuilder::new()
+        .data(Data::from(br#"{"message": "a post"}"#.to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let stats = endpoint::stats(&ALICE.did, &provider).await.expect("should get stats");
+    assert_eq!(stats.messages, 5);
+    assert_eq!(stats.data_bytes, 30 + write.descriptor.data_size as u64);
+    assert_eq!(stats.protocols, 2);
+
+    // --------------------------------------------------
+    // Alice deletes the larger record.
+    // --------------------------------------------------
+    let delete = DeleteBuilder::new()
+        .record_id(&writes[1].record_id)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create delete");
+    let reply = endpoint::handle(&ALICE.did, delete, &provider).await.expect("should delete");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // the initial write is retained alongside the delete, but its data is not
+    let stats = endpoint::stats(&ALICE.did, &provider).await.expect("should get stats");
+    assert_eq!(stats.messages, 6);
+    assert_eq!(stats.data_bytes, 10 + write.descriptor.data_size as u64);
+    assert_eq!(stats.protocols, 2);
+}
+
+// Should report every store as healthy when the provider is reachable.
+#[tokio::test]
+async fn health() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let report = endpoint::health(&provider).await.expect("should check health");
+    assert!(report.healthy());
+
+    for component in [&report.message_store, &report.block_store, &report.event_log] {
+        assert!(component.ok);
+        assert!(component.error.is_none());
+    }
+}
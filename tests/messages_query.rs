@@ -9,7 +9,7 @@ use std::sync::LazyLock;
 use dwn_node::interfaces::grants::{GrantBuilder, Scope};
 use dwn_node::interfaces::messages::{MessagesFilter, QueryBuilder, ReadBuilder};
 use dwn_node::interfaces::protocols::{ConfigureBuilder, Definition};
-use dwn_node::interfaces::records::{Data, ProtocolBuilder, WriteBuilder};
+use dwn_node::interfaces::records::{Data, DeleteBuilder, ProtocolBuilder, WriteBuilder};
 use dwn_node::{Error, Interface, Message, Method, StatusCode, endpoint};
 use test_node::key_store;
 use test_node::provider::ProviderImpl;
@@ -134,6 +134,183 @@ async fn owner_messages() {
     assert_eq!(reply.status.code, StatusCode::OK);
 }
 
+// Should page through messages and fetch only the most recent messages.
+#[tokio::test]
+async fn latest_messages() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes 5 records.
+    // --------------------------------------------------
+    let mut expected_cids = vec![];
+    for i in 1..=5 {
+        let write = WriteBuilder::new()
+            .data(Data::from(format!("record {i}").into_bytes()))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        expected_cids.push(write.cid().unwrap());
+
+        let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // --------------------------------------------------
+    // Alice queries for the 2 most recent messages, newest first.
+    // --------------------------------------------------
+    let query =
+        QueryBuilder::new().latest(2).sign(&*ALICE).build().await.expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries, vec![expected_cids[4].clone(), expected_cids[3].clone()]);
+    assert!(query_reply.cursor.is_some());
+
+    // --------------------------------------------------
+    // Alice pages through all messages, 2 at a time.
+    // --------------------------------------------------
+    let mut cids = vec![];
+    let mut cursor = None;
+    loop {
+        let mut builder = QueryBuilder::new().limit(2);
+        if let Some(cursor) = cursor {
+            builder = builder.cursor(cursor);
+        }
+        let query = builder.sign(&*ALICE).build().await.expect("should create query");
+        let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+        assert_eq!(reply.status.code, StatusCode::OK);
+
+        let query_reply = reply.body.expect("should have reply");
+        let entries = query_reply.entries.expect("should have entries");
+        assert!(entries.len() <= 2);
+        cids.extend(entries);
+
+        cursor = query_reply.cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(cids, expected_cids);
+}
+
+// Should filter messages by interface and method.
+#[tokio::test]
+async fn method_filter() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a record.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice deletes the record.
+    // --------------------------------------------------
+    let delete = DeleteBuilder::new()
+        .record_id(&write.record_id)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create delete");
+    let delete_cid = delete.cid().expect("should get CID");
+
+    let reply = endpoint::handle(&ALICE.did, delete, &provider).await.expect("should delete");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice queries for `RecordsDelete` messages and expects only the delete.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .add_filter(MessagesFilter::new().interface(Interface::Records).method(Method::Delete))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0], delete_cid);
+}
+
+// Should filter messages by author.
+#[tokio::test]
+async fn author_filter() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a protocol allowing anyone to write.
+    // --------------------------------------------------
+    let allow_any = include_bytes!("protocols/allow-any.json");
+    let definition: Definition = serde_json::from_slice(allow_any).expect("should deserialize");
+
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice and Bob each write a record to Alice's web node.
+    // --------------------------------------------------
+    let schema = definition.types["post"].schema.clone().expect("should have schema");
+    let mut bob_cids = vec![];
+
+    for author in [&*ALICE, &*BOB] {
+        let write = WriteBuilder::new()
+            .protocol(ProtocolBuilder {
+                protocol: &definition.protocol,
+                protocol_path: "post",
+                parent_context_id: None,
+            })
+            .schema(&schema)
+            .data(Data::from(br#"{"message": "test record write"}"#.to_vec()))
+            .sign(author)
+            .build()
+            .await
+            .expect("should create write");
+        if author.did == BOB.did {
+            bob_cids.push(write.cid().unwrap());
+        }
+
+        let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // --------------------------------------------------
+    // Alice queries for messages authored by Bob.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .add_filter(MessagesFilter::new().author(&BOB.did))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries, bob_cids);
+}
+
 // Should return a status of Forbidden (403) if the requestor is not the owner
 // and has no permission grant.
 #[tokio::test]
@@ -141,7 +318,9 @@ async fn no_grant() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     let query = QueryBuilder::new().sign(&*ALICE).build().await.expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&BOB.did, query, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&BOB.did, query, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "author has no grant");
@@ -188,6 +367,7 @@ async fn match_grant_scope() {
         .scope(Scope::Messages {
             method: Method::Query,
             protocol: None,
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
@@ -315,6 +495,7 @@ async fn mismatched_grant_scope() {
     let builder = GrantBuilder::new().granted_to(&BOB.did).scope(Scope::Messages {
         method: Method::Subscribe,
         protocol: None,
+        interface: None,
     });
     let bob_grant = builder.sign(&*ALICE).build().await.expect("should create grant");
 
@@ -332,7 +513,9 @@ async fn mismatched_grant_scope() {
         .await
         .expect("should create write");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, query, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "method is not within grant scope");
@@ -383,6 +566,7 @@ async fn match_protocol_scope() {
         .scope(Scope::Messages {
             method: Method::Query,
             protocol: Some("http://protocol1".to_string()),
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
@@ -459,6 +643,7 @@ async fn mismatched_protocol_scope() {
         .scope(Scope::Messages {
             method: Method::Query,
             protocol: Some("http://protocol1".to_string()),
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
@@ -481,8 +666,145 @@ async fn mismatched_protocol_scope() {
         .await
         .expect("should create write");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, query, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "filter and grant protocols do not match");
 }
+
+// Should limit grant access to messages of the interface the grant is scoped to.
+#[tokio::test]
+async fn interface_scope() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a protocol and writes a record.
+    // --------------------------------------------------
+    let configure = ConfigureBuilder::new()
+        .definition(Definition::new("http://minimal.xyz"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply =
+        endpoint::handle(&ALICE.did, configure.clone(), &provider).await.expect("should configure");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let write = WriteBuilder::new()
+        .data(Data::from(br#"{"message": "test record write"}"#.to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice grants Bob access to `Records` interface messages only.
+    // --------------------------------------------------
+    let query_grant = GrantBuilder::new()
+        .granted_to(&BOB.did)
+        .scope(Scope::Messages {
+            method: Method::Query,
+            protocol: None,
+            interface: Some(Interface::Records),
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create grant");
+    let reply =
+        endpoint::handle(&ALICE.did, query_grant.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let read_grant = GrantBuilder::new()
+        .granted_to(&BOB.did)
+        .scope(Scope::Messages {
+            method: Method::Read,
+            protocol: None,
+            interface: Some(Interface::Records),
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create grant");
+    let reply =
+        endpoint::handle(&ALICE.did, read_grant.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Bob can query for `Records` messages.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .add_filter(MessagesFilter::new().interface(Interface::Records))
+        .permission_grant_id(&query_grant.record_id)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert!(entries.contains(&write.cid().expect("should have cid")));
+    assert!(!entries.contains(&configure.cid().expect("should have cid")));
+
+    // --------------------------------------------------
+    // Bob cannot query for `Protocols` messages, or for all messages.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .add_filter(MessagesFilter::new().interface(Interface::Protocols))
+        .permission_grant_id(&query_grant.record_id)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, query, &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "filter and grant interfaces do not match");
+
+    let query = QueryBuilder::new()
+        .permission_grant_id(&query_grant.record_id)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, query, &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "filter and grant interfaces do not match");
+
+    // --------------------------------------------------
+    // Bob can read a `Records` message but not a `Protocols` message.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .message_cid(write.cid().expect("should have cid"))
+        .permission_grant_id(&read_grant.record_id)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let read = ReadBuilder::new()
+        .message_cid(configure.cid().expect("should have cid"))
+        .permission_grant_id(&read_grant.record_id)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create read");
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, read, &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "message failed scope authorization");
+}
@@ -51,6 +51,7 @@ async fn read_message() {
         .scope(Scope::Messages {
             method: Method::Read,
             protocol: None,
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
@@ -191,7 +192,75 @@ async fn forbidden() {
         .await
         .expect("should create read");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&BOB.did, read, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&BOB.did, read, &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "missing grant ID");
+}
+
+// Should allow anyone to read messages they are permitted to see without a
+// grant.
+#[tokio::test]
+async fn permitted_without_grant() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a published record and a record for Bob.
+    // --------------------------------------------------
+    let published = WriteBuilder::new()
+        .data(Data::from(br#"{"message": "published record"}"#.to_vec()))
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, published.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let for_bob = WriteBuilder::new()
+        .data(Data::from(br#"{"message": "record for Bob"}"#.to_vec()))
+        .recipient(&BOB.did)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, for_bob.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Bob reads both records without a grant.
+    // --------------------------------------------------
+    for write in [&published, &for_bob] {
+        let message_cid = write.cid().expect("should get CID");
+        let read = ReadBuilder::new()
+            .message_cid(message_cid.clone())
+            .sign(&*BOB)
+            .build()
+            .await
+            .expect("should create read");
+        let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+        assert_eq!(reply.status.code, StatusCode::OK);
+
+        let entry = reply.body.expect("should have body").entry.expect("should have entry");
+        assert_eq!(entry.message_cid, message_cid);
+    }
+
+    // --------------------------------------------------
+    // Carol cannot read the record for Bob.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .message_cid(for_bob.cid().expect("should get CID"))
+        .sign(&*CAROL)
+        .build()
+        .await
+        .expect("should create read");
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, read, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "missing grant ID");
@@ -397,7 +466,9 @@ async fn owner_not_author() {
         .await
         .expect("should create read");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, read, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, read, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "missing grant ID");
@@ -410,7 +481,9 @@ async fn owner_not_author() {
         .await
         .expect("should create read");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, read, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, read, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "missing grant ID");
@@ -495,7 +568,7 @@ async fn invalid_interface() {
         .granted_to(&BOB.did)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://minimal.xyz".to_string(),
+            protocol: Some("http://minimal.xyz".to_string()),
             limited_to: None,
         })
         .sign(&*ALICE)
@@ -520,7 +593,9 @@ async fn invalid_interface() {
         .await
         .expect("should create read");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, read, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, read, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "interface is not within grant scope");
@@ -558,6 +633,7 @@ async fn permissive_grant() {
         .scope(Scope::Messages {
             method: Method::Read,
             protocol: None,
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
@@ -620,7 +696,7 @@ async fn protocol_grant() {
     let carol_request = RequestBuilder::new()
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://minimal.xyz".to_string(),
+            protocol: Some("http://minimal.xyz".to_string()),
             limited_to: None,
         })
         .sign(&*CAROL)
@@ -640,7 +716,7 @@ async fn protocol_grant() {
         .granted_to(&CAROL.did)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://minimal.xyz".to_string(),
+            protocol: Some("http://minimal.xyz".to_string()),
             limited_to: None,
         })
         .sign(&*ALICE)
@@ -741,6 +817,7 @@ async fn protocol_grant() {
         .scope(Scope::Messages {
             method: Method::Read,
             protocol: Some("http://minimal.xyz".to_string()),
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
@@ -761,7 +838,9 @@ async fn protocol_grant() {
         .await
         .expect("should create read");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, read, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, read, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "missing grant ID");
@@ -864,7 +943,9 @@ async fn protocol_grant() {
         .await
         .expect("should create read");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, read, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, read, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "missing grant ID");
@@ -925,6 +1006,7 @@ async fn invalid_protocol_grant() {
         .scope(Scope::Messages {
             method: Method::Read,
             protocol: Some("http://minimal.xyz".to_string()),
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
@@ -947,7 +1029,9 @@ async fn invalid_protocol_grant() {
         .await
         .expect("should create read");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, read, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, read, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "no grant found");
@@ -981,6 +1065,7 @@ async fn delete_with_no_write() {
         .scope(Scope::Messages {
             method: Method::Read,
             protocol: Some("http://minimal.xyz".to_string()),
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
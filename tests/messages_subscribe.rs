@@ -2,14 +2,17 @@
 
 use core::panic;
 use std::io::Cursor;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
+use std::task::Poll;
 use std::time::Duration;
 
 use dwn_node::authorization::Authorization;
+use dwn_node::event::Subscriber;
 use dwn_node::interfaces::grants::{GrantBuilder, Scope};
 use dwn_node::interfaces::messages::{MessagesFilter, QueryBuilder, SubscribeBuilder};
 use dwn_node::interfaces::protocols::{ConfigureBuilder, Definition};
 use dwn_node::interfaces::records::{Data, ProtocolBuilder, WriteBuilder};
+use dwn_node::store::Entry;
 use dwn_node::{Error, Interface, Message, Method, StatusCode, endpoint};
 use futures::StreamExt;
 use test_node::key_store;
@@ -123,7 +126,9 @@ async fn unauthorized() {
     // Bob attempts to subscribe to Alice's event stream.
     // --------------------------------------------------
     let subscribe = SubscribeBuilder::new().sign(&*BOB).build().await.expect("should build");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, subscribe, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, subscribe, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "missing permission grant");
@@ -142,6 +147,7 @@ async fn interface_scope() {
         .scope(Scope::Messages {
             method: Method::Subscribe,
             protocol: None,
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
@@ -269,7 +275,7 @@ async fn unauthorized_interface() {
         .granted_to(&BOB.did)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://allow-any".to_string(),
+            protocol: Some("http://allow-any".to_string()),
             limited_to: None,
         })
         .sign(&*ALICE)
@@ -292,7 +298,9 @@ async fn unauthorized_interface() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, subscribe, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, subscribe, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "interface is not within grant scope");
@@ -311,6 +319,7 @@ async fn unauthorized_method() {
         .scope(Scope::Messages {
             method: Method::Query,
             protocol: None,
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
@@ -332,7 +341,9 @@ async fn unauthorized_method() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, subscribe, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, subscribe, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "method is not within grant scope");
@@ -386,6 +397,7 @@ async fn protocol_filter() {
         .scope(Scope::Messages {
             method: Method::Subscribe,
             protocol: Some("http://protocol1.xyz".to_string()),
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
@@ -526,6 +538,7 @@ async fn invalid_protocol() {
         .scope(Scope::Messages {
             method: Method::Subscribe,
             protocol: Some("http://protocol1.xyz".to_string()),
+            interface: None,
         })
         .sign(&*ALICE)
         .build()
@@ -548,7 +561,9 @@ async fn invalid_protocol() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, subscribe, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, subscribe, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "filter and grant protocols do not match");
@@ -565,8 +580,71 @@ async fn invalid_protocol() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, subscribe, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, subscribe, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "filter and grant protocols do not match");
 }
+
+// Should drop the oldest buffered events and report the gap when a consumer
+// cannot keep up.
+#[tokio::test]
+async fn slow_consumer() {
+    // --------------------------------------------------
+    // Alice creates 5 record writes to use as events.
+    // --------------------------------------------------
+    let mut events = vec![];
+    for i in 0..5 {
+        let reader = Cursor::new(format!(r#"{{"message": "record {i}"}}"#).into_bytes());
+        let write = WriteBuilder::new()
+            .data(Data::Stream(reader))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        events.push(Entry::from(&write));
+    }
+    let expected =
+        events[3..].iter().map(|e| e.cid().expect("should have cid")).collect::<Vec<_>>();
+
+    // --------------------------------------------------
+    // The consumer waits while all events are published to a subscriber
+    // buffering at most 2 events.
+    // --------------------------------------------------
+    let mut subscriber = Subscriber::new(futures::stream::iter(events)).bounded(2);
+    time::sleep(Duration::from_millis(100)).await;
+
+    // --------------------------------------------------
+    // Only the 2 newest events are received, with a gap of 3.
+    // --------------------------------------------------
+    assert_eq!(subscriber.missed(), 3);
+    let mut received = vec![];
+    while let Some(event) = subscriber.next().await {
+        received.push(event.cid().expect("should have cid"));
+    }
+    assert_eq!(received, expected);
+    assert_eq!(subscriber.missed(), 0);
+}
+
+// Should stop reading from the producer once the subscriber is dropped, even
+// when no further events are produced.
+#[tokio::test]
+async fn dropped_subscriber() {
+    let source = Arc::new(());
+    let held = Arc::clone(&source);
+    let pending = futures::stream::poll_fn(move |_| {
+        let _held = &held;
+        Poll::<Option<Entry>>::Pending
+    });
+
+    let subscriber = Subscriber::new(pending).bounded(2);
+    time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(Arc::strong_count(&source), 2);
+
+    // the producer task exits, releasing the source stream
+    drop(subscriber);
+    time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(Arc::strong_count(&source), 1);
+}
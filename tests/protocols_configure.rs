@@ -6,9 +6,12 @@
 use std::collections::BTreeMap;
 use std::sync::LazyLock;
 
+use base64ct::{Base64UrlUnpadded, Encoding};
+use dwn_node::hd_key::PrivateKeyJwk;
 use dwn_node::interfaces::grants::{GrantBuilder, RevocationBuilder, Scope};
 use dwn_node::interfaces::protocols::{
     Action, ActionRule, Actor, ConfigureBuilder, Definition, ProtocolType, QueryBuilder, RuleSet,
+    Size,
 };
 use dwn_node::provider::MessageStore;
 use dwn_node::store::ProtocolsQueryBuilder;
@@ -16,6 +19,8 @@ use dwn_node::{Error, Message, Method, StatusCode, endpoint};
 use test_node::key_store;
 use test_node::provider::ProviderImpl;
 use tokio::time;
+use vercre_infosec::Signer;
+use vercre_infosec::jose::{Curve, KeyType, PublicKeyJwk};
 
 static ALICE: LazyLock<key_store::Keyring> = LazyLock::new(|| key_store::new_keyring());
 static BOB: LazyLock<key_store::Keyring> = LazyLock::new(|| key_store::new_keyring());
@@ -141,6 +146,64 @@ async fn overwrite_older() {
     assert_eq!(entries.len(), 1);
 }
 
+// Should reject a definition with an older version than the configured
+// definition.
+#[tokio::test]
+async fn version_downgrade() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures version 1.1.0 of a protocol.
+    // --------------------------------------------------
+    let configure = ConfigureBuilder::new()
+        .definition(Definition::new("http://minimal.xyz").version("1.1.0"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice attempts to configure an older version and fails.
+    // --------------------------------------------------
+    let configure = ConfigureBuilder::new()
+        .definition(Definition::new("http://minimal.xyz").version("1.0.9"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let Err(Error::Conflict(e)) = endpoint::handle(&ALICE.did, configure, &provider).await else {
+        panic!("should be Conflict");
+    };
+    assert_eq!(e, "version 1.0.9 is older than the configured version 1.1.0");
+
+    // --------------------------------------------------
+    // Alice configures a newer version.
+    // --------------------------------------------------
+    let configure = ConfigureBuilder::new()
+        .definition(Definition::new("http://minimal.xyz").version("1.10.0"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // An invalid version is rejected when building.
+    // --------------------------------------------------
+    let Err(Error::BadRequest(e)) = Definition::new("http://minimal.xyz").version("1.x").validate()
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "invalid version 1.x: expected MAJOR.MINOR.PATCH");
+}
+
 // Should overwrite existing protocol with an identical timestamp when new
 // protocol is lexicographically larger.
 #[tokio::test]
@@ -152,6 +215,7 @@ async fn overwrite_smaller() {
         ProtocolType {
             schema: None,
             data_formats: Some(vec!["bar1".to_string()]),
+            data_schema: None,
         },
     );
     let definition_2 = Definition::new("http://minimal.xyz").add_type(
@@ -159,6 +223,7 @@ async fn overwrite_smaller() {
         ProtocolType {
             schema: None,
             data_formats: Some(vec!["bar2".to_string()]),
+            data_schema: None,
         },
     );
     let definition_3 = Definition::new("http://minimal.xyz").add_type(
@@ -166,6 +231,7 @@ async fn overwrite_smaller() {
         ProtocolType {
             schema: None,
             data_formats: Some(vec!["bar3".to_string()]),
+            data_schema: None,
         },
     );
 
@@ -273,6 +339,7 @@ async fn invalid_schema() {
             ProtocolType {
                 schema: Some("bad-schema.xyz/".to_string()),
                 data_formats: None,
+                data_schema: None,
             },
         ))
         .sign(&*ALICE)
@@ -286,6 +353,7 @@ async fn invalid_schema() {
         ProtocolType {
             schema: Some("bad-schema.xyz/".to_string()),
             data_formats: None,
+            data_schema: None,
         },
     );
 
@@ -307,7 +375,9 @@ async fn no_grant() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, configure, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, configure, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "author has no grant");
@@ -597,6 +667,136 @@ async fn invalid_read_action() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 }
 
+// Should reject an invalid definition when building the configure message,
+// naming the offending protocol path.
+#[tokio::test]
+async fn invalid_definition() {
+    // --------------------------------------------------
+    // An action referencing an undeclared role.
+    // --------------------------------------------------
+    let definition = Definition::new("http://foo.xyz")
+        .add_type("foo", ProtocolType::default())
+        .add_type("bar", ProtocolType::default())
+        .add_rule(
+            "foo",
+            RuleSet {
+                structure: BTreeMap::from([(
+                    "bar".to_string(),
+                    RuleSet {
+                        actions: Some(vec![ActionRule {
+                            role: Some("friend".to_string()),
+                            can: vec![Action::Create],
+                            ..ActionRule::default()
+                        }]),
+                        ..RuleSet::default()
+                    },
+                )]),
+                ..RuleSet::default()
+            },
+        );
+
+    let Err(Error::BadRequest(e)) = definition.validate() else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "missing role friend in action at foo/bar");
+
+    let e = ConfigureBuilder::new()
+        .definition(definition)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect_err("should not build");
+    assert!(e.to_string().contains("missing role friend in action at foo/bar"));
+
+    // --------------------------------------------------
+    // An action referencing a protocol path not in the structure.
+    // --------------------------------------------------
+    let definition =
+        Definition::new("http://foo.xyz").add_type("foo", ProtocolType::default()).add_rule(
+            "foo",
+            RuleSet {
+                actions: Some(vec![ActionRule {
+                    who: Some(Actor::Author),
+                    of: Some("bar".to_string()),
+                    can: vec![Action::Create],
+                    ..ActionRule::default()
+                }]),
+                ..RuleSet::default()
+            },
+        );
+
+    let Err(Error::BadRequest(e)) = definition.validate() else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "invalid protocol path bar in action at foo");
+
+    // --------------------------------------------------
+    // A rule set that is not a declared type.
+    // --------------------------------------------------
+    let definition = Definition::new("http://foo.xyz").add_rule("foo", RuleSet::default());
+
+    let Err(Error::BadRequest(e)) = definition.validate() else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "rule set foo is not declared as an allowed type");
+
+    // --------------------------------------------------
+    // An incoherent `$size` range.
+    // --------------------------------------------------
+    let definition =
+        Definition::new("http://foo.xyz").add_type("foo", ProtocolType::default()).add_rule(
+            "foo",
+            RuleSet {
+                size: Some(Size {
+                    min: Some(100),
+                    max: Some(10),
+                }),
+                ..RuleSet::default()
+            },
+        );
+
+    let Err(Error::BadRequest(e)) = definition.validate() else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "invalid size range at foo");
+
+    // --------------------------------------------------
+    // Conflicting recipient rules.
+    // --------------------------------------------------
+    let definition =
+        Definition::new("http://foo.xyz").add_type("foo", ProtocolType::default()).add_rule(
+            "foo",
+            RuleSet {
+                require_recipient: Some(true),
+                forbid_recipient: Some(true),
+                ..RuleSet::default()
+            },
+        );
+
+    let Err(Error::BadRequest(e)) = definition.validate() else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "conflicting recipient rules at foo");
+
+    // --------------------------------------------------
+    // A role record type that forbids recipients.
+    // --------------------------------------------------
+    let definition =
+        Definition::new("http://foo.xyz").add_type("foo", ProtocolType::default()).add_rule(
+            "foo",
+            RuleSet {
+                role: Some(true),
+                forbid_recipient: Some(true),
+                ..RuleSet::default()
+            },
+        );
+
+    let Err(Error::BadRequest(e)) = definition.validate() else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "role records require a recipient at foo");
+}
+
 // Should allow an external party to configure a protocol when they have a valid grant.
 #[tokio::test]
 async fn valid_grant() {
@@ -649,7 +849,8 @@ async fn valid_grant() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, configure.clone(), &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, configure.clone(), &provider).await
     else {
         panic!("should not configure protocol");
     };
@@ -680,7 +881,9 @@ async fn valid_grant() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, configure, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, configure, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "grant not granted to grantee");
@@ -727,6 +930,24 @@ async fn configure_scope() {
         .expect("should configure protocol");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
+    // --------------------------------------------------
+    // Bob updates the permitted protocol.
+    // --------------------------------------------------
+    let definition = Definition::new("https://example.com/protocol/allowed")
+        .add_type("foo", ProtocolType::default())
+        .add_rule("foo", RuleSet::default());
+    let configure = ConfigureBuilder::new()
+        .definition(definition)
+        .permission_grant_id(&bob_grant_id)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should build");
+
+    let reply =
+        endpoint::handle(&ALICE.did, configure, &provider).await.expect("should update protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
     // --------------------------------------------------
     //  Bob fails to configure a protocol for a different protocol.
     // --------------------------------------------------
@@ -738,7 +959,8 @@ async fn configure_scope() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, configure.clone(), &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, configure.clone(), &provider).await
     else {
         panic!("should not configure protocol");
     };
@@ -808,3 +1030,69 @@ async fn delete_older_events() {
     assert_eq!(entries.len(), 1);
     assert_eq!(entries[0].cid().unwrap(), newest_cid);
 }
+
+// Should validate encryption keys added to a definition and return the
+// encryption settings for a protocol path.
+#[tokio::test]
+async fn encryption() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let alice_kid = ALICE.verification_method().await.expect("should get kid");
+    let alice_private_jwk = PrivateKeyJwk {
+        public_key: PublicKeyJwk {
+            kty: KeyType::Okp,
+            crv: Curve::Ed25519,
+            x: Base64UrlUnpadded::encode_string(ALICE.public_key().as_bytes()),
+            ..PublicKeyJwk::default()
+        },
+        d: "8rmFFiUcTjjrL5mgBzWykaH39D64VD0mbDHwILvsu30".to_string(),
+    };
+    let bob_kid = BOB.verification_method().await.expect("should get kid");
+    let bob_private_jwk = PrivateKeyJwk {
+        public_key: PublicKeyJwk {
+            kty: KeyType::Okp,
+            crv: Curve::Ed25519,
+            x: Base64UrlUnpadded::encode_string(BOB.public_key().as_bytes()),
+            ..PublicKeyJwk::default()
+        },
+        d: "n8Rcm64tLob0nveDUuXzP-CnLmn3V11vRqk6E3FuKCo".to_string(),
+    };
+
+    let chat = include_bytes!("protocols/chat.json");
+    let definition: Definition = serde_json::from_slice(chat).expect("should deserialize");
+
+    // --------------------------------------------------
+    // Alice's key must match the key in her DID document.
+    // --------------------------------------------------
+    let Err(Error::BadRequest(e)) = definition
+        .clone()
+        .with_verified_encryption(&alice_kid, bob_private_jwk.clone(), provider.clone())
+        .await
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "encryption key does not match the DID document");
+
+    let definition = definition
+        .with_verified_encryption(&alice_kid, alice_private_jwk.clone(), provider.clone())
+        .await
+        .expect("should add encryption");
+
+    // --------------------------------------------------
+    // Encryption settings can be retrieved by protocol path.
+    // --------------------------------------------------
+    let encryption = definition.encryption_for("thread/message").expect("should have encryption");
+    assert_eq!(encryption.root_key_id, alice_kid);
+    assert!(definition.encryption_for("thread/unknown").is_none());
+
+    // --------------------------------------------------
+    // Encryption cannot be added again using a different key.
+    // --------------------------------------------------
+    let Err(Error::BadRequest(e)) = definition.clone().with_encryption(&bob_kid, bob_private_jwk)
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, format!("encryption is already configured using root key {alice_kid}"));
+
+    definition.with_encryption(&alice_kid, alice_private_jwk).expect("should re-add encryption");
+}
@@ -7,12 +7,13 @@ use std::sync::LazyLock;
 use std::time::Duration;
 
 use base64ct::{Base64UrlUnpadded, Encoding};
-use chrono::Days;
+use chrono::{Days, TimeDelta};
 use dwn_node::interfaces::grants::{GrantBuilder, RevocationBuilder, Scope};
 use dwn_node::interfaces::protocols::{
     ConfigureBuilder, Definition, ProtocolType, ProtocolsFilter, QueryBuilder,
 };
-use dwn_node::{Error, Method, StatusCode, cid, endpoint};
+use dwn_node::store::Pagination;
+use dwn_node::{Error, ForbiddenCode, Method, StatusCode, cid, endpoint};
 use test_node::key_store;
 use test_node::provider::ProviderImpl;
 use tokio::time;
@@ -138,6 +139,141 @@ async fn unauthorized() {
     assert_eq!(body.entries.unwrap().len(), 2);
 }
 
+// Should filter protocols by published state, returning only published
+// protocols to anyone other than the owner.
+#[tokio::test]
+async fn published_filter() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures 3 protocols: 1 unpublished + 2 published.
+    // --------------------------------------------------
+    for i in 1..=3 {
+        let configure = ConfigureBuilder::new()
+            .definition(
+                Definition::new(format!("http://protocol-{i}.xyz"))
+                    .add_type("foo", ProtocolType::default())
+                    .published(i > 1),
+            )
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should build");
+        let reply =
+            endpoint::handle(&ALICE.did, configure, &provider).await.expect("should configure");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // --------------------------------------------------
+    // Alice queries for her unpublished protocols.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .published(Some(false))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should match");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let entries = body.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].descriptor.definition.protocol, "http://protocol-1.xyz");
+
+    // --------------------------------------------------
+    // Alice queries for her published protocols.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .published(Some(true))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should match");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    assert_eq!(body.entries.unwrap().len(), 2);
+
+    // --------------------------------------------------
+    // Bob queries for unpublished protocols and finds none.
+    // --------------------------------------------------
+    let query =
+        QueryBuilder::new().published(Some(false)).sign(&*BOB).build().await.expect("should build");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should match");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    assert!(body.entries.unwrap().is_empty());
+
+    // --------------------------------------------------
+    // An anonymous user queries for published protocols.
+    // --------------------------------------------------
+    let query = QueryBuilder::new().published(Some(true)).build();
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should match");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    assert_eq!(body.entries.unwrap().len(), 2);
+}
+
+// Should page through configured protocols using the returned cursor.
+#[tokio::test]
+async fn paginate() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures 5 protocols.
+    // --------------------------------------------------
+    for i in 1..=5 {
+        let configure = ConfigureBuilder::new()
+            .definition(Definition::new(format!("http://protocol-{i}.xyz")))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should build");
+        let reply =
+            endpoint::handle(&ALICE.did, configure, &provider).await.expect("should configure");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // --------------------------------------------------
+    // Alice pages through her protocols, 2 at a time.
+    // --------------------------------------------------
+    let mut protocols = vec![];
+    let mut cursor = None;
+
+    loop {
+        let mut pagination = Pagination::new().limit(2);
+        if let Some(cursor) = cursor {
+            pagination = pagination.cursor(cursor);
+        }
+        let query = QueryBuilder::new()
+            .pagination(pagination)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should build");
+        let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should match");
+        assert_eq!(reply.status.code, StatusCode::OK);
+
+        let body = reply.body.expect("should have body");
+        let entries = body.entries.expect("should have entries");
+        assert!(entries.len() <= 2);
+        protocols.extend(entries.into_iter().map(|e| e.descriptor.definition.protocol));
+
+        cursor = body.cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    protocols.sort();
+    protocols.dedup();
+    assert_eq!(protocols.len(), 5);
+}
+
 // Should fail with a status of BadRequest (400) if protocol is not normalized.
 #[tokio::test]
 async fn bad_protocol() {
@@ -151,7 +287,8 @@ async fn bad_protocol() {
         .expect("should build");
 
     query.descriptor.filter = Some(ProtocolsFilter {
-        protocol: "protocol-3.xyz/".to_string(),
+        protocol: Some("protocol-3.xyz/".to_string()),
+        published: None,
     });
 
     let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
@@ -267,7 +404,9 @@ async fn valid_grant() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, query, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "grant not granted to grantee");
@@ -458,12 +597,66 @@ async fn expired_grant() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+    let Err(err) = endpoint::handle(&ALICE.did, query, &provider).await else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(err.forbidden_code(), Some(ForbiddenCode::GrantExpired));
+    let Error::Forbidden { message: e, .. } = err else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "grant has expired");
 }
 
+// Should reject a grant once the node's clock has passed its expiry, even
+// though the query's `message_timestamp` precedes it.
+#[tokio::test]
+async fn expired_grant_clock() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice grants Bob permission to query protocols for an hour.
+    // --------------------------------------------------
+    let bob_grant = GrantBuilder::new()
+        .granted_to(&BOB.did)
+        .scope(Scope::Protocols {
+            method: Method::Query,
+            protocol: None,
+        })
+        .expires_in(3600)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create grant");
+
+    let bob_grant_id = bob_grant.record_id.clone();
+
+    let reply =
+        endpoint::handle(&ALICE.did, bob_grant.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Bob queries for Alice's protocols using the grant.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .permission_grant_id(&bob_grant_id)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, query.clone(), &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    // --------------------------------------------------
+    // The clock moves past the grant's expiry and the same query is rejected.
+    // --------------------------------------------------
+    provider.clock.advance(TimeDelta::hours(2));
+
+    let Err(err) = endpoint::handle(&ALICE.did, query, &provider).await else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(err.forbidden_code(), Some(ForbiddenCode::GrantExpired));
+}
+
 // Should reject an external party when they present a grant that is not yet active.
 #[tokio::test]
 async fn inactive_grant() {
@@ -508,7 +701,9 @@ async fn inactive_grant() {
         .expect("should subtract");
     query.descriptor.base.message_timestamp = older_timestamp;
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, query, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "grant is not yet active");
@@ -526,7 +721,7 @@ async fn invalid_scope() {
         .granted_to(&BOB.did)
         .scope(Scope::Records {
             method: Method::Read,
-            protocol: "https://example.com/protocol/test".to_string(),
+            protocol: Some("https://example.com/protocol/test".to_string()),
             limited_to: None,
         })
         .sign(&*ALICE)
@@ -550,7 +745,9 @@ async fn invalid_scope() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, query, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "interface is not within grant scope");
@@ -571,7 +768,9 @@ async fn missing_grant() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, query, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "no grant found");
@@ -589,7 +788,7 @@ async fn incorrect_grantor() {
         .granted_to(&CAROL.did)
         .scope(Scope::Records {
             method: Method::Read,
-            protocol: "https://example.com/protocol/test".to_string(),
+            protocol: Some("https://example.com/protocol/test".to_string()),
             limited_to: None,
         })
         .sign(&*ALICE)
@@ -619,7 +818,9 @@ async fn incorrect_grantor() {
         .await
         .expect("should build");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&BOB.did, query, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&BOB.did, query, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "grant not granted by grantor");
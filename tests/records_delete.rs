@@ -3,16 +3,16 @@
 use std::io::Read;
 use std::sync::LazyLock;
 
-use chrono::Days;
+use chrono::{Days, TimeDelta};
 use dwn_node::interfaces::messages;
 use dwn_node::interfaces::messages::MessagesFilter;
 use dwn_node::interfaces::protocols::{ConfigureBuilder, Definition};
 use dwn_node::interfaces::records::{
     Data, DeleteBuilder, DeleteDescriptor, ProtocolBuilder, QueryBuilder, ReadBuilder,
-    RecordsFilter, WriteBuilder,
+    RecordsFilter, WriteBuilder, preview_delete,
 };
-use dwn_node::provider::{EventLog, MessageStore};
-use dwn_node::{Error, Interface, Method, StatusCode, endpoint, store};
+use dwn_node::provider::{EventLog, MessageStore, gc};
+use dwn_node::{Error, Interface, Message, Method, StatusCode, endpoint, store};
 use test_node::key_store;
 use test_node::provider::ProviderImpl;
 
@@ -466,7 +466,9 @@ async fn ancestor_recipient() {
         .await
         .expect("should create delete");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, delete, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, delete, &provider).await
+    else {
         panic!("should be NotFound");
     };
     assert_eq!(e, "action not permitted");
@@ -538,7 +540,9 @@ async fn direct_recipient() {
         .await
         .expect("should create delete");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, delete, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, delete, &provider).await
+    else {
         panic!("should be NotFound");
     };
     assert_eq!(e, "action not permitted");
@@ -629,7 +633,9 @@ async fn ancestor_author() {
         .await
         .expect("should create delete");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, delete, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, delete, &provider).await
+    else {
         panic!("should be NotFound");
     };
     assert_eq!(e, "action not permitted");
@@ -745,7 +751,9 @@ async fn context_role() {
         .await
         .expect("should create delete");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, delete, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, delete, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "action not permitted");
@@ -840,7 +848,9 @@ async fn root_role() {
         .await
         .expect("should create delete");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, delete, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, delete, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "action not permitted");
@@ -890,7 +900,9 @@ async fn forbidden() {
         .await
         .expect("should create delete");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, delete, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, delete, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "delete request failed authorization");
@@ -1103,3 +1115,408 @@ async fn delete_updates() {
     let (entries, _) = EventLog::query(&provider, &ALICE.did, &query).await.expect("should query");
     assert_eq!(entries.len(), 2);
 }
+
+// Should preview the records removed by a pruning delete without deleting them.
+#[tokio::test]
+async fn preview_prune() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a nested protocol (foo->bar->baz).
+    // --------------------------------------------------
+    let nested = include_bytes!("protocols/nested.json");
+    let definition: Definition = serde_json::from_slice(nested).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes a thread: foo -> 2 x bar -> baz.
+    // --------------------------------------------------
+    let foo = WriteBuilder::new()
+        .data(Data::from(b"foo".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://nested.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .schema("foo")
+        .data_format("text/plain")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, foo.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let mut bars = vec![];
+    for data in [b"bar_1", b"bar_2"] {
+        let bar = WriteBuilder::new()
+            .data(Data::from(data.to_vec()))
+            .protocol(ProtocolBuilder {
+                protocol: "http://nested.xyz",
+                protocol_path: "foo/bar",
+                parent_context_id: foo.context_id.clone(),
+            })
+            .schema("bar")
+            .data_format("text/plain")
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, bar.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        bars.push(bar);
+    }
+
+    let baz = WriteBuilder::new()
+        .data(Data::from(b"baz".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://nested.xyz",
+            protocol_path: "foo/bar/baz",
+            parent_context_id: bars[0].context_id.clone(),
+        })
+        .schema("baz")
+        .data_format("text/plain")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, baz.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice previews pruning the thread.
+    // --------------------------------------------------
+    let delete = DeleteBuilder::new()
+        .record_id(&foo.record_id)
+        .prune(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create delete");
+    let preview =
+        preview_delete(&ALICE.did, &delete, &provider).await.expect("should preview delete");
+
+    let mut expected = vec![
+        foo.record_id.clone(),
+        bars[0].record_id.clone(),
+        bars[1].record_id.clone(),
+        baz.record_id.clone(),
+    ];
+    expected.sort();
+    let mut record_ids = preview.record_ids;
+    record_ids.sort();
+    assert_eq!(record_ids, expected);
+    assert_eq!(preview.total_bytes, 3 + 5 + 5 + 3);
+
+    // --------------------------------------------------
+    // The thread has not been deleted.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://nested.xyz"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have body").entries.expect("should have entries");
+    assert_eq!(entries.len(), 4);
+}
+
+// Should prune a record's descendants only when the author is permitted to
+// delete every descendant.
+#[tokio::test]
+async fn prune_descendants() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a thread protocol.
+    // --------------------------------------------------
+    let thread_prune = include_bytes!("protocols/thread-prune.json");
+    let definition: Definition = serde_json::from_slice(thread_prune).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Bob writes a thread and Bob and Carol each write a chat in it.
+    // --------------------------------------------------
+    let thread = WriteBuilder::new()
+        .data(Data::from(b"thread".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: &definition.protocol,
+            protocol_path: "thread",
+            parent_context_id: None,
+        })
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, thread.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let mut chats = vec![];
+    for signer in [&*BOB, &*CAROL] {
+        let chat = WriteBuilder::new()
+            .data(Data::from(b"chat".to_vec()))
+            .protocol(ProtocolBuilder {
+                protocol: &definition.protocol,
+                protocol_path: "thread/chat",
+                parent_context_id: thread.context_id.clone(),
+            })
+            .sign(signer)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, chat.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        chats.push(chat);
+    }
+
+    // --------------------------------------------------
+    // Bob is unable to prune the thread as he cannot delete Carol's chat.
+    // --------------------------------------------------
+    let delete = DeleteBuilder::new()
+        .record_id(&thread.record_id)
+        .prune(true)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create delete");
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, delete, &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "action not permitted");
+
+    // nothing has been deleted
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol(&definition.protocol))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have body").entries.expect("should have entries");
+    assert_eq!(entries.len(), 3);
+
+    // --------------------------------------------------
+    // Carol deletes her chat, after which Bob can prune the thread.
+    // --------------------------------------------------
+    let delete = DeleteBuilder::new()
+        .record_id(&chats[1].record_id)
+        .sign(&*CAROL)
+        .build()
+        .await
+        .expect("should create delete");
+    let reply = endpoint::handle(&ALICE.did, delete, &provider).await.expect("should delete");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let delete = DeleteBuilder::new()
+        .record_id(&thread.record_id)
+        .prune(true)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create delete");
+    let reply =
+        endpoint::handle(&ALICE.did, delete.clone(), &provider).await.expect("should delete");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol(&definition.protocol))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert!(reply.body.is_none());
+
+    // --------------------------------------------------
+    // A delete event is logged for the thread and for each of its chats. Each
+    // carries Bob's signed delete, replacing Carol's purged delete event.
+    // --------------------------------------------------
+    let query = messages::QueryBuilder::new()
+        .add_filter(MessagesFilter::new().interface(Interface::Records).method(Method::Delete))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let query = store::Query::from(query);
+
+    let (entries, _) = EventLog::query(&provider, &ALICE.did, &query).await.expect("should query");
+    assert_eq!(entries.len(), 3);
+
+    let mut record_ids = vec![];
+    for entry in &entries {
+        assert_eq!(entry.cid().unwrap(), delete.cid().unwrap());
+        record_ids.push(entry.indexes()["recordId"].clone());
+    }
+    record_ids.sort();
+
+    let mut expected =
+        vec![thread.record_id.clone(), chats[0].record_id.clone(), chats[1].record_id.clone()];
+    expected.sort();
+    assert_eq!(record_ids, expected);
+}
+
+// Should retain a delete's tombstone until its retention period expires.
+#[tokio::test]
+async fn tombstone_retention() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes two records.
+    // --------------------------------------------------
+    let retained = WriteBuilder::new()
+        .data(Data::from(b"retained".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, retained.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let expired = WriteBuilder::new()
+        .data(Data::from(b"expired".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, expired.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice deletes both records, retaining one tombstone for a day.
+    // --------------------------------------------------
+    let retained_delete = DeleteBuilder::new()
+        .record_id(&retained.record_id)
+        .retain_for(TimeDelta::days(1))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create delete");
+    let reply = endpoint::handle(&ALICE.did, retained_delete.clone(), &provider)
+        .await
+        .expect("should delete");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let expired_delete = DeleteBuilder::new()
+        .record_id(&expired.record_id)
+        .retain_for(TimeDelta::zero())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create delete");
+    let reply = endpoint::handle(&ALICE.did, expired_delete.clone(), &provider)
+        .await
+        .expect("should delete");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Both delete events are present and neither record can be read.
+    // --------------------------------------------------
+    let query = messages::QueryBuilder::new()
+        .add_filter(MessagesFilter::new().interface(Interface::Records))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert!(entries.contains(&retained_delete.cid().unwrap()));
+    assert!(entries.contains(&expired_delete.cid().unwrap()));
+
+    for record_id in [&retained.record_id, &expired.record_id] {
+        let read = ReadBuilder::new()
+            .filter(RecordsFilter::new().record_id(record_id))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create read");
+        let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+        assert_eq!(reply.status.code, StatusCode::NOT_FOUND);
+    }
+
+    // --------------------------------------------------
+    // Collect expired tombstones.
+    // --------------------------------------------------
+    let removed = gc::collect_tombstones(&ALICE.did, &provider).await.expect("should collect");
+    assert_eq!(removed, 1);
+
+    // --------------------------------------------------
+    // Only the retained delete event remains.
+    // --------------------------------------------------
+    let query = messages::QueryBuilder::new()
+        .add_filter(MessagesFilter::new().interface(Interface::Records))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert!(entries.contains(&retained_delete.cid().unwrap()));
+    assert!(!entries.contains(&expired_delete.cid().unwrap()));
+    assert!(!entries.contains(&expired.cid().unwrap()));
+
+    // --------------------------------------------------
+    // Neither record can be read.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&retained.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::NOT_FOUND);
+
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&expired.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let Err(Error::NotFound(_)) = endpoint::handle(&ALICE.did, read, &provider).await else {
+        panic!("should be NotFound");
+    };
+
+    // --------------------------------------------------
+    // Replaying the collected record's initial write does not recreate it.
+    // --------------------------------------------------
+    let Err(Error::Conflict(_)) = endpoint::handle(&ALICE.did, expired.clone(), &provider).await
+    else {
+        panic!("should be Conflict");
+    };
+
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&expired.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let Err(Error::NotFound(_)) = endpoint::handle(&ALICE.did, read, &provider).await else {
+        panic!("should be NotFound");
+    };
+}
@@ -4,12 +4,15 @@ use std::io::Cursor;
 use std::sync::LazyLock;
 
 use chrono::{DateTime, Duration, Utc};
-use dwn_node::interfaces::protocols::{ConfigureBuilder, Definition};
+use dwn_node::interfaces::grants::{GrantBuilder, RecordsScope, Scope};
+use dwn_node::interfaces::protocols::{ConfigureBuilder, Definition, ProtocolType, RuleSet};
 use dwn_node::interfaces::records::{
-    Data, ProtocolBuilder, QueryBuilder, RecordsFilter, Sort, WriteBuilder,
+    Data, DelegatedGrant, GroupBy, ProtocolBuilder, QueryBuilder, ReadBuilder, RecordsFilter, Sort,
+    SortKey, Tag, TagFilter, WriteBuilder,
 };
-use dwn_node::store::{MAX_ENCODED_SIZE, Pagination};
-use dwn_node::{DateRange, Error, Message, Range, StatusCode, endpoint};
+use dwn_node::provider::{BlockStore, MessageStore};
+use dwn_node::store::{self, MAX_ENCODED_SIZE, Pagination};
+use dwn_node::{DateRange, Error, Message, Method, Range, StatusCode, cid, endpoint};
 use rand::RngCore;
 use test_node::key_store;
 use test_node::provider::ProviderImpl;
@@ -140,6 +143,235 @@ async fn matches() {
     assert_eq!(entries.len(), 1);
 }
 
+// Should repair a corrupted index by rebuilding indexes from stored messages.
+#[tokio::test]
+async fn rebuild_indexes() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice creates a record with a schema.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .schema("schema_1")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("schema_1"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query.clone(), &provider).await.expect("should query");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+
+    // --------------------------------------------------
+    // The `schema` index is lost, breaking queries filtering on schema.
+    // --------------------------------------------------
+    let index_cid = cid::from_value(&format!("{}-schema", ALICE.did)).expect("should compute cid");
+    BlockStore::delete(&provider, &ALICE.did, "MESSAGE", &index_cid)
+        .await
+        .expect("should delete index");
+
+    let reply = endpoint::handle(&ALICE.did, query.clone(), &provider).await.expect("should query");
+    assert!(reply.body.is_none());
+
+    // --------------------------------------------------
+    // Rebuilding (repeatedly) restores the index.
+    // --------------------------------------------------
+    provider.rebuild_indexes(&ALICE.did).await.expect("should rebuild indexes");
+    provider.rebuild_indexes(&ALICE.did).await.expect("should rebuild indexes");
+
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+}
+
+// Should return every record matching any of the specified record IDs.
+#[tokio::test]
+async fn record_ids() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes three records, publishing only the first.
+    // --------------------------------------------------
+    let mut writes = vec![];
+    for (i, data) in [b"first", b"other", b"third"].iter().enumerate() {
+        let write = WriteBuilder::new()
+            .data(Data::from(data.to_vec()))
+            .published(i == 0)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        writes.push(write);
+    }
+
+    // --------------------------------------------------
+    // Alice queries for the first and last records.
+    // --------------------------------------------------
+    let record_ids = vec![writes[0].record_id.clone(), writes[2].record_id.clone()];
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_ids(record_ids.clone()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|entry| record_ids.contains(&entry.write.record_id)));
+
+    // --------------------------------------------------
+    // Bob only receives the published record.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_ids(record_ids))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, writes[0].record_id);
+}
+
+// Should exclude records written under the protocol specified by
+// `not_protocol`, returning only records the requestor is authorized to read.
+#[tokio::test]
+async fn not_protocol() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures two protocols.
+    // --------------------------------------------------
+    for bytes in [
+        include_bytes!("protocols/minimal.json").as_slice(),
+        include_bytes!("protocols/free-for-all.json").as_slice(),
+    ] {
+        let definition: Definition = serde_json::from_slice(bytes).expect("should deserialize");
+        let configure = ConfigureBuilder::new()
+            .definition(definition)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should build");
+        let reply = endpoint::handle(&ALICE.did, configure, &provider)
+            .await
+            .expect("should configure protocol");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // --------------------------------------------------
+    // Alice writes a published and an unpublished record to the minimal
+    // protocol, and a published post to the free-for-all protocol.
+    // --------------------------------------------------
+    let mut minimal = vec![];
+    for published in [true, false] {
+        let write = WriteBuilder::new()
+            .data(Data::from(b"minimal record".to_vec()))
+            .protocol(ProtocolBuilder {
+                protocol: "http://minimal.xyz",
+                protocol_path: "foo",
+                parent_context_id: None,
+            })
+            .published(published)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        minimal.push(write);
+    }
+
+    let post = WriteBuilder::new()
+        .data(Data::from(br#"{"message": "free-for-all post"}"#.to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://free-for-all-protocol.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, post.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice excludes the minimal protocol, receiving only the post.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().not_protocol("http://minimal.xyz"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, post.record_id);
+
+    // --------------------------------------------------
+    // Bob excludes the free-for-all protocol, receiving only the published
+    // minimal record.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().not_protocol("http://free-for-all-protocol.xyz"))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, minimal[0].record_id);
+
+    // --------------------------------------------------
+    // Alice excludes the post schema, receiving only the minimal records.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().not_schema("post"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 2);
+    let ids = minimal.iter().map(|write| &write.record_id).collect::<Vec<_>>();
+    assert!(entries.iter().all(|entry| ids.contains(&&entry.write.record_id)));
+}
+
 // Should return `encoded_data` if data size is within the spec threshold.
 #[tokio::test]
 async fn encoded_data() {
@@ -215,6 +447,48 @@ async fn no_encoded_data() {
     assert!(entry.write.encoded_data.is_none());
 }
 
+// Should omit `encoded_data` when only record metadata is requested.
+#[tokio::test]
+async fn metadata_only() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice creates a record.
+    // --------------------------------------------------
+    let stream = Cursor::new(br#"{"message": "test record write"}"#.to_vec());
+
+    let write = WriteBuilder::new()
+        .data(Data::Stream(stream.clone()))
+        .schema("http://metadata-only.xyz")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice queries for the record's metadata only.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&write.record_id))
+        .no_data(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    let entry = &entries[0];
+    assert!(entry.write.encoded_data.is_none());
+    assert_eq!(entry.write.record_id, write.record_id);
+    assert_eq!(entry.write.descriptor.data_cid, write.descriptor.data_cid);
+    assert_eq!(entry.write.descriptor.schema, write.descriptor.schema);
+}
+
 // Should return `initial_write` when RecordsWrite is not initial write.
 #[tokio::test]
 async fn initial_write() {
@@ -256,6 +530,87 @@ async fn initial_write() {
     assert!(entry.initial_write.is_some());
 }
 
+// Should only return `initial_write` for updated records, and only when
+// requested.
+#[tokio::test]
+async fn include_initial_write() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice creates 2 records and updates the second.
+    // --------------------------------------------------
+    let unchanged = WriteBuilder::new()
+        .data(Data::from(b"never updated".to_vec()))
+        .schema("history")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, unchanged.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"created".to_vec()))
+        .schema("history")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let update = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"modified".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, update, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice queries for the full retained history.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("history"))
+        .include_initial_write(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 2);
+    for entry in &entries {
+        if entry.write.record_id == unchanged.record_id {
+            assert!(entry.initial_write.is_none());
+        } else {
+            let initial_write = entry.initial_write.as_ref().expect("should have initial write");
+            assert_eq!(initial_write.cid().unwrap(), initial.cid().unwrap());
+        }
+    }
+
+    // --------------------------------------------------
+    // Alice queries for the latest writes only.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("history"))
+        .include_initial_write(false)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|entry| entry.initial_write.is_none()));
+}
+
 // Should be able to query by attester.
 #[tokio::test]
 async fn attester() {
@@ -325,7 +680,21 @@ async fn attester() {
     assert_eq!(attester, BOB.did);
 
     // --------------------------------------------------
-    // Check that 3rd attester will return no results.
+    // Alice creates a record co-attested by Bob and Carol.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::Stream(stream.clone()))
+        .schema("schema_3")
+        .attest(&[&*BOB, &*CAROL])
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Query by the second attester.
     // --------------------------------------------------
     let query = QueryBuilder::new()
         .filter(RecordsFilter::new().attester(&CAROL.did))
@@ -335,7 +704,25 @@ async fn attester() {
         .expect("should create query");
     let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
     assert_eq!(reply.status.code, StatusCode::OK);
-    assert!(reply.body.is_none());
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, write.record_id);
+
+    // --------------------------------------------------
+    // Check that an unknown attester will return no results.
+    // --------------------------------------------------
+    let dave = key_store::new_keyring();
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().attester(&dave.did))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+    assert!(reply.body.is_none());
 }
 
 // Should be able to query by author.
@@ -868,6 +1255,132 @@ async fn unpublished() {
     assert!(reply.body.is_none());
 }
 
+// Should list published or unpublished records, combined with protocol
+// filters, without exposing another author's unpublished records.
+#[tokio::test]
+async fn published_filter() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a protocol anyone can write to.
+    // --------------------------------------------------
+    let def_json = serde_json::json!({
+        "published" : true,
+        "protocol"  : "http://drafts.xyz",
+        "types"     : {
+            "post": {}
+        },
+        "structure": {
+            "post": {
+                "$actions": [{
+                    "who": "anyone",
+                    "can": ["create"]
+                }]
+            }
+        }
+    });
+    let definition: Definition = serde_json::from_value(def_json).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes a published post and a draft; Bob writes a draft.
+    // --------------------------------------------------
+    let mut writes = vec![];
+    for (author, published) in [(&*ALICE, true), (&*ALICE, false), (&*BOB, false)] {
+        let write = WriteBuilder::new()
+            .data(Data::from(b"some data".to_vec()))
+            .protocol(ProtocolBuilder {
+                protocol: "http://drafts.xyz",
+                protocol_path: "post",
+                parent_context_id: None,
+            })
+            .published(published)
+            .sign(author)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        writes.push(write);
+    }
+
+    let filter = RecordsFilter::new().protocol("http://drafts.xyz").protocol_path("post");
+
+    // --------------------------------------------------
+    // Alice lists only drafts.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(filter.clone().published(false))
+        .date_sort(Sort::CreatedAsc)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    let ids = entries.iter().map(|e| e.write.record_id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids, vec![writes[1].record_id.clone(), writes[2].record_id.clone()]);
+
+    // --------------------------------------------------
+    // Alice lists only published records.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(filter.clone().published(true))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, writes[0].record_id);
+
+    // --------------------------------------------------
+    // Alice clears the flag and lists all records.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(filter.clone().published(true).published(None))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 3);
+
+    // --------------------------------------------------
+    // Bob lists drafts but only sees his own.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(filter.published(false))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, writes[2].record_id);
+}
+
 // Should be able to query for a record by data_cid.
 #[tokio::test]
 async fn data_cid() {
@@ -904,6 +1417,150 @@ async fn data_cid() {
     assert_eq!(entries.len(), 1);
 }
 
+// Should only return records pinned by the owner when filtering by pin
+// status, and only updated records when filtering by revision.
+#[tokio::test]
+async fn pinned_and_revised() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes 3 records and pins the first.
+    // --------------------------------------------------
+    let mut writes = vec![];
+    for _ in 0..3 {
+        let write = WriteBuilder::new()
+            .data(Data::from(b"some data".to_vec()))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        writes.push(write);
+    }
+
+    dwn_node::interfaces::records::pin(&ALICE.did, &writes[0].record_id, true, &provider)
+        .await
+        .expect("should pin");
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().pinned(true))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, writes[0].record_id);
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().pinned(false))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 2);
+
+    // --------------------------------------------------
+    // Alice updates the pinned record and one other record.
+    // --------------------------------------------------
+    for write in &writes[..2] {
+        let update = WriteBuilder::from(write.clone())
+            .data(Data::from(b"updated data".to_vec()))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply = endpoint::handle(&ALICE.did, update, &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // the pin is carried forward to the latest write
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().pinned(true))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, writes[0].record_id);
+    assert_eq!(entries[0].write.descriptor.data_size, b"updated data".len());
+
+    // --------------------------------------------------
+    // The pin is stored with the message, so is restored when the `pinned`
+    // index is lost and indexes are rebuilt.
+    // --------------------------------------------------
+    let entry = MessageStore::get(&provider, &ALICE.did, &entries[0].write.cid().unwrap())
+        .await
+        .expect("should get message")
+        .expect("should have message");
+    assert!(entry.is_pinned());
+
+    let index_cid = cid::from_value(&format!("{}-pinned", ALICE.did)).expect("should compute cid");
+    BlockStore::delete(&provider, &ALICE.did, "MESSAGE", &index_cid)
+        .await
+        .expect("should delete index");
+    provider.rebuild_indexes(&ALICE.did).await.expect("should rebuild indexes");
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().pinned(true))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, writes[0].record_id);
+
+    // --------------------------------------------------
+    // Alice queries for records with more than one retained revision.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().revised(true))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|e| e.write.record_id != writes[2].record_id));
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().revised(false))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, writes[2].record_id);
+
+    // --------------------------------------------------
+    // Alice unpins the record.
+    // --------------------------------------------------
+    dwn_node::interfaces::records::pin(&ALICE.did, &writes[0].record_id, false, &provider)
+        .await
+        .expect("should unpin");
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().pinned(true))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert!(reply.body.is_none());
+}
+
 // Should be able to query for a record by data_size (half-open range).
 #[tokio::test]
 async fn data_size_part_range() {
@@ -1128,56 +1785,168 @@ async fn data_size_full_range() {
     assert_eq!(entries.len(), 3);
 }
 
-// Should be able to query for records where date_created is within a specfied range.
+// Should be able to combine a data_size range with a protocol filter.
 #[tokio::test]
-async fn date_created_range() {
+async fn data_size_protocol() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice creates 3 records with varying created dates.
+    // Alice configures a protocol.
     // --------------------------------------------------
-    let first_2022 = DateTime::parse_from_rfc3339("2022-01-01T00:00:00-00:00").unwrap();
-    let write_2022 = WriteBuilder::new()
-        .data(Data::from(b"2022".to_vec()))
-        .date_created(first_2022.into())
-        .message_timestamp(first_2022.into())
+    let allow_any = include_bytes!("protocols/allow-any.json");
+    let definition: Definition = serde_json::from_slice(allow_any).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, write_2022.clone(), &provider).await.expect("should write");
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    let first_2023 = DateTime::parse_from_rfc3339("2023-01-01T00:00:00-00:00").unwrap();
-    let write_2023 = WriteBuilder::new()
-        .data(Data::from(b"2023".to_vec()))
-        .date_created(first_2023.into())
-        .message_timestamp(first_2023.into())
+    // --------------------------------------------------
+    // Alice writes a small and a large protocol record, and a large
+    // non-protocol record.
+    // --------------------------------------------------
+    let mut data = [0u8; 10];
+    rand::thread_rng().fill_bytes(&mut data);
+
+    let small = WriteBuilder::new()
+        .data(Data::from(data.to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, write_2023.clone(), &provider).await.expect("should write");
+    let reply = endpoint::handle(&ALICE.did, small.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    let first_2024 = DateTime::parse_from_rfc3339("2024-01-01T00:00:00-00:00").unwrap();
-    let write_2024 = WriteBuilder::new()
-        .data(Data::from(b"2024".to_vec()))
-        .date_created(first_2024.into())
-        .message_timestamp(first_2024.into())
+    let mut data = vec![0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data);
+
+    let large = WriteBuilder::new()
+        .data(Data::from(data.clone()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, write_2024.clone(), &provider).await.expect("should write");
+    let reply = endpoint::handle(&ALICE.did, large.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    // --------------------------------------------------
-    // From (greater than).
-    // --------------------------------------------------
+    let other = WriteBuilder::new()
+        .data(Data::from(data))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, other, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Only the large protocol record is larger than the encoding threshold.
+    // --------------------------------------------------
+    let filter = RecordsFilter::new()
+        .protocol("http://allow-any.xyz")
+        .data_size(Range::new().gt(MAX_ENCODED_SIZE));
+    let query = QueryBuilder::new()
+        .filter(filter)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, large.record_id);
+
+    // --------------------------------------------------
+    // Range bounds are inclusive when using `ge` and `le`.
+    // --------------------------------------------------
+    let filter =
+        RecordsFilter::new().protocol("http://allow-any.xyz").data_size(Range::new().ge(10).le(10));
+    let query = QueryBuilder::new()
+        .filter(filter)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, small.record_id);
+}
+
+// Should be able to query for records where date_created is within a specfied range.
+#[tokio::test]
+async fn date_created_range() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice creates 3 records with varying created dates.
+    // --------------------------------------------------
+    let first_2022 = DateTime::parse_from_rfc3339("2022-01-01T00:00:00-00:00").unwrap();
+    let write_2022 = WriteBuilder::new()
+        .data(Data::from(b"2022".to_vec()))
+        .date_created(first_2022.into())
+        .message_timestamp(first_2022.into())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, write_2022.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let first_2023 = DateTime::parse_from_rfc3339("2023-01-01T00:00:00-00:00").unwrap();
+    let write_2023 = WriteBuilder::new()
+        .data(Data::from(b"2023".to_vec()))
+        .date_created(first_2023.into())
+        .message_timestamp(first_2023.into())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, write_2023.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let first_2024 = DateTime::parse_from_rfc3339("2024-01-01T00:00:00-00:00").unwrap();
+    let write_2024 = WriteBuilder::new()
+        .data(Data::from(b"2024".to_vec()))
+        .date_created(first_2024.into())
+        .message_timestamp(first_2024.into())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, write_2024.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // From (greater than).
+    // --------------------------------------------------
     let last_2022 = DateTime::parse_from_rfc3339("2022-12-31T00:00:00-00:00").unwrap();
 
     let query = QueryBuilder::new()
@@ -1820,6 +2589,88 @@ async fn date_updated() {
     assert_eq!(entries[0].write.record_id, write_2023.record_id);
 }
 
+// Should return records with a `message_timestamp` within an open-ended range,
+// AND-ed with other filter conditions, for both queries and reads.
+#[tokio::test]
+async fn message_timestamp() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice creates records with varying timestamps and schemas.
+    // --------------------------------------------------
+    let first_2022 = DateTime::parse_from_rfc3339("2022-01-01T00:00:00-00:00").unwrap();
+    let first_2023 = DateTime::parse_from_rfc3339("2023-01-01T00:00:00-00:00").unwrap();
+
+    let mut writes = vec![];
+    for (timestamp, schema) in [
+        (first_2022, "http://timestamp.xyz"),
+        (first_2023, "http://timestamp.xyz"),
+        (first_2023, "http://other-timestamp.xyz"),
+    ] {
+        let write = WriteBuilder::new()
+            .data(Data::from(schema.as_bytes().to_vec()))
+            .schema(schema)
+            .message_timestamp(timestamp.into())
+            .date_created(timestamp.into())
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        writes.push(write);
+    }
+
+    // --------------------------------------------------
+    // Query using only a lower bound (inclusive) and schema.
+    // --------------------------------------------------
+    let filter = RecordsFilter::new()
+        .schema("http://timestamp.xyz")
+        .message_timestamp(DateRange::new().gt(first_2023.into()));
+    let query = QueryBuilder::new()
+        .filter(filter.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, writes[1].record_id);
+
+    // --------------------------------------------------
+    // Query using only an upper bound (exclusive).
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().message_timestamp(DateRange::new().lt(first_2023.into())))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, writes[0].record_id);
+
+    // --------------------------------------------------
+    // Read using the same filter.
+    // --------------------------------------------------
+    let read = ReadBuilder::new().filter(filter).sign(&*ALICE).build().await.expect("should build");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let record = body.entry.records_write.expect("should have records_write");
+    assert_eq!(record.record_id, writes[1].record_id);
+}
+
 // Should be able use range and exact match queries together.
 #[tokio::test]
 async fn range_and_match() {
@@ -2533,67 +3384,112 @@ async fn paginate_descending() {
     }
 }
 
-// Should allow an anonymous query to return published records.
+// Should limit the (serialized) size of each page of results.
 #[tokio::test]
-async fn anonymous() {
+async fn max_response_bytes() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Create records.
+    // Alice creates 5 records with large-ish encoded data.
     // --------------------------------------------------
-    let write_1 = WriteBuilder::new()
-        .data(Data::from(b"schema1".to_vec()))
-        .schema("http://schema1")
-        .published(true)
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, write_1.clone(), &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    for _ in 0..5 {
+        let mut data = [0u8; 2000];
+        rand::thread_rng().fill_bytes(&mut data);
 
-    let write_2 = WriteBuilder::new()
-        .data(Data::from(b"schema2".to_vec()))
-        .schema("http://schema2")
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, write_2.clone(), &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        let write = WriteBuilder::new()
+            .data(Data::from(data.to_vec()))
+            .schema("schema")
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
 
     // --------------------------------------------------
-    // Query anonymously.
+    // Alice pages through the records using a byte budget.
     // --------------------------------------------------
-    let early_date = DateTime::parse_from_rfc3339("2000-01-01T00:00:00-00:00").unwrap();
+    let max_bytes = 8000;
+    let mut all_entries = vec![];
+    let mut cursor = None;
+
+    loop {
+        let query = QueryBuilder::new()
+            .filter(RecordsFilter::new().schema("schema"))
+            .pagination(Pagination { limit: None, cursor })
+            .max_response_bytes(max_bytes)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create query");
+        let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+        assert_eq!(reply.status.code, StatusCode::OK);
+
+        let query_reply = reply.body.expect("should have reply");
+        let entries = query_reply.entries.expect("should have entries");
+
+        let page_bytes: usize =
+            entries.iter().map(|e| serde_json::to_vec(e).expect("should serialize").len()).sum();
+        assert!(page_bytes <= max_bytes);
+        assert!(entries.len() < 5);
+
+        all_entries.extend(entries);
+        cursor = query_reply.cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(all_entries.len(), 5);
 
+    // --------------------------------------------------
+    // A single entry exceeding the budget is returned alone.
+    // --------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().date_created(DateRange::new().gt(early_date.into())))
+        .filter(RecordsFilter::new().schema("schema"))
+        .max_response_bytes(1)
+        .sign(&*ALICE)
         .build()
+        .await
         .expect("should create query");
     let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
     assert_eq!(reply.status.code, StatusCode::OK);
 
     let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 1);
-    assert_eq!(entries[0].write.record_id, write_1.record_id);
+    assert_eq!(query_reply.entries.expect("should have entries").len(), 1);
+    assert!(query_reply.cursor.is_some());
 }
 
-// Should only return records meant for the specified recipient(s).
+// Should index tags derived by the protocol's `$derive` rules.
 #[tokio::test]
-async fn recipient_query() {
+async fn derived_tags() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice configures a protocol.
-    // --------------------------------------------------
-    let allow_any = include_bytes!("protocols/allow-any.json");
-    let definition: Definition = serde_json::from_slice(allow_any).expect("should deserialize");
+    // Alice configures a protocol deriving a lowercase `email` tag.
+    // --------------------------------------------------
+    let def_json = serde_json::json!({
+        "published" : true,
+        "protocol"  : "https://example.com/contacts",
+        "types"     : {
+            "contact": {}
+        },
+        "structure": {
+            "contact": {
+                "$derive": {
+                    "emailLower": {
+                        "from": "email",
+                        "transform": "lowercase"
+                    }
+                }
+            }
+        }
+    });
+    let definition: Definition = serde_json::from_value(def_json).expect("should deserialize");
+
     let configure = ConfigureBuilder::new()
-        .definition(definition.clone())
+        .definition(definition)
         .sign(&*ALICE)
         .build()
         .await
@@ -2604,317 +3500,141 @@ async fn recipient_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice creates 2 records each for Bob and Carol; 2 public, 2 private.
+    // Alice writes a contact with a mixed-case email tag.
     // --------------------------------------------------
-    let alice_bob_private = WriteBuilder::new()
-        .data(Data::from(br#"Hello Bob (private)"#.to_vec()))
-        .recipient(&BOB.did)
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
         .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
+            protocol: "https://example.com/contacts",
+            protocol_path: "contact",
             parent_context_id: None,
         })
-        .schema("post")
-        .data_format("application/json")
+        .add_tag("email", Tag::String("Alice@Example.COM".to_string()))
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, alice_bob_private.clone(), &provider)
-        .await
-        .expect("should write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    let alice_bob_public = WriteBuilder::new()
-        .data(Data::from(br#"Hello Bob (public)"#.to_vec()))
-        .recipient(&BOB.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
-            parent_context_id: None,
-        })
-        .schema("post")
-        .data_format("application/json")
-        .published(true)
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, alice_bob_public.clone(), &provider)
-        .await
-        .expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-
-    let alice_carol_private = WriteBuilder::new()
-        .data(Data::from(br#"Hello Carol (private)"#.to_vec()))
-        .recipient(&CAROL.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
-            parent_context_id: None,
-        })
-        .schema("post")
-        .data_format("application/json")
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, alice_carol_private.clone(), &provider)
-        .await
-        .expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-
-    let alice_carol_public = WriteBuilder::new()
-        .data(Data::from(br#"Hello Carol (public)"#.to_vec()))
-        .recipient(&CAROL.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
-            parent_context_id: None,
-        })
-        .schema("post")
-        .data_format("application/json")
-        .published(true)
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, alice_carol_public.clone(), &provider)
-        .await
-        .expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    // the derived tag is not part of the message
+    let tags = write.descriptor.tags.as_ref().expect("should have tags");
+    assert!(!tags.contains_key("emailLower"));
 
     // --------------------------------------------------
-    // Carol creates 2 records each for Alice and Bob; 2 public, 2 private.
+    // The record can be queried using the derived tag.
     // --------------------------------------------------
-    let carol_alice_private = WriteBuilder::new()
-        .data(Data::from(br#"Hello Alice (private)"#.to_vec()))
-        .recipient(&ALICE.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
-            parent_context_id: None,
-        })
-        .schema("post")
-        .data_format("application/json")
-        .sign(&*CAROL)
-        .build()
-        .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, carol_alice_private.clone(), &provider)
-        .await
-        .expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-
-    let carol_alice_public = WriteBuilder::new()
-        .data(Data::from(br#"Hello Alice (public)"#.to_vec()))
-        .recipient(&ALICE.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
-            parent_context_id: None,
-        })
-        .schema("post")
-        .data_format("application/json")
-        .published(true)
-        .sign(&*CAROL)
-        .build()
-        .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, carol_alice_public.clone(), &provider)
-        .await
-        .expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-
-    let carol_bob_private = WriteBuilder::new()
-        .data(Data::from(br#"Hello Bob (private)"#.to_vec()))
-        .recipient(&BOB.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
-            parent_context_id: None,
-        })
-        .schema("post")
-        .data_format("application/json")
-        .sign(&*CAROL)
-        .build()
-        .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, carol_bob_private.clone(), &provider)
-        .await
-        .expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    let filter = RecordsFilter::new()
+        .protocol("https://example.com/contacts")
+        .protocol_path("contact")
+        .add_tag("emailLower", TagFilter::Equal(Tag::String("alice@example.com".to_string())));
+    let query = store::RecordsQueryBuilder::new().add_filter(filter).build();
+    let (entries, _) =
+        MessageStore::query(&provider, &ALICE.did, &query).await.expect("should query");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].as_write().expect("should be write").record_id, write.record_id);
+}
 
-    let carol_bob_public = WriteBuilder::new()
-        .data(Data::from(br#"Hello Bob (public)"#.to_vec()))
-        .recipient(&BOB.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
-            parent_context_id: None,
-        })
-        .schema("post")
-        .published(true)
-        .data_format("application/json")
-        .sign(&*CAROL)
-        .build()
-        .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, carol_bob_public.clone(), &provider)
-        .await
-        .expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+// Should filter records on tags using equality, prefix, and numeric range
+// comparisons.
+#[tokio::test]
+async fn tag_filters() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Bob creates 2 records each for Alice and Carol; 2 public, 2 private.
+    // Alice writes records with a variety of tags.
     // --------------------------------------------------
-    let bob_alice_private = WriteBuilder::new()
-        .data(Data::from(br#"Hello Alice (private)"#.to_vec()))
-        .recipient(&ALICE.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
-            parent_context_id: None,
-        })
-        .schema("post")
-        .data_format("application/json")
-        .sign(&*BOB)
-        .build()
-        .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, bob_alice_private.clone(), &provider)
-        .await
-        .expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-
-    let bob_alice_public = WriteBuilder::new()
-        .data(Data::from(br#"Hello Alice (public)"#.to_vec()))
-        .recipient(&ALICE.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
-            parent_context_id: None,
-        })
-        .schema("post")
-        .data_format("application/json")
-        .published(true)
-        .sign(&*BOB)
-        .build()
-        .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, bob_alice_public.clone(), &provider)
-        .await
-        .expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    let books = [(2019, "dwn basics"), (2021, "dwn advanced"), (2024, "rust"), (2025, "dwn")];
+    let mut record_ids = vec![];
+    for (year, title) in books {
+        let write = WriteBuilder::new()
+            .data(Data::from(b"some data".to_vec()))
+            .schema("http://book")
+            .add_tag("year", Tag::Number(year))
+            .add_tag("title", Tag::String(title.to_string()))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        record_ids.push(write.record_id);
+    }
 
-    let bob_carol_private = WriteBuilder::new()
-        .data(Data::from(br#"Hello Carol (private)"#.to_vec()))
-        .recipient(&CAROL.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
-            parent_context_id: None,
-        })
-        .schema("post")
-        .data_format("application/json")
-        .sign(&*BOB)
+    // a record without a `year` tag and one with a non-numeric `year` tag
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .schema("http://book")
+        .add_tag("title", Tag::String("untitled".to_string()))
+        .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, bob_carol_private.clone(), &provider)
-        .await
-        .expect("should write");
+    let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    let bob_carol_public = WriteBuilder::new()
-        .data(Data::from(br#"Hello Carol (public)"#.to_vec()))
-        .recipient(&CAROL.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://allow-any.xyz",
-            protocol_path: "post",
-            parent_context_id: None,
-        })
-        .schema("post")
-        .data_format("application/json")
-        .published(true)
-        .sign(&*BOB)
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .schema("http://book")
+        .add_tag("year", Tag::String("2022".to_string()))
+        .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, bob_carol_public.clone(), &provider)
-        .await
-        .expect("should write");
+    let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Bob queries for messages with himself and Alice as recipients.
+    // Alice queries for records with a `year` in range.
     // --------------------------------------------------
     let query = QueryBuilder::new()
         .filter(
             RecordsFilter::new()
-                .protocol("http://allow-any.xyz")
-                .protocol_path("post")
-                .add_recipient(&BOB.did)
-                .add_recipient(&ALICE.did),
+                .schema("http://book")
+                .add_tag("year", TagFilter::range(2020, 2024)),
         )
-        .sign(&*BOB)
+        .date_sort(Sort::CreatedAsc)
+        .sign(&*ALICE)
         .build()
         .await
         .expect("should create query");
     let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
     assert_eq!(reply.status.code, StatusCode::OK);
 
-    let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
-
-    // Bob should be able to see 7 messages
-    assert_eq!(entries.len(), 7);
-    assert!(entries.iter().any(|e| e.write.record_id == alice_bob_private.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_private.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == bob_alice_private.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == alice_bob_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == bob_alice_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == carol_alice_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_public.record_id));
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    let ids = entries.iter().map(|e| e.write.record_id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids, vec![record_ids[1].clone(), record_ids[2].clone()]);
 
     // --------------------------------------------------
-    // Carol queries for messages with herself as recipient.
+    // Alice queries for records with a `title` prefix.
     // --------------------------------------------------
     let query = QueryBuilder::new()
         .filter(
             RecordsFilter::new()
-                .protocol("http://allow-any.xyz")
-                .protocol_path("post")
-                .add_recipient(&CAROL.did),
+                .schema("http://book")
+                .add_tag("title", TagFilter::starts_with("dwn")),
         )
-        .sign(&*CAROL)
+        .date_sort(Sort::CreatedAsc)
+        .sign(&*ALICE)
         .build()
         .await
         .expect("should create query");
     let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
     assert_eq!(reply.status.code, StatusCode::OK);
 
-    let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
-
-    // Carol should be able to see 4 messages
-    assert_eq!(entries.len(), 4);
-    assert!(entries.iter().any(|e| e.write.record_id == alice_carol_private.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_private.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == alice_carol_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_public.record_id));
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    let ids = entries.iter().map(|e| e.write.record_id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids, vec![record_ids[0].clone(), record_ids[1].clone(), record_ids[3].clone()]);
 
     // --------------------------------------------------
-    // Alice queries for published records with herself and Bob as recipients.
+    // Alice queries for records with an exact numeric `year`.
     // --------------------------------------------------
     let query = QueryBuilder::new()
         .filter(
             RecordsFilter::new()
-                .protocol("http://allow-any.xyz")
-                .protocol_path("post")
-                .add_recipient(&ALICE.did)
-                .add_recipient(&BOB.did)
-                .published(true),
+                .schema("http://book")
+                .add_tag("year", TagFilter::equal(Tag::Number(2025))),
         )
         .sign(&*ALICE)
         .build()
@@ -2923,48 +3643,344 @@ async fn recipient_query() {
     let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
     assert_eq!(reply.status.code, StatusCode::OK);
 
-    let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, record_ids[3]);
+}
 
-    // Carol should be able to see 4 messages
-    assert_eq!(entries.len(), 4);
-    assert!(entries.iter().any(|e| e.write.record_id == bob_alice_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == carol_alice_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == alice_bob_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_public.record_id));
+// Should filter and sort signed and floating point tags numerically.
+#[tokio::test]
+async fn numeric_tag_filters() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Carol queries for private messages with herself and Alice as recipients.
+    // Alice writes records with negative, fractional, and whole number tags.
     // --------------------------------------------------
-    let query = QueryBuilder::new()
-        .filter(
-            RecordsFilter::new()
-                .protocol("http://allow-any.xyz")
-                .protocol_path("post")
-                .add_recipient(&CAROL.did)
-                .add_recipient(&ALICE.did)
-                .published(false),
+    let readings = [Tag::Integer(-20), Tag::Float(-2.5), Tag::Float(0.5), Tag::Number(3)];
+    let mut record_ids = vec![];
+    for reading in readings {
+        let write = WriteBuilder::new()
+            .data(Data::from(b"some data".to_vec()))
+            .schema("http://reading")
+            .add_tag("celsius", reading)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        record_ids.push(write.record_id);
+    }
+
+    // --------------------------------------------------
+    // Alice queries for readings in a range spanning zero.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(
+            RecordsFilter::new()
+                .schema("http://reading")
+                .add_tag("celsius", TagFilter::float_range(-10.0, 1.0)),
         )
-        .sign(&*CAROL)
+        .add_sort(SortKey::ascending("tag.celsius"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    let ids = entries.iter().map(|e| e.write.record_id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids, vec![record_ids[1].clone(), record_ids[2].clone()]);
+
+    // --------------------------------------------------
+    // Alice sorts every reading by temperature.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("http://reading"))
+        .add_sort(SortKey::descending("tag.celsius"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    let ids = entries.iter().map(|e| e.write.record_id.clone()).collect::<Vec<_>>();
+    let expected = [3, 2, 1, 0].iter().map(|&i| record_ids[i].clone()).collect::<Vec<_>>();
+    assert_eq!(ids, expected);
+
+    // --------------------------------------------------
+    // A whole number float equals the same integer.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(
+            RecordsFilter::new()
+                .schema("http://reading")
+                .add_tag("celsius", TagFilter::equal(Tag::Float(3.0))),
+        )
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, record_ids[3]);
+
+    // whole number floats serialize as integers
+    let json = serde_json::to_string(&Tag::Float(3.0)).expect("should serialize");
+    assert_eq!(json, "3");
+
+    // --------------------------------------------------
+    // An integer range only matches unsigned integer tags.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(
+            RecordsFilter::new()
+                .schema("http://reading")
+                .add_tag("celsius", TagFilter::range(0, 5)),
+        )
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, record_ids[3]);
+}
+
+// Should filter records by the protocol configuration they were written
+// under.
+#[tokio::test]
+async fn protocol_config() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let definition = Definition::new("http://minimal.xyz")
+        .add_type("foo", ProtocolType::default())
+        .add_rule("foo", RuleSet::default());
+
+    // --------------------------------------------------
+    // Alice configures version 1.0.0 and writes a record.
+    // --------------------------------------------------
+    let configure_v1 = ConfigureBuilder::new()
+        .definition(definition.clone().version("1.0.0"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure_v1.clone(), &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let write_v1 = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://minimal.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, write_v1.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice configures version 2.0.0 and writes another record.
+    // --------------------------------------------------
+    let configure_v2 = ConfigureBuilder::new()
+        .definition(definition.version("2.0.0"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure_v2.clone(), &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let write_v2 = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://minimal.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, write_v2.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice queries for records written under each version.
+    // --------------------------------------------------
+    for (configure, write) in [(configure_v1, write_v1), (configure_v2, write_v2)] {
+        let filter = RecordsFilter::new()
+            .protocol("http://minimal.xyz")
+            .protocol_config(configure.cid().expect("should have CID"));
+        let query = QueryBuilder::new()
+            .filter(filter)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create query");
+        let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+        assert_eq!(reply.status.code, StatusCode::OK);
+
+        let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].write.record_id, write.record_id);
+    }
+}
+
+// Should retain the protocol configuration index of superseded writes, even
+// after the protocol is reconfigured.
+#[tokio::test]
+async fn protocol_config_superseded() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let definition = Definition::new("http://minimal.xyz")
+        .add_type("foo", ProtocolType::default())
+        .add_rule("foo", RuleSet::default());
+    let configure = |version: &'static str| {
+        ConfigureBuilder::new().definition(definition.clone().version(version)).sign(&*ALICE)
+    };
+    let count = async |configure_cid: String| {
+        let filter = RecordsFilter::new().protocol_config(configure_cid);
+        let query = store::RecordsQueryBuilder::new().add_filter(filter).include_archived(true);
+        let (entries, _) =
+            MessageStore::query(&provider, &ALICE.did, &query.build()).await.expect("should query");
+        entries.len()
+    };
+
+    // --------------------------------------------------
+    // Alice configures version 1.0.0, then writes and updates a record.
+    // --------------------------------------------------
+    let configure_v1 = configure("1.0.0").build().await.expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure_v1.clone(), &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://minimal.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let update = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"updated data".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // the superseded initial write is still indexed by its configuration
+    let v1_cid = configure_v1.cid().expect("should have CID");
+    assert_eq!(count(v1_cid.clone()).await, 2);
+
+    // --------------------------------------------------
+    // Alice configures version 2.0.0 and updates the record again.
+    // --------------------------------------------------
+    let configure_v2 = configure("2.0.0").build().await.expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure_v2.clone(), &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let update = WriteBuilder::from(update)
+        .data(Data::from(b"updated again".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, update, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // the archived initial write is still attributed to version 1.0.0, and
+    // only the latest update to version 2.0.0
+    assert_eq!(count(v1_cid).await, 1);
+    assert_eq!(count(configure_v2.cid().expect("should have CID")).await, 1);
+}
+
+// Should allow an anonymous query to return published records.
+#[tokio::test]
+async fn anonymous() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Create records.
+    // --------------------------------------------------
+    let write_1 = WriteBuilder::new()
+        .data(Data::from(b"schema1".to_vec()))
+        .schema("http://schema1")
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, write_1.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let write_2 = WriteBuilder::new()
+        .data(Data::from(b"schema2".to_vec()))
+        .schema("http://schema2")
+        .sign(&*ALICE)
         .build()
         .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, write_2.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Query anonymously.
+    // --------------------------------------------------
+    let early_date = DateTime::parse_from_rfc3339("2000-01-01T00:00:00-00:00").unwrap();
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().date_created(DateRange::new().gt(early_date.into())))
+        .build()
         .expect("should create query");
     let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
     assert_eq!(reply.status.code, StatusCode::OK);
 
     let query_reply = reply.body.expect("should have reply");
     let entries = query_reply.entries.expect("should have entries");
-
-    // Carol should be able to see 3 messages
-    assert_eq!(entries.len(), 3);
-    assert!(entries.iter().any(|e| e.write.record_id == alice_carol_private.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_private.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == carol_alice_private.record_id));
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, write_1.record_id);
 }
 
-// Should only return records authored by the specified author(s).
+// Should only return records meant for the specified recipient(s).
 #[tokio::test]
-async fn author_query() {
+async fn recipient_query() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
@@ -2984,10 +4000,10 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice creates 2 records each for Bob and Carol: 1 public, 1 private.
+    // Alice creates 2 records each for Bob and Carol; 2 public, 2 private.
     // --------------------------------------------------
     let alice_bob_private = WriteBuilder::new()
-        .data(Data::from(b"Hello Bob".to_vec()))
+        .data(Data::from(br#"Hello Bob (private)"#.to_vec()))
         .recipient(&BOB.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3006,7 +4022,7 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     let alice_bob_public = WriteBuilder::new()
-        .data(Data::from(b"Hello Bob".to_vec()))
+        .data(Data::from(br#"Hello Bob (public)"#.to_vec()))
         .recipient(&BOB.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3026,7 +4042,7 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     let alice_carol_private = WriteBuilder::new()
-        .data(Data::from(b"Hello Carol".to_vec()))
+        .data(Data::from(br#"Hello Carol (private)"#.to_vec()))
         .recipient(&CAROL.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3045,7 +4061,7 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     let alice_carol_public = WriteBuilder::new()
-        .data(Data::from(b"Hello Carol".to_vec()))
+        .data(Data::from(br#"Hello Carol (public)"#.to_vec()))
         .recipient(&CAROL.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3065,10 +4081,10 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Carol creates 2 records each for Alice and Bob: 1 public, 1 private.
+    // Carol creates 2 records each for Alice and Bob; 2 public, 2 private.
     // --------------------------------------------------
     let carol_alice_private = WriteBuilder::new()
-        .data(Data::from(b"Hello Alice".to_vec()))
+        .data(Data::from(br#"Hello Alice (private)"#.to_vec()))
         .recipient(&ALICE.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3087,7 +4103,7 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     let carol_alice_public = WriteBuilder::new()
-        .data(Data::from(b"Hello Alice".to_vec()))
+        .data(Data::from(br#"Hello Alice (public)"#.to_vec()))
         .recipient(&ALICE.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3107,7 +4123,7 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     let carol_bob_private = WriteBuilder::new()
-        .data(Data::from(b"Hello Bob".to_vec()))
+        .data(Data::from(br#"Hello Bob (private)"#.to_vec()))
         .recipient(&BOB.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3126,7 +4142,7 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     let carol_bob_public = WriteBuilder::new()
-        .data(Data::from(b"Hello Bob".to_vec()))
+        .data(Data::from(br#"Hello Bob (public)"#.to_vec()))
         .recipient(&BOB.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3146,10 +4162,10 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Bob creates 2 records each for Alice and Carol: 1 public, 1 private.
+    // Bob creates 2 records each for Alice and Carol; 2 public, 2 private.
     // --------------------------------------------------
     let bob_alice_private = WriteBuilder::new()
-        .data(Data::from(b"Hello Alice".to_vec()))
+        .data(Data::from(br#"Hello Alice (private)"#.to_vec()))
         .recipient(&ALICE.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3168,7 +4184,7 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     let bob_alice_public = WriteBuilder::new()
-        .data(Data::from(b"Hello Alice".to_vec()))
+        .data(Data::from(br#"Hello Alice (public)"#.to_vec()))
         .recipient(&ALICE.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3188,7 +4204,7 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     let bob_carol_private = WriteBuilder::new()
-        .data(Data::from(b"Hello Carol".to_vec()))
+        .data(Data::from(br#"Hello Carol (private)"#.to_vec()))
         .recipient(&CAROL.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3207,7 +4223,7 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     let bob_carol_public = WriteBuilder::new()
-        .data(Data::from(b"Hello Carol".to_vec()))
+        .data(Data::from(br#"Hello Carol (public)"#.to_vec()))
         .recipient(&CAROL.did)
         .protocol(ProtocolBuilder {
             protocol: "http://allow-any.xyz",
@@ -3227,15 +4243,15 @@ async fn author_query() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Bob queries for messages with himself and Alice as authors.
+    // Bob queries for messages with himself and Alice as recipients.
     // --------------------------------------------------
     let query = QueryBuilder::new()
         .filter(
             RecordsFilter::new()
                 .protocol("http://allow-any.xyz")
                 .protocol_path("post")
-                .add_author(&BOB.did)
-                .add_author(&ALICE.did),
+                .add_recipient(&BOB.did)
+                .add_recipient(&ALICE.did),
         )
         .sign(&*BOB)
         .build()
@@ -3250,22 +4266,22 @@ async fn author_query() {
     // Bob should be able to see 7 messages
     assert_eq!(entries.len(), 7);
     assert!(entries.iter().any(|e| e.write.record_id == alice_bob_private.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_private.record_id));
     assert!(entries.iter().any(|e| e.write.record_id == bob_alice_private.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_private.record_id));
     assert!(entries.iter().any(|e| e.write.record_id == alice_bob_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == alice_carol_public.record_id));
     assert!(entries.iter().any(|e| e.write.record_id == bob_alice_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == carol_alice_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_public.record_id));
 
     // --------------------------------------------------
-    // Carol queries for messages with herself as author.
+    // Carol queries for messages with herself as recipient.
     // --------------------------------------------------
     let query = QueryBuilder::new()
         .filter(
             RecordsFilter::new()
                 .protocol("http://allow-any.xyz")
                 .protocol_path("post")
-                .add_author(&CAROL.did),
+                .add_recipient(&CAROL.did),
         )
         .sign(&*CAROL)
         .build()
@@ -3279,21 +4295,21 @@ async fn author_query() {
 
     // Carol should be able to see 4 messages
     assert_eq!(entries.len(), 4);
-    assert!(entries.iter().any(|e| e.write.record_id == carol_alice_private.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_private.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == carol_alice_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == alice_carol_private.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_private.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == alice_carol_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_public.record_id));
 
     // --------------------------------------------------
-    // Alice queries for published records with herself and Bob as authors.
+    // Alice queries for published records with herself and Bob as recipients.
     // --------------------------------------------------
     let query = QueryBuilder::new()
         .filter(
             RecordsFilter::new()
                 .protocol("http://allow-any.xyz")
                 .protocol_path("post")
-                .add_author(&ALICE.did)
-                .add_author(&BOB.did)
+                .add_recipient(&ALICE.did)
+                .add_recipient(&BOB.did)
                 .published(true),
         )
         .sign(&*ALICE)
@@ -3308,10 +4324,10 @@ async fn author_query() {
 
     // Carol should be able to see 4 messages
     assert_eq!(entries.len(), 4);
-    assert!(entries.iter().any(|e| e.write.record_id == alice_bob_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == alice_carol_public.record_id));
     assert!(entries.iter().any(|e| e.write.record_id == bob_alice_public.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == carol_alice_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == alice_bob_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_public.record_id));
 
     // --------------------------------------------------
     // Carol queries for private messages with herself and Alice as recipients.
@@ -3321,8 +4337,8 @@ async fn author_query() {
             RecordsFilter::new()
                 .protocol("http://allow-any.xyz")
                 .protocol_path("post")
-                .add_author(&CAROL.did)
-                .add_author(&ALICE.did)
+                .add_recipient(&CAROL.did)
+                .add_recipient(&ALICE.did)
                 .published(false),
         )
         .sign(&*CAROL)
@@ -3338,13 +4354,13 @@ async fn author_query() {
     // Carol should be able to see 3 messages
     assert_eq!(entries.len(), 3);
     assert!(entries.iter().any(|e| e.write.record_id == alice_carol_private.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_private.record_id));
     assert!(entries.iter().any(|e| e.write.record_id == carol_alice_private.record_id));
-    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_private.record_id));
 }
 
 // Should only return records authored by the specified author(s).
 #[tokio::test]
-async fn paginate_non_owner() {
+async fn author_query() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
@@ -3364,102 +4380,406 @@ async fn paginate_non_owner() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Public records.
+    // Alice creates 2 records each for Bob and Carol: 1 public, 1 private.
     // --------------------------------------------------
-    let mut timestamp = DateTime::parse_from_rfc3339("2024-12-31T00:00:00-00:00").unwrap();
-    let mut sorted_writes = vec![];
-
-    // Bob
-    for i in 0..5 {
-        timestamp += Duration::minutes(1);
+    let alice_bob_private = WriteBuilder::new()
+        .data(Data::from(b"Hello Bob".to_vec()))
+        .recipient(&BOB.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, alice_bob_private.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-        let write = WriteBuilder::new()
-            .message_timestamp(timestamp.into())
-            .date_created(timestamp.into())
-            .data(Data::from(format!("bob_private_{}", i).into_bytes()))
-            .protocol(ProtocolBuilder {
-                protocol: "http://allow-any.xyz",
-                protocol_path: "post",
-                parent_context_id: None,
-            })
-            .schema("post")
-            .published(true)
-            .sign(&*BOB)
-            .build()
-            .await
-            .expect("should create write");
-        let reply =
-            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
-        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-        sorted_writes.push(write);
-    }
+    let alice_bob_public = WriteBuilder::new()
+        .data(Data::from(b"Hello Bob".to_vec()))
+        .recipient(&BOB.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, alice_bob_public.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    // Alice
-    for i in 0..5 {
-        timestamp += Duration::minutes(1);
+    let alice_carol_private = WriteBuilder::new()
+        .data(Data::from(b"Hello Carol".to_vec()))
+        .recipient(&CAROL.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, alice_carol_private.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-        let write = WriteBuilder::new()
-            .message_timestamp(timestamp.into())
-            .date_created(timestamp.into())
-            .data(Data::from(format!("alice_private_{}", i).into_bytes()))
-            .protocol(ProtocolBuilder {
-                protocol: "http://allow-any.xyz",
-                protocol_path: "post",
-                parent_context_id: None,
-            })
-            .schema("post")
-            .published(true)
-            .sign(&*ALICE)
-            .build()
-            .await
-            .expect("should create write");
-        let reply =
-            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
-        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-        sorted_writes.push(write);
-    }
+    let alice_carol_public = WriteBuilder::new()
+        .data(Data::from(b"Hello Carol".to_vec()))
+        .recipient(&CAROL.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, alice_carol_public.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Private records.
+    // Carol creates 2 records each for Alice and Bob: 1 public, 1 private.
     // --------------------------------------------------
-    // Alice
-    for i in 0..5 {
-        timestamp += Duration::minutes(1);
+    let carol_alice_private = WriteBuilder::new()
+        .data(Data::from(b"Hello Alice".to_vec()))
+        .recipient(&ALICE.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .sign(&*CAROL)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, carol_alice_private.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-        let write = WriteBuilder::new()
-            .message_timestamp(timestamp.into())
-            .date_created(timestamp.into())
-            .data(Data::from(format!("alice_public_{}", i).into_bytes()))
-            .protocol(ProtocolBuilder {
-                protocol: "http://allow-any.xyz",
-                protocol_path: "post",
-                parent_context_id: None,
-            })
-            .schema("post")
-            .sign(&*ALICE)
-            .build()
-            .await
-            .expect("should create write");
-        let reply =
-            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
-        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-        sorted_writes.push(write);
-    }
+    let carol_alice_public = WriteBuilder::new()
+        .data(Data::from(b"Hello Alice".to_vec()))
+        .recipient(&ALICE.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .published(true)
+        .sign(&*CAROL)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, carol_alice_public.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    // Bob
-    for i in 0..5 {
-        timestamp += Duration::minutes(1);
+    let carol_bob_private = WriteBuilder::new()
+        .data(Data::from(b"Hello Bob".to_vec()))
+        .recipient(&BOB.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .sign(&*CAROL)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, carol_bob_private.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-        let write = WriteBuilder::new()
-            .message_timestamp(timestamp.into())
-            .date_created(timestamp.into())
-            .data(Data::from(format!("bob_public_{}", i).into_bytes()))
-            .protocol(ProtocolBuilder {
-                protocol: "http://allow-any.xyz",
-                protocol_path: "post",
+    let carol_bob_public = WriteBuilder::new()
+        .data(Data::from(b"Hello Bob".to_vec()))
+        .recipient(&BOB.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .published(true)
+        .data_format("application/json")
+        .sign(&*CAROL)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, carol_bob_public.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Bob creates 2 records each for Alice and Carol: 1 public, 1 private.
+    // --------------------------------------------------
+    let bob_alice_private = WriteBuilder::new()
+        .data(Data::from(b"Hello Alice".to_vec()))
+        .recipient(&ALICE.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, bob_alice_private.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let bob_alice_public = WriteBuilder::new()
+        .data(Data::from(b"Hello Alice".to_vec()))
+        .recipient(&ALICE.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .published(true)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, bob_alice_public.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let bob_carol_private = WriteBuilder::new()
+        .data(Data::from(b"Hello Carol".to_vec()))
+        .recipient(&CAROL.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, bob_carol_private.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let bob_carol_public = WriteBuilder::new()
+        .data(Data::from(b"Hello Carol".to_vec()))
+        .recipient(&CAROL.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://allow-any.xyz",
+            protocol_path: "post",
+            parent_context_id: None,
+        })
+        .schema("post")
+        .data_format("application/json")
+        .published(true)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, bob_carol_public.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Bob queries for messages with himself and Alice as authors.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(
+            RecordsFilter::new()
+                .protocol("http://allow-any.xyz")
+                .protocol_path("post")
+                .add_author(&BOB.did)
+                .add_author(&ALICE.did),
+        )
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+
+    // Bob should be able to see 7 messages
+    assert_eq!(entries.len(), 7);
+    assert!(entries.iter().any(|e| e.write.record_id == alice_bob_private.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == bob_alice_private.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_private.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == alice_bob_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == alice_carol_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == bob_alice_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_public.record_id));
+
+    // --------------------------------------------------
+    // Carol queries for messages with herself as author.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(
+            RecordsFilter::new()
+                .protocol("http://allow-any.xyz")
+                .protocol_path("post")
+                .add_author(&CAROL.did),
+        )
+        .sign(&*CAROL)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+
+    // Carol should be able to see 4 messages
+    assert_eq!(entries.len(), 4);
+    assert!(entries.iter().any(|e| e.write.record_id == carol_alice_private.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_private.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == carol_alice_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_public.record_id));
+
+    // --------------------------------------------------
+    // Alice queries for published records with herself and Bob as authors.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(
+            RecordsFilter::new()
+                .protocol("http://allow-any.xyz")
+                .protocol_path("post")
+                .add_author(&ALICE.did)
+                .add_author(&BOB.did)
+                .published(true),
+        )
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+
+    // Carol should be able to see 4 messages
+    assert_eq!(entries.len(), 4);
+    assert!(entries.iter().any(|e| e.write.record_id == alice_bob_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == alice_carol_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == bob_alice_public.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == bob_carol_public.record_id));
+
+    // --------------------------------------------------
+    // Carol queries for private messages with herself and Alice as recipients.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(
+            RecordsFilter::new()
+                .protocol("http://allow-any.xyz")
+                .protocol_path("post")
+                .add_author(&CAROL.did)
+                .add_author(&ALICE.did)
+                .published(false),
+        )
+        .sign(&*CAROL)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+
+    // Carol should be able to see 3 messages
+    assert_eq!(entries.len(), 3);
+    assert!(entries.iter().any(|e| e.write.record_id == alice_carol_private.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == carol_alice_private.record_id));
+    assert!(entries.iter().any(|e| e.write.record_id == carol_bob_private.record_id));
+}
+
+// Should only return records authored by the specified author(s).
+#[tokio::test]
+async fn paginate_non_owner() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a protocol.
+    // --------------------------------------------------
+    let allow_any = include_bytes!("protocols/allow-any.json");
+    let definition: Definition = serde_json::from_slice(allow_any).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Public records.
+    // --------------------------------------------------
+    let mut timestamp = DateTime::parse_from_rfc3339("2024-12-31T00:00:00-00:00").unwrap();
+    let mut sorted_writes = vec![];
+
+    // Bob
+    for i in 0..5 {
+        timestamp += Duration::minutes(1);
+
+        let write = WriteBuilder::new()
+            .message_timestamp(timestamp.into())
+            .date_created(timestamp.into())
+            .data(Data::from(format!("bob_private_{}", i).into_bytes()))
+            .protocol(ProtocolBuilder {
+                protocol: "http://allow-any.xyz",
+                protocol_path: "post",
                 parent_context_id: None,
             })
             .schema("post")
+            .published(true)
             .sign(&*BOB)
             .build()
             .await
@@ -3470,46 +4790,312 @@ async fn paginate_non_owner() {
         sorted_writes.push(write);
     }
 
-    // Alice for Bob
-    for i in 0..5 {
-        timestamp += Duration::minutes(1);
+    // Alice
+    for i in 0..5 {
+        timestamp += Duration::minutes(1);
+
+        let write = WriteBuilder::new()
+            .message_timestamp(timestamp.into())
+            .date_created(timestamp.into())
+            .data(Data::from(format!("alice_private_{}", i).into_bytes()))
+            .protocol(ProtocolBuilder {
+                protocol: "http://allow-any.xyz",
+                protocol_path: "post",
+                parent_context_id: None,
+            })
+            .schema("post")
+            .published(true)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        sorted_writes.push(write);
+    }
+
+    // --------------------------------------------------
+    // Private records.
+    // --------------------------------------------------
+    // Alice
+    for i in 0..5 {
+        timestamp += Duration::minutes(1);
+
+        let write = WriteBuilder::new()
+            .message_timestamp(timestamp.into())
+            .date_created(timestamp.into())
+            .data(Data::from(format!("alice_public_{}", i).into_bytes()))
+            .protocol(ProtocolBuilder {
+                protocol: "http://allow-any.xyz",
+                protocol_path: "post",
+                parent_context_id: None,
+            })
+            .schema("post")
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        sorted_writes.push(write);
+    }
+
+    // Bob
+    for i in 0..5 {
+        timestamp += Duration::minutes(1);
+
+        let write = WriteBuilder::new()
+            .message_timestamp(timestamp.into())
+            .date_created(timestamp.into())
+            .data(Data::from(format!("bob_public_{}", i).into_bytes()))
+            .protocol(ProtocolBuilder {
+                protocol: "http://allow-any.xyz",
+                protocol_path: "post",
+                parent_context_id: None,
+            })
+            .schema("post")
+            .sign(&*BOB)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        sorted_writes.push(write);
+    }
+
+    // Alice for Bob
+    for i in 0..5 {
+        timestamp += Duration::minutes(1);
+
+        let write = WriteBuilder::new()
+            .message_timestamp(timestamp.into())
+            .date_created(timestamp.into())
+            .data(Data::from(format!("alice_public_{}", i).into_bytes()))
+            .protocol(ProtocolBuilder {
+                protocol: "http://allow-any.xyz",
+                protocol_path: "post",
+                parent_context_id: None,
+            })
+            .schema("post")
+            .recipient(&BOB.did)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        sorted_writes.push(write);
+    }
+
+    // sort oldest to newest
+    sorted_writes.sort_by(|a, b| {
+        a.descriptor.base.message_timestamp.cmp(&b.descriptor.base.message_timestamp)
+    });
+
+    // --------------------------------------------------
+    // Alice fetches all records.
+    // -------------------------------------------------
+    let mut all_entries = vec![];
+
+    // page 1
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").protocol_path("post"))
+        .date_sort(Sort::CreatedAsc)
+        .pagination(Pagination::new().limit(10))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 10);
+    all_entries.extend(entries);
+
+    // page 2
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").protocol_path("post"))
+        .date_sort(Sort::CreatedAsc)
+        .pagination(Pagination::new().limit(10).cursor(query_reply.cursor.unwrap()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 10);
+    all_entries.extend(entries);
+
+    // page 3
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").protocol_path("post"))
+        .date_sort(Sort::CreatedAsc)
+        .pagination(Pagination::new().limit(5).cursor(query_reply.cursor.unwrap()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 5);
+    all_entries.extend(entries);
+
+    assert!(query_reply.cursor.is_none());
+    for i in 0..25 {
+        assert_eq!(all_entries[i].write.record_id, sorted_writes[i].record_id);
+    }
+
+    // --------------------------------------------------
+    // Bob fetches records he has permission to access.
+    // -------------------------------------------------
+    let mut all_entries = vec![];
+
+    // page 1
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").protocol_path("post"))
+        .date_sort(Sort::CreatedAsc)
+        .pagination(Pagination::new().limit(10))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 10);
+    all_entries.extend(entries);
+
+    // page 2
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").protocol_path("post"))
+        .date_sort(Sort::CreatedAsc)
+        .pagination(Pagination::new().limit(10).cursor(query_reply.cursor.unwrap()))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 10);
+    all_entries.extend(entries);
+
+    assert!(query_reply.cursor.is_none());
+
+    // filter sorted records for Bob
+    let bob_sorted = sorted_writes
+        .iter()
+        .filter(|w| {
+            w.authorization.signature.did().unwrap() == BOB.did
+                || w.descriptor.recipient.clone().unwrap_or_default() == BOB.did
+                || w.descriptor.published.unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    for i in 0..20 {
+        assert_eq!(all_entries[i].write.record_id, bob_sorted[i].record_id);
+    }
+}
+
+// Should treat records where `published` set to false as unpublished.
+#[tokio::test]
+async fn published_false() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice creates an unpublished record.
+    // -------------------------------------------------
+    let unpublished = WriteBuilder::new()
+        .data(Data::from(b"1".to_vec()))
+        .schema("http://schema1")
+        .published(false)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, unpublished.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice fetches the unpublished record.
+    // -------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("http://schema1"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
 
-        let write = WriteBuilder::new()
-            .message_timestamp(timestamp.into())
-            .date_created(timestamp.into())
-            .data(Data::from(format!("alice_public_{}", i).into_bytes()))
-            .protocol(ProtocolBuilder {
-                protocol: "http://allow-any.xyz",
-                protocol_path: "post",
-                parent_context_id: None,
-            })
-            .schema("post")
-            .recipient(&BOB.did)
-            .sign(&*ALICE)
-            .build()
-            .await
-            .expect("should create write");
-        let reply =
-            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
-        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-        sorted_writes.push(write);
-    }
+    // --------------------------------------------------
+    // Bob attempts to fetch the unpublished record, but fails.
+    // -------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("http://schema1"))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+    assert!(reply.body.is_none());
+}
 
-    // sort oldest to newest
-    sorted_writes.sort_by(|a, b| {
-        a.descriptor.base.message_timestamp.cmp(&b.descriptor.base.message_timestamp)
-    });
+// Should not fetch entries across tenants.
+#[tokio::test]
+async fn tenant_bound() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice fetches all records.
+    // 2 owners create records.
     // -------------------------------------------------
-    let mut all_entries = vec![];
+    let alice_write = WriteBuilder::new()
+        .data(Data::from(b"1".to_vec()))
+        .schema("http://schema1")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, alice_write, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    // page 1
+    let bob_write = WriteBuilder::new()
+        .data(Data::from(b"1".to_vec()))
+        .schema("http://schema1")
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&BOB.did, bob_write, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice fetches her record.
+    // -------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").protocol_path("post"))
-        .date_sort(Sort::CreatedAsc)
-        .pagination(Pagination::new().limit(10))
+        .filter(RecordsFilter::new().schema("http://schema1"))
         .sign(&*ALICE)
         .build()
         .await
@@ -3519,59 +5105,218 @@ async fn paginate_non_owner() {
 
     let query_reply = reply.body.expect("should have reply");
     let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 10);
-    all_entries.extend(entries);
+    assert_eq!(entries.len(), 1);
+}
+
+// Should return a status of BadRequest (400) if protocol is not normalized.
+#[tokio::test]
+async fn bad_protocol() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let mut query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("example.com/"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+
+    // builder corrects invalid protocols
+    query.descriptor.filter.protocol = Some("example.com/".to_string());
+
+    let Err(Error::BadRequest(msg)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+        panic!("should return BadRequest");
+    };
+    assert_eq!(msg, "invalid URL: example.com/");
+}
+
+// Should return a status of BadRequest (400) if schema is not normalized.
+#[tokio::test]
+async fn bad_schema() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let mut query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("example.com/"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+
+    // builder corrects invalid protocols
+    query.descriptor.filter.schema = Some("example.com/".to_string());
+
+    let Err(Error::BadRequest(msg)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+        panic!("should return BadRequest");
+    };
+    assert_eq!(msg, "invalid URL: example.com/");
+}
+
+// Should return a status of BadRequest (400) when published is `false` and a `date_published` is set.
+#[tokio::test]
+async fn bad_date_published() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
 
-    // page 2
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").protocol_path("post"))
-        .date_sort(Sort::CreatedAsc)
-        .pagination(Pagination::new().limit(10).cursor(query_reply.cursor.unwrap()))
+        .filter(
+            RecordsFilter::new().published(false).date_published(DateRange::new().gt(Utc::now())),
+        )
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let Err(Error::BadRequest(msg)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+        panic!("should return BadRequest");
+    };
+    assert!(msg.starts_with("validation failed for"));
+}
+
+// Should return a status of Unauthorized (401) when anonymous query has filter
+// explicitly for unpublished records.
+#[tokio::test]
+async fn anonymous_unpublished() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().published(false).schema("http://schema"))
+        .build()
+        .expect("should create query");
+    let Err(Error::Unauthorized(msg)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+        panic!("should return Unauthorized");
+    };
+    assert_eq!(msg, "missing authorization");
+}
+
+// Should return messages scoped to the specified `context_id`.
+#[tokio::test]
+async fn context_id() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a nested protocol (foo->bar->baz).
+    // --------------------------------------------------
+    let nested = include_bytes!("protocols/nested.json");
+    let definition: Definition = serde_json::from_slice(nested).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes 2 foo records.
+    // --------------------------------------------------
+    let foo_1 = WriteBuilder::new()
+        .data(Data::from(b"foo_1".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://nested.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .schema("foo")
+        .data_format("text/plain")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, foo_1.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let foo_2 = WriteBuilder::new()
+        .data(Data::from(b"foo_2".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://nested.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .schema("foo")
+        .data_format("text/plain")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, foo_2.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes 2 foo/bar records.
+    // --------------------------------------------------
+    let bar_1 = WriteBuilder::new()
+        .data(Data::from(b"bar_1".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://nested.xyz",
+            protocol_path: "foo/bar",
+            parent_context_id: foo_1.context_id.clone(),
+        })
+        .schema("bar")
+        .data_format("text/plain")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, bar_1.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let bar_2 = WriteBuilder::new()
+        .data(Data::from(b"bar_2".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://nested.xyz",
+            protocol_path: "foo/bar",
+            parent_context_id: foo_1.context_id.clone(),
+        })
+        .schema("bar")
+        .data_format("text/plain")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, bar_2.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes 2 foo/bar/baz records.
+    // --------------------------------------------------
+    let baz_1 = WriteBuilder::new()
+        .data(Data::from(b"baz_1".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://nested.xyz",
+            protocol_path: "foo/bar/baz",
+            parent_context_id: bar_1.context_id.clone(),
+        })
+        .schema("baz")
+        .data_format("text/plain")
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create query");
-    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
-    assert_eq!(reply.status.code, StatusCode::OK);
-
-    let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 10);
-    all_entries.extend(entries);
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, baz_1.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    // page 3
-    let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").protocol_path("post"))
-        .date_sort(Sort::CreatedAsc)
-        .pagination(Pagination::new().limit(5).cursor(query_reply.cursor.unwrap()))
+    let baz_2 = WriteBuilder::new()
+        .data(Data::from(b"baz_2".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://nested.xyz",
+            protocol_path: "foo/bar/baz",
+            parent_context_id: bar_1.context_id.clone(),
+        })
+        .schema("baz")
+        .data_format("text/plain")
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create query");
-    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
-    assert_eq!(reply.status.code, StatusCode::OK);
-
-    let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 5);
-    all_entries.extend(entries);
-
-    assert!(query_reply.cursor.is_none());
-    for i in 0..25 {
-        assert_eq!(all_entries[i].write.record_id, sorted_writes[i].record_id);
-    }
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, baz_2.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Bob fetches records he has permission to access.
-    // -------------------------------------------------
-    let mut all_entries = vec![];
-
-    // page 1
+    // Alice queries for records in foo_1 path.
+    // --------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").protocol_path("post"))
-        .date_sort(Sort::CreatedAsc)
-        .pagination(Pagination::new().limit(10))
-        .sign(&*BOB)
+        .filter(RecordsFilter::new().context_id(foo_1.context_id.unwrap()))
+        .sign(&*ALICE)
         .build()
         .await
         .expect("should create query");
@@ -3580,15 +5325,21 @@ async fn paginate_non_owner() {
 
     let query_reply = reply.body.expect("should have reply");
     let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 10);
-    all_entries.extend(entries);
+    assert_eq!(entries.len(), 5);
 
-    // page 2
+    let record_ids = entries.iter().map(|e| &e.write.record_id).collect::<Vec<_>>();
+    assert!(record_ids.contains(&&foo_1.record_id));
+    assert!(record_ids.contains(&&bar_1.record_id));
+    assert!(record_ids.contains(&&bar_2.record_id));
+    assert!(record_ids.contains(&&baz_1.record_id));
+    assert!(record_ids.contains(&&baz_2.record_id));
+
+    // --------------------------------------------------
+    // Alice queries for records in bar_1 path.
+    // --------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").protocol_path("post"))
-        .date_sort(Sort::CreatedAsc)
-        .pagination(Pagination::new().limit(10).cursor(query_reply.cursor.unwrap()))
-        .sign(&*BOB)
+        .filter(RecordsFilter::new().context_id(bar_1.context_id.unwrap()))
+        .sign(&*ALICE)
         .build()
         .await
         .expect("should create query");
@@ -3597,51 +5348,18 @@ async fn paginate_non_owner() {
 
     let query_reply = reply.body.expect("should have reply");
     let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 10);
-    all_entries.extend(entries);
-
-    assert!(query_reply.cursor.is_none());
-
-    // filter sorted records for Bob
-    let bob_sorted = sorted_writes
-        .iter()
-        .filter(|w| {
-            w.authorization.signature.did().unwrap() == BOB.did
-                || w.descriptor.recipient.clone().unwrap_or_default() == BOB.did
-                || w.descriptor.published.unwrap_or_default()
-        })
-        .collect::<Vec<_>>();
-
-    for i in 0..20 {
-        assert_eq!(all_entries[i].write.record_id, bob_sorted[i].record_id);
-    }
-}
+    assert_eq!(entries.len(), 3);
 
-// Should treat records where `published` set to false as unpublished.
-#[tokio::test]
-async fn published_false() {
-    let provider = ProviderImpl::new().await.expect("should create provider");
+    let record_ids = entries.iter().map(|e| &e.write.record_id).collect::<Vec<_>>();
+    assert!(record_ids.contains(&&bar_1.record_id));
+    assert!(record_ids.contains(&&baz_1.record_id));
+    assert!(record_ids.contains(&&baz_2.record_id));
 
     // --------------------------------------------------
-    // Alice creates an unpublished record.
-    // -------------------------------------------------
-    let unpublished = WriteBuilder::new()
-        .data(Data::from(b"1".to_vec()))
-        .schema("http://schema1")
-        .published(false)
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, unpublished.clone(), &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-
+    // Alice queries for records in baz_1 path.
     // --------------------------------------------------
-    // Alice fetches the unpublished record.
-    // -------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().schema("http://schema1"))
+        .filter(RecordsFilter::new().context_id(baz_1.context_id.unwrap()))
         .sign(&*ALICE)
         .build()
         .await
@@ -3653,152 +5371,147 @@ async fn published_false() {
     let entries = query_reply.entries.expect("should have entries");
     assert_eq!(entries.len(), 1);
 
-    // --------------------------------------------------
-    // Bob attempts to fetch the unpublished record, but fails.
-    // -------------------------------------------------
-    let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().schema("http://schema1"))
-        .sign(&*BOB)
-        .build()
-        .await
-        .expect("should create query");
-    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
-    assert_eq!(reply.status.code, StatusCode::OK);
-    assert!(reply.body.is_none());
+    let record_ids = entries.iter().map(|e| &e.write.record_id).collect::<Vec<_>>();
+    assert!(record_ids.contains(&&baz_1.record_id));
 }
 
-// Should not fetch entries across tenants.
+// Should return a context's entire subtree, limited to the records the
+// requestor is authorized to read.
 #[tokio::test]
-async fn tenant_bound() {
+async fn context_subtree() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // 2 owners create records.
-    // -------------------------------------------------
-    let alice_write = WriteBuilder::new()
-        .data(Data::from(b"1".to_vec()))
-        .schema("http://schema1")
+    // Alice configures a thread protocol.
+    // --------------------------------------------------
+    let thread_role = include_bytes!("protocols/thread-role.json");
+    let definition: Definition = serde_json::from_slice(thread_role).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, alice_write, &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-
-    let bob_write = WriteBuilder::new()
-        .data(Data::from(b"1".to_vec()))
-        .schema("http://schema1")
-        .sign(&*BOB)
-        .build()
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
         .await
-        .expect("should create write");
-    let reply = endpoint::handle(&BOB.did, bob_write, &provider).await.expect("should write");
+        .expect("should configure protocol");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice fetches her record.
-    // -------------------------------------------------
-    let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().schema("http://schema1"))
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create query");
-    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
-    assert_eq!(reply.status.code, StatusCode::OK);
-
-    let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 1);
-}
-
-// Should return a status of BadRequest (400) if protocol is not normalized.
-#[tokio::test]
-async fn bad_protocol() {
-    let provider = ProviderImpl::new().await.expect("should create provider");
-
-    let mut query = QueryBuilder::new()
-        .filter(RecordsFilter::new().protocol("example.com/"))
+    // Alice writes a thread with Bob as recipient.
+    // --------------------------------------------------
+    let thread = WriteBuilder::new()
+        .data(Data::from(b"A thread".to_vec()))
+        .recipient(&BOB.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://thread-role.xyz",
+            protocol_path: "thread",
+            parent_context_id: None,
+        })
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create query");
-
-    // builder corrects invalid protocols
-    query.descriptor.filter.protocol = Some("example.com/".to_string());
-
-    let Err(Error::BadRequest(msg)) = endpoint::handle(&ALICE.did, query, &provider).await else {
-        panic!("should return BadRequest");
-    };
-    assert_eq!(msg, "invalid URL: example.com/");
-}
-
-// Should return a status of BadRequest (400) if schema is not normalized.
-#[tokio::test]
-async fn bad_schema() {
-    let provider = ProviderImpl::new().await.expect("should create provider");
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, thread.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    let mut query = QueryBuilder::new()
-        .filter(RecordsFilter::new().schema("example.com/"))
+    // --------------------------------------------------
+    // Alice adds Bob as a thread participant.
+    // --------------------------------------------------
+    let participant = WriteBuilder::new()
+        .data(Data::from(b"Bob is a participant".to_vec()))
+        .recipient(&BOB.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://thread-role.xyz",
+            protocol_path: "thread/participant",
+            parent_context_id: thread.context_id.clone(),
+        })
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create query");
-
-    // builder corrects invalid protocols
-    query.descriptor.filter.schema = Some("example.com/".to_string());
-
-    let Err(Error::BadRequest(msg)) = endpoint::handle(&ALICE.did, query, &provider).await else {
-        panic!("should return BadRequest");
-    };
-    assert_eq!(msg, "invalid URL: example.com/");
-}
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, participant.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-// Should return a status of BadRequest (400) when published is `false` and a `date_published` is set.
-#[tokio::test]
-async fn bad_date_published() {
-    let provider = ProviderImpl::new().await.expect("should create provider");
+    // --------------------------------------------------
+    // Alice writes 2 unpublished chat records to herself.
+    // --------------------------------------------------
+    let mut chat_ids = vec![];
+    for _ in 0..2 {
+        let chat = WriteBuilder::new()
+            .data(Data::from(b"A private chat".to_vec()))
+            .recipient(&ALICE.did)
+            .protocol(ProtocolBuilder {
+                protocol: "http://thread-role.xyz",
+                protocol_path: "thread/chat",
+                parent_context_id: thread.context_id.clone(),
+            })
+            .published(false)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, chat.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        chat_ids.push(chat.record_id);
+    }
 
+    // --------------------------------------------------
+    // Alice queries the thread's context and receives the whole subtree.
+    // --------------------------------------------------
+    let context_id = thread.context_id.clone().expect("should have context_id");
     let query = QueryBuilder::new()
-        .filter(
-            RecordsFilter::new().published(false).date_published(DateRange::new().gt(Utc::now())),
-        )
+        .filter(RecordsFilter::new().context_id(&context_id))
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create query");
-    let Err(Error::BadRequest(msg)) = endpoint::handle(&ALICE.did, query, &provider).await else {
-        panic!("should return BadRequest");
-    };
-    assert!(msg.starts_with("validation failed for"));
-}
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
 
-// Should return a status of Forbidden (403) when anonymous query has filter
-// explicitly for unpublished records.
-#[tokio::test]
-async fn anonymous_unpublished() {
-    let provider = ProviderImpl::new().await.expect("should create provider");
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 4);
 
+    let record_ids = entries.iter().map(|e| &e.write.record_id).collect::<Vec<_>>();
+    assert!(record_ids.contains(&&thread.record_id));
+    assert!(record_ids.contains(&&participant.record_id));
+    assert!(chat_ids.iter().all(|id| record_ids.contains(&id)));
+
+    // --------------------------------------------------
+    // Bob queries the same context and only receives the records he can read.
+    // --------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().published(false).schema("http://schema"))
+        .filter(RecordsFilter::new().context_id(&context_id))
+        .sign(&*BOB)
         .build()
+        .await
         .expect("should create query");
-    let Err(Error::Forbidden(msg)) = endpoint::handle(&ALICE.did, query, &provider).await else {
-        panic!("should return BadRequest");
-    };
-    assert_eq!(msg, "missing authorization");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 2);
+
+    let record_ids = entries.iter().map(|e| &e.write.record_id).collect::<Vec<_>>();
+    assert!(record_ids.contains(&&thread.record_id));
+    assert!(record_ids.contains(&&participant.record_id));
 }
 
-// Should return messages scoped to the specified `context_id`.
+// Should not use protocol authorization if protocol_role is not set.
 #[tokio::test]
-async fn context_id() {
+async fn protocol_no_role() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice configures a nested protocol (foo->bar->baz).
+    // Alice configures a thread protocol.
     // --------------------------------------------------
-    let nested = include_bytes!("protocols/nested.json");
-    let definition: Definition = serde_json::from_slice(nested).expect("should deserialize");
+    let thread_role = include_bytes!("protocols/thread-role.json");
+    let definition: Definition = serde_json::from_slice(thread_role).expect("should deserialize");
     let configure = ConfigureBuilder::new()
         .definition(definition.clone())
         .sign(&*ALICE)
@@ -3811,139 +5524,169 @@ async fn context_id() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice writes 2 foo records.
+    // Alice writes a thread record.
     // --------------------------------------------------
-    let foo_1 = WriteBuilder::new()
-        .data(Data::from(b"foo_1".to_vec()))
+    let thread = WriteBuilder::new()
+        .data(Data::from(b"A new thread".to_vec()))
         .protocol(ProtocolBuilder {
-            protocol: "http://nested.xyz",
-            protocol_path: "foo",
+            protocol: "http://thread-role.xyz",
+            protocol_path: "thread",
             parent_context_id: None,
         })
-        .schema("foo")
-        .data_format("text/plain")
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, foo_1.clone(), &provider).await.expect("should write");
+    let reply =
+        endpoint::handle(&ALICE.did, thread.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    let foo_2 = WriteBuilder::new()
-        .data(Data::from(b"foo_2".to_vec()))
+    // --------------------------------------------------
+    // Alice writes a chat record addressed to BOB.
+    // --------------------------------------------------
+    let chat_bob = WriteBuilder::new()
+        .data(Data::from(b"Bob can read this".to_vec()))
+        .recipient(&BOB.did)
         .protocol(ProtocolBuilder {
-            protocol: "http://nested.xyz",
-            protocol_path: "foo",
-            parent_context_id: None,
+            protocol: "http://thread-role.xyz",
+            protocol_path: "thread/chat",
+            parent_context_id: thread.context_id.clone(),
         })
-        .schema("foo")
-        .data_format("text/plain")
+        .published(false)
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, foo_2.clone(), &provider).await.expect("should write");
+    let reply =
+        endpoint::handle(&ALICE.did, chat_bob.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice writes 2 foo/bar records.
+    // Alice writes 2 more chat records NOT addressed to BOB.
     // --------------------------------------------------
-    let bar_1 = WriteBuilder::new()
-        .data(Data::from(b"bar_1".to_vec()))
-        .protocol(ProtocolBuilder {
-            protocol: "http://nested.xyz",
-            protocol_path: "foo/bar",
-            parent_context_id: foo_1.context_id.clone(),
-        })
-        .schema("bar")
-        .data_format("text/plain")
-        .sign(&*ALICE)
+    for _ in 0..2 {
+        let chat = WriteBuilder::new()
+            .data(Data::from(b"Bob cannot read this".to_vec()))
+            .recipient(&ALICE.did)
+            .protocol(ProtocolBuilder {
+                protocol: "http://thread-role.xyz",
+                protocol_path: "thread/chat",
+                parent_context_id: thread.context_id.clone(),
+            })
+            .published(false)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, chat.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // --------------------------------------------------
+    // Bob queries without invoking protocol role.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://thread-role.xyz"))
+        .sign(&*BOB)
         .build()
         .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, bar_1.clone(), &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
 
-    let bar_2 = WriteBuilder::new()
-        .data(Data::from(b"bar_2".to_vec()))
-        .protocol(ProtocolBuilder {
-            protocol: "http://nested.xyz",
-            protocol_path: "foo/bar",
-            parent_context_id: foo_1.context_id.clone(),
-        })
-        .schema("bar")
-        .data_format("text/plain")
-        .sign(&*ALICE)
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, chat_bob.record_id);
+
+    // --------------------------------------------------
+    // Bob queries without invoking protocol role and only unpublished records.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://thread-role.xyz").published(false))
+        .sign(&*BOB)
         .build()
         .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, bar_2.clone(), &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let entries = query_reply.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, chat_bob.record_id);
+}
+
+// Should allow queries authorized using a root-level role.
+#[tokio::test]
+async fn protocol_role() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice writes 2 foo/bar/baz records.
+    // Alice configures a friend protocol.
     // --------------------------------------------------
-    let baz_1 = WriteBuilder::new()
-        .data(Data::from(b"baz_1".to_vec()))
-        .protocol(ProtocolBuilder {
-            protocol: "http://nested.xyz",
-            protocol_path: "foo/bar/baz",
-            parent_context_id: bar_1.context_id.clone(),
-        })
-        .schema("baz")
-        .data_format("text/plain")
+    let friend_role = include_bytes!("protocols/friend-role.json");
+    let definition: Definition = serde_json::from_slice(friend_role).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, baz_1.clone(), &provider).await.expect("should write");
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    let baz_2 = WriteBuilder::new()
-        .data(Data::from(b"baz_2".to_vec()))
+    // --------------------------------------------------
+    // Alice writes a 'friend' role record with Bob as recipient.
+    // --------------------------------------------------
+    let bob_friend = WriteBuilder::new()
+        .data(Data::from(b"Bob is a friend".to_vec()))
+        .recipient(&BOB.did)
         .protocol(ProtocolBuilder {
-            protocol: "http://nested.xyz",
-            protocol_path: "foo/bar/baz",
-            parent_context_id: bar_1.context_id.clone(),
+            protocol: "http://friend-role.xyz",
+            protocol_path: "friend",
+            parent_context_id: None,
         })
-        .schema("baz")
-        .data_format("text/plain")
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, baz_2.clone(), &provider).await.expect("should write");
+    let reply =
+        endpoint::handle(&ALICE.did, bob_friend.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice queries for records in foo_1 path.
+    // Alice writes 3 chat records.
     // --------------------------------------------------
-    let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().context_id(foo_1.context_id.unwrap()))
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create query");
-    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
-    assert_eq!(reply.status.code, StatusCode::OK);
-
-    let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 5);
-
-    let record_ids = entries.iter().map(|e| &e.write.record_id).collect::<Vec<_>>();
-    assert!(record_ids.contains(&&foo_1.record_id));
-    assert!(record_ids.contains(&&bar_1.record_id));
-    assert!(record_ids.contains(&&bar_2.record_id));
-    assert!(record_ids.contains(&&baz_1.record_id));
-    assert!(record_ids.contains(&&baz_2.record_id));
+    for _ in 0..3 {
+        let chat = WriteBuilder::new()
+            .data(Data::from(b"Bob can read this because he is a friend".to_vec()))
+            .recipient(&ALICE.did)
+            .protocol(ProtocolBuilder {
+                protocol: "http://friend-role.xyz",
+                protocol_path: "chat",
+                parent_context_id: None,
+            })
+            .published(false)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, chat.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
 
     // --------------------------------------------------
-    // Alice queries for records in bar_1 path.
+    // Bob queries uses his friend role to query for records.
     // --------------------------------------------------
-    let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().context_id(bar_1.context_id.unwrap()))
-        .sign(&*ALICE)
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://friend-role.xyz").protocol_path("chat"))
+        .protocol_role("friend")
+        .sign(&*BOB)
         .build()
         .await
         .expect("should create query");
@@ -3954,17 +5697,18 @@ async fn context_id() {
     let entries = query_reply.entries.expect("should have entries");
     assert_eq!(entries.len(), 3);
 
-    let record_ids = entries.iter().map(|e| &e.write.record_id).collect::<Vec<_>>();
-    assert!(record_ids.contains(&&bar_1.record_id));
-    assert!(record_ids.contains(&&baz_1.record_id));
-    assert!(record_ids.contains(&&baz_2.record_id));
-
     // --------------------------------------------------
-    // Alice queries for records in baz_1 path.
+    // Bob uses his friend role and an 'unpublished' filter to query for records.
     // --------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().context_id(baz_1.context_id.unwrap()))
-        .sign(&*ALICE)
+        .filter(
+            RecordsFilter::new()
+                .protocol("http://friend-role.xyz")
+                .protocol_path("chat")
+                .published(false),
+        )
+        .protocol_role("friend")
+        .sign(&*BOB)
         .build()
         .await
         .expect("should create query");
@@ -3973,19 +5717,16 @@ async fn context_id() {
 
     let query_reply = reply.body.expect("should have reply");
     let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 1);
-
-    let record_ids = entries.iter().map(|e| &e.write.record_id).collect::<Vec<_>>();
-    assert!(record_ids.contains(&&baz_1.record_id));
+    assert_eq!(entries.len(), 3);
 }
 
-// Should not use protocol authorization if protocol_role is not set.
+// Should allow queries authorize using a context role.
 #[tokio::test]
-async fn protocol_no_role() {
+async fn context_role() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice configures a thread protocol.
+    // Alice configures a friend protocol.
     // --------------------------------------------------
     let thread_role = include_bytes!("protocols/thread-role.json");
     let definition: Definition = serde_json::from_slice(thread_role).expect("should deserialize");
@@ -4001,10 +5742,11 @@ async fn protocol_no_role() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice writes a thread record.
+    // Alice writes a thread.
     // --------------------------------------------------
     let thread = WriteBuilder::new()
-        .data(Data::from(b"A new thread".to_vec()))
+        .data(Data::from(b"Bob is a friend".to_vec()))
+        .recipient(&BOB.did)
         .protocol(ProtocolBuilder {
             protocol: "http://thread-role.xyz",
             protocol_path: "thread",
@@ -4019,31 +5761,31 @@ async fn protocol_no_role() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice writes a chat record addressed to BOB.
+    // Alice writes a 'participant' role record with Bob as recipient.
     // --------------------------------------------------
-    let chat_bob = WriteBuilder::new()
-        .data(Data::from(b"Bob can read this".to_vec()))
+    let participant_role = WriteBuilder::new()
+        .data(Data::from(b"Bob is a friend".to_vec()))
         .recipient(&BOB.did)
         .protocol(ProtocolBuilder {
             protocol: "http://thread-role.xyz",
-            protocol_path: "thread/chat",
+            protocol_path: "thread/participant",
             parent_context_id: thread.context_id.clone(),
         })
-        .published(false)
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, chat_bob.clone(), &provider).await.expect("should write");
+    let reply = endpoint::handle(&ALICE.did, participant_role.clone(), &provider)
+        .await
+        .expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice writes 2 more chat records NOT addressed to BOB.
+    // Alice writes 3 chat records.
     // --------------------------------------------------
-    for _ in 0..2 {
+    for _ in 0..3 {
         let chat = WriteBuilder::new()
-            .data(Data::from(b"Bob cannot read this".to_vec()))
+            .data(Data::from(b"Bob can read this because he is a friend".to_vec()))
             .recipient(&ALICE.did)
             .protocol(ProtocolBuilder {
                 protocol: "http://thread-role.xyz",
@@ -4061,27 +5803,16 @@ async fn protocol_no_role() {
     }
 
     // --------------------------------------------------
-    // Bob queries without invoking protocol role.
-    // --------------------------------------------------
-    let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().protocol("http://thread-role.xyz"))
-        .sign(&*BOB)
-        .build()
-        .await
-        .expect("should create query");
-    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
-    assert_eq!(reply.status.code, StatusCode::OK);
-
-    let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 1);
-    assert_eq!(entries[0].write.record_id, chat_bob.record_id);
-
-    // --------------------------------------------------
-    // Bob queries without invoking protocol role and only unpublished records.
+    // Bob uses his 'friend' role to query for chat records.
     // --------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().protocol("http://thread-role.xyz").published(false))
+        .filter(
+            RecordsFilter::new()
+                .protocol("http://thread-role.xyz")
+                .protocol_path("thread/chat")
+                .context_id(thread.context_id.unwrap()),
+        )
+        .protocol_role("thread/participant")
         .sign(&*BOB)
         .build()
         .await
@@ -4091,13 +5822,12 @@ async fn protocol_no_role() {
 
     let query_reply = reply.body.expect("should have reply");
     let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 1);
-    assert_eq!(entries[0].write.record_id, chat_bob.record_id);
+    assert_eq!(entries.len(), 3);
 }
 
-// Should allow queries authorized using a root-level role.
+// Should not execute protocol queries where `protocol_path` is missing.
 #[tokio::test]
-async fn protocol_role() {
+async fn no_protocol_path() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
@@ -4117,9 +5847,9 @@ async fn protocol_role() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice writes a 'friend' role record with Bob as recipient.
+    // Alice writes a friend role record.
     // --------------------------------------------------
-    let bob_friend = WriteBuilder::new()
+    let friend = WriteBuilder::new()
         .data(Data::from(b"Bob is a friend".to_vec()))
         .recipient(&BOB.did)
         .protocol(ProtocolBuilder {
@@ -4132,7 +5862,7 @@ async fn protocol_role() {
         .await
         .expect("should create write");
     let reply =
-        endpoint::handle(&ALICE.did, bob_friend.clone(), &provider).await.expect("should write");
+        endpoint::handle(&ALICE.did, friend.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
@@ -4158,48 +5888,187 @@ async fn protocol_role() {
     }
 
     // --------------------------------------------------
-    // Bob queries uses his friend role to query for records.
+    // Bob uses his 'friend' role to query for chat records BUT does not have `protocol_path` set.
     // --------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().protocol("http://friend-role.xyz").protocol_path("chat"))
+        .filter(RecordsFilter::new().protocol("http://friend-role.xyz"))
         .protocol_role("friend")
         .sign(&*BOB)
         .build()
         .await
         .expect("should create query");
-    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
-    assert_eq!(reply.status.code, StatusCode::OK);
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "missing `protocol_path`");
+}
 
-    let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 3);
+// Should not execute context role authorized queries when `context_id` is missing.
+#[tokio::test]
+async fn no_context_id() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Bob uses his friend role and an 'unpublished' filter to query for records.
+    // Alice configures a friend protocol.
+    // --------------------------------------------------
+    let thread_role = include_bytes!("protocols/thread-role.json");
+    let definition: Definition = serde_json::from_slice(thread_role).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes a friend role record.
+    // --------------------------------------------------
+    let thread = WriteBuilder::new()
+        .data(Data::from(b"Bob is a friend".to_vec()))
+        .recipient(&BOB.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://thread-role.xyz",
+            protocol_path: "thread",
+            parent_context_id: None,
+        })
+        // .context_id() deliberately omitted
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, thread.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes a 'participant' role record with Bob as recipient.
+    // --------------------------------------------------
+    let participant_role = WriteBuilder::new()
+        .data(Data::from(b"Bob is a friend".to_vec()))
+        .recipient(&BOB.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://thread-role.xyz",
+            protocol_path: "thread/participant",
+            parent_context_id: thread.context_id.clone(),
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, participant_role.clone(), &provider)
+        .await
+        .expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes 3 chat records.
+    // --------------------------------------------------
+    for _ in 0..3 {
+        let chat = WriteBuilder::new()
+            .data(Data::from(b"Bob can read this because he is a friend".to_vec()))
+            .recipient(&ALICE.did)
+            .protocol(ProtocolBuilder {
+                protocol: "http://thread-role.xyz",
+                protocol_path: "thread/chat",
+                parent_context_id: thread.context_id.clone(),
+            })
+            .published(false)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, chat.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // --------------------------------------------------
+    // Bob uses his thread participant role to query BUT omits the `context_id`.
     // --------------------------------------------------
     let query = QueryBuilder::new()
         .filter(
-            RecordsFilter::new()
-                .protocol("http://friend-role.xyz")
-                .protocol_path("chat")
-                .published(false),
+            RecordsFilter::new().protocol("http://thread-role.xyz").protocol_path("thread/chat"),
         )
+        .protocol_role("thread/participant")
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "missing `context_id`");
+}
+
+// Should reject root-level role authorized queries if a matching root-level
+// role record is not found for the message author.
+#[tokio::test]
+async fn no_root_role_record() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a friend protocol.
+    // --------------------------------------------------
+    let friend_role = include_bytes!("protocols/friend-role.json");
+    let definition: Definition = serde_json::from_slice(friend_role).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes 3 chat records.
+    // --------------------------------------------------
+    for _ in 0..3 {
+        let chat = WriteBuilder::new()
+            .data(Data::from(b"Bob can read this because he is a friend".to_vec()))
+            .recipient(&ALICE.did)
+            .protocol(ProtocolBuilder {
+                protocol: "http://friend-role.xyz",
+                protocol_path: "chat",
+                parent_context_id: None,
+            })
+            .published(false)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, chat.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // --------------------------------------------------
+    // Bob uses his friend participant role to query.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://friend-role.xyz").protocol_path("chat"))
         .protocol_role("friend")
         .sign(&*BOB)
         .build()
         .await
         .expect("should create query");
-    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
-    assert_eq!(reply.status.code, StatusCode::OK);
-
-    let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 3);
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, query, &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "unable to find record for role");
 }
 
-// Should allow queries authorize using a context role.
+// Should reject context role authorized queries if a matching context role record is not found.
 #[tokio::test]
-async fn context_role() {
+async fn no_context_role() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
@@ -4219,7 +6088,7 @@ async fn context_role() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice writes a thread.
+    // Alice writes a thread role record.
     // --------------------------------------------------
     let thread = WriteBuilder::new()
         .data(Data::from(b"Bob is a friend".to_vec()))
@@ -4229,6 +6098,7 @@ async fn context_role() {
             protocol_path: "thread",
             parent_context_id: None,
         })
+        // .context_id() deliberately omitted
         .sign(&*ALICE)
         .build()
         .await
@@ -4237,26 +6107,6 @@ async fn context_role() {
         endpoint::handle(&ALICE.did, thread.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    // --------------------------------------------------
-    // Alice writes a 'participant' role record with Bob as recipient.
-    // --------------------------------------------------
-    let participant_role = WriteBuilder::new()
-        .data(Data::from(b"Bob is a friend".to_vec()))
-        .recipient(&BOB.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://thread-role.xyz",
-            protocol_path: "thread/participant",
-            parent_context_id: thread.context_id.clone(),
-        })
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, participant_role.clone(), &provider)
-        .await
-        .expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-
     // --------------------------------------------------
     // Alice writes 3 chat records.
     // --------------------------------------------------
@@ -4280,7 +6130,7 @@ async fn context_role() {
     }
 
     // --------------------------------------------------
-    // Bob uses his 'friend' role to query for chat records.
+    // Bob uses his thread participant role to query BUT omits the `context_id`.
     // --------------------------------------------------
     let query = QueryBuilder::new()
         .filter(
@@ -4294,206 +6144,442 @@ async fn context_role() {
         .build()
         .await
         .expect("should create query");
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, query, &provider).await
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "unable to find record for role");
+}
+
+// Should sort results using composite sort keys, ordering records missing a
+// sort field last.
+#[tokio::test]
+async fn composite_sort() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice creates records with varying priorities and created dates.
+    // --------------------------------------------------
+    let created = DateTime::parse_from_rfc3339("2024-01-01T00:00:00-00:00").unwrap();
+
+    let mut writes = vec![];
+    for (i, priority) in (0..).zip([Some(1), Some(2), Some(2), None, Some(10)]) {
+        let date_created = created + Duration::seconds(i);
+        let mut builder = WriteBuilder::new()
+            .data(Data::from(format!("record {i}").into_bytes()))
+            .schema("http://composite-sort.xyz")
+            .date_created(date_created.into())
+            .message_timestamp(date_created.into());
+        if let Some(priority) = priority {
+            builder = builder.add_tag("priority", Tag::Number(priority));
+        }
+        let write = builder.sign(&*ALICE).build().await.expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        writes.push(write);
+    }
+
+    // --------------------------------------------------
+    // Alice queries by priority (descending) then created date (ascending).
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("http://composite-sort.xyz"))
+        .add_sort(SortKey::descending("tag.priority"))
+        .add_sort(SortKey::from(Sort::CreatedAsc))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
     let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
     assert_eq!(reply.status.code, StatusCode::OK);
 
-    let query_reply = reply.body.expect("should have reply");
-    let entries = query_reply.entries.expect("should have entries");
-    assert_eq!(entries.len(), 3);
+    let body = reply.body.expect("should have body");
+    let entries = body.entries.expect("should have entries");
+    let record_ids = entries.iter().map(|e| e.write.record_id.clone()).collect::<Vec<_>>();
+    let expected = [4, 1, 2, 0, 3].iter().map(|&i| writes[i].record_id.clone()).collect::<Vec<_>>();
+    assert_eq!(record_ids, expected);
+
+    // --------------------------------------------------
+    // Composite sort cannot be combined with `date_sort`.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("http://composite-sort.xyz"))
+        .date_sort(Sort::CreatedAsc)
+        .add_sort(SortKey::descending("tag.priority"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "cannot specify both `date_sort` and `sort`");
 }
 
-// Should not execute protocol queries where `protocol_path` is missing.
+// Should return a status of Unauthorized (401) when signature check fails.
 #[tokio::test]
-async fn no_protocol_path() {
+async fn bad_signature() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
-    // --------------------------------------------------
-    // Alice configures a friend protocol.
-    // --------------------------------------------------
-    let friend_role = include_bytes!("protocols/friend-role.json");
-    let definition: Definition = serde_json::from_slice(friend_role).expect("should deserialize");
-    let configure = ConfigureBuilder::new()
-        .definition(definition.clone())
+    let mut query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("http://schema"))
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should build");
-    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .expect("should create query");
+
+    query.authorization.as_mut().unwrap().signature.signatures[0].signature =
+        "badsignature".to_string();
+
+    let Err(Error::Unauthorized(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+        panic!("should be Unauthorized");
+    };
+    assert!(e.starts_with("failed to authenticate: "));
+}
+
+// Should return a status of BadRequest (400) when the message cannot be parsed.
+#[tokio::test]
+async fn bad_message() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let mut query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("http://schema"))
+        .sign(&*ALICE)
+        .build()
         .await
-        .expect("should configure protocol");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        .expect("should create query");
+
+    query.descriptor.filter = RecordsFilter::default();
+
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert!(e.starts_with("validation failed for "));
+}
+
+// Should find records by searching indexed string fields, ranking results by
+// the number of field hits.
+#[tokio::test]
+async fn text_search() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice writes a friend role record.
+    // Alice configures two protocols.
+    // --------------------------------------------------
+    for protocol in ["https://example.com/photos", "https://example.com/notes"] {
+        let def_json = serde_json::json!({
+            "published" : true,
+            "protocol"  : protocol,
+            "types"     : {
+                "entry": {}
+            },
+            "structure": {
+                "entry": {}
+            }
+        });
+        let definition: Definition = serde_json::from_value(def_json).expect("should deserialize");
+        let configure = ConfigureBuilder::new()
+            .definition(definition)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should build");
+        let reply = endpoint::handle(&ALICE.did, configure, &provider)
+            .await
+            .expect("should configure protocol");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
     // --------------------------------------------------
-    let friend = WriteBuilder::new()
-        .data(Data::from(b"Bob is a friend".to_vec()))
-        .recipient(&BOB.did)
+    // Alice writes a record under each protocol.
+    // --------------------------------------------------
+    let photos = WriteBuilder::new()
+        .data(Data::from(b"photo".to_vec()))
         .protocol(ProtocolBuilder {
-            protocol: "http://friend-role.xyz",
-            protocol_path: "friend",
+            protocol: "https://example.com/photos",
+            protocol_path: "entry",
             parent_context_id: None,
         })
+        .add_tag("caption", Tag::String("Holiday Notes".to_string()))
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
     let reply =
-        endpoint::handle(&ALICE.did, friend.clone(), &provider).await.expect("should write");
+        endpoint::handle(&ALICE.did, photos.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let notes = WriteBuilder::new()
+        .data(Data::from(b"note".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "https://example.com/notes",
+            protocol_path: "entry",
+            parent_context_id: None,
+        })
+        .add_tag("topic", Tag::String("notes".to_string()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, notes.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice writes 3 chat records.
+    // Alice finds the photos record by a protocol URI fragment.
     // --------------------------------------------------
-    for _ in 0..3 {
-        let chat = WriteBuilder::new()
-            .data(Data::from(b"Bob can read this because he is a friend".to_vec()))
-            .recipient(&ALICE.did)
-            .protocol(ProtocolBuilder {
-                protocol: "http://friend-role.xyz",
-                protocol_path: "chat",
-                parent_context_id: None,
-            })
-            .published(false)
-            .sign(&*ALICE)
-            .build()
-            .await
-            .expect("should create write");
-        let reply =
-            endpoint::handle(&ALICE.did, chat.clone(), &provider).await.expect("should write");
-        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-    }
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().text("PHOTOS"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, photos.record_id);
 
     // --------------------------------------------------
-    // Bob uses his 'friend' role to query for chat records BUT does not have `protocol_path` set.
+    // Records with more field hits are ranked first.
     // --------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().protocol("http://friend-role.xyz"))
-        .protocol_role("friend")
-        .sign(&*BOB)
+        .filter(RecordsFilter::new().text("notes"))
+        .sign(&*ALICE)
         .build()
         .await
         .expect("should create query");
-    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
-        panic!("should be BadRequest");
-    };
-    assert_eq!(e, "missing `protocol_path`");
-}
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
 
-// Should not execute context role authorized queries when `context_id` is missing.
-#[tokio::test]
-async fn no_context_id() {
-    let provider = ProviderImpl::new().await.expect("should create provider");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].write.record_id, notes.record_id);
+    assert_eq!(entries[1].write.record_id, photos.record_id);
 
     // --------------------------------------------------
-    // Alice configures a friend protocol.
+    // Results are ranked before they are paginated.
     // --------------------------------------------------
-    let thread_role = include_bytes!("protocols/thread-role.json");
-    let definition: Definition = serde_json::from_slice(thread_role).expect("should deserialize");
-    let configure = ConfigureBuilder::new()
-        .definition(definition.clone())
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().text("notes"))
+        .pagination(Pagination::new().limit(1))
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should build");
-    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+
+    let body = reply.body.expect("should have reply");
+    let entries = body.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, notes.record_id);
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().text("notes"))
+        .pagination(Pagination::new().limit(1).cursor(body.cursor.expect("should have cursor")))
+        .sign(&*ALICE)
+        .build()
         .await
-        .expect("should configure protocol");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, photos.record_id);
+
+    // --------------------------------------------------
+    // Searched fields can be restricted.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().text("notes").text_fields(["protocol"]))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, notes.record_id);
+
+    // --------------------------------------------------
+    // Bob cannot find Alice's unpublished records.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().text("example"))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+    assert!(reply.body.is_none());
+}
+
+// Should return only the number of matching records the caller can see.
+#[tokio::test]
+async fn count_only() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes 5 published and 5 unpublished records.
+    // --------------------------------------------------
+    for published in [true, false] {
+        for i in 0..5 {
+            let write = WriteBuilder::new()
+                .data(Data::from(format!("record {i}").into_bytes()))
+                .schema("counted")
+                .published(published)
+                .sign(&*ALICE)
+                .build()
+                .await
+                .expect("should create write");
+            let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+            assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        }
+    }
 
     // --------------------------------------------------
-    // Alice writes a friend role record.
+    // An anonymous count only includes published records.
     // --------------------------------------------------
-    let thread = WriteBuilder::new()
-        .data(Data::from(b"Bob is a friend".to_vec()))
-        .recipient(&BOB.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://thread-role.xyz",
-            protocol_path: "thread",
-            parent_context_id: None,
-        })
-        // .context_id() deliberately omitted
-        .sign(&*ALICE)
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("counted"))
+        .count_only(true)
         .build()
-        .await
-        .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, thread.clone(), &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    assert!(query_reply.entries.is_none());
+    assert_eq!(query_reply.count, Some(5));
 
     // --------------------------------------------------
-    // Alice writes a 'participant' role record with Bob as recipient.
+    // Alice (owner) counts all records.
     // --------------------------------------------------
-    let participant_role = WriteBuilder::new()
-        .data(Data::from(b"Bob is a friend".to_vec()))
-        .recipient(&BOB.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://thread-role.xyz",
-            protocol_path: "thread/participant",
-            parent_context_id: thread.context_id.clone(),
-        })
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("counted"))
+        .pagination(Pagination::new().limit(2))
+        .count_only(true)
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, participant_role.clone(), &provider)
-        .await
-        .expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    assert!(query_reply.entries.is_none());
+    assert!(query_reply.cursor.is_none());
+    assert_eq!(query_reply.count, Some(10));
+}
+
+// Should return the number of records per protocol the caller can see.
+#[tokio::test]
+async fn group_by() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice writes 3 chat records.
+    // Alice configures two protocols.
     // --------------------------------------------------
-    for _ in 0..3 {
-        let chat = WriteBuilder::new()
-            .data(Data::from(b"Bob can read this because he is a friend".to_vec()))
-            .recipient(&ALICE.did)
-            .protocol(ProtocolBuilder {
-                protocol: "http://thread-role.xyz",
-                protocol_path: "thread/chat",
-                parent_context_id: thread.context_id.clone(),
-            })
-            .published(false)
+    for protocol in ["http://foo.xyz", "http://bar.xyz"] {
+        let definition = Definition::new(protocol)
+            .add_type("item", ProtocolType::default())
+            .add_rule("item", RuleSet::default());
+        let configure = ConfigureBuilder::new()
+            .definition(definition)
             .sign(&*ALICE)
             .build()
             .await
-            .expect("should create write");
-        let reply =
-            endpoint::handle(&ALICE.did, chat.clone(), &provider).await.expect("should write");
+            .expect("should build");
+        let reply = endpoint::handle(&ALICE.did, configure, &provider)
+            .await
+            .expect("should configure protocol");
         assert_eq!(reply.status.code, StatusCode::ACCEPTED);
     }
 
     // --------------------------------------------------
-    // Bob uses his thread participant role to query BUT omits the `context_id`.
+    // Alice writes 3 foo records, 2 of them published, and 2 unpublished bar
+    // records, 1 of them for Bob.
+    // --------------------------------------------------
+    let records = [
+        ("http://foo.xyz", true, None),
+        ("http://foo.xyz", true, None),
+        ("http://foo.xyz", false, None),
+        ("http://bar.xyz", false, Some(&BOB.did)),
+        ("http://bar.xyz", false, None),
+    ];
+    for (protocol, published, recipient) in records {
+        let mut builder = WriteBuilder::new()
+            .data(Data::from(b"an item".to_vec()))
+            .protocol(ProtocolBuilder {
+                protocol,
+                protocol_path: "item",
+                parent_context_id: None,
+            })
+            .published(published);
+        if let Some(recipient) = recipient {
+            builder = builder.recipient(recipient);
+        }
+        let write = builder.sign(&*ALICE).build().await.expect("should create write");
+        let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // --------------------------------------------------
+    // Alice (owner) counts all records per protocol.
     // --------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(
-            RecordsFilter::new().protocol("http://thread-role.xyz").protocol_path("thread/chat"),
-        )
-        .protocol_role("thread/participant")
+        .filter(RecordsFilter::new().protocol_path("item"))
+        .group_by(GroupBy::Protocol)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    assert!(query_reply.entries.is_none());
+    let groups = query_reply.groups.expect("should have groups");
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups["http://foo.xyz"], 3);
+    assert_eq!(groups["http://bar.xyz"], 2);
+
+    // --------------------------------------------------
+    // Bob's counts only include published records and records for him.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol_path("item"))
+        .group_by(GroupBy::Protocol)
         .sign(&*BOB)
         .build()
         .await
         .expect("should create query");
-    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
-        panic!("should be BadRequest");
-    };
-    assert_eq!(e, "missing `context_id`");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let query_reply = reply.body.expect("should have reply");
+    let groups = query_reply.groups.expect("should have groups");
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups["http://foo.xyz"], 2);
+    assert_eq!(groups["http://bar.xyz"], 1);
 }
 
-// Should reject root-level role authorized queries if a matching root-level
-// role record is not found for the message author.
+// Should return the union of records matching any of the sub-filters, limited
+// to records the caller is authorized to see.
 #[tokio::test]
-async fn no_root_role_record() {
+async fn any_of() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice configures a friend protocol.
+    // Alice configures a protocol.
     // --------------------------------------------------
-    let friend_role = include_bytes!("protocols/friend-role.json");
-    let definition: Definition = serde_json::from_slice(friend_role).expect("should deserialize");
+    let allow_any = include_bytes!("protocols/allow-any.json");
+    let definition: Definition = serde_json::from_slice(allow_any).expect("should deserialize");
     let configure = ConfigureBuilder::new()
-        .definition(definition.clone())
+        .definition(definition)
         .sign(&*ALICE)
         .build()
         .await
@@ -4504,164 +6590,153 @@ async fn no_root_role_record() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice writes 3 chat records.
+    // Alice writes a record for Bob and one for Carol. Bob and Carol each
+    // write a record.
     // --------------------------------------------------
-    for _ in 0..3 {
-        let chat = WriteBuilder::new()
-            .data(Data::from(b"Bob can read this because he is a friend".to_vec()))
-            .recipient(&ALICE.did)
+    let mut record_ids = vec![];
+    for (author, recipient) in
+        [(&*ALICE, Some(&BOB.did)), (&*ALICE, Some(&CAROL.did)), (&*BOB, None), (&*CAROL, None)]
+    {
+        let mut builder = WriteBuilder::new()
+            .data(Data::from(b"inbox message".to_vec()))
             .protocol(ProtocolBuilder {
-                protocol: "http://friend-role.xyz",
-                protocol_path: "chat",
+                protocol: "http://allow-any.xyz",
+                protocol_path: "post",
                 parent_context_id: None,
             })
-            .published(false)
-            .sign(&*ALICE)
-            .build()
-            .await
-            .expect("should create write");
-        let reply =
-            endpoint::handle(&ALICE.did, chat.clone(), &provider).await.expect("should write");
+            .schema("post")
+            .data_format("application/json");
+        if let Some(recipient) = recipient {
+            builder = builder.recipient(recipient);
+        }
+        let write = builder.sign(author).build().await.expect("should create write");
+        record_ids.push(write.record_id.clone());
+
+        let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
         assert_eq!(reply.status.code, StatusCode::ACCEPTED);
     }
 
     // --------------------------------------------------
-    // Bob uses his friend participant role to query.
+    // Bob queries for records sent to him OR authored by him.
     // --------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().protocol("http://friend-role.xyz").protocol_path("chat"))
-        .protocol_role("friend")
+        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").any_of(vec![
+            RecordsFilter::new().add_recipient(&BOB.did),
+            RecordsFilter::new().add_author(&BOB.did),
+        ]))
         .sign(&*BOB)
         .build()
         .await
         .expect("should create query");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
-        panic!("should be Forbidden");
-    };
-    assert_eq!(e, "unable to find record for role");
-}
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
 
-// Should reject context role authorized queries if a matching context role record is not found.
-#[tokio::test]
-async fn no_context_role() {
-    let provider = ProviderImpl::new().await.expect("should create provider");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().any(|e| e.write.record_id == record_ids[0]));
+    assert!(entries.iter().any(|e| e.write.record_id == record_ids[2]));
 
     // --------------------------------------------------
-    // Alice configures a friend protocol.
+    // Bob cannot use the OR filter to see Carol's records.
     // --------------------------------------------------
-    let thread_role = include_bytes!("protocols/thread-role.json");
-    let definition: Definition = serde_json::from_slice(thread_role).expect("should deserialize");
-    let configure = ConfigureBuilder::new()
-        .definition(definition.clone())
-        .sign(&*ALICE)
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").any_of(vec![
+            RecordsFilter::new().add_recipient(&CAROL.did),
+            RecordsFilter::new().add_author(&CAROL.did),
+        ]))
+        .sign(&*BOB)
         .build()
         .await
-        .expect("should build");
-    let reply = endpoint::handle(&ALICE.did, configure, &provider)
-        .await
-        .expect("should configure protocol");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+    assert!(reply.body.is_none());
 
     // --------------------------------------------------
-    // Alice writes a thread role record.
+    // Alice (owner) can query Carol's records.
     // --------------------------------------------------
-    let thread = WriteBuilder::new()
-        .data(Data::from(b"Bob is a friend".to_vec()))
-        .recipient(&BOB.did)
-        .protocol(ProtocolBuilder {
-            protocol: "http://thread-role.xyz",
-            protocol_path: "thread",
-            parent_context_id: None,
-        })
-        // .context_id() deliberately omitted
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().protocol("http://allow-any.xyz").any_of(vec![
+            RecordsFilter::new().add_recipient(&CAROL.did),
+            RecordsFilter::new().add_author(&CAROL.did),
+        ]))
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, thread.clone(), &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
 
-    // --------------------------------------------------
-    // Alice writes 3 chat records.
-    // --------------------------------------------------
-    for _ in 0..3 {
-        let chat = WriteBuilder::new()
-            .data(Data::from(b"Bob can read this because he is a friend".to_vec()))
-            .recipient(&ALICE.did)
-            .protocol(ProtocolBuilder {
-                protocol: "http://thread-role.xyz",
-                protocol_path: "thread/chat",
-                parent_context_id: thread.context_id.clone(),
-            })
-            .published(false)
-            .sign(&*ALICE)
-            .build()
-            .await
-            .expect("should create write");
-        let reply =
-            endpoint::handle(&ALICE.did, chat.clone(), &provider).await.expect("should write");
-        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
-    }
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().any(|e| e.write.record_id == record_ids[1]));
+    assert!(entries.iter().any(|e| e.write.record_id == record_ids[3]));
 
     // --------------------------------------------------
-    // Bob uses his thread participant role to query BUT omits the `context_id`.
+    // Nested OR filters are rejected.
     // --------------------------------------------------
     let query = QueryBuilder::new()
-        .filter(
-            RecordsFilter::new()
-                .protocol("http://thread-role.xyz")
-                .protocol_path("thread/chat")
-                .context_id(thread.context_id.unwrap()),
-        )
-        .protocol_role("thread/participant")
-        .sign(&*BOB)
+        .filter(RecordsFilter::new().any_of(vec![
+            RecordsFilter::new().any_of(vec![RecordsFilter::new().add_author(&BOB.did)]),
+        ]))
+        .sign(&*ALICE)
         .build()
         .await
         .expect("should create query");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
         panic!("should be BadRequest");
     };
-    assert_eq!(e, "unable to find record for role");
+    assert_eq!(e, "nested `any_of` filters are not supported");
 }
 
-// Should return a status of Unauthorized (401) when signature check fails.
+// Should only allow a schema-scoped delegated grant to query records of the
+// grant's schema.
 #[tokio::test]
-async fn bad_signature() {
+async fn delegated_grant_schema() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
-    let mut query = QueryBuilder::new()
-        .filter(RecordsFilter::new().schema("http://schema"))
+    // --------------------------------------------------
+    // Alice grants Bob's app a delegated grant to query Person records.
+    // --------------------------------------------------
+    let app_grant = GrantBuilder::new()
+        .granted_to(&BOB.did)
+        .scope(Scope::Records {
+            method: Method::Query,
+            protocol: Some("http://minimal.xyz".to_string()),
+            limited_to: Some(RecordsScope::Schema("https://schema.org/Person".to_string())),
+        })
+        .delegated(true)
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create query");
-
-    query.authorization.as_mut().unwrap().signature.signatures[0].signature =
-        "badsignature".to_string();
-
-    let Err(Error::Unauthorized(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
-        panic!("should be Unauthorized");
-    };
-    assert!(e.starts_with("failed to authenticate: "));
-}
-
-// Should return a status of BadRequest (400) when the message cannot be parsed.
-#[tokio::test]
-async fn bad_message() {
-    let provider = ProviderImpl::new().await.expect("should create provider");
+        .expect("should create grant");
 
-    let mut query = QueryBuilder::new()
-        .filter(RecordsFilter::new().schema("http://schema"))
-        .sign(&*ALICE)
+    // --------------------------------------------------
+    // The app queries for Person records on behalf of Alice.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("https://schema.org/Person"))
+        .delegated_grant(DelegatedGrant::from(app_grant.clone()))
+        .sign(&*BOB)
         .build()
         .await
         .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
 
-    query.descriptor.filter = RecordsFilter::default();
-
-    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, query, &provider).await else {
-        panic!("should be BadRequest");
+    // --------------------------------------------------
+    // The app attempts to query for records of another schema.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().schema("https://schema.org/Organization"))
+        .delegated_grant(DelegatedGrant::from(app_grant))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create query");
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, query, &provider).await
+    else {
+        panic!("should be Forbidden");
     };
-    assert!(e.starts_with("validation failed for "));
+    assert_eq!(e, "grant and record schemas do not match");
 }
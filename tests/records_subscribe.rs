@@ -4,15 +4,17 @@ use std::sync::LazyLock;
 use std::time::Duration;
 
 use dwn_node::interfaces::records::{
-    Data, QueryBuilder, RecordsFilter, SubscribeBuilder, WriteBuilder,
+    Data, DeleteBuilder, QueryBuilder, RecordsFilter, SubscribeBuilder, WriteBuilder,
 };
-use dwn_node::{Message, StatusCode, endpoint};
+use dwn_node::{Error, Message, StatusCode, endpoint};
 use futures::StreamExt;
 use test_node::key_store;
 use test_node::provider::ProviderImpl;
 use tokio::time;
 
 static ALICE: LazyLock<key_store::Keyring> = LazyLock::new(|| key_store::new_keyring());
+static BOB: LazyLock<key_store::Keyring> = LazyLock::new(|| key_store::new_keyring());
+static CAROL: LazyLock<key_store::Keyring> = LazyLock::new(|| key_store::new_keyring());
 
 // The owner should be able to subscribe their own event stream.
 #[tokio::test]
@@ -28,7 +30,7 @@ async fn owner_events() {
     let reply = endpoint::handle(&ALICE.did, subscribe, &provider)
         .await
         .expect("should configure protocol");
-    assert_eq!(reply.status.code, StatusCode::OK);
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
     let mut subscribe_reply = reply.body.expect("should have body");
 
     // --------------------------------------------------
@@ -80,3 +82,266 @@ async fn owner_events() {
         panic!("should have found event");
     }
 }
+
+// Non-owners should only receive events for records they are permitted to
+// read, including deletes of those records.
+#[tokio::test]
+async fn non_owner_events() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Bob subscribes to Alice's event stream.
+    // --------------------------------------------------
+    let filter = RecordsFilter::new().schema("http://subscribe.xyz");
+    let subscribe =
+        SubscribeBuilder::new().filter(filter).sign(&*BOB).build().await.expect("should build");
+    let reply = endpoint::handle(&ALICE.did, subscribe, &provider).await.expect("should subscribe");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    let mut subscribe_reply = reply.body.expect("should have body");
+
+    // --------------------------------------------------
+    // Alice writes a private record, then a published record.
+    // --------------------------------------------------
+    let private = WriteBuilder::new()
+        .data(Data::from(b"private".to_vec()))
+        .schema("http://subscribe.xyz")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, private.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let published = WriteBuilder::new()
+        .data(Data::from(b"published".to_vec()))
+        .schema("http://subscribe.xyz")
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, published.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice deletes the published record.
+    // --------------------------------------------------
+    let delete = DeleteBuilder::new()
+        .record_id(&published.record_id)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create delete");
+    let delete_cid = delete.cid().expect("should have cid");
+    let reply = endpoint::handle(&ALICE.did, delete, &provider).await.expect("should delete");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Bob receives the published write and its delete, but not the private
+    // write.
+    // --------------------------------------------------
+    let private_cid = private.cid().expect("should have cid");
+    let published_cid = published.cid().expect("should have cid");
+
+    let find_events = async move {
+        let mut received = vec![];
+        while let Some(event) = subscribe_reply.subscription.next().await {
+            let cid = event.cid().unwrap();
+            assert_ne!(cid, private_cid);
+            received.push(cid);
+            if received.len() == 2 {
+                break;
+            }
+        }
+        received
+    };
+    let Ok(received) = time::timeout(Duration::from_millis(500), find_events).await else {
+        panic!("should have found events");
+    };
+    assert_eq!(received, vec![published_cid, delete_cid]);
+}
+
+// Non-owners subscribing to published records should receive published
+// records only.
+#[tokio::test]
+async fn non_owner_published() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Bob subscribes to Alice's published records.
+    // --------------------------------------------------
+    let filter = RecordsFilter::new().schema("http://subscribe.xyz").published(true);
+    let subscribe =
+        SubscribeBuilder::new().filter(filter).sign(&*BOB).build().await.expect("should build");
+    let reply = endpoint::handle(&ALICE.did, subscribe, &provider).await.expect("should subscribe");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    let mut subscribe_reply = reply.body.expect("should have body");
+
+    // --------------------------------------------------
+    // Alice writes a private record for Bob, then a published record.
+    // --------------------------------------------------
+    let private = WriteBuilder::new()
+        .data(Data::from(b"private".to_vec()))
+        .schema("http://subscribe.xyz")
+        .recipient(&BOB.did)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, private.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let published = WriteBuilder::new()
+        .data(Data::from(b"published".to_vec()))
+        .schema("http://subscribe.xyz")
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, published.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Bob receives the published write only.
+    // --------------------------------------------------
+    let published_cid = published.cid().expect("should have cid");
+
+    let find_event = async move { subscribe_reply.subscription.next().await };
+    let Ok(Some(event)) = time::timeout(Duration::from_millis(500), find_event).await else {
+        panic!("should have found event");
+    };
+    assert_eq!(event.cid().expect("should have cid"), published_cid);
+}
+
+// Subscribers should receive deletes for records written before they
+// subscribed.
+#[tokio::test]
+async fn earlier_record_deleted() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a record.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .schema("http://subscribe.xyz")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice subscribes to own event stream, then deletes the record.
+    // --------------------------------------------------
+    let filter = RecordsFilter::new().schema("http://subscribe.xyz");
+    let subscribe =
+        SubscribeBuilder::new().filter(filter).sign(&*ALICE).build().await.expect("should build");
+    let reply = endpoint::handle(&ALICE.did, subscribe, &provider).await.expect("should subscribe");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    let mut subscribe_reply = reply.body.expect("should have body");
+
+    let delete = DeleteBuilder::new()
+        .record_id(&write.record_id)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create delete");
+    let delete_cid = delete.cid().expect("should have cid");
+    let reply = endpoint::handle(&ALICE.did, delete, &provider).await.expect("should delete");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice receives the delete.
+    // --------------------------------------------------
+    let find_event = async move { subscribe_reply.subscription.next().await };
+    let Ok(Some(event)) = time::timeout(Duration::from_millis(500), find_event).await else {
+        panic!("should have found event");
+    };
+    assert_eq!(event.cid().expect("should have cid"), delete_cid);
+}
+
+// A non-owner's subscription should not be widened beyond the subscriber's
+// filter: a filter for another recipient does not select records received by
+// the subscriber.
+#[tokio::test]
+async fn non_owner_narrowed() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Bob subscribes to Alice's records for Carol.
+    // --------------------------------------------------
+    let filter = RecordsFilter::new().schema("http://subscribe.xyz").add_recipient(&CAROL.did);
+    let subscribe =
+        SubscribeBuilder::new().filter(filter).sign(&*BOB).build().await.expect("should build");
+    let reply = endpoint::handle(&ALICE.did, subscribe, &provider).await.expect("should subscribe");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    let mut subscribe_reply = reply.body.expect("should have body");
+
+    // --------------------------------------------------
+    // Alice writes a private record for Bob, then a published record for
+    // Carol.
+    // --------------------------------------------------
+    let private = WriteBuilder::new()
+        .data(Data::from(b"private".to_vec()))
+        .schema("http://subscribe.xyz")
+        .recipient(&BOB.did)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, private.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let published = WriteBuilder::new()
+        .data(Data::from(b"published".to_vec()))
+        .schema("http://subscribe.xyz")
+        .recipient(&CAROL.did)
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, published.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Bob receives the published record for Carol only.
+    // --------------------------------------------------
+    let published_cid = published.cid().expect("should have cid");
+
+    let find_event = async move { subscribe_reply.subscription.next().await };
+    let Ok(Some(event)) = time::timeout(Duration::from_millis(500), find_event).await else {
+        panic!("should have found event");
+    };
+    assert_eq!(event.cid().expect("should have cid"), published_cid);
+}
+
+// Should return Unauthorized (401) when an anonymous subscription includes
+// unpublished records.
+#[tokio::test]
+async fn anonymous_unpublished() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let mut subscribe = SubscribeBuilder::new()
+        .filter(RecordsFilter::new().schema("http://subscribe.xyz"))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create subscribe");
+    subscribe.authorization = None;
+
+    let Err(Error::Unauthorized(msg)) = endpoint::handle(&ALICE.did, subscribe, &provider).await
+    else {
+        panic!("should return Unauthorized");
+    };
+    assert_eq!(msg, "missing authorization");
+}
@@ -1,7 +1,11 @@
 //! Records Write
 
+use std::collections::BTreeMap;
 use std::io::Cursor;
+use std::pin::Pin;
 use std::sync::LazyLock;
+use std::sync::atomic::Ordering;
+use std::task::{Context, Poll};
 
 use base64ct::{Base64UrlUnpadded, Encoding};
 use chrono::{DateTime, Duration, Utc};
@@ -11,12 +15,15 @@ use dwn_node::interfaces::grants::{Conditions, GrantBuilder, Publication, Record
 use dwn_node::interfaces::messages::MessagesFilter;
 use dwn_node::interfaces::protocols::{ConfigureBuilder, Definition, ProtocolType, RuleSet, Size};
 use dwn_node::interfaces::records::{
-    Attestation, Data, DeleteBuilder, EncryptOptions, ProtocolBuilder, QueryBuilder, ReadBuilder,
-    Recipient, RecordsFilter, SignaturePayload, WriteBuilder,
+    Attestation, Data, DataReader, DelegatedGrant, DeleteBuilder, EncryptOptions, ProtocolBuilder,
+    QueryBuilder, ReadBuilder, Recipient, RecordsFilter, SignaturePayload, Tag, WriteBuilder,
 };
-use dwn_node::provider::EventLog;
+use dwn_node::provider::{DataStore, EventLog, gc};
 use dwn_node::store::MAX_ENCODED_SIZE;
-use dwn_node::{Error, Interface, Message, Method, StatusCode, endpoint, interfaces, store};
+use dwn_node::{
+    DateRange, Error, Interface, Message, Method, StatusCode, endpoint, interfaces, store,
+};
+use futures::{AsyncRead, AsyncReadExt};
 use rand::RngCore;
 use test_node::key_store;
 use test_node::provider::ProviderImpl;
@@ -227,13 +234,13 @@ async fn update_smaller_cid() {
     assert_eq!(e, "an update with a larger CID already exists");
 }
 
-// Should allow data format of a flat-space record to be updated to any value.
+// Should reject an update when its expected parent is not the latest write.
 #[tokio::test]
-async fn update_flat_space() {
+async fn expected_parent() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Write a record.
+    // Alice writes a record.
     // --------------------------------------------------
     let initial = WriteBuilder::new()
         .data(Data::from(b"a new write record".to_vec()))
@@ -244,49 +251,63 @@ async fn update_flat_space() {
     let reply =
         endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    let initial_cid = initial.cid().expect("should have CID");
 
     // --------------------------------------------------
-    // Update the record with a new data format.
+    // A client updates the record, expecting the initial write as parent.
     // --------------------------------------------------
-    let update = WriteBuilder::from(initial.clone())
-        .data(Data::from(b"update write record".to_vec()))
-        .data_format("a-new-data-format")
+    let update1 = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"update from client 1".to_vec()))
+        .expected_parent_cid(&initial_cid)
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
     let reply =
-        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+        endpoint::handle(&ALICE.did, update1.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Verify the data format has been updated.
+    // A second client's update, also derived from the initial write, is
+    // rejected.
     // --------------------------------------------------
-    let query = QueryBuilder::new()
-        .filter(RecordsFilter::new().record_id(&initial.record_id))
+    let update2 = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"update from client 2".to_vec()))
+        .expected_parent_cid(&initial_cid)
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create query");
-    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::OK);
+        .expect("should create write");
+    let Err(Error::Conflict(e)) = endpoint::handle(&ALICE.did, update2, &provider).await else {
+        panic!("should be Conflict");
+    };
+    assert_eq!(e, "latest write does not match expected parent");
 
-    let body = reply.body.expect("should have body");
-    let entries = body.entries.expect("should have entries");
-    assert_eq!(entries.len(), 1);
-    assert_eq!(entries[0].write.descriptor.data_format, update.descriptor.data_format);
+    // --------------------------------------------------
+    // The second client updates the latest write.
+    // --------------------------------------------------
+    let update3 = WriteBuilder::from(update1.clone())
+        .data(Data::from(b"update from client 2".to_vec()))
+        .expected_parent_cid(update1.cid().expect("should have CID"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, update3, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 }
 
-// Should not allow immutable properties to be updated.
+// Should reject a create-only write when the record already exists.
 #[tokio::test]
-async fn immutable_unchanged() {
+async fn create_only() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Write a record.
+    // Alice creates a record.
     // --------------------------------------------------
     let initial = WriteBuilder::new()
-        .data(Data::from(b"new write record".to_vec()))
+        .data(Data::from(b"a new write record".to_vec()))
+        .create_only(true)
         .sign(&*ALICE)
         .build()
         .await
@@ -296,160 +317,250 @@ async fn immutable_unchanged() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Verify `date_created` cannot be updated.
+    // A retry of the create is acknowledged.
     // --------------------------------------------------
-    let date_created = Utc::now();
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    let update = WriteBuilder::new()
-        .record_id(initial.record_id.clone())
-        .date_created(date_created)
+    // --------------------------------------------------
+    // A create-only write for the same record is rejected.
+    // --------------------------------------------------
+    let write = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"a second create".to_vec()))
+        .create_only(true)
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, update.clone(), &provider).await
-    else {
-        panic!("should be BadRequest");
+    let Err(Error::Conflict(e)) = endpoint::handle(&ALICE.did, write, &provider).await else {
+        panic!("should be Conflict");
     };
-    assert_eq!(e, "immutable properties do not match");
+    assert_eq!(e, "record already exists");
 
     // --------------------------------------------------
-    // Verify `schema` cannot be updated.
+    // A regular update succeeds.
     // --------------------------------------------------
-    let update = WriteBuilder::new()
-        .record_id(initial.record_id.clone())
-        .schema("new-schema")
+    let update = WriteBuilder::from(initial)
+        .data(Data::from(b"an update".to_vec()))
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, update.clone(), &provider).await
-    else {
-        panic!("should be BadRequest");
-    };
-    assert_eq!(e, "immutable properties do not match");
+    let reply = endpoint::handle(&ALICE.did, update, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 }
 
-// Should inherit data from previous write when `data_cid` and `data_size`
-// match and no data stream is provided.
+// Should treat a write reusing an earlier write's idempotency key as a retry,
+// even though its timestamp (and so CID) differs.
 #[tokio::test]
-async fn inherit_data() {
+async fn idempotency_key() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Write a record.
+    // Alice writes a record using an idempotency key.
     // --------------------------------------------------
-    let initial = WriteBuilder::new()
-        .data(Data::from(b"some data".to_vec()))
+    let write = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
+        .idempotency_key("order-1234")
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Update the record, providing data to calculate CID and size, but without
-    // adding to block store.
+    // Alice retries the write, with a new timestamp, using the same key.
     // --------------------------------------------------
-    let update = WriteBuilder::from(initial.clone())
+    let retry = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
+        .idempotency_key("order-1234")
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_ne!(retry.cid().unwrap(), write.cid().unwrap());
+
+    let reply = endpoint::handle(&ALICE.did, retry, &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Verify the initial write and it's data are still available.
+    // A write using a different key creates a new record.
     // --------------------------------------------------
-    let read = QueryBuilder::new()
-        .filter(RecordsFilter::new().record_id(&update.record_id))
+    let other = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
+        .idempotency_key("order-5678")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, other.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Only the first write and the differently keyed write were saved.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().data_format("application/json"))
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create query");
-    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should write");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
     assert_eq!(reply.status.code, StatusCode::OK);
 
     let body = reply.body.expect("should have body");
     let entries = body.entries.expect("should have entries");
-    assert_eq!(entries.len(), 1);
-    assert_eq!(entries[0].write.encoded_data, Some(Base64UrlUnpadded::encode_string(b"some data")));
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().any(|entry| entry.write.record_id == write.record_id));
+    assert!(entries.iter().any(|entry| entry.write.record_id == other.record_id));
 }
 
-// ln 367: Should allow an initial write without data.
+// Should answer a retried write with the reply the original write received,
+// whether a success other than 202 or an error.
 #[tokio::test]
-async fn initial_no_data() {
+async fn idempotency_key_reply() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Write a record with no data.
+    // Alice writes a private, non-queryable record without data.
     // --------------------------------------------------
-    let initial = WriteBuilder::new().sign(&*ALICE).build().await.expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    let write = WriteBuilder::new()
+        .idempotency_key("order-1234")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::NO_CONTENT);
 
     // --------------------------------------------------
-    // Verify the record cannot be queried for.
+    // Alice's retry is answered with the original reply's status.
     // --------------------------------------------------
-    let read = QueryBuilder::new()
-        .filter(RecordsFilter::new().record_id(&initial.record_id))
+    let retry = WriteBuilder::new()
+        .idempotency_key("order-1234")
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create read");
-    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::OK);
-    assert!(reply.body.is_none());
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, retry, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::NO_CONTENT);
 
     // --------------------------------------------------
-    // Update the record, adding data.
+    // Alice writes a record, then updates it with a key, expecting the wrong
+    // parent.
     // --------------------------------------------------
-    let update = WriteBuilder::from(initial.clone())
-        .data(Data::from(b"update write record".to_vec()))
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"a new write record".to_vec()))
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
     let reply =
-        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
+    let update = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"an update".to_vec()))
+        .idempotency_key("order-5678")
+        .expected_parent_cid("not-the-parent")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::Conflict(e)) = endpoint::handle(&ALICE.did, update, &provider).await else {
+        panic!("should be Conflict");
+    };
+    assert_eq!(e, "latest write does not match expected parent");
+
     // --------------------------------------------------
-    // Verify the data format has been updated.
+    // A retry using the same key is answered with the original error, and
+    // the record is not updated.
     // --------------------------------------------------
-    let read = QueryBuilder::new()
+    let retry = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"an update".to_vec()))
+        .idempotency_key("order-5678")
+        .expected_parent_cid(initial.cid().expect("should have CID"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::Conflict(e)) = endpoint::handle(&ALICE.did, retry, &provider).await else {
+        panic!("should be Conflict");
+    };
+    assert_eq!(e, "latest write does not match expected parent");
+
+    let read = ReadBuilder::new()
         .filter(RecordsFilter::new().record_id(&initial.record_id))
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create read");
-    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should write");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    let body = reply.body.expect("should have body");
+    let write = body.entry.records_write.expect("should have write");
+    assert_eq!(write.cid().unwrap(), initial.cid().unwrap());
+}
+
+// Should measure the idempotency window on the provider's clock, so backdated
+// writes sharing a key are not treated as retries.
+#[tokio::test]
+async fn idempotency_key_backdated() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes two records, a second apart but two days ago, using the
+    // same idempotency key.
+    // --------------------------------------------------
+    let timestamp = Utc::now() - Duration::days(2);
+    let mut record_ids = vec![];
+    for offset in [0, 1] {
+        let write = WriteBuilder::new()
+            .data(Data::from(b"a new write record".to_vec()))
+            .idempotency_key("order-1234")
+            .date_created(timestamp + Duration::seconds(offset))
+            .message_timestamp(timestamp + Duration::seconds(offset))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        record_ids.push(write.record_id);
+    }
+
+    // --------------------------------------------------
+    // Both writes were saved.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().data_format("application/json"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
     assert_eq!(reply.status.code, StatusCode::OK);
 
     let body = reply.body.expect("should have body");
     let entries = body.entries.expect("should have entries");
-    assert_eq!(entries.len(), 1);
-    assert_eq!(
-        entries[0].write.encoded_data,
-        Some(Base64UrlUnpadded::encode_string(b"update write record"))
-    );
+    assert_eq!(entries.len(), 2);
+    assert!(record_ids.iter().all(|id| entries.iter().any(|e| &e.write.record_id == id)));
 }
 
-// ln 409: Should not allow a record to be updated without data.
+// Should reject an update with a `message_timestamp` earlier than the author's
+// most recent message when strict timestamps are enabled.
 #[tokio::test]
-async fn update_no_data() {
-    let provider = ProviderImpl::new().await.expect("should create provider");
+async fn strict_timestamps() {
+    let mut provider = ProviderImpl::new().await.expect("should create provider");
+    provider.strict_timestamps = true;
 
     // --------------------------------------------------
-    // Write a record.
+    // Alice writes a record and updates it.
     // --------------------------------------------------
     let initial = WriteBuilder::new()
-        .data(Data::from(b"some data".to_vec()))
+        .data(Data::from(b"a new write record".to_vec()))
         .sign(&*ALICE)
         .build()
         .await
@@ -458,55 +569,56 @@ async fn update_no_data() {
         endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
+    let message_timestamp = initial.descriptor.base.message_timestamp + Duration::seconds(2);
+    let update = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"update 1".to_vec()))
+        .message_timestamp(message_timestamp.into())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, update, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
     // --------------------------------------------------
-    // Update the record, providing data to calculate CID and size, but without
-    // setting `data_stream`.
+    // Alice's next update has an earlier timestamp than her previous update.
     // --------------------------------------------------
-    let update = WriteBuilder::from(initial.clone())
-        .data(Data::Bytes(b"update write record".to_vec()))
+    let message_timestamp = initial.descriptor.base.message_timestamp + Duration::seconds(1);
+    let rewound = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"update 2".to_vec()))
+        .message_timestamp(message_timestamp.into())
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, update.clone(), &provider).await
+
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, rewound.clone(), &provider).await
     else {
         panic!("should be BadRequest");
     };
-    assert_eq!(e, "data CID does not match descriptor `data_cid`");
+    assert_eq!(e, "message timestamp is earlier than the author's most recent message");
 
     // --------------------------------------------------
-    // Verify the initial write and it's data are still available.
+    // Without strict timestamps, the update is rejected as a conflict.
     // --------------------------------------------------
-    let read = QueryBuilder::new()
-        .filter(RecordsFilter::new().record_id(&initial.record_id))
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create read");
-    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::OK);
+    provider.strict_timestamps = false;
 
-    let body = reply.body.expect("should have body");
-    let entries = body.entries.expect("should have entries");
-    assert_eq!(entries.len(), 1);
-    assert_eq!(entries[0].write.encoded_data, Some(Base64UrlUnpadded::encode_string(b"some data")));
+    let Err(Error::Conflict(e)) = endpoint::handle(&ALICE.did, rewound, &provider).await else {
+        panic!("should be Conflict");
+    };
+    assert_eq!(e, "a more recent update exists");
 }
 
-// Should inherit data from previous writes when data size greater than
-// `encoded_data` threshold.
+// Should allow data format of a flat-space record to be updated to any value.
 #[tokio::test]
-async fn retain_large_data() {
+async fn update_flat_space() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice writes a record with a lot of data.
+    // Write a record.
     // --------------------------------------------------
-    let mut data = [0u8; MAX_ENCODED_SIZE + 10];
-    rand::thread_rng().fill_bytes(&mut data);
-    let stream = Cursor::new(data.to_vec());
-
     let initial = WriteBuilder::new()
-        .data(Data::Stream(stream.clone()))
+        .data(Data::from(b"a new write record".to_vec()))
         .sign(&*ALICE)
         .build()
         .await
@@ -516,10 +628,11 @@ async fn retain_large_data() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Update the record but not data.
+    // Update the record with a new data format.
     // --------------------------------------------------
     let update = WriteBuilder::from(initial.clone())
-        .published(true)
+        .data(Data::from(b"update write record".to_vec()))
+        .data_format("a-new-data-format")
         .sign(&*ALICE)
         .build()
         .await
@@ -529,39 +642,33 @@ async fn retain_large_data() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Verify the initial write's data is still available.
+    // Verify the data format has been updated.
     // --------------------------------------------------
-    let read = ReadBuilder::new()
+    let query = QueryBuilder::new()
         .filter(RecordsFilter::new().record_id(&initial.record_id))
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create read");
-
-    let reply = endpoint::handle(&ALICE.did, read.clone(), &provider).await.expect("should write");
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::OK);
 
     let body = reply.body.expect("should have body");
-    assert!(body.entry.records_write.is_some());
-    let read_stream = body.entry.data.expect("should have data");
-    assert_eq!(read_stream.into_inner(), data.to_vec());
+    let entries = body.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.descriptor.data_format, update.descriptor.data_format);
 }
 
-// Should inherit data from previous writes when data size less than
-// `encoded_data` threshold.
+// Should not allow immutable properties to be updated.
 #[tokio::test]
-async fn retain_small_data() {
+async fn immutable_unchanged() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice writes a record with a lot of data.
+    // Write a record.
     // --------------------------------------------------
-    let mut data = [0u8; 10];
-    rand::thread_rng().fill_bytes(&mut data);
-    let write_stream = Cursor::new(data.to_vec());
-
     let initial = WriteBuilder::new()
-        .data(Data::Stream(write_stream.clone()))
+        .data(Data::from(b"new write record".to_vec()))
         .sign(&*ALICE)
         .build()
         .await
@@ -571,35 +678,814 @@ async fn retain_small_data() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Update the record but not data.
+    // Verify `date_created` cannot be updated.
     // --------------------------------------------------
-    let update = WriteBuilder::from(initial.clone())
-        .published(true)
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create write");
+    let date_created = Utc::now();
+
+    let update = WriteBuilder::new()
+        .record_id(initial.record_id.clone())
+        .date_created(date_created)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, update.clone(), &provider).await
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "immutable properties do not match");
+
+    // --------------------------------------------------
+    // Verify `schema` cannot be updated.
+    // --------------------------------------------------
+    let update = WriteBuilder::new()
+        .record_id(initial.record_id.clone())
+        .schema("new-schema")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, update.clone(), &provider).await
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "immutable properties do not match");
+}
+
+// Should dry run a write without persisting it.
+#[tokio::test]
+async fn dry_run() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Write a record.
+    // --------------------------------------------------
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Dry run a valid update with data too large to encode in the message.
+    // --------------------------------------------------
+    let mut data = vec![0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data);
+
+    let update = WriteBuilder::from(initial.clone())
+        .data(Data::from(data))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    let batches = provider.block_batches.load(Ordering::SeqCst);
+    let status =
+        endpoint::validate(&ALICE.did, update.clone(), &provider).await.expect("should validate");
+    assert_eq!(status.code, StatusCode::ACCEPTED);
+
+    // nothing was written to the block store
+    assert_eq!(provider.block_batches.load(Ordering::SeqCst), batches);
+
+    // --------------------------------------------------
+    // Dry run an update changing an immutable property.
+    // --------------------------------------------------
+    let invalid = WriteBuilder::new()
+        .record_id(initial.record_id.clone())
+        .schema("new-schema")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::validate(&ALICE.did, invalid, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "immutable properties do not match");
+
+    // --------------------------------------------------
+    // Only the initial write was persisted and logged.
+    // --------------------------------------------------
+    let (events, _) = EventLog::events(&provider, &ALICE.did, None).await.expect("should fetch");
+    assert_eq!(events.len(), 1);
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.expect("should have reply").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.descriptor.data_cid, initial.descriptor.data_cid);
+}
+
+// Should inherit data from previous write when `data_cid` and `data_size`
+// match and no data stream is provided.
+#[tokio::test]
+async fn inherit_data() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Write a record.
+    // --------------------------------------------------
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Update the record, providing data to calculate CID and size, but without
+    // adding to block store.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(initial.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Verify the initial write and it's data are still available.
+    // --------------------------------------------------
+    let read = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&update.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let entries = body.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.encoded_data, Some(Base64UrlUnpadded::encode_string(b"some data")));
+}
+
+// Should allow a new record to reference data already stored for another
+// record, without providing the data again.
+#[tokio::test]
+async fn reference_data() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes record A with data.
+    // --------------------------------------------------
+    let mut data = vec![0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data);
+
+    let write_a = WriteBuilder::new()
+        .data(Data::from(data.clone()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, write_a.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes record B referencing A's data.
+    // --------------------------------------------------
+    let write_b = WriteBuilder::new()
+        .reference_data(&write_a.descriptor.data_cid, write_a.descriptor.data_size)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, write_b.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&write_b.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let read_data = body.entry.data.expect("should have data");
+    assert_eq!(read_data.into_inner(), data);
+
+    // --------------------------------------------------
+    // Referencing data that has not been stored fails.
+    // --------------------------------------------------
+    let write_c = WriteBuilder::new()
+        .reference_data("bafkreihxrkspxsocoaoetqjm3iop26svz2k622cgart56v2ng7g6q6ofwa", 10)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write_c, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "referenced data not found");
+}
+
+// ln 367: Should allow an initial write without data.
+#[tokio::test]
+async fn initial_no_data() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Write a record with no data.
+    // --------------------------------------------------
+    let initial = WriteBuilder::new().sign(&*ALICE).build().await.expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::NO_CONTENT);
+
+    // --------------------------------------------------
+    // Verify the record cannot be queried for.
+    // --------------------------------------------------
+    let read = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::OK);
+    assert!(reply.body.is_none());
+
+    // --------------------------------------------------
+    // Update the record, adding data.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"update write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Verify the data format has been updated.
+    // --------------------------------------------------
+    let read = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let entries = body.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].write.encoded_data,
+        Some(Base64UrlUnpadded::encode_string(b"update write record"))
+    );
+}
+
+// ln 409: Should not allow a record to be updated without data.
+#[tokio::test]
+async fn update_no_data() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Write a record.
+    // --------------------------------------------------
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Update the record, providing data to calculate CID and size, but without
+    // setting `data_stream`.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(initial.clone())
+        .data(Data::Bytes(b"update write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, update.clone(), &provider).await
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "data CID does not match descriptor `data_cid`");
+
+    // --------------------------------------------------
+    // Verify the initial write and it's data are still available.
+    // --------------------------------------------------
+    let read = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let entries = body.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.encoded_data, Some(Base64UrlUnpadded::encode_string(b"some data")));
+}
+
+// Should carry tags forward to updates made using `WriteBuilder::from`.
+#[tokio::test]
+async fn update_retains_tags() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a tagged record.
+    // --------------------------------------------------
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .add_tag("genre", Tag::String("sci-fi".to_string()))
+        .add_tag("year", Tag::Number(1965))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice updates only the record's data.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"updated data".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice adds a tag, merging it with the existing tags.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(update)
+        .add_tag("author", Tag::String("Herbert".to_string()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // The latest write retains the initial tags.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let entries = body.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+
+    let tags = entries[0].write.descriptor.tags.as_ref().expect("should have tags");
+    assert_eq!(tags.len(), 3);
+    assert_eq!(tags.get("genre"), Some(&Tag::String("sci-fi".to_string())));
+    assert_eq!(tags.get("year"), Some(&Tag::Number(1965)));
+    assert_eq!(tags.get("author"), Some(&Tag::String("Herbert".to_string())));
+}
+
+// Should inherit data from previous writes when data size greater than
+// `encoded_data` threshold.
+#[tokio::test]
+async fn retain_large_data() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a record with a lot of data.
+    // --------------------------------------------------
+    let mut data = [0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data);
+    let stream = Cursor::new(data.to_vec());
+
+    let initial = WriteBuilder::new()
+        .data(Data::Stream(stream.clone()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Update the record but not data.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(initial.clone())
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Verify the initial write's data is still available.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+
+    let reply = endpoint::handle(&ALICE.did, read.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    assert!(body.entry.records_write.is_some());
+    let read_stream = body.entry.data.expect("should have data");
+    assert_eq!(read_stream.into_inner(), data.to_vec());
+}
+
+// Should inherit data from previous writes when data size less than
+// `encoded_data` threshold.
+#[tokio::test]
+async fn retain_small_data() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a record with a lot of data.
+    // --------------------------------------------------
+    let mut data = [0u8; 10];
+    rand::thread_rng().fill_bytes(&mut data);
+    let write_stream = Cursor::new(data.to_vec());
+
+    let initial = WriteBuilder::new()
+        .data(Data::Stream(write_stream.clone()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Update the record but not data.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(initial.clone())
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
     let reply =
         endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Verify the initial write's data is still available.
+    // Verify the initial write's data is still available.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+
+    let reply = endpoint::handle(&ALICE.did, read.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    assert!(body.entry.records_write.is_some());
+    let read_stream = body.entry.data.expect("should have data");
+    assert_eq!(read_stream.into_inner(), data.to_vec());
+}
+
+// Should stream data from an asynchronous reader into the `DataStore`.
+#[tokio::test]
+async fn stream_data() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let mut data = [0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data);
+
+    // --------------------------------------------------
+    // The CID computed from a reader matches that of the `Stream` path.
+    // --------------------------------------------------
+    let (data_cid, data_size) =
+        dwn_node::cid::from_async_reader(futures::io::Cursor::new(data.to_vec()))
+            .await
+            .expect("should compute CID");
+    let (stream_cid, _) =
+        dwn_node::cid::from_reader(Cursor::new(data.to_vec())).expect("should compute CID");
+    assert_eq!(data_cid, stream_cid);
+
+    // --------------------------------------------------
+    // Alice writes a record, streaming the data.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::Reader {
+            reader: DataReader::new(futures::io::Cursor::new(data.to_vec())),
+            data_cid,
+            data_size,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Verify the data was stored.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&write.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let read_stream = body.entry.data.expect("should have data");
+    assert_eq!(read_stream.into_inner(), data.to_vec());
+}
+
+// Should store and fetch a multi-block record's data blocks in batches.
+#[tokio::test]
+async fn batch_blocks() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a multi-block record containing repeated blocks.
+    // --------------------------------------------------
+    let mut data = vec![0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data[..MAX_ENCODED_SIZE / 2]);
+
+    let write = WriteBuilder::new()
+        .data(Data::from(data.clone()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    let batches = provider.block_batches.load(Ordering::SeqCst);
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // blocks are stored in fewer batches than there are blocks
+    let stored = provider.block_batches.load(Ordering::SeqCst) - batches;
+    assert!(stored > 1);
+    assert!(stored < data.len() / 64);
+
+    // --------------------------------------------------
+    // The data read back is unchanged.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&write.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let read_stream = body.entry.data.expect("should have data");
+    assert_eq!(read_stream.into_inner(), data);
+}
+
+// Should return a status of BadRequest (400) when the data reader fails part
+// way through the data.
+#[tokio::test]
+async fn stream_data_error() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let mut data = [0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data);
+    let (data_cid, data_size) =
+        dwn_node::cid::from_reader(Cursor::new(data.to_vec())).expect("should compute CID");
+
+    // --------------------------------------------------
+    // Alice writes a record using a reader that fails after some data.
+    // --------------------------------------------------
+    let reader =
+        futures::io::Cursor::new(data[..MAX_ENCODED_SIZE / 2].to_vec()).chain(FailingReader);
+    let write = WriteBuilder::new()
+        .data(Data::Reader {
+            reader: DataReader::new(reader),
+            data_cid,
+            data_size,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write.clone(), &provider).await
+    else {
+        panic!("should be BadRequest");
+    };
+    assert!(e.starts_with("issue reading data"));
+
+    // --------------------------------------------------
+    // The record was not saved.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&write.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let Err(Error::NotFound(_)) = endpoint::handle(&ALICE.did, read, &provider).await else {
+        panic!("should be NotFound");
+    };
+}
+
+// Should return an error, rather than panic, when computing the CID of data
+// from a reader that fails part way through the data.
+#[tokio::test]
+async fn cid_reader_error() {
+    let mut data = [0u8; MAX_ENCODED_SIZE];
+    rand::thread_rng().fill_bytes(&mut data);
+
+    let reader =
+        futures::io::Cursor::new(data[..MAX_ENCODED_SIZE / 2].to_vec()).chain(FailingReader);
+    let Err(Error::BadRequest(e)) = dwn_node::cid::from_async_reader(reader).await else {
+        panic!("should be BadRequest");
+    };
+    assert!(e.starts_with("issue reading data"));
+}
+
+// Should return a status of BadRequest (400) when a write is handled again
+// after its data reader has been read.
+#[tokio::test]
+async fn stream_data_consumed() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let mut data = [0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data);
+    let (data_cid, data_size) =
+        dwn_node::cid::from_reader(Cursor::new(data.to_vec())).expect("should compute CID");
+
+    let write = WriteBuilder::new()
+        .data(Data::Reader {
+            reader: DataReader::new(futures::io::Cursor::new(data.to_vec())),
+            data_cid,
+            data_size,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    // --------------------------------------------------
+    // A dry run reads the data.
+    // --------------------------------------------------
+    let status =
+        endpoint::validate(&ALICE.did, write.clone(), &provider).await.expect("should validate");
+    assert_eq!(status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Handling the write again is rejected rather than saved without data.
     // --------------------------------------------------
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write.clone(), &provider).await
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "data reader has already been read");
+
     let read = ReadBuilder::new()
-        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .filter(RecordsFilter::new().record_id(&write.record_id))
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create read");
+    let Err(Error::NotFound(_)) = endpoint::handle(&ALICE.did, read, &provider).await else {
+        panic!("should be NotFound");
+    };
+}
 
-    let reply = endpoint::handle(&ALICE.did, read.clone(), &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::OK);
+// Should round-trip data through `Data::from_async_read` and
+// `Data::into_async_read`, producing the same CID as the in-memory path.
+#[tokio::test]
+async fn async_read_round_trip() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
 
-    let body = reply.body.expect("should have body");
-    assert!(body.entry.records_write.is_some());
-    let read_stream = body.entry.data.expect("should have data");
-    assert_eq!(read_stream.into_inner(), data.to_vec());
+    let mut large = [0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut large);
+
+    for data in [b"a small payload".to_vec(), large.to_vec()] {
+        // --------------------------------------------------
+        // Converting to and from an async reader preserves the data.
+        // --------------------------------------------------
+        let mut reader = Data::from(data.clone()).into_async_read().expect("should have reader");
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).await.expect("should read");
+        assert_eq!(buffer, data);
+
+        // --------------------------------------------------
+        // Alice writes a record using data consumed from an async reader.
+        // --------------------------------------------------
+        let (data_cid, _) =
+            dwn_node::cid::from_async_reader(futures::io::Cursor::new(data.clone()))
+                .await
+                .expect("should compute CID");
+        let (in_memory_cid, _) =
+            dwn_node::cid::from_reader(Cursor::new(data.clone())).expect("should compute CID");
+        assert_eq!(data_cid, in_memory_cid);
+
+        let from_reader =
+            Data::from_async_read(futures::io::Cursor::new(data.clone()), &data_cid, data.len());
+        let write = WriteBuilder::new()
+            .data(from_reader)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        assert_eq!(write.descriptor.data_cid, data_cid);
+
+        let reply =
+            endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+        // --------------------------------------------------
+        // Verify the data was stored.
+        // --------------------------------------------------
+        let read = ReadBuilder::new()
+            .filter(RecordsFilter::new().record_id(&write.record_id))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create read");
+        let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+        assert_eq!(reply.status.code, StatusCode::OK);
+
+        let body = reply.body.expect("should have body");
+        let read_stream = body.entry.data.expect("should have data");
+        assert_eq!(read_stream.into_inner(), data);
+    }
+
+    // --------------------------------------------------
+    // Readers providing fewer or more bytes than expected are rejected.
+    // --------------------------------------------------
+    for (data, expected_size) in [(b"short".to_vec(), 10), (large.to_vec(), MAX_ENCODED_SIZE)] {
+        let (data_cid, _) =
+            dwn_node::cid::from_reader(Cursor::new(data.clone())).expect("should compute CID");
+        let from_reader =
+            Data::from_async_read(futures::io::Cursor::new(data), data_cid, expected_size);
+        let write = WriteBuilder::new()
+            .data(from_reader)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+
+        let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write, &provider).await else {
+            panic!("should be BadRequest");
+        };
+        assert!(e.contains(&format!("data size does not match expected size {expected_size}")));
+    }
+}
+
+// An `AsyncRead` that always fails.
+struct FailingReader;
+
+impl AsyncRead for FailingReader {
+    fn poll_read(
+        self: Pin<&mut Self>, _: &mut Context<'_>, _: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(Err(std::io::Error::other("connection reset")))
+    }
 }
 
 // Should fail when data size greater than `encoded_data` threshold and
@@ -1100,6 +1986,163 @@ async fn update_published() {
     );
 }
 
+// Should set `date_published` to the update's `message_timestamp` when a
+// record transitions to published without an explicit `date_published`.
+#[tokio::test]
+async fn update_published_date() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes an unpublished record.
+    // --------------------------------------------------
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice publishes the record without specifying `date_published`.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(initial.clone())
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // The stored `date_published` is the update's `message_timestamp`.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let entries = body.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.descriptor.published, Some(true));
+    assert_eq!(
+        entries[0].write.descriptor.date_published.map(|date| date.timestamp_micros()),
+        Some(update.descriptor.base.message_timestamp.timestamp_micros())
+    );
+
+    // --------------------------------------------------
+    // The record is found by its `date_published`.
+    // --------------------------------------------------
+    let timestamp = update.descriptor.base.message_timestamp;
+    let range = DateRange::new()
+        .gt(timestamp - Duration::microseconds(1))
+        .lt(timestamp + Duration::microseconds(1));
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().date_published(range))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let entries = body.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.record_id, initial.record_id);
+}
+
+// Should keep the date a record was first published when later writes leave
+// it published without a `date_published`.
+#[tokio::test]
+async fn carry_date_published() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes an unpublished record.
+    // --------------------------------------------------
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice publishes the record using a descriptor without `date_published`.
+    // --------------------------------------------------
+    let mut published = WriteBuilder::from(initial.clone())
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    published.descriptor.date_published = None;
+    published.sign_as_author(None, None, &*ALICE).await.expect("should sign");
+
+    let reply =
+        endpoint::handle(&ALICE.did, published.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice updates the published record, again without `date_published`.
+    // --------------------------------------------------
+    let mut update = WriteBuilder::from(published.clone())
+        .data(Data::from(b"updated write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    update.descriptor.date_published = None;
+    update.sign_as_author(None, None, &*ALICE).await.expect("should sign");
+    assert_eq!(update.descriptor.published, Some(true));
+    assert!(update.descriptor.base.message_timestamp > published.descriptor.base.message_timestamp);
+
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // The record is found by the time it was published, not by the time of
+    // the update.
+    // --------------------------------------------------
+    let query_at = |timestamp: DateTime<Utc>| {
+        let range = DateRange::new()
+            .gt(timestamp - Duration::microseconds(1))
+            .lt(timestamp + Duration::microseconds(1));
+        QueryBuilder::new().filter(RecordsFilter::new().date_published(range)).sign(&*ALICE)
+    };
+
+    let query = query_at(published.descriptor.base.message_timestamp)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.and_then(|body| body.entries).unwrap_or_default();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.cid().expect("should have CID"), update.cid().expect("CID"));
+
+    let query = query_at(update.descriptor.base.message_timestamp)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let entries = reply.body.and_then(|body| body.entries).unwrap_or_default();
+    assert!(entries.is_empty());
+}
+
 // Should fail when updating a record but its initial write cannot be found.
 #[tokio::test]
 async fn no_initial_write() {
@@ -1118,6 +2161,53 @@ async fn no_initial_write() {
     assert_eq!(e, "initial write not found");
 }
 
+// Should fail when a write taking the initial write's place claims a
+// `record_id` not derived from its descriptor.
+#[tokio::test]
+async fn mismatched_record_id() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // A different descriptor claiming the initial write's `record_id`.
+    // --------------------------------------------------
+    let date_created = initial.descriptor.date_created;
+    let forged = WriteBuilder::new()
+        .data(Data::from(b"forged write record".to_vec()))
+        .record_id(&initial.record_id)
+        .date_created(date_created)
+        .message_timestamp(date_created)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, forged, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "record_id does not match descriptor");
+
+    // --------------------------------------------------
+    // An update of the same descriptor is accepted.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(initial)
+        .data(Data::from(b"updated write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, update, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+}
+
 // Should fail when creating a record if `date_created` and `message_timestamp`
 // do not match.
 #[tokio::test]
@@ -1157,46 +2247,155 @@ async fn invalid_context_id() {
         .await
         .expect("should create write");
 
-    initial.context_id =
-        Some("bafkreihs5gnovjoqueffglvevvohpgts3aj5ykgmlqm7quuotujxtxtp7f".to_string());
-
-    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, initial, &provider).await else {
-        panic!("should be BadRequest");
-    };
-    assert_eq!(e, "invalid context ID");
+    initial.context_id =
+        Some("bafkreihs5gnovjoqueffglvevvohpgts3aj5ykgmlqm7quuotujxtxtp7f".to_string());
+
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, initial, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "invalid context ID");
+}
+
+// Should log an event on initial write.
+#[tokio::test]
+async fn log_initial_write() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Write a record.
+    // --------------------------------------------------
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Verify an event was logged.
+    // --------------------------------------------------
+    let query = interfaces::messages::QueryBuilder::new()
+        .add_filter(MessagesFilter::new().interface(Interface::Records))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+
+    let query = store::Query::from(query);
+    let (events, _) = EventLog::query(&provider, &ALICE.did, &query).await.expect("should fetch");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].watermark(), Some(1));
+}
+
+// Should acknowledge a retried write without reprocessing or logging it again.
+#[tokio::test]
+async fn retried_write() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Write a record, then retry the same write.
+    // --------------------------------------------------
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"new write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Verify a single event was logged.
+    // --------------------------------------------------
+    let query = interfaces::messages::QueryBuilder::new()
+        .add_filter(MessagesFilter::new().interface(Interface::Records))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+
+    let query = store::Query::from(query);
+    let (events, _) = EventLog::query(&provider, &ALICE.did, &query).await.expect("should fetch");
+    assert_eq!(events.len(), 1);
+
+    // --------------------------------------------------
+    // Verify an update (different CID) is still applied.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(initial)
+        .data(Data::from(b"updated write record".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, update, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let (events, _) = EventLog::query(&provider, &ALICE.did, &query).await.expect("should fetch");
+    assert_eq!(events.len(), 2);
 }
 
-// Should log an event on initial write.
+// Should process the data of a retried write when the earlier write was stored
+// without its data, and otherwise return the write's original status.
 #[tokio::test]
-async fn log_initial_write() {
+async fn retried_write_data() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Write a record.
+    // Alice writes a record without a data stream, then retries it.
     // --------------------------------------------------
+    let mut data = [0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data);
+
     let initial = WriteBuilder::new()
-        .data(Data::from(b"new write record".to_vec()))
+        .data(Data::Bytes(data.to_vec()))
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::NO_CONTENT);
+
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::NO_CONTENT);
+
+    // --------------------------------------------------
+    // Alice retries the write with its data.
+    // --------------------------------------------------
+    let mut retry = initial.clone();
+    retry.data_stream = Some(Cursor::new(data.to_vec()));
+    let reply = endpoint::handle(&ALICE.did, retry.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // a further retry is acknowledged with the stored write's status
     let reply =
         endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Verify an event was logged.
+    // The record's data can now be read.
     // --------------------------------------------------
-    let query = interfaces::messages::QueryBuilder::new()
-        .add_filter(MessagesFilter::new().interface(Interface::Records))
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create query");
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::OK);
 
-    let query = store::Query::from(query);
-    let (events, _) = EventLog::query(&provider, &ALICE.did, &query).await.expect("should fetch");
-    assert_eq!(events.len(), 1);
+    let body = reply.body.expect("should have body");
+    let read_stream = body.entry.data.expect("should have data");
+    assert_eq!(read_stream.into_inner(), data.to_vec());
 }
 
 // Should only ever retain (at most) the initial and most recent writes.
@@ -1254,6 +2453,143 @@ async fn retain_two_writes() {
 
     assert_eq!(events[0].cid(), initial.cid());
     assert_eq!(events[1].cid(), update2.cid());
+
+    // --------------------------------------------------
+    // Verify watermarks increase and can be used to fetch later events.
+    // --------------------------------------------------
+    let initial_mark = events[0].watermark().expect("should have watermark");
+    let update2_mark = events[1].watermark().expect("should have watermark");
+    assert!(initial_mark < update2_mark);
+
+    let watermark = EventLog::watermark(&provider, &ALICE.did).await.expect("should get watermark");
+    assert_eq!(watermark, update2_mark);
+
+    let (events, _) = EventLog::query(&provider, &ALICE.did, &query.clone().since(initial_mark))
+        .await
+        .expect("should fetch");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].cid(), update2.cid());
+
+    let (events, _) = EventLog::query(&provider, &ALICE.did, &query.clone().since(watermark))
+        .await
+        .expect("should fetch");
+    assert!(events.is_empty());
+}
+
+// Should reclaim data blocks orphaned by an update while retaining the data
+// of the latest write.
+#[tokio::test]
+async fn collect_orphans() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a record with a lot of data.
+    // --------------------------------------------------
+    let mut data = [0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data);
+
+    let initial = WriteBuilder::new()
+        .data(Data::Stream(Cursor::new(data.to_vec())))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, initial.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice updates the record with new data, superseding the original.
+    // --------------------------------------------------
+    let mut new_data = [0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut new_data);
+
+    let update = WriteBuilder::from(initial.clone())
+        .data(Data::Stream(Cursor::new(new_data.to_vec())))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice updates the record again, without data.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(update.clone())
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Collect orphaned blocks: the original data's blocks are reclaimed.
+    // --------------------------------------------------
+    let reclaimed = gc::collect_orphans(&ALICE.did, &provider).await.expect("should collect");
+    assert!(reclaimed.blocks > data.len() / 64);
+    assert!(reclaimed.bytes > data.len());
+
+    // nothing is left to collect
+    let reclaimed = gc::collect_orphans(&ALICE.did, &provider).await.expect("should collect");
+    assert_eq!(reclaimed, gc::Reclaimed::default());
+
+    // --------------------------------------------------
+    // Verify the latest write's data survives.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let read_stream = body.entry.data.expect("should have data");
+    assert_eq!(read_stream.into_inner(), new_data.to_vec());
+}
+
+// Should stamp stored data using the provider's clock, so data never
+// referenced by a message is collected once the grace period has passed on
+// that clock.
+#[tokio::test]
+async fn collect_unreferenced() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+    provider.clock.advance(Duration::days(-1));
+
+    // --------------------------------------------------
+    // Data is stored without a message referencing it.
+    // --------------------------------------------------
+    let mut data = [0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data);
+    let (data_cid, _) =
+        dwn_node::cid::from_reader(Cursor::new(data.to_vec())).expect("should compute CID");
+
+    DataStore::put(
+        &provider,
+        &ALICE.did,
+        "unreferenced",
+        &data_cid,
+        futures::io::Cursor::new(data.to_vec()),
+    )
+    .await
+    .expect("should store data");
+
+    // --------------------------------------------------
+    // The data is retained during the grace period, then collected.
+    // --------------------------------------------------
+    let reclaimed = gc::collect_orphans(&ALICE.did, &provider).await.expect("should collect");
+    assert_eq!(reclaimed, gc::Reclaimed::default());
+
+    provider.clock.advance(Duration::minutes(11));
+    let reclaimed = gc::collect_orphans(&ALICE.did, &provider).await.expect("should collect");
+    assert!(reclaimed.bytes > data.len());
 }
 
 // Should allow anyone to create a record using the "anyone create" rule.
@@ -1382,7 +2718,9 @@ async fn anyone_update() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, bob_doc, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, bob_doc, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "action not permitted");
@@ -1560,7 +2898,8 @@ async fn ancestor_update() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, bob_tag.clone(), &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, bob_tag.clone(), &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -1616,7 +2955,7 @@ async fn direct_update() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) =
+    let Err(Error::Forbidden { message: e, .. }) =
         endpoint::handle(&ALICE.did, carol_update.clone(), &provider).await
     else {
         panic!("should be Forbidden");
@@ -1693,7 +3032,7 @@ async fn block_non_author() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) =
+    let Err(Error::Forbidden { message: e, .. }) =
         endpoint::handle(&BOB.did, carol_caption.clone(), &provider).await
     else {
         panic!("should be Forbidden");
@@ -1828,7 +3167,8 @@ async fn ancestor_author_update() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, bob_post.clone(), &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, bob_post.clone(), &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -1932,6 +3272,107 @@ async fn no_role_recipient() {
     assert_eq!(e, "role record is missing recipient");
 }
 
+// Should enforce `$requireRecipient` and `$forbidRecipient` protocol rules.
+#[tokio::test]
+async fn recipient_rules() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures the recipient-rules protocol.
+    // --------------------------------------------------
+    let recipient_rules = include_bytes!("protocols/recipient-rules.json");
+    let definition: Definition =
+        serde_json::from_slice(recipient_rules).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice attempts (and fails) to add a role record with no recipient.
+    // --------------------------------------------------
+    let member = WriteBuilder::new()
+        .data(Data::from(b"A member".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://recipient-rules.xyz",
+            protocol_path: "member",
+            parent_context_id: None,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, member, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "role record is missing recipient");
+
+    // --------------------------------------------------
+    // Alice attempts (and fails) to write a message with no recipient.
+    // --------------------------------------------------
+    let message = WriteBuilder::new()
+        .data(Data::from(b"A message".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://recipient-rules.xyz",
+            protocol_path: "message",
+            parent_context_id: None,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, message, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "protocol type requires a recipient");
+
+    // --------------------------------------------------
+    // Alice attempts (and fails) to publish an announcement to Bob.
+    // --------------------------------------------------
+    let announcement = WriteBuilder::new()
+        .data(Data::from(b"An announcement".to_vec()))
+        .recipient(&BOB.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://recipient-rules.xyz",
+            protocol_path: "announcement",
+            parent_context_id: None,
+        })
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, announcement, &provider).await
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "protocol type does not allow a recipient");
+
+    // --------------------------------------------------
+    // Alice publishes an announcement with no recipient.
+    // --------------------------------------------------
+    let announcement = WriteBuilder::new()
+        .data(Data::from(b"An announcement".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://recipient-rules.xyz",
+            protocol_path: "announcement",
+            parent_context_id: None,
+        })
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, announcement, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+}
+
 // Should allow a role record to be created for the same recipient after their
 // previous record has been deleted.
 #[tokio::test]
@@ -2005,6 +3446,68 @@ async fn recreate_role() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 }
 
+// Should reject a duplicate root-level role record for the same recipient.
+#[tokio::test]
+async fn duplicate_role() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures the friend-role protocol.
+    // --------------------------------------------------
+    let friend_role = include_bytes!("protocols/friend-role.json");
+    let definition: Definition = serde_json::from_slice(friend_role).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice adds Bob as a friend.
+    // --------------------------------------------------
+    let bob_friend = WriteBuilder::new()
+        .data(Data::from(b"Bob is my friend".to_vec()))
+        .recipient(&BOB.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://friend-role.xyz",
+            protocol_path: "friend",
+            parent_context_id: None,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, bob_friend.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice attempts (and fails) to add Bob as a friend again.
+    // --------------------------------------------------
+    let bob_friend = WriteBuilder::new()
+        .data(Data::from(b"Bob is still my friend".to_vec()))
+        .recipient(&BOB.did)
+        .protocol(ProtocolBuilder {
+            protocol: "http://friend-role.xyz",
+            protocol_path: "friend",
+            parent_context_id: None,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, bob_friend, &provider).await
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "recipient already has this role record");
+}
+
 // Should allow records to be created and updated using a context role.
 #[tokio::test]
 async fn context_role() {
@@ -2561,7 +4064,8 @@ async fn invalid_protocol_role() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, bob_chat.clone(), &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, bob_chat.clone(), &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -2607,7 +4111,8 @@ async fn unassigned_protocol_role() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, bob_chat.clone(), &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, bob_chat.clone(), &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -2880,7 +4385,8 @@ async fn forbidden_role_path() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, chat.clone(), &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, chat.clone(), &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -2924,7 +4430,8 @@ async fn invalid_role_path() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, chat.clone(), &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, chat.clone(), &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -3098,7 +4605,8 @@ async fn no_author_update() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, update.clone(), &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -3253,7 +4761,9 @@ async fn unauthorized_create() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, response, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, response, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "action not permitted");
@@ -3282,7 +4792,8 @@ async fn no_protocol_definition() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, application, &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, application, &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -3327,7 +4838,8 @@ async fn invalid_schema() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, application, &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, application, &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -3370,13 +4882,74 @@ async fn invalid_protocol_path() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, application, &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, application, &provider).await
     else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "invalid protocol path");
 }
 
+// Should fail to build a write when the protocol path is not in the protocol
+// definition.
+#[tokio::test]
+async fn protocol_from_definition() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    let issuance = include_bytes!("protocols/credential-issuance.json");
+    let definition: Definition = serde_json::from_slice(issuance).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // An invalid protocol path is rejected when the write is built.
+    // --------------------------------------------------
+    let Err(e) = WriteBuilder::new()
+        .data(Data::from(b"credential application data".to_vec()))
+        .recipient(&ISSUER.did)
+        .protocol_from(&definition, "invalidType", None)
+        .sign(&*ALICE)
+        .build()
+        .await
+    else {
+        panic!("should fail to build");
+    };
+    assert_eq!(
+        e.to_string(),
+        "protocol path invalidType not found in protocol http://credential-issuance-protocol.xyz"
+    );
+
+    // --------------------------------------------------
+    // A valid protocol path is written as for `WriteBuilder::protocol`.
+    // --------------------------------------------------
+    let application = WriteBuilder::new()
+        .data(Data::from(b"credential application data".to_vec()))
+        .recipient(&ISSUER.did)
+        .protocol_from(&definition, "credentialApplication", None)
+        .schema("https://identity.foundation/credential-manifest/schemas/credential-application")
+        .data_format("application/json")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    assert_eq!(
+        application.descriptor.protocol.as_deref(),
+        Some("http://credential-issuance-protocol.xyz")
+    );
+    assert_eq!(application.descriptor.protocol_path.as_deref(), Some("credentialApplication"));
+
+    let reply = endpoint::handle(&ALICE.did, application, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+}
+
 // Should prevent record creation when protocol path is incorrect.
 // That is, the path is valid but it is used incorrectly.
 #[tokio::test]
@@ -3416,23 +4989,276 @@ async fn incorrect_protocol_path() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, application, &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, application, &provider).await
     else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "invalid protocol path for parentless record");
 }
 
-// Should prevent use of invalid data formats for a given protocol.
+// Should prevent use of invalid data formats for a given protocol.
+#[tokio::test]
+async fn invalid_data_format() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures the social media protocol.
+    // --------------------------------------------------
+    let social_media = include_bytes!("protocols/social-media.json");
+    let definition: Definition = serde_json::from_slice(social_media).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes an image to her web node.
+    // --------------------------------------------------
+    let image = WriteBuilder::new()
+        .data(Data::from(b"cafe-aesthetic.jpg".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://social-media.xyz",
+            protocol_path: "image",
+            parent_context_id: None,
+        })
+        .schema("imageSchema")
+        .data_format("image/jpeg")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, image.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice attempts (and fails) to update the image to an invalid format.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(image.clone())
+        .data_format("not-permitted-data-format")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, update, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "data format not allowed by protocol");
+
+    // --------------------------------------------------
+    // Alice updates the image to a permitted data format.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(image.clone())
+        .data_format("image/gif")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Verify the data format was updated.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&image.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+
+    let reply = endpoint::handle(&ALICE.did, read.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    assert!(body.entry.records_write.is_some());
+    let write = body.entry.records_write.expect("should exist");
+    assert_eq!(write.descriptor.data_format.as_deref(), Some("image/gif"));
+}
+
+// Should only accept data formats permitted for the record's protocol path.
+#[tokio::test]
+async fn protocol_data_format() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a protocol restricting captions to plain text.
+    // --------------------------------------------------
+    let def_json = serde_json::json!({
+        "published" : true,
+        "protocol"  : "http://captions.xyz",
+        "types"     : {
+            "image": {},
+            "caption": {
+                "dataFormats": ["text/plain"]
+            }
+        },
+        "structure": {
+            "image": {
+                "caption": {}
+            }
+        }
+    });
+    let definition: Definition = serde_json::from_value(def_json).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes an image, which has no data format restriction.
+    // --------------------------------------------------
+    let image = WriteBuilder::new()
+        .data(Data::from(b"cafe-aesthetic.jpg".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://captions.xyz",
+            protocol_path: "image",
+            parent_context_id: None,
+        })
+        .data_format("image/jpeg")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, image.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // A JSON caption is rejected.
+    // --------------------------------------------------
+    let caption = WriteBuilder::new()
+        .data(Data::from(br#"{"caption": "coffee"}"#.to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://captions.xyz",
+            protocol_path: "image/caption",
+            parent_context_id: image.context_id.clone(),
+        })
+        .data_format("application/json")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, caption, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "data format not allowed by protocol");
+
+    // --------------------------------------------------
+    // A plain text caption is accepted.
+    // --------------------------------------------------
+    let caption = WriteBuilder::new()
+        .data(Data::from(b"coffee".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://captions.xyz",
+            protocol_path: "image/caption",
+            parent_context_id: image.context_id,
+        })
+        .data_format("text/plain")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, caption, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+}
+
+// Should allow any data format when protocol does not explicitly specify
+// permitted data format(s).
+#[tokio::test]
+async fn any_data_format() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a minimal protocol.
+    // --------------------------------------------------
+    let minimal = include_bytes!("protocols/minimal.json");
+    let definition: Definition = serde_json::from_slice(minimal).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes an image to her web node.
+    // --------------------------------------------------
+    let image = WriteBuilder::new()
+        .data(Data::from(b"cafe-aesthetic.jpg".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://minimal.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .schema("any-schema")
+        .data_format("any-data-format")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, image.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice updates the image to another format.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(image.clone())
+        .data_format("any-new-data-format")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Verify the data format was updated.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&image.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+
+    let reply = endpoint::handle(&ALICE.did, read.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    assert!(body.entry.records_write.is_some());
+    let write = body.entry.records_write.expect("should exist");
+    assert_eq!(write.descriptor.data_format.as_deref(), Some("any-new-data-format"));
+}
+
+// Should allow `data_format` to be omitted only when the protocol type does
+// not declare permitted data format(s).
 #[tokio::test]
-async fn invalid_data_format() {
+async fn optional_data_format() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice configures the social media protocol.
+    // Alice configures a protocol with and without required data formats.
     // --------------------------------------------------
-    let social_media = include_bytes!("protocols/social-media.json");
-    let definition: Definition = serde_json::from_slice(social_media).expect("should deserialize");
+    let optional = include_bytes!("protocols/optional-data-format.json");
+    let definition: Definition = serde_json::from_slice(optional).expect("should deserialize");
     let configure = ConfigureBuilder::new()
         .definition(definition.clone())
         .sign(&*ALICE)
@@ -3445,78 +5271,81 @@ async fn invalid_data_format() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice writes an image to her web node.
+    // Alice writes a note without a data format.
     // --------------------------------------------------
-    let image = WriteBuilder::new()
-        .data(Data::from(b"cafe-aesthetic.jpg".to_vec()))
+    let note = WriteBuilder::new()
+        .data(Data::from(b"a note".to_vec()))
         .protocol(ProtocolBuilder {
-            protocol: "http://social-media.xyz",
-            protocol_path: "image",
+            protocol: "http://optional-data-format.xyz",
+            protocol_path: "note",
             parent_context_id: None,
         })
-        .schema("imageSchema")
-        .data_format("image/jpeg")
+        .omit_data_format()
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, image.clone(), &provider).await.expect("should write");
+    let reply = endpoint::handle(&ALICE.did, note.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    // --------------------------------------------------
-    // Alice attempts (and fails) to update the image to an invalid format.
-    // --------------------------------------------------
-    let update = WriteBuilder::from(image.clone())
-        .data_format("not-permitted-data-format")
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&note.record_id))
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, update, &provider).await else {
-        panic!("should be Forbidden");
-    };
-    assert_eq!(e, "invalid data format");
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let write = body.entry.records_write.expect("should exist");
+    assert!(write.descriptor.data_format.is_none());
 
     // --------------------------------------------------
-    // Alice updates the image to a permitted data format.
+    // Alice attempts (and fails) to write an image without a data format.
     // --------------------------------------------------
-    let update = WriteBuilder::from(image.clone())
-        .data_format("image/gif")
+    let image = WriteBuilder::new()
+        .data(Data::from(b"cafe-aesthetic.jpg".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://optional-data-format.xyz",
+            protocol_path: "image",
+            parent_context_id: None,
+        })
+        .omit_data_format()
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, image, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "data format required by protocol");
 
     // --------------------------------------------------
-    // Verify the data format was updated.
+    // Alice attempts (and fails) to write a non-protocol record without a
+    // data format.
     // --------------------------------------------------
-    let read = ReadBuilder::new()
-        .filter(RecordsFilter::new().record_id(&image.record_id))
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .omit_data_format()
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create read");
-
-    let reply = endpoint::handle(&ALICE.did, read.clone(), &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::OK);
-
-    let body = reply.body.expect("should have body");
-    assert!(body.entry.records_write.is_some());
-    let write = body.entry.records_write.expect("should exist");
-    assert_eq!(write.descriptor.data_format, "image/gif");
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "missing data format");
 }
 
-// Should allow any data format when protocol does not explicitly specify
-// permitted data format(s).
+// Should reject writes to a deactivated protocol while existing records
+// remain readable, and accept writes again once the protocol is reactivated.
 #[tokio::test]
-async fn any_data_format() {
+async fn deactivated_protocol() {
     let provider = ProviderImpl::new().await.expect("should create provider");
 
     // --------------------------------------------------
-    // Alice configures a minimal protocol.
+    // Alice configures a minimal protocol and writes a record.
     // --------------------------------------------------
     let minimal = include_bytes!("protocols/minimal.json");
     let definition: Definition = serde_json::from_slice(minimal).expect("should deserialize");
@@ -3531,55 +5360,85 @@ async fn any_data_format() {
         .expect("should configure protocol");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
-    // --------------------------------------------------
-    // Alice writes an image to her web node.
-    // --------------------------------------------------
-    let image = WriteBuilder::new()
-        .data(Data::from(b"cafe-aesthetic.jpg".to_vec()))
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
         .protocol(ProtocolBuilder {
             protocol: "http://minimal.xyz",
             protocol_path: "foo",
             parent_context_id: None,
         })
-        .schema("any-schema")
-        .data_format("any-data-format")
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create write");
-    let reply = endpoint::handle(&ALICE.did, image.clone(), &provider).await.expect("should write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice updates the image to another format.
+    // Alice deactivates the protocol.
     // --------------------------------------------------
-    let update = WriteBuilder::from(image.clone())
-        .data_format("any-new-data-format")
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .deactivate()
         .sign(&*ALICE)
         .build()
         .await
-        .expect("should create write");
-    let reply =
-        endpoint::handle(&ALICE.did, update.clone(), &provider).await.expect("should write");
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Verify the data format was updated.
+    // A new write to the protocol is forbidden.
+    // --------------------------------------------------
+    let rejected = WriteBuilder::new()
+        .data(Data::from(b"more data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://minimal.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, rejected.clone(), &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "protocol is deactivated");
+
+    // --------------------------------------------------
+    // The existing record remains readable.
     // --------------------------------------------------
     let read = ReadBuilder::new()
-        .filter(RecordsFilter::new().record_id(&image.record_id))
+        .filter(RecordsFilter::new().record_id(&write.record_id))
         .sign(&*ALICE)
         .build()
         .await
         .expect("should create read");
-
-    let reply = endpoint::handle(&ALICE.did, read.clone(), &provider).await.expect("should write");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
     assert_eq!(reply.status.code, StatusCode::OK);
 
-    let body = reply.body.expect("should have body");
-    assert!(body.entry.records_write.is_some());
-    let write = body.entry.records_write.expect("should exist");
-    assert_eq!(write.descriptor.data_format, "any-new-data-format");
+    // --------------------------------------------------
+    // Alice reactivates the protocol and the write succeeds.
+    // --------------------------------------------------
+    let configure = ConfigureBuilder::new()
+        .definition(definition)
+        .active(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let reply = endpoint::handle(&ALICE.did, rejected, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 }
 
 // Should notnallow a record to be created when it's schema is invalid for the
@@ -3620,7 +5479,9 @@ async fn schema_hierarchy() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, response1, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, response1, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "invalid protocol path");
@@ -3662,7 +5523,8 @@ async fn schema_hierarchy() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, application2, &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, application2, &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -3705,7 +5567,8 @@ async fn schema_hierarchy() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, application3, &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, application3, &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -3771,7 +5634,8 @@ async fn owner_no_rule() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, bob_write.clone(), &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, bob_write.clone(), &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -3936,7 +5800,8 @@ async fn invalid_parent_id() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&PFI.did, fulfillment.clone(), &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&PFI.did, fulfillment.clone(), &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -4074,7 +5939,9 @@ async fn protocol_not_normalized() {
     email.context_id = Some(email.record_id.clone());
     email.sign_as_author(None, None, &*ALICE).await.expect("should sign");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, email, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, email, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "unable to find protocol definition");
@@ -4496,13 +6363,13 @@ async fn protocol_size_range() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, too_big, &provider).await else {
-        panic!("should be Forbidden");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, too_big, &provider).await else {
+        panic!("should be BadRequest");
     };
-    assert_eq!(e, "data size is greater than allowed");
+    assert_eq!(e, "data size outside protocol bounds");
 }
 
-// Should fail authorization if protocol message size is less than specified
+// Should reject a write if protocol message size is less than specified
 // minimum size.
 #[tokio::test]
 async fn protocol_min_size() {
@@ -4554,10 +6421,10 @@ async fn protocol_min_size() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, too_small, &provider).await else {
-        panic!("should be Forbidden");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, too_small, &provider).await else {
+        panic!("should be BadRequest");
     };
-    assert_eq!(e, "data size is less than allowed");
+    assert_eq!(e, "data size outside protocol bounds");
 
     // --------------------------------------------------
     // Alice writes a record at the maximum size.
@@ -4581,7 +6448,7 @@ async fn protocol_min_size() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 }
 
-// Should fail authorization if protocol message size is greater than specified
+// Should reject a write if protocol message size is greater than specified
 // maximum size.
 #[tokio::test]
 async fn protocol_max_size() {
@@ -4633,10 +6500,10 @@ async fn protocol_max_size() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, too_big, &provider).await else {
-        panic!("should be Forbidden");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, too_big, &provider).await else {
+        panic!("should be BadRequest");
     };
-    assert_eq!(e, "data size is greater than allowed");
+    assert_eq!(e, "data size outside protocol bounds");
 
     // --------------------------------------------------
     // Alice writes a record at the maximum size.
@@ -4660,6 +6527,218 @@ async fn protocol_max_size() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 }
 
+// Should enforce protocol size bounds for both encoded and block-stored data.
+#[tokio::test]
+async fn protocol_size_bounds() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a protocol with data size bounds.
+    // --------------------------------------------------
+    let size_bounds = include_bytes!("protocols/size-bounds.json");
+    let definition: Definition = serde_json::from_slice(size_bounds).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Writes outside the bounds are rejected, writes within are accepted.
+    // --------------------------------------------------
+    for (size, accepted) in
+        [(5, false), (20, true), (MAX_ENCODED_SIZE + 5000, true), (50000, false)]
+    {
+        let mut data = vec![0u8; size];
+        rand::thread_rng().fill_bytes(&mut data);
+
+        let write = WriteBuilder::new()
+            .data(Data::from(data))
+            .protocol(ProtocolBuilder {
+                protocol: "http://size-bounds.xyz",
+                protocol_path: "blob",
+                parent_context_id: None,
+            })
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+
+        if accepted {
+            let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+            assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+        } else {
+            let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write, &provider).await
+            else {
+                panic!("should be BadRequest");
+            };
+            assert_eq!(e, "data size outside protocol bounds");
+        }
+    }
+}
+
+// Should validate JSON record data against the protocol type's JSON Schema.
+#[tokio::test]
+async fn protocol_data_schema() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a protocol with a JSON Schema for record data.
+    // --------------------------------------------------
+    let json_schema = include_bytes!("protocols/json-schema.json");
+    let definition: Definition = serde_json::from_slice(json_schema).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes a profile conforming to the schema.
+    // --------------------------------------------------
+    let conforming = WriteBuilder::new()
+        .data(Data::from(br#"{"name": "Alice"}"#.to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://json-schema.xyz",
+            protocol_path: "profile",
+            parent_context_id: None,
+        })
+        .data_format("application/json")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, conforming, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice attempts (and fails) to write a profile not conforming to the
+    // schema.
+    // --------------------------------------------------
+    let nonconforming = WriteBuilder::new()
+        .data(Data::from(br#"{"nickname": "Al"}"#.to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://json-schema.xyz",
+            protocol_path: "profile",
+            parent_context_id: None,
+        })
+        .data_format("application/json")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, nonconforming, &provider).await
+    else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "data does not conform to schema");
+}
+
+// Should retain the latest write's data when an update sharing the data fails
+// JSON Schema validation.
+#[tokio::test]
+async fn protocol_data_schema_retain() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a protocol with a JSON Schema for record data.
+    // --------------------------------------------------
+    let json_schema = include_bytes!("protocols/json-schema.json");
+    let mut definition: Definition =
+        serde_json::from_slice(json_schema).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes a profile too large to encode in the message.
+    // --------------------------------------------------
+    let data = format!(r#"{{"name": "{}"}}"#, "a".repeat(MAX_ENCODED_SIZE)).into_bytes();
+    let write = WriteBuilder::new()
+        .data(Data::from(data.clone()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://json-schema.xyz",
+            protocol_path: "profile",
+            parent_context_id: None,
+        })
+        .data_format("application/json")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice restricts the schema so the profile no longer conforms.
+    // --------------------------------------------------
+    let profile = definition.types.get_mut("profile").expect("should have profile type");
+    profile.data_schema = Some(serde_json::json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string", "maxLength": 10 }
+        }
+    }));
+    let configure = ConfigureBuilder::new()
+        .definition(definition)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // An update with the same data fails validation.
+    // --------------------------------------------------
+    let update = WriteBuilder::from(write.clone())
+        .data(Data::from(data.clone()))
+        .published(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, update, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "data does not conform to schema");
+
+    // --------------------------------------------------
+    // The original write's data can still be read.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&write.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let read_stream = body.entry.data.expect("should have data");
+    assert_eq!(read_stream.into_inner(), data);
+}
+
 // Should fail when write references a parent that has been deleted.
 #[tokio::test]
 async fn deleted_parent() {
@@ -4728,10 +6807,10 @@ async fn deleted_parent() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, bar1, &provider).await else {
-        panic!("should be Forbidden");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, bar1, &provider).await else {
+        panic!("should be BadRequest");
     };
-    assert_eq!(e, "unable to find parent record");
+    assert_eq!(e, "parent record not found");
 }
 
 // Should fail when write references a different parent to the one specified
@@ -4796,10 +6875,54 @@ async fn incorrect_parent_context() {
     bar1.record_id = bar1.entry_id(&ALICE.did).expect("should create record ID");
     bar1.sign_as_author(None, None, &*ALICE).await.expect("should sign");
 
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, bar1, &provider).await else {
-        panic!("should be Forbidden");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, bar1, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "invalid context ID");
+}
+
+// Should fail when write references a parent context that does not exist.
+#[tokio::test]
+async fn missing_parent_context() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a nested protocol: foo -> bar -> baz.
+    // --------------------------------------------------
+    let nested = include_bytes!("protocols/nested.json");
+    let definition: Definition = serde_json::from_slice(nested).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice attempts (and fails) to write bar1 under a non-existent context.
+    // --------------------------------------------------
+    let bar1 = WriteBuilder::new()
+        .data(Data::from(b"some request".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://nested.xyz",
+            protocol_path: "foo/bar",
+            parent_context_id: Some("bafkreinonexistentparent".to_string()),
+        })
+        .schema("bar")
+        .data_format("text/plain")
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, bar1, &provider).await else {
+        panic!("should be BadRequest");
     };
-    assert_eq!(e, "incorrect parent `context_id`");
+    assert_eq!(e, "parent record not found");
 }
 
 // Should allow writes when protocol and grant scope matches.
@@ -4830,7 +6953,7 @@ async fn protocol_grant_match() {
         .granted_to(&BOB.did)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://minimal.xyz".to_string(),
+            protocol: Some("http://minimal.xyz".to_string()),
             limited_to: None,
         })
         .sign(&*ALICE)
@@ -4895,42 +7018,305 @@ async fn protocol_grant_mismatch() {
     assert_eq!(reply.status.code, StatusCode::ACCEPTED);
 
     // --------------------------------------------------
-    // Alice grants Bob permission to read records using the email protocol.
+    // Alice grants Bob permission to read records using the email protocol.
+    // --------------------------------------------------
+    let bob_grant = GrantBuilder::new()
+        .granted_to(&BOB.did)
+        .scope(Scope::Records {
+            method: Method::Write,
+            protocol: Some("http://email-protocol.xyz".to_string()),
+            limited_to: None,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create grant");
+    let reply =
+        endpoint::handle(&ALICE.did, bob_grant.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Bob reads the record using the grant and theminimal protocol.
+    // --------------------------------------------------
+    let bob_write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://minimal.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .permission_grant_id(bob_grant.record_id)
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, bob_write, &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "scope protocol does not match write protocol");
+}
+
+// Should allow an app to write a record on behalf of a user using a delegated
+// grant.
+#[tokio::test]
+async fn delegated_grant() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a minimal protocol.
+    // --------------------------------------------------
+    let minimal = include_bytes!("protocols/minimal.json");
+    let definition: Definition = serde_json::from_slice(minimal).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice grants Bob's app a delegated grant to write records.
+    // --------------------------------------------------
+    let app_grant = GrantBuilder::new()
+        .granted_to(&BOB.did)
+        .scope(Scope::Records {
+            method: Method::Write,
+            protocol: Some("http://minimal.xyz".to_string()),
+            limited_to: None,
+        })
+        .delegated(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create grant");
+
+    // --------------------------------------------------
+    // The app writes a record on behalf of Alice.
+    // --------------------------------------------------
+    let app_write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://minimal.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .delegated_grant(DelegatedGrant::from(app_grant))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create write");
+    let reply =
+        endpoint::handle(&ALICE.did, app_write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice queries for the record and finds she is its author.
+    // --------------------------------------------------
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&app_write.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let entries = body.entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.authorization.author().expect("should have author"), ALICE.did);
+}
+
+// Should prevent an app from writing outside the scope of a delegated grant.
+#[tokio::test]
+async fn delegated_grant_scope() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a minimal protocol.
+    // --------------------------------------------------
+    let minimal = include_bytes!("protocols/minimal.json");
+    let definition: Definition = serde_json::from_slice(minimal).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice grants Bob's app a delegated grant scoped to another protocol.
+    // --------------------------------------------------
+    let app_grant = GrantBuilder::new()
+        .granted_to(&BOB.did)
+        .scope(Scope::Records {
+            method: Method::Write,
+            protocol: Some("http://email-protocol.xyz".to_string()),
+            limited_to: None,
+        })
+        .delegated(true)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create grant");
+
+    // --------------------------------------------------
+    // The app attempts to write a record using the minimal protocol.
+    // --------------------------------------------------
+    let app_write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://minimal.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .delegated_grant(DelegatedGrant::from(app_grant))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, app_write, &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "scope protocol does not match write protocol");
+}
+
+// Should prevent an app from using a grant not issued as a delegated grant.
+#[tokio::test]
+async fn delegated_grant_not_delegated() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a minimal protocol.
+    // --------------------------------------------------
+    let minimal = include_bytes!("protocols/minimal.json");
+    let definition: Definition = serde_json::from_slice(minimal).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice grants Bob's app a regular (non-delegated) grant.
+    // --------------------------------------------------
+    let app_grant = GrantBuilder::new()
+        .granted_to(&BOB.did)
+        .scope(Scope::Records {
+            method: Method::Write,
+            protocol: Some("http://minimal.xyz".to_string()),
+            limited_to: None,
+        })
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create grant");
+
+    // --------------------------------------------------
+    // The app attempts to use the grant as a delegated grant.
+    // --------------------------------------------------
+    let app_write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://minimal.xyz",
+            protocol_path: "foo",
+            parent_context_id: None,
+        })
+        .delegated_grant(DelegatedGrant::from(app_grant))
+        .sign(&*BOB)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, app_write, &provider).await
+    else {
+        panic!("should be Forbidden");
+    };
+    assert_eq!(e, "grant is not a delegated grant");
+}
+
+// Should prevent an app from presenting a delegated grant its signature does
+// not commit to.
+#[tokio::test]
+async fn delegated_grant_swapped() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a minimal protocol.
+    // --------------------------------------------------
+    let minimal = include_bytes!("protocols/minimal.json");
+    let definition: Definition = serde_json::from_slice(minimal).expect("should deserialize");
+    let configure = ConfigureBuilder::new()
+        .definition(definition.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice grants Bob's app two delegated grants.
     // --------------------------------------------------
-    let bob_grant = GrantBuilder::new()
-        .granted_to(&BOB.did)
-        .scope(Scope::Records {
-            method: Method::Write,
-            protocol: "http://email-protocol.xyz".to_string(),
-            limited_to: None,
-        })
-        .sign(&*ALICE)
-        .build()
-        .await
-        .expect("should create grant");
-    let reply =
-        endpoint::handle(&ALICE.did, bob_grant.clone(), &provider).await.expect("should write");
-    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    let mut grants = vec![];
+    for protocol in ["http://minimal.xyz", "http://email-protocol.xyz"] {
+        let grant = GrantBuilder::new()
+            .granted_to(&BOB.did)
+            .scope(Scope::Records {
+                method: Method::Write,
+                protocol: Some(protocol.to_string()),
+                limited_to: None,
+            })
+            .delegated(true)
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create grant");
+        grants.push(DelegatedGrant::from(grant));
+    }
 
     // --------------------------------------------------
-    // Bob reads the record using the grant and theminimal protocol.
+    // The app signs a write using the first grant, then swaps in the second.
     // --------------------------------------------------
-    let bob_write = WriteBuilder::new()
+    let mut app_write = WriteBuilder::new()
         .data(Data::from(b"some data".to_vec()))
         .protocol(ProtocolBuilder {
             protocol: "http://minimal.xyz",
             protocol_path: "foo",
             parent_context_id: None,
         })
-        .permission_grant_id(bob_grant.record_id)
+        .delegated_grant(grants[0].clone())
         .sign(&*BOB)
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, bob_write, &provider).await else {
+    app_write.authorization.author_delegated_grant = Some(grants[1].clone());
+
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, app_write, &provider).await
+    else {
         panic!("should be Forbidden");
     };
-    assert_eq!(e, "scope protocol does not match write protocol");
+    assert_eq!(e, "delegated grant ID does not match the signature");
 }
 
 // Should allow writes when protocol and context grant scope match.
@@ -4981,7 +7367,7 @@ async fn protocol_context_grant() {
         .granted_to(&BOB.did)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://email-protocol.xyz".to_string(),
+            protocol: Some("http://email-protocol.xyz".to_string()),
             limited_to: Some(RecordsScope::ContextId(alice_write.context_id.clone().unwrap())),
         })
         .sign(&*ALICE)
@@ -5061,7 +7447,7 @@ async fn protocol_context_no_grant() {
         .granted_to(&BOB.did)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://email-protocol.xyz".to_string(),
+            protocol: Some("http://email-protocol.xyz".to_string()),
             limited_to: Some(RecordsScope::ContextId("nonexistentparentid".to_string())),
         })
         .sign(&*ALICE)
@@ -5089,7 +7475,9 @@ async fn protocol_context_no_grant() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, bob_write, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, bob_write, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "record not part of grant context");
@@ -5123,7 +7511,7 @@ async fn protocol_path_grant() {
         .granted_to(&BOB.did)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://minimal.xyz".to_string(),
+            protocol: Some("http://minimal.xyz".to_string()),
             limited_to: Some(RecordsScope::ProtocolPath("foo".to_string())),
         })
         .sign(&*ALICE)
@@ -5181,7 +7569,7 @@ async fn protocol_path_no_grant() {
         .granted_to(&BOB.did)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://minimal.xyz".to_string(),
+            protocol: Some("http://minimal.xyz".to_string()),
             limited_to: Some(RecordsScope::ProtocolPath("some-other-path".to_string())),
         })
         .sign(&*ALICE)
@@ -5207,7 +7595,9 @@ async fn protocol_path_no_grant() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, bob_write, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, bob_write, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "grant and record protocol paths do not match");
@@ -5242,11 +7632,12 @@ async fn grant_publish_required() {
         .granted_to(&BOB.did)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://minimal.xyz".to_string(),
+            protocol: Some("http://minimal.xyz".to_string()),
             limited_to: None,
         })
         .conditions(Conditions {
             publication: Some(Publication::Required),
+            ..Conditions::default()
         })
         .sign(&*ALICE)
         .build()
@@ -5290,7 +7681,8 @@ async fn grant_publish_required() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, unpublished, &provider).await
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, unpublished, &provider).await
     else {
         panic!("should be Forbidden");
     };
@@ -5326,11 +7718,12 @@ async fn grant_publish_prohibited() {
         .granted_to(&BOB.did)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://minimal.xyz".to_string(),
+            protocol: Some("http://minimal.xyz".to_string()),
             limited_to: None,
         })
         .conditions(Conditions {
             publication: Some(Publication::Prohibited),
+            ..Conditions::default()
         })
         .sign(&*ALICE)
         .build()
@@ -5374,7 +7767,9 @@ async fn grant_publish_prohibited() {
         .build()
         .await
         .expect("should create write");
-    let Err(Error::Forbidden(e)) = endpoint::handle(&ALICE.did, published, &provider).await else {
+    let Err(Error::Forbidden { message: e, .. }) =
+        endpoint::handle(&ALICE.did, published, &provider).await
+    else {
         panic!("should be Forbidden");
     };
     assert_eq!(e, "grant prohibits publishing message");
@@ -5409,10 +7804,13 @@ async fn grant_publish_undefined() {
         .granted_to(&BOB.did)
         .scope(Scope::Records {
             method: Method::Write,
-            protocol: "http://minimal.xyz".to_string(),
+            protocol: Some("http://minimal.xyz".to_string()),
             limited_to: None,
         })
-        .conditions(Conditions { publication: None })
+        .conditions(Conditions {
+            publication: None,
+            ..Conditions::default()
+        })
         .sign(&*ALICE)
         .build()
         .await
@@ -5790,6 +8188,352 @@ async fn attestation_descriptor_cid() {
     assert_eq!(e, "message and authorization attestation CIDs do not match");
 }
 
+// Should fail when the attester did not sign the message's descriptor.
+#[tokio::test]
+async fn attestation_signature() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice attempts to write a record with an attestation Bob made over a
+    // different descriptor.
+    // --------------------------------------------------
+    let mut write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .attest(&[&*BOB])
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    let payload = Attestation {
+        descriptor_cid: dwn_node::cid::from_value(&"somerandomrecordid")
+            .expect("should create CID"),
+    };
+    let attestation =
+        JwsBuilder::new().payload(payload).add_signer(&*BOB).build().await.expect("should sign");
+    write.attestation = Some(attestation);
+
+    // re-sign so the authorization commits to the altered attestation
+    let payload = SignaturePayload {
+        base: JwsPayload {
+            descriptor_cid: dwn_node::cid::from_value(&write.descriptor).unwrap(),
+            ..JwsPayload::default()
+        },
+        record_id: write.record_id.clone(),
+        context_id: write.context_id.clone(),
+        attestation_cid: Some(dwn_node::cid::from_value(&write.attestation).unwrap()),
+        ..SignaturePayload::default()
+    };
+    write.authorization.signature =
+        JwsBuilder::new().payload(payload).add_signer(&*ALICE).build().await.expect("should sign");
+
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "attestation does not match message descriptor");
+
+    // --------------------------------------------------
+    // Alice attempts to write a record with a tampered attestation signature.
+    // --------------------------------------------------
+    let mut write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .attest(&[&*BOB])
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    write.attestation.as_mut().unwrap().signatures[0].signature = "bad_signature".to_string();
+
+    // re-sign so the authorization commits to the altered attestation
+    let payload = SignaturePayload {
+        base: JwsPayload {
+            descriptor_cid: dwn_node::cid::from_value(&write.descriptor).unwrap(),
+            ..JwsPayload::default()
+        },
+        record_id: write.record_id.clone(),
+        context_id: write.context_id.clone(),
+        attestation_cid: Some(dwn_node::cid::from_value(&write.attestation).unwrap()),
+        ..SignaturePayload::default()
+    };
+    write.authorization.signature =
+        JwsBuilder::new().payload(payload).add_signer(&*ALICE).build().await.expect("should sign");
+
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert!(e.starts_with("invalid attestation signature"));
+}
+
+// Should verify every attester's signature and report all attesters on read.
+#[tokio::test]
+async fn multiple_attesters() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a record attested by both Bob and Carol.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .attest(&[&*BOB, &*CAROL])
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    assert_eq!(write.attestation.as_ref().unwrap().signatures.len(), 2);
+
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice reads the record and sees both attesters.
+    // --------------------------------------------------
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&write.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &provider).await.expect("should read");
+    assert_eq!(reply.status.code, StatusCode::OK);
+
+    let body = reply.body.expect("should have body");
+    let read_write = body.entry.records_write.expect("should have write");
+    assert_eq!(read_write.authorization.author().unwrap(), ALICE.did);
+
+    let attesters = read_write.attesters().expect("should get attesters");
+    assert_eq!(attesters, vec![BOB.did.clone(), CAROL.did.clone()]);
+
+    // --------------------------------------------------
+    // Alice attempts to write a record with a tampered second attestation.
+    // --------------------------------------------------
+    let mut write = WriteBuilder::new()
+        .data(Data::from(b"some other data".to_vec()))
+        .attest(&[&*BOB, &*CAROL])
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    write.attestation.as_mut().unwrap().signatures[1].signature = "bad_signature".to_string();
+
+    // re-sign so the authorization commits to the altered attestation
+    write.sign_as_author(None, None, &*ALICE).await.expect("should sign");
+
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert!(e.starts_with("invalid attestation signature"));
+}
+
+// Should return a status of BadRequest (400) when tags do not conform to the
+// protocol's `$tags` schema.
+#[tokio::test]
+async fn invalid_tags() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a protocol with a numeric `year` tag.
+    // --------------------------------------------------
+    let def_json = serde_json::json!({
+        "published" : true,
+        "protocol"  : "http://books-protocol.xyz",
+        "types"     : {
+            "book": {}
+        },
+        "structure": {
+            "book": {
+                "$tags": {
+                    "$requiredTags": ["year"],
+                    "year": {
+                        "type": "number"
+                    }
+                }
+            }
+        }
+    });
+    let definition: Definition = serde_json::from_value(def_json).expect("should deserialize");
+
+    let configure = ConfigureBuilder::new()
+        .definition(definition)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes a book with a string `year` tag.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://books-protocol.xyz",
+            protocol_path: "book",
+            parent_context_id: None,
+        })
+        .add_tag("year", Tag::String("2024".to_string()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "tags do not match schema");
+
+    // --------------------------------------------------
+    // Alice writes a book with a numeric `year` tag.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://books-protocol.xyz",
+            protocol_path: "book",
+            parent_context_id: None,
+        })
+        .add_tag("year", Tag::Number(2024))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+}
+
+// Should accept typed tags set in bulk, reject tags not declared in the
+// protocol's `$tags` schema, and allow free-form tags outside of a protocol.
+#[tokio::test]
+async fn typed_tags() {
+    let provider = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice configures a protocol with typed tags.
+    // --------------------------------------------------
+    let def_json = serde_json::json!({
+        "published" : true,
+        "protocol"  : "http://books-protocol.xyz",
+        "types"     : {
+            "book": {}
+        },
+        "structure": {
+            "book": {
+                "$tags": {
+                    "title": { "type": "string" },
+                    "offset": { "type": "integer" },
+                    "rating": { "type": "number" },
+                    "reviewed": { "type": "boolean" }
+                }
+            }
+        }
+    });
+    let definition: Definition = serde_json::from_value(def_json).expect("should deserialize");
+
+    let configure = ConfigureBuilder::new()
+        .definition(definition)
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should build");
+    let reply = endpoint::handle(&ALICE.did, configure, &provider)
+        .await
+        .expect("should configure protocol");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes a book with typed tags.
+    // --------------------------------------------------
+    let tags = BTreeMap::from([
+        ("title".to_string(), Tag::String("Dune".to_string())),
+        ("offset".to_string(), Tag::Integer(-2)),
+        ("rating".to_string(), Tag::Float(4.5)),
+        ("reviewed".to_string(), Tag::Boolean(true)),
+    ]);
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://books-protocol.xyz",
+            protocol_path: "book",
+            parent_context_id: None,
+        })
+        .tags(tags.clone())
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&write.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &provider).await.expect("should query");
+    let body = reply.body.expect("should have body");
+    let entries = body.entries.expect("should have entries");
+    assert_eq!(entries[0].write.descriptor.tags, Some(tags));
+
+    // --------------------------------------------------
+    // Alice writes a book with an undeclared tag.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://books-protocol.xyz",
+            protocol_path: "book",
+            parent_context_id: None,
+        })
+        .add_tag("colour", Tag::String("blue".to_string()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "tags do not match schema");
+
+    // --------------------------------------------------
+    // Alice writes a book with a mistyped tag.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .protocol(ProtocolBuilder {
+            protocol: "http://books-protocol.xyz",
+            protocol_path: "book",
+            parent_context_id: None,
+        })
+        .add_tag("offset", Tag::Float(1.5))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let Err(Error::BadRequest(e)) = endpoint::handle(&ALICE.did, write, &provider).await else {
+        panic!("should be BadRequest");
+    };
+    assert_eq!(e, "tags do not match schema");
+
+    // --------------------------------------------------
+    // Alice writes a non-protocol record with free-form tags.
+    // --------------------------------------------------
+    let write = WriteBuilder::new()
+        .data(Data::from(b"some data".to_vec()))
+        .add_tag("colour", Tag::String("blue".to_string()))
+        .add_tag("offset", Tag::Float(1.5))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write, &provider).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+}
+
 // TODO: Should fail when an unknown error is returned.
 #[tokio::test]
 #[ignore]
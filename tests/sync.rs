@@ -0,0 +1,297 @@
+//! Sync
+
+use std::io::Read as _;
+use std::sync::LazyLock;
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use dwn_node::endpoint::Reply;
+use dwn_node::interfaces::messages::{Query, QueryReply, Read};
+use dwn_node::interfaces::records::{
+    Data, DataReader, QueryBuilder, ReadBuilder, RecordsFilter, WriteBuilder,
+};
+use dwn_node::provider::EventLog;
+use dwn_node::provider::sync::{self, Remote};
+use dwn_node::store::{EntryType, MAX_ENCODED_SIZE};
+use dwn_node::{Error, Message, Result, StatusCode, endpoint};
+use futures::io::AllowStdIo;
+use rand::RngCore;
+use test_node::key_store;
+use test_node::provider::ProviderImpl;
+
+static ALICE: LazyLock<key_store::Keyring> = LazyLock::new(|| key_store::new_keyring());
+
+// A remote node, reached through its messages interface.
+struct Node<'a>(&'a ProviderImpl);
+
+impl Remote for Node<'_> {
+    async fn query(&self, owner: &str, query: Query) -> Result<Reply<QueryReply>> {
+        endpoint::handle(owner, query, self.0).await
+    }
+
+    async fn read(&self, owner: &str, read: Read) -> Result<(EntryType, Option<DataReader>)> {
+        let reply = endpoint::handle(owner, read, self.0).await?;
+        let Some(entry) = reply.body.and_then(|body| body.entry) else {
+            return Err(Error::NotFound("message not found".to_string()));
+        };
+        let data = entry.data.map(|data| DataReader::new(AllowStdIo::new(data)));
+        Ok((entry.message, data))
+    }
+}
+
+// Should pull messages from a remote node, resolving conflicts using the
+// timestamp/CID tie-break.
+#[tokio::test]
+async fn pull() {
+    let local = ProviderImpl::new().await.expect("should create provider");
+    let remote = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a record to the remote node.
+    // --------------------------------------------------
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"initial".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, initial.clone(), &remote).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // The local node pulls the record from the remote node.
+    // --------------------------------------------------
+    let pulled =
+        sync::pull(&ALICE.did, &local, &Node(&remote), &*ALICE, None).await.expect("should pull");
+    assert_eq!(pulled.since, Some(EventLog::watermark(&remote, &ALICE.did).await.unwrap()));
+    assert_eq!(pulled.watermark, EventLog::watermark(&local, &ALICE.did).await.unwrap());
+    assert_eq!(pulled.applied, 1);
+    assert!(pulled.failed.is_empty());
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &local).await.expect("should query");
+    let entries = reply.body.expect("should have body").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.encoded_data, Some(Base64UrlUnpadded::encode_string(b"initial")));
+
+    // --------------------------------------------------
+    // Alice updates the record on both nodes, with the local update being
+    // the more recent.
+    // --------------------------------------------------
+    let stale = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"stale".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let latest = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"latest".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    let reply = endpoint::handle(&ALICE.did, latest, &local).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    let reply = endpoint::handle(&ALICE.did, stale, &remote).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Alice writes a second record to the remote node.
+    // --------------------------------------------------
+    let other = WriteBuilder::new()
+        .data(Data::from(b"other".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, other.clone(), &remote).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // Pulling from the last watermark skips the stale update and applies
+    // the new record.
+    // --------------------------------------------------
+    let since = pulled.since;
+    let pulled =
+        sync::pull(&ALICE.did, &local, &Node(&remote), &*ALICE, since).await.expect("should pull");
+    assert_eq!(pulled.since, Some(EventLog::watermark(&remote, &ALICE.did).await.unwrap()));
+    assert_eq!(pulled.watermark, EventLog::watermark(&local, &ALICE.did).await.unwrap());
+    assert_eq!(pulled.applied, 1);
+    assert_eq!(pulled.skipped, 1);
+    assert!(pulled.failed.is_empty());
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&initial.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &local).await.expect("should query");
+    let entries = reply.body.expect("should have body").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].write.encoded_data, Some(Base64UrlUnpadded::encode_string(b"latest")));
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().record_id(&other.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &local).await.expect("should query");
+    let entries = reply.body.expect("should have body").entries.expect("should have entries");
+    assert_eq!(entries.len(), 1);
+
+    // --------------------------------------------------
+    // Pulling again leaves the watermark unchanged.
+    // --------------------------------------------------
+    let next = sync::pull(&ALICE.did, &local, &Node(&remote), &*ALICE, pulled.since)
+        .await
+        .expect("should pull");
+    assert_eq!(next.since, pulled.since);
+    assert_eq!(next.watermark, pulled.watermark);
+    assert_eq!(next.applied + next.skipped, 0);
+}
+
+// Should pull every event from a remote log spanning more than one page.
+#[tokio::test]
+async fn pull_pages() {
+    let local = ProviderImpl::new().await.expect("should create provider");
+    let remote = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes more records to the remote node than fit in a page.
+    // --------------------------------------------------
+    for i in 0..105 {
+        let write = WriteBuilder::new()
+            .data(Data::from(format!("record {i}").into_bytes()))
+            .sign(&*ALICE)
+            .build()
+            .await
+            .expect("should create write");
+        let reply = endpoint::handle(&ALICE.did, write, &remote).await.expect("should write");
+        assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    }
+
+    // --------------------------------------------------
+    // The local node pulls every record.
+    // --------------------------------------------------
+    let pulled =
+        sync::pull(&ALICE.did, &local, &Node(&remote), &*ALICE, None).await.expect("should pull");
+    assert_eq!(pulled.applied, 105);
+    assert_eq!(pulled.since, Some(EventLog::watermark(&remote, &ALICE.did).await.unwrap()));
+    assert_eq!(pulled.watermark, EventLog::watermark(&local, &ALICE.did).await.unwrap());
+
+    let query = QueryBuilder::new()
+        .filter(RecordsFilter::new().data_format("application/json"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create query");
+    let reply = endpoint::handle(&ALICE.did, query, &local).await.expect("should query");
+    let entries = reply.body.expect("should have body").entries.expect("should have entries");
+    assert_eq!(entries.len(), 105);
+}
+
+// Should stream pulled data too large to encode in the message.
+#[tokio::test]
+async fn pull_data() {
+    let local = ProviderImpl::new().await.expect("should create provider");
+    let remote = ProviderImpl::new().await.expect("should create provider");
+
+    let mut data = vec![0u8; MAX_ENCODED_SIZE + 10];
+    rand::thread_rng().fill_bytes(&mut data);
+
+    let write = WriteBuilder::new()
+        .data(Data::from(data.clone()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, write.clone(), &remote).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let pulled =
+        sync::pull(&ALICE.did, &local, &Node(&remote), &*ALICE, None).await.expect("should pull");
+    assert_eq!(pulled.applied, 1);
+    assert!(pulled.failed.is_empty());
+
+    let read = ReadBuilder::new()
+        .filter(RecordsFilter::new().record_id(&write.record_id))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create read");
+    let reply = endpoint::handle(&ALICE.did, read, &local).await.expect("should read");
+    let body = reply.body.expect("should have body");
+    let mut stream = body.entry.data.expect("should have data");
+    let mut pulled_data = vec![];
+    stream.read_to_end(&mut pulled_data).expect("should read data");
+    assert_eq!(pulled_data, data);
+}
+
+// Should report conflicts other than the local node already having the
+// message, or a more recent one, as failures.
+#[tokio::test]
+async fn pull_conflict() {
+    let local = ProviderImpl::new().await.expect("should create provider");
+    let remote = ProviderImpl::new().await.expect("should create provider");
+
+    // --------------------------------------------------
+    // Alice writes a record to the remote node and pulls it.
+    // --------------------------------------------------
+    let initial = WriteBuilder::new()
+        .data(Data::from(b"initial".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let reply = endpoint::handle(&ALICE.did, initial.clone(), &remote).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    let pulled =
+        sync::pull(&ALICE.did, &local, &Node(&remote), &*ALICE, None).await.expect("should pull");
+    assert_eq!(pulled.applied, 1);
+
+    // --------------------------------------------------
+    // Alice updates the record locally, then updates the remote record
+    // expecting the initial write to be the latest.
+    // --------------------------------------------------
+    let local_update = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"local".to_vec()))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+    let remote_update = WriteBuilder::from(initial.clone())
+        .data(Data::from(b"remote".to_vec()))
+        .expected_parent_cid(initial.cid().expect("should have CID"))
+        .sign(&*ALICE)
+        .build()
+        .await
+        .expect("should create write");
+
+    let reply = endpoint::handle(&ALICE.did, local_update, &local).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+    let reply =
+        endpoint::handle(&ALICE.did, remote_update.clone(), &remote).await.expect("should write");
+    assert_eq!(reply.status.code, StatusCode::ACCEPTED);
+
+    // --------------------------------------------------
+    // The remote update is more recent but no longer derives from the local
+    // node's latest write, so fails rather than being skipped.
+    // --------------------------------------------------
+    let pulled = sync::pull(&ALICE.did, &local, &Node(&remote), &*ALICE, pulled.since)
+        .await
+        .expect("should pull");
+    assert_eq!(pulled.applied + pulled.skipped, 0);
+    assert_eq!(pulled.failed.len(), 1);
+
+    let (message_cid, e) = &pulled.failed[0];
+    assert_eq!(message_cid, &remote_update.cid().expect("should have CID"));
+    assert!(matches!(e, Error::Conflict(_)));
+}